@@ -0,0 +1,89 @@
+use shaderc::CompileOptions;
+use shaderc::Compiler;
+use shaderc::IncludeType;
+use shaderc::ResolvedInclude;
+use shaderc::ShaderKind;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+const SHADER_DIR: &str = "shaders";
+const INCLUDE_DIR: &str = "shaders/include";
+
+// Compiles every stage under `shaders/` to SPIR-V and writes them to
+// `$OUT_DIR/shaders.rs` as word arrays named after the file, e.g.
+// `debug.vert` becomes `DEBUG_VERT`; see src/spirv.rs.
+//
+// `#include <name>` searches shaders/include and `#include "name"` the
+// including file's directory first.
+fn main() {
+    println!("cargo:rerun-if-changed={}", SHADER_DIR);
+    println!("cargo:rerun-if-changed={}", INCLUDE_DIR);
+    for header in sorted_files(INCLUDE_DIR) {
+        println!("cargo:rerun-if-changed={}", header.display());
+    }
+
+    let mut compiler = Compiler::new().unwrap();
+    let mut options = CompileOptions::new().unwrap();
+    options.set_include_callback(|name, ty, from, _depth| {
+        let mut dirs = vec![PathBuf::from(INCLUDE_DIR)];
+        if let IncludeType::Relative = ty {
+            if let Some(parent) = Path::new(from).parent() {
+                dirs.insert(0, parent.to_owned());
+            }
+        }
+        dirs.iter()
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| format!("{} not found", name))
+            .and_then(|path| {
+                let content = fs::read_to_string(&path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                Ok(ResolvedInclude {
+                    resolved_name: path.display().to_string(),
+                    content,
+                })
+            })
+    });
+
+    let mut generated = String::new();
+    for path in sorted_files(SHADER_DIR) {
+        let kind = match path.extension().and_then(|e| e.to_str()) {
+            Some("vert") => ShaderKind::Vertex,
+            Some("frag") => ShaderKind::Fragment,
+            Some("comp") => ShaderKind::Compute,
+            _ => continue,
+        };
+        println!("cargo:rerun-if-changed={}", path.display());
+        let source = fs::read_to_string(&path).unwrap();
+        let name = path.display().to_string();
+        let artifact = compiler
+            .compile_into_spirv(&source, kind, &name, "main", Some(&options))
+            .unwrap_or_else(|e| panic!("{}", e));
+        let words = artifact.as_binary();
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        writeln!(
+            generated,
+            "pub static {}: [u32; {}] = {:?};",
+            file_name.replace('.', "_").to_uppercase(),
+            words.len(),
+            words
+        )
+        .unwrap();
+    }
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("shaders.rs"), generated).unwrap();
+}
+
+fn sorted_files(dir: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    paths
+}
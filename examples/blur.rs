@@ -0,0 +1,127 @@
+use std::time::Instant;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::format::Format;
+use vulkano::image::{Dimensions, StorageImage};
+use vulkano::sampler::Filter;
+use vulkano::sync::{self, GpuFuture};
+use vulkano_triangle::blur::{self, Blur};
+use vulkano_triangle::logger;
+use vulkano_triangle::renderer;
+
+// Blurs an image file on the GPU without a window, e.g.
+// `cargo run --example blur -- in.png out.png 8`.
+fn main() {
+    logger::init("info");
+    let mut args = std::env::args().skip(1);
+    let input = args.next().expect("usage: blur <in> <out> [radius]");
+    let output = args.next().expect("usage: blur <in> <out> [radius]");
+    let radius = args
+        .next()
+        .map(|radius| radius.parse().unwrap())
+        .unwrap_or(8);
+
+    let (device, queue) = renderer::headless_device();
+    println!("Using device: {}", device.physical_device().name());
+
+    let source = image::open(&input).unwrap().to_rgba();
+    let (width, height) = source.dimensions();
+    let dimensions = Dimensions::Dim2d { width, height };
+    let corner = [width as i32, height as i32, 1];
+
+    let pixels = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage {
+            transfer_source: true,
+            transfer_destination: true,
+            ..BufferUsage::none()
+        },
+        source.into_raw().into_iter(),
+    )
+    .unwrap();
+    let staging = StorageImage::new(
+        device.clone(),
+        dimensions,
+        Format::R8G8B8A8Unorm,
+        Some(queue.family()),
+    )
+    .unwrap();
+    let hdr = StorageImage::new(
+        device.clone(),
+        dimensions,
+        blur::FORMAT,
+        Some(queue.family()),
+    )
+    .unwrap();
+
+    let blur = Blur::new(device.clone());
+    let targets =
+        blur.targets(device.clone(), &queue, hdr.clone(), [width, height]);
+
+    let builder = AutoCommandBufferBuilder::primary_one_time_submit(
+        device.clone(),
+        queue.family(),
+    )
+    .unwrap()
+    .copy_buffer_to_image(pixels.clone(), staging.clone())
+    .unwrap()
+    .blit_image(
+        staging.clone(),
+        [0, 0, 0],
+        corner,
+        0,
+        0,
+        hdr.clone(),
+        [0, 0, 0],
+        corner,
+        0,
+        0,
+        1,
+        Filter::Nearest,
+    )
+    .unwrap();
+    let command_buffer = blur
+        .record(builder, &targets, radius, radius as f32 / 2.0)
+        .blit_image(
+            hdr,
+            [0, 0, 0],
+            corner,
+            0,
+            0,
+            staging.clone(),
+            [0, 0, 0],
+            corner,
+            0,
+            0,
+            1,
+            Filter::Nearest,
+        )
+        .unwrap()
+        .copy_image_to_buffer(staging, pixels.clone())
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    sync::now(device)
+        .then_execute(queue, command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap()
+        .wait(None)
+        .unwrap();
+    println!(
+        "Blurred {}x{} with radius {} in {:.2} ms",
+        width,
+        height,
+        radius,
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+
+    let blurred = pixels.read().unwrap().to_vec();
+    image::RgbaImage::from_raw(width, height, blurred)
+        .unwrap()
+        .save(&output)
+        .unwrap();
+    println!("Saved {}", output);
+}
@@ -0,0 +1,75 @@
+use std::time::Instant;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::sync::{self, GpuFuture};
+use vulkano_triangle::gpusort::{Entry, Order, Sorter};
+use vulkano_triangle::logger;
+use vulkano_triangle::renderer;
+use vulkano_triangle::trace::Random;
+
+// Sorts random keys on the GPU without a window and checks the result on
+// the CPU, e.g. `cargo run --example compute -- 100000`.
+fn main() {
+    logger::init("info");
+    let count = std::env::args()
+        .nth(1)
+        .map(|count| count.parse().unwrap())
+        .unwrap_or(1 << 16);
+
+    let (device, queue) = renderer::headless_device();
+    println!("Using device: {}", device.physical_device().name());
+
+    let mut random = Random(0x9e37_79b9);
+    let entries: Vec<Entry> = (0..count)
+        .map(|value| Entry {
+            key: random.next(),
+            value,
+        })
+        .collect();
+
+    let buffer = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage {
+            storage_buffer: true,
+            ..BufferUsage::none()
+        },
+        entries.into_iter(),
+    )
+    .unwrap();
+
+    let sorter = Sorter::new(device.clone()).unwrap();
+    let set = sorter.descriptor_set(buffer.clone()).unwrap();
+    let command_buffer = sorter
+        .record(
+            AutoCommandBufferBuilder::primary_one_time_submit(
+                device.clone(),
+                queue.family(),
+            )
+            .unwrap(),
+            set,
+            count,
+            Order::Ascending,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    sync::now(device)
+        .then_execute(queue, command_buffer)
+        .unwrap()
+        .then_signal_fence_and_flush()
+        .unwrap()
+        .wait(None)
+        .unwrap();
+    println!(
+        "Sorted {} entries in {:.2} ms",
+        count,
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+
+    let sorted = buffer.read().unwrap();
+    assert!(sorted.windows(2).all(|pair| pair[0].key <= pair[1].key));
+    assert!(sorted.iter().all(|entry| entry.value < count));
+    println!("Order verified");
+}
@@ -0,0 +1,97 @@
+use cgmath::{Matrix4, SquareMatrix};
+use std::time::Instant;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano_triangle::dbgpipe;
+use vulkano_triangle::error::Error;
+use vulkano_triangle::logger;
+use vulkano_triangle::offscreen::{self, Offscreen};
+use vulkano_triangle::renderer;
+use vulkano_triangle::snapshot::Snapshot;
+use vulkano_triangle::transparent;
+
+// Renders a snapshot to a PNG without a window or swapchain, e.g.
+// `cargo run --example headless -- state.json out.png 1280 720`.
+fn main() -> Result<(), Error> {
+    logger::init("info");
+    let mut args = std::env::args().skip(1);
+    let usage = "usage: headless <state.json> <out> [width height]";
+    let state = Snapshot::load(args.next().expect(usage)).unwrap();
+    let output = args.next().expect(usage);
+    let width = args.next().map(|w| w.parse().unwrap()).unwrap_or(800);
+    let height = args.next().map(|h| h.parse().unwrap()).unwrap_or(600);
+
+    let (device, queue) = renderer::headless_device();
+    println!("Using device: {}", device.physical_device().name());
+
+    let target = Offscreen::new(device.clone(), queue, [width, height]);
+    let pipeline =
+        dbgpipe::build_for_format(device.clone(), offscreen::FORMAT)?;
+    let framebuffer = target
+        .framebuffer(pipeline.render_pass.clone(), Some(dbgpipe::DEPTH_FORMAT));
+    let vertices = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::vertex_buffer(),
+        state
+            .scene
+            .iter()
+            .map(|&position| dbgpipe::Vertex { position }),
+    )
+    .unwrap();
+    let set = dbgpipe::view_set(
+        device,
+        &pipeline,
+        state.camera.view_projection().into(),
+    );
+    let dynamic_state = target.dynamic_state();
+    let instances = transparent::draw_list(
+        &state.transparent,
+        &state.camera.view(),
+        state.camera.cull_mask,
+    );
+
+    let start = Instant::now();
+    let image = target.render(|builder| {
+        let mut builder = builder
+            .begin_render_pass(
+                framebuffer,
+                false,
+                vec![[0.0, 0.0, 1.0, 1.0].into(), 1.0f32.into()],
+            )
+            .unwrap()
+            .draw(
+                pipeline.pipeline.clone(),
+                &dynamic_state,
+                vec![vertices.clone()],
+                vec![set.clone()],
+                dbgpipe::vs::ty::Push {
+                    model: Matrix4::identity().into(),
+                },
+            )
+            .unwrap();
+        for instance in &instances {
+            builder = builder
+                .draw(
+                    pipeline.transparent.clone(),
+                    &dynamic_state,
+                    vec![vertices.clone()],
+                    vec![set.clone()],
+                    dbgpipe::TransparentPush {
+                        model: instance.model().into(),
+                        color: instance.color,
+                    },
+                )
+                .unwrap();
+        }
+        builder.end_render_pass().unwrap()
+    });
+    println!(
+        "Rendered {}x{} in {:.2} ms",
+        width,
+        height,
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+
+    image.save(&output).unwrap();
+    println!("Saved {}", output);
+    Ok(())
+}
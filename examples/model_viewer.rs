@@ -0,0 +1,136 @@
+use cgmath::{Matrix4, SquareMatrix};
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano_triangle::dbgpipe;
+use vulkano_triangle::error::Error;
+use vulkano_triangle::logger;
+use vulkano_triangle::renderer::{self, App, Options, Renderer};
+use vulkano_triangle::snapshot::Snapshot;
+use vulkano_triangle::transparent;
+use winit::event::VirtualKeyCode;
+use winit::event_loop::EventLoop;
+
+// Draws the opaque scene and sorted transparent instances of a snapshot,
+// e.g. `cargo run --example model_viewer -- state.json`.
+struct ModelViewer {
+    state: Snapshot,
+    pipeline: dbgpipe::Pipeline,
+    vertices: Arc<CpuAccessibleBuffer<[dbgpipe::Vertex]>>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    wireframe: bool,
+}
+
+impl App for ModelViewer {
+    fn resized(&mut self, renderer: &Renderer) {
+        self.framebuffers = renderer.framebuffers(
+            self.pipeline.render_pass.clone(),
+            Some(dbgpipe::DEPTH_FORMAT),
+        );
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &Renderer,
+        image_num: usize,
+        builder: AutoCommandBufferBuilder,
+    ) -> AutoCommandBufferBuilder {
+        let dynamic_state = renderer.dynamic_state();
+        let opaque = match &self.pipeline.wireframe {
+            Some(wireframe) if self.wireframe => wireframe,
+            _ => &self.pipeline.pipeline,
+        };
+        let mut builder = builder
+            .begin_render_pass(
+                self.framebuffers[image_num].clone(),
+                false,
+                vec![[0.0, 0.0, 1.0, 1.0].into(), 1.0f32.into()],
+            )
+            .unwrap()
+            .draw(
+                opaque.clone(),
+                &dynamic_state,
+                vec![self.vertices.clone()],
+                vec![self.set.clone()],
+                dbgpipe::vs::ty::Push {
+                    model: Matrix4::identity().into(),
+                },
+            )
+            .unwrap();
+
+        let instances = transparent::draw_list(
+            &self.state.transparent,
+            &self.state.camera.view(),
+            self.state.camera.cull_mask,
+        );
+        for instance in &instances {
+            builder = builder
+                .draw(
+                    self.pipeline.transparent.clone(),
+                    &dynamic_state,
+                    vec![self.vertices.clone()],
+                    vec![self.set.clone()],
+                    dbgpipe::TransparentPush {
+                        model: instance.model().into(),
+                        color: instance.color,
+                    },
+                )
+                .unwrap();
+        }
+        builder.end_render_pass().unwrap()
+    }
+
+    fn key_pressed(&mut self, key: VirtualKeyCode) {
+        if key == VirtualKeyCode::W {
+            self.wireframe = !self.wireframe;
+        }
+    }
+}
+
+fn main() -> Result<(), Error> {
+    logger::init("info");
+    let state = match std::env::args().nth(1) {
+        Some(path) => Snapshot::load(&path).unwrap(),
+        None => Snapshot::default(),
+    };
+
+    let events_loop = EventLoop::new();
+    let renderer = Renderer::new(
+        &events_loop,
+        &Options {
+            title: "model viewer".to_owned(),
+            force_sdr: state.force_sdr,
+            ..Options::default()
+        },
+    )?;
+
+    let pipeline =
+        dbgpipe::build(renderer.device.clone(), renderer.swapchain.clone())?;
+    let vertices = CpuAccessibleBuffer::from_iter(
+        renderer.device.clone(),
+        BufferUsage::vertex_buffer(),
+        state
+            .scene
+            .iter()
+            .map(|&position| dbgpipe::Vertex { position }),
+    )
+    .unwrap();
+    let set = dbgpipe::view_set(
+        renderer.device.clone(),
+        &pipeline,
+        state.camera.view_projection().into(),
+    );
+
+    let app = ModelViewer {
+        state,
+        pipeline,
+        vertices,
+        set,
+        framebuffers: Vec::new(),
+        wireframe: false,
+    };
+    renderer::run(renderer, events_loop, app)
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+use std::time::Instant;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano_triangle::camera::Camera;
+use vulkano_triangle::dbgpipe;
+use vulkano_triangle::error::Error;
+use vulkano_triangle::logger;
+use vulkano_triangle::particles::{Emitter, System};
+use vulkano_triangle::renderer::{self, App, Options, Renderer};
+use winit::event::VirtualKeyCode;
+use winit::event_loop::EventLoop;
+
+struct Particles {
+    pipeline: dbgpipe::Pipeline,
+    system: System,
+    emitter: Emitter,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    last_frame: Instant,
+    frame: u32,
+}
+
+impl App for Particles {
+    fn resized(&mut self, renderer: &Renderer) {
+        self.framebuffers = renderer.framebuffers(
+            self.pipeline.render_pass.clone(),
+            Some(dbgpipe::DEPTH_FORMAT),
+        );
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &Renderer,
+        image_num: usize,
+        builder: AutoCommandBufferBuilder,
+    ) -> AutoCommandBufferBuilder {
+        let now = Instant::now();
+        let dt = (now - self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        self.frame = self.frame.wrapping_add(1);
+
+        let builder = self
+            .system
+            .update(builder, &self.emitter, dt, self.frame)
+            .unwrap();
+        let builder = self
+            .system
+            .sort(builder, &Camera::default().view())
+            .unwrap();
+        let builder = builder
+            .begin_render_pass(
+                self.framebuffers[image_num].clone(),
+                false,
+                vec![[0.0, 0.0, 0.0, 1.0].into(), 1.0f32.into()],
+            )
+            .unwrap();
+        self.system
+            .draw(
+                builder,
+                &renderer.dynamic_state(),
+                self.set.clone(),
+                &self.emitter,
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+    }
+
+    fn key_pressed(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Up => self.emitter.rate *= 2.0,
+            VirtualKeyCode::Down => self.emitter.rate *= 0.5,
+            _ => (),
+        }
+    }
+}
+
+fn main() -> Result<(), Error> {
+    logger::init("info");
+    let events_loop = EventLoop::new();
+    let renderer = Renderer::new(
+        &events_loop,
+        &Options {
+            title: "particles".to_owned(),
+            ..Options::default()
+        },
+    )?;
+
+    let emitter = Emitter {
+        enabled: true,
+        ..Emitter::default()
+    };
+    let pipeline =
+        dbgpipe::build(renderer.device.clone(), renderer.swapchain.clone())?;
+    let system =
+        System::new(renderer.device.clone(), &pipeline, emitter.count)?;
+    let set = dbgpipe::view_set(
+        renderer.device.clone(),
+        &pipeline,
+        Camera::default().view_projection().into(),
+    );
+
+    let app = Particles {
+        pipeline,
+        system,
+        emitter,
+        set,
+        framebuffers: Vec::new(),
+        last_frame: Instant::now(),
+        frame: 0,
+    };
+    renderer::run(renderer, events_loop, app)
+}
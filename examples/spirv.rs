@@ -0,0 +1,148 @@
+use cgmath::{Matrix4, SquareMatrix};
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::{
+    DescriptorSet, PersistentDescriptorSet,
+};
+use vulkano::framebuffer::{FramebufferAbstract, Subpass};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano_triangle::dbgpipe;
+use vulkano_triangle::error::Error;
+use vulkano_triangle::logger;
+use vulkano_triangle::renderer::{self, App, Options, Renderer};
+use vulkano_triangle::spirv;
+use winit::event_loop::EventLoop;
+
+// Draws a triangle with shaders loaded from SPIR-V files at runtime, e.g.
+// `glslc shaders/debug.vert -o vert.spv`, then
+// `cargo run --example spirv -- vert.spv frag.spv`.
+//
+// The pipeline's description is reflected from the modules, but the draw
+// still feeds them dbgpipe's inputs: a vec4 position at location 0, the
+// view-projection uniform at set 0 binding 0 and a mat4 model push
+// constant.
+struct Triangle {
+    // Only its render pass is used.
+    debug: dbgpipe::Pipeline,
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    vertices: Arc<CpuAccessibleBuffer<[dbgpipe::Vertex]>>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+}
+
+impl App for Triangle {
+    fn resized(&mut self, renderer: &Renderer) {
+        self.framebuffers = renderer.framebuffers(
+            self.debug.render_pass.clone(),
+            Some(dbgpipe::DEPTH_FORMAT),
+        );
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &Renderer,
+        image_num: usize,
+        builder: AutoCommandBufferBuilder,
+    ) -> AutoCommandBufferBuilder {
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_num].clone(),
+                false,
+                vec![[0.0, 0.0, 1.0, 1.0].into(), 1.0f32.into()],
+            )
+            .unwrap()
+            .draw(
+                self.pipeline.clone(),
+                &renderer.dynamic_state(),
+                vec![self.vertices.clone()],
+                vec![self.set.clone()],
+                dbgpipe::vs::ty::Push {
+                    model: Matrix4::identity().into(),
+                },
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+    }
+}
+
+fn main() -> Result<(), Error> {
+    logger::init("info");
+    let mut args = std::env::args().skip(1);
+    let usage = "usage: spirv <vertex.spv> <fragment.spv>";
+    let vertex_path = args.next().expect(usage);
+    let fragment_path = args.next().expect(usage);
+
+    let events_loop = EventLoop::new();
+    let renderer = Renderer::new(
+        &events_loop,
+        &Options {
+            title: "spirv".to_owned(),
+            ..Options::default()
+        },
+    )?;
+    let device = renderer.device.clone();
+
+    let (vertex_module, vertex) =
+        spirv::load_reflected(device.clone(), &vertex_path)?;
+    let (fragment_module, fragment) =
+        spirv::load_reflected(device.clone(), &fragment_path)?;
+    let (vs, fs) = unsafe {
+        (
+            vertex.entry_point(&vertex_module),
+            fragment.entry_point(&fragment_module),
+        )
+    };
+
+    let debug = dbgpipe::build(device.clone(), renderer.swapchain.clone())?;
+    let pipeline = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<dbgpipe::Vertex>()
+            .vertex_shader(vs, ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .depth_stencil_simple_depth()
+            .fragment_shader(fs, ())
+            .render_pass(Subpass::from(debug.render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("runtime spirv"))?,
+    );
+
+    let vertices = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::vertex_buffer(),
+        vec![
+            [-0.5, 0.5, 0.0, 1.0],
+            [0.0, -0.5, 0.0, 1.0],
+            [0.5, 0.5, 0.0, 1.0],
+        ]
+        .into_iter()
+        .map(|position| dbgpipe::Vertex { position }),
+    )
+    .unwrap();
+    let vp = CpuAccessibleBuffer::from_data(
+        device.clone(),
+        BufferUsage::uniform_buffer(),
+        dbgpipe::ViewBlock {
+            vp: Matrix4::identity().into(),
+        },
+    )
+    .unwrap();
+    let set = Arc::new(
+        PersistentDescriptorSet::start(pipeline.clone(), 0)
+            .add_buffer(vp)
+            .unwrap()
+            .build()
+            .unwrap(),
+    );
+
+    let app = Triangle {
+        debug,
+        pipeline,
+        vertices,
+        set,
+        framebuffers: Vec::new(),
+    };
+    renderer::run(renderer, events_loop, app)
+}
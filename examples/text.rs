@@ -0,0 +1,149 @@
+use std::sync::Arc;
+use std::time::Instant;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::format::Format;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::image::{Dimensions, ImmutableImage};
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+use vulkano::sync::GpuFuture;
+use vulkano_triangle::bmpfont;
+use vulkano_triangle::bmptxtpipe;
+use vulkano_triangle::error::Error;
+use vulkano_triangle::logger;
+use vulkano_triangle::renderer::{self, App, Options, Renderer};
+use winit::event_loop::EventLoop;
+
+const SCALE: f32 = 4.0;
+
+struct Text {
+    pipeline: bmptxtpipe::Pipeline,
+    mvp_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
+    font_set: Arc<dyn DescriptorSet + Send + Sync>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    scale: f32,
+    start: Instant,
+    last_frame: Instant,
+}
+
+impl App for Text {
+    fn resized(&mut self, renderer: &Renderer) {
+        self.framebuffers =
+            renderer.framebuffers(self.pipeline.render_pass.clone(), None);
+
+        // Glyphs stay the same apparent size on high-DPI displays but
+        // cover more pixels.
+        self.scale = SCALE * renderer.scale_factor() as f32;
+
+        // Pixel coordinates with the origin in the top left corner.
+        let [width, height] = renderer.images[0].dimensions();
+        let mvp =
+            cgmath::ortho(0.0, width as f32, 0.0, height as f32, -1.0, 1.0);
+        self.mvp_set = Some(bmptxtpipe::mvp_set(
+            renderer.device.clone(),
+            &self.pipeline,
+            mvp.into(),
+        ));
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &Renderer,
+        image_num: usize,
+        builder: AutoCommandBufferBuilder,
+    ) -> AutoCommandBufferBuilder {
+        let now = Instant::now();
+        let frame_ms = (now - self.last_frame).as_secs_f64() * 1000.0;
+        self.last_frame = now;
+
+        let text = format!(
+            "vulkano triangle\nframe: {:.2} ms\nuptime: {:.1} s",
+            frame_ms,
+            (now - self.start).as_secs_f32()
+        );
+        let vertices = CpuAccessibleBuffer::from_iter(
+            renderer.device.clone(),
+            BufferUsage::vertex_buffer(),
+            bmpfont::quads(&text, [4.0 * self.scale; 2], self.scale)
+                .into_iter(),
+        )
+        .unwrap();
+
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_num].clone(),
+                false,
+                vec![[0.0, 0.0, 0.0, 1.0].into()],
+            )
+            .unwrap()
+            .draw(
+                self.pipeline.pipeline.clone(),
+                &renderer.dynamic_state(),
+                vec![vertices],
+                (self.mvp_set.clone().unwrap(), self.font_set.clone()),
+                (),
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+    }
+}
+
+fn main() -> Result<(), Error> {
+    logger::init("info");
+    let events_loop = EventLoop::new();
+    let renderer = Renderer::new(
+        &events_loop,
+        &Options {
+            title: "text".to_owned(),
+            ..Options::default()
+        },
+    )?;
+    let device = renderer.device.clone();
+
+    let pipeline =
+        bmptxtpipe::build(device.clone(), renderer.swapchain.clone())?;
+
+    let atlas = bmpfont::atlas();
+    let (width, height) = atlas.dimensions();
+    let (font, upload) = ImmutableImage::from_iter(
+        atlas.into_raw().into_iter(),
+        Dimensions::Dim2d { width, height },
+        Format::R8G8B8A8Unorm,
+        renderer.queue.clone(),
+    )
+    .unwrap();
+    upload
+        .then_signal_fence_and_flush()
+        .unwrap()
+        .wait(None)
+        .unwrap();
+
+    let sampler = Sampler::new(
+        device.clone(),
+        Filter::Nearest,
+        Filter::Nearest,
+        MipmapMode::Nearest,
+        SamplerAddressMode::ClampToEdge,
+        SamplerAddressMode::ClampToEdge,
+        SamplerAddressMode::ClampToEdge,
+        0.0,
+        1.0,
+        0.0,
+        0.0,
+    )
+    .unwrap();
+    let font_set = bmptxtpipe::bitmap_set(&pipeline, font, sampler);
+
+    let app = Text {
+        pipeline,
+        mvp_set: None,
+        font_set,
+        framebuffers: Vec::new(),
+        scale: SCALE,
+        start: Instant::now(),
+        last_frame: Instant::now(),
+    };
+    renderer::run(renderer, events_loop, app)
+}
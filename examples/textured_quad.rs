@@ -0,0 +1,137 @@
+use cgmath::{Matrix4, SquareMatrix};
+use image::{Rgba, RgbaImage};
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::format::Format;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::image::{Dimensions, ImmutableImage};
+use vulkano::sampler::Sampler;
+use vulkano::sync::GpuFuture;
+use vulkano_triangle::bmptxtpipe;
+use vulkano_triangle::error::Error;
+use vulkano_triangle::logger;
+use vulkano_triangle::renderer::{self, App, Options, Renderer};
+use winit::event_loop::EventLoop;
+
+struct TexturedQuad {
+    pipeline: bmptxtpipe::Pipeline,
+    vertices: Arc<CpuAccessibleBuffer<[bmptxtpipe::Vertex]>>,
+    sets: (
+        Arc<dyn DescriptorSet + Send + Sync>,
+        Arc<dyn DescriptorSet + Send + Sync>,
+    ),
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+}
+
+impl App for TexturedQuad {
+    fn resized(&mut self, renderer: &Renderer) {
+        self.framebuffers =
+            renderer.framebuffers(self.pipeline.render_pass.clone(), None);
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &Renderer,
+        image_num: usize,
+        builder: AutoCommandBufferBuilder,
+    ) -> AutoCommandBufferBuilder {
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_num].clone(),
+                false,
+                vec![[0.1, 0.1, 0.1, 1.0].into()],
+            )
+            .unwrap()
+            .draw(
+                self.pipeline.pipeline.clone(),
+                &renderer.dynamic_state(),
+                vec![self.vertices.clone()],
+                self.sets.clone(),
+                (),
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+    }
+}
+
+fn checker(size: u32, cells: u32) -> RgbaImage {
+    RgbaImage::from_fn(size, size, |x, y| {
+        if (x * cells / size + y * cells / size) % 2 == 0 {
+            Rgba([230, 230, 230, 255])
+        } else {
+            Rgba([200, 40, 40, 255])
+        }
+    })
+}
+
+fn main() -> Result<(), Error> {
+    logger::init("info");
+    let events_loop = EventLoop::new();
+    let renderer = Renderer::new(
+        &events_loop,
+        &Options {
+            title: "textured quad".to_owned(),
+            ..Options::default()
+        },
+    )?;
+    let device = renderer.device.clone();
+
+    let pipeline =
+        bmptxtpipe::build(device.clone(), renderer.swapchain.clone())?;
+
+    let (texture, upload) = ImmutableImage::from_iter(
+        checker(256, 8).into_raw().into_iter(),
+        Dimensions::Dim2d {
+            width: 256,
+            height: 256,
+        },
+        Format::R8G8B8A8Srgb,
+        renderer.queue.clone(),
+    )
+    .unwrap();
+    upload
+        .then_signal_fence_and_flush()
+        .unwrap()
+        .wait(None)
+        .unwrap();
+
+    let vertices = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::vertex_buffer(),
+        vec![
+            ([-0.5, -0.5], [0.0, 0.0]),
+            ([0.5, -0.5], [1.0, 0.0]),
+            ([0.5, 0.5], [1.0, 1.0]),
+            ([-0.5, -0.5], [0.0, 0.0]),
+            ([0.5, 0.5], [1.0, 1.0]),
+            ([-0.5, 0.5], [0.0, 1.0]),
+        ]
+        .into_iter()
+        .map(|(position, uv)| bmptxtpipe::Vertex { position, uv }),
+    )
+    .unwrap();
+
+    let sets = (
+        bmptxtpipe::mvp_set(
+            device.clone(),
+            &pipeline,
+            Matrix4::identity().into(),
+        ),
+        bmptxtpipe::bitmap_set(
+            &pipeline,
+            texture,
+            Sampler::simple_repeat_linear(device),
+        ),
+    );
+
+    let app = TexturedQuad {
+        pipeline,
+        vertices,
+        sets,
+        framebuffers: Vec::new(),
+    };
+    renderer::run(renderer, events_loop, app)
+}
@@ -0,0 +1,94 @@
+use cgmath::{Matrix4, SquareMatrix};
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano_triangle::dbgpipe;
+use vulkano_triangle::error::Error;
+use vulkano_triangle::logger;
+use vulkano_triangle::renderer::{self, App, Options, Renderer};
+use winit::event_loop::EventLoop;
+
+struct Triangle {
+    pipeline: dbgpipe::Pipeline,
+    vertices: Arc<CpuAccessibleBuffer<[dbgpipe::Vertex]>>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+}
+
+impl App for Triangle {
+    fn resized(&mut self, renderer: &Renderer) {
+        self.framebuffers = renderer.framebuffers(
+            self.pipeline.render_pass.clone(),
+            Some(dbgpipe::DEPTH_FORMAT),
+        );
+    }
+
+    fn draw(
+        &mut self,
+        renderer: &Renderer,
+        image_num: usize,
+        builder: AutoCommandBufferBuilder,
+    ) -> AutoCommandBufferBuilder {
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_num].clone(),
+                false,
+                vec![[0.0, 0.0, 1.0, 1.0].into(), 1.0f32.into()],
+            )
+            .unwrap()
+            .draw(
+                self.pipeline.pipeline.clone(),
+                &renderer.dynamic_state(),
+                vec![self.vertices.clone()],
+                vec![self.set.clone()],
+                dbgpipe::vs::ty::Push {
+                    model: Matrix4::identity().into(),
+                },
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+    }
+}
+
+fn main() -> Result<(), Error> {
+    logger::init("info");
+    let events_loop = EventLoop::new();
+    let renderer = Renderer::new(
+        &events_loop,
+        &Options {
+            title: "triangle".to_owned(),
+            ..Options::default()
+        },
+    )?;
+
+    let pipeline =
+        dbgpipe::build(renderer.device.clone(), renderer.swapchain.clone())?;
+    let vertices = CpuAccessibleBuffer::from_iter(
+        renderer.device.clone(),
+        BufferUsage::vertex_buffer(),
+        vec![
+            [-0.5, 0.5, 0.0, 1.0],
+            [0.0, -0.5, 0.0, 1.0],
+            [0.5, 0.5, 0.0, 1.0],
+        ]
+        .into_iter()
+        .map(|position| dbgpipe::Vertex { position }),
+    )
+    .unwrap();
+    let set = dbgpipe::view_set(
+        renderer.device.clone(),
+        &pipeline,
+        Matrix4::identity().into(),
+    );
+
+    let app = Triangle {
+        pipeline,
+        vertices,
+        set,
+        framebuffers: Vec::new(),
+    };
+    renderer::run(renderer, events_loop, app)
+}
@@ -0,0 +1,15 @@
+#version 450
+
+layout (location = 0) in vec4 position;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+layout (push_constant) uniform Push {
+    mat4 model;
+} push;
+
+void main() {
+    gl_Position = vp_inst.vp * push.model * position;
+}
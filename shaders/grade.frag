@@ -0,0 +1,65 @@
+#version 450
+#include <tonemap.glsl>
+
+layout (location = 0) in vec2 uv;
+
+layout (set = 0, binding = 0) uniform sampler2D scene;
+layout (set = 0, binding = 1) uniform sampler3D lut;
+layout (set = 0, binding = 2) uniform sampler2D depth_map;
+layout (set = 0, binding = 3) uniform sampler2D albedo_map;
+layout (set = 0, binding = 4) uniform sampler2D normal_map;
+layout (set = 0, binding = 5) uniform sampler2D material_map;
+layout (set = 0, binding = 6) uniform sampler2D overdraw_map;
+
+// encoding: see hdr::Output, paper_white: nits of SDR white on HDR displays
+layout (constant_id = 0) const uint encoding = 0;
+layout (constant_id = 1) const float paper_white = 200.0;
+
+// mode: see debugview::DebugView, available: bitmask of views with data
+layout (push_constant) uniform View {
+    uint mode;
+    uint available;
+} view;
+
+layout (location = 0) out vec4 f_color;
+
+vec3 heat(float t) {
+    return clamp(vec3(t * 3.0, t * 3.0 - 1.0, t * 3.0 - 2.0), 0.0, 1.0);
+}
+
+void main() {
+    if (view.mode != 0 && (view.available & (1u << view.mode)) == 0) {
+        float checker = mod(floor(uv.x * 32.0) + floor(uv.y * 32.0), 2.0);
+        f_color = vec4(mix(vec3(1.0, 0.0, 1.0), vec3(0.0), checker), 1.0);
+        return;
+    }
+
+    switch (view.mode) {
+    case 1:
+        f_color = vec4(vec3(texture(depth_map, uv).r), 1.0);
+        return;
+    case 2:
+        f_color = vec4(texture(normal_map, uv).xyz * 0.5 + 0.5, 1.0);
+        return;
+    case 3:
+        f_color = vec4(texture(albedo_map, uv).rgb, 1.0);
+        return;
+    case 4:
+        f_color = vec4(vec3(texture(material_map, uv).r), 1.0);
+        return;
+    case 5:
+        f_color = vec4(heat(texture(overdraw_map, uv).r / 8.0), 1.0);
+        return;
+    }
+
+    vec4 color = texture(scene, uv);
+    float size = float(textureSize(lut, 0).x);
+    vec3 coord = clamp(color.rgb, 0.0, 1.0) * ((size - 1.0) / size)
+        + 0.5 / size;
+    vec3 graded = texture(lut, coord).rgb;
+    if (encoding != 0) {
+        // keep highlights above SDR white instead of clipping them
+        graded += max(color.rgb - 1.0, 0.0);
+    }
+    f_color = vec4(encode_output(graded, encoding, paper_white), color.a);
+}
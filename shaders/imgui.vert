@@ -0,0 +1,20 @@
+#version 450
+
+layout (location = 0) in vec2 position;
+layout (location = 1) in vec2 uv;
+layout (location = 2) in vec4 color;
+
+// Maps ImGui's display rectangle to clip space.
+layout (push_constant) uniform Push {
+    vec2 scale;
+    vec2 translate;
+} push;
+
+layout (location = 0) out vec2 v_uv;
+layout (location = 1) out vec4 v_color;
+
+void main() {
+    v_uv = uv;
+    v_color = color;
+    gl_Position = vec4(position * push.scale + push.translate, 0.0, 1.0);
+}
@@ -0,0 +1,17 @@
+// Shared lighting terms. Directions point from the light into the scene.
+
+float lambert(vec3 normal, vec3 light_direction) {
+    return max(dot(normal, -normalize(light_direction)), 0.0);
+}
+
+// fog: x start, y end, z density, w mode (0 off, 1 linear, 2 exp); see
+// fog::Fog::params
+float fog_factor(vec4 fog, float distance) {
+    if (fog.w == 1.0) {
+        return clamp((distance - fog.x) / max(fog.y - fog.x, 1e-5), 0.0, 1.0);
+    }
+    if (fog.w == 2.0) {
+        return 1.0 - exp(-fog.z * distance);
+    }
+    return 0.0;
+}
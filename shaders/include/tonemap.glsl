@@ -0,0 +1,27 @@
+// Output encodings for the swapchain; see hdr::Output.
+
+// SMPTE ST 2084 inverse EOTF, from absolute nits
+vec3 pq(vec3 nits) {
+    const float m1 = 0.1593017578125;
+    const float m2 = 78.84375;
+    const float c1 = 0.8359375;
+    const float c2 = 18.8515625;
+    const float c3 = 18.6875;
+    vec3 y = pow(clamp(nits / 10000.0, 0.0, 1.0), vec3(m1));
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3(m2));
+}
+
+// encoding: 0 sdr, 1 scRGB, 2 HDR10; paper_white: nits of SDR white
+vec3 encode_output(vec3 linear, uint encoding, float paper_white) {
+    const mat3 bt709_to_bt2020 = mat3(
+        0.6274, 0.0691, 0.0164,
+        0.3293, 0.9195, 0.0880,
+        0.0433, 0.0114, 0.8956);
+    switch (encoding) {
+    case 1:
+        return linear * (paper_white / 80.0);
+    case 2:
+        return pq(bt709_to_bt2020 * linear * paper_white);
+    }
+    return linear;
+}
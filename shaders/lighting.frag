@@ -0,0 +1,127 @@
+#version 450
+#include <lighting.glsl>
+
+// see gbufpipe::MAX_LOCAL_LIGHTS
+#define MAX_LOCAL_LIGHTS 8
+
+layout (location = 0) in vec2 uv;
+
+layout (input_attachment_index = 0, set = 0, binding = 0)
+    uniform subpassInput u_albedo;
+layout (input_attachment_index = 1, set = 0, binding = 1)
+    uniform subpassInput u_normal;
+layout (input_attachment_index = 2, set = 0, binding = 2)
+    uniform subpassInput u_material;
+
+layout (input_attachment_index = 3, set = 0, binding = 3)
+    uniform subpassInput u_depth;
+
+layout (set = 1, binding = 0) uniform SCENE_BLOCK {
+    mat4 inverse_view_projection;
+    vec4 eye;
+    vec4 light_direction;
+    vec4 light_color;
+    vec4 fog_color;
+    // x: start, y: end, z: density, w: mode (0 off, 1 linear, 2 exp)
+    vec4 fog_params;
+    // x: how many local lights are set
+    vec4 local_count;
+    // xyz: position, w: range, 0 when unlimited
+    vec4 local_position[MAX_LOCAL_LIGHTS];
+    // rgb: color, w: cosine of a spot light's inner cone angle
+    vec4 local_color[MAX_LOCAL_LIGHTS];
+    // xyz: spot direction, w: cosine of the outer cone angle, below -1 for
+    // point lights
+    vec4 local_direction[MAX_LOCAL_LIGHTS];
+} scene;
+
+// see probes::ProbeGrid; ambient holds 6 axis colors per probe
+layout (set = 1, binding = 1) uniform PROBE_BLOCK {
+    vec4 grid_min;
+    vec4 grid_step;
+    vec4 grid_dims;
+    vec4 ambient[192];
+} probes;
+
+layout (location = 0) out vec4 f_color;
+
+vec3 ambient_cube(int probe, vec3 n) {
+    vec3 n2 = n * n;
+    ivec3 negative = ivec3(lessThan(n, vec3(0.0)));
+    int base = probe * 6;
+    return n2.x * probes.ambient[base + negative.x].rgb
+        + n2.y * probes.ambient[base + 2 + negative.y].rgb
+        + n2.z * probes.ambient[base + 4 + negative.z].rgb;
+}
+
+vec3 probe_irradiance(vec3 position, vec3 normal) {
+    ivec3 dims = ivec3(probes.grid_dims.xyz);
+    vec3 cell = clamp(
+        (position - probes.grid_min.xyz) / probes.grid_step.xyz,
+        vec3(0.0),
+        vec3(dims - 1));
+    ivec3 base = min(ivec3(floor(cell)), dims - 2);
+    vec3 t = cell - vec3(base);
+
+    vec3 irradiance = vec3(0.0);
+    for (int corner = 0; corner < 8; corner++) {
+        ivec3 offset = ivec3(corner & 1, (corner >> 1) & 1, corner >> 2);
+        ivec3 probe = base + offset;
+        vec3 weights = mix(1.0 - t, t, vec3(offset));
+        int index = probe.x + dims.x * (probe.y + dims.y * probe.z);
+        irradiance += weights.x * weights.y * weights.z
+            * ambient_cube(index, normal);
+    }
+    return irradiance;
+}
+
+// Inverse square falloff, windowed to reach zero at the light's range, as
+// KHR_lights_punctual suggests.
+vec3 local_light(int i, vec3 position, vec3 normal) {
+    vec3 to_light = scene.local_position[i].xyz - position;
+    float distance = max(length(to_light), 1e-4);
+    float range = scene.local_position[i].w;
+    float window = range > 0.0
+        ? clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0)
+        : 1.0;
+    float attenuation = window * window / (distance * distance);
+
+    float outer = scene.local_direction[i].w;
+    if (outer >= -1.0) {
+        float inner = scene.local_color[i].w;
+        float angle = dot(
+            normalize(scene.local_direction[i].xyz),
+            -to_light / distance);
+        attenuation *= clamp((angle - outer) / max(inner - outer, 1e-4),
+            0.0, 1.0);
+    }
+    return scene.local_color[i].rgb * attenuation
+        * lambert(normal, -to_light);
+}
+
+void main() {
+    vec4 albedo = subpassLoad(u_albedo);
+    vec3 normal = subpassLoad(u_normal).xyz;
+    vec4 material = subpassLoad(u_material);
+    float depth = subpassLoad(u_depth).r;
+
+    vec4 world = scene.inverse_view_projection
+        * vec4(uv * 2.0 - 1.0, depth, 1.0);
+    vec3 position = world.xyz / world.w;
+    float distance = length(position - scene.eye.xyz);
+
+    float n_dot_l = lambert(normal, scene.light_direction.xyz);
+    vec3 ambient = probe_irradiance(position, normal);
+    vec3 direct = scene.light_color.rgb * n_dot_l;
+    int local_count = min(int(scene.local_count.x), MAX_LOCAL_LIGHTS);
+    for (int i = 0; i < local_count; i++) {
+        direct += local_light(i, position, normal);
+    }
+    vec3 lit = albedo.rgb * (ambient * material.r + direct);
+    vec3 color = mix(albedo.rgb, lit, material.g);
+
+    float fog = fog_factor(scene.fog_params, distance);
+    color = mix(color, scene.fog_color.rgb, fog);
+
+    f_color = vec4(color, albedo.a);
+}
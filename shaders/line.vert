@@ -0,0 +1,15 @@
+#version 450
+
+layout (location = 0) in vec4 position;
+layout (location = 1) in vec4 color;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+layout (location = 0) out vec4 v_color;
+
+void main() {
+    v_color = color;
+    gl_Position = vp_inst.vp * position;
+}
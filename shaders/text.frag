@@ -0,0 +1,10 @@
+#version 450
+layout (location = 0) in vec2 uv;
+
+layout (set = 1, binding = 1) uniform sampler2D bitmap;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    f_color = texture(bitmap, uv);
+}
@@ -0,0 +1,15 @@
+#version 450
+
+layout (location = 0) in vec2 position;
+layout (location = 1) in vec2 uv;
+
+layout (set = 0, binding = 0) uniform MVP_BLOCK {
+    mat4 mvp;
+} mvp_inst;
+
+layout (location = 0) out vec2 out_uv;
+
+void main() {
+    gl_Position = mvp_inst.mvp * vec4(position, 0, 1);
+    out_uv = uv;
+}
@@ -0,0 +1,11 @@
+#version 450
+
+layout (push_constant) uniform Push {
+    layout (offset = 64) vec4 color;
+} push;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    f_color = push.color;
+}
@@ -0,0 +1,436 @@
+use crate::skinpipe;
+use cgmath::InnerSpace;
+use cgmath::Matrix4;
+use cgmath::One;
+use cgmath::Quaternion;
+use cgmath::SquareMatrix;
+use cgmath::Vector3;
+use cgmath::Vector4;
+use cgmath::VectorSpace;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Pose {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Pose {
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(
+                self.scale.x,
+                self.scale.y,
+                self.scale.z,
+            )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub parent: Option<usize>,
+    pub rest: Pose,
+    // Model space to the joint's space at bind time.
+    pub inverse_bind: Matrix4<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+}
+
+impl Skeleton {
+    // One skinning matrix per joint for `poses`, as `skinpipe` expects.
+    pub fn palette(&self, poses: &[Pose]) -> Vec<Matrix4<f32>> {
+        let mut world: Vec<Option<Matrix4<f32>>> = vec![None; poses.len()];
+        (0..self.joints.len())
+            .map(|i| {
+                self.world(i, poses, &mut world) * self.joints[i].inverse_bind
+            })
+            .collect()
+    }
+
+    // Joint `i`'s model space transform, memoized in `world`, since glTF
+    // doesn't order joints parents first.
+    fn world(
+        &self,
+        i: usize,
+        poses: &[Pose],
+        world: &mut [Option<Matrix4<f32>>],
+    ) -> Matrix4<f32> {
+        if let Some(matrix) = world[i] {
+            return matrix;
+        }
+        let local = poses[i].matrix();
+        let matrix = match self.joints[i].parent {
+            Some(parent) => self.world(parent, poses, world) * local,
+            None => local,
+        };
+        world[i] = Some(matrix);
+        matrix
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    // Values come in threes per key: in-tangent, value, out-tangent.
+    CubicSpline,
+}
+
+// Keyframes for one property of one joint. Vectors are stored with w = 0,
+// rotations as x, y, z, w.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub joint: usize,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub values: Vec<[f32; 4]>,
+}
+
+impl Channel {
+    pub fn sample(&self, time: f32) -> Vector4<f32> {
+        let value = |i: usize| match self.interpolation {
+            Interpolation::CubicSpline => Vector4::from(self.values[i * 3 + 1]),
+            _ => Vector4::from(self.values[i]),
+        };
+        let (key, next, t, span) = locate(&self.times, time);
+        let sampled = match self.interpolation {
+            Interpolation::Step => value(key),
+            Interpolation::Linear if self.property == Property::Rotation => {
+                let a = quaternion(value(key));
+                let b = quaternion(value(next));
+                let q = a.slerp(b, t);
+                Vector4::new(q.v.x, q.v.y, q.v.z, q.s)
+            }
+            Interpolation::Linear => value(key).lerp(value(next), t),
+            Interpolation::CubicSpline => {
+                let out_tangent = Vector4::from(self.values[key * 3 + 2]);
+                let in_tangent = Vector4::from(self.values[next * 3]);
+                let [a, b, c, d] = hermite(t, span);
+                value(key) * a
+                    + out_tangent * b
+                    + value(next) * c
+                    + in_tangent * d
+            }
+        };
+        if self.property == Property::Rotation {
+            sampled.normalize()
+        } else {
+            sampled
+        }
+    }
+}
+
+// Blend shape weights over time, `targets` values per key.
+#[derive(Debug, Clone)]
+pub struct WeightChannel {
+    pub targets: usize,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub values: Vec<f32>,
+}
+
+impl WeightChannel {
+    pub fn sample(&self, time: f32) -> Vec<f32> {
+        let n = self.targets;
+        let (key, next, t, span) = locate(&self.times, time);
+        let value = |k: usize, i: usize| match self.interpolation {
+            Interpolation::CubicSpline => self.values[(k * 3 + 1) * n + i],
+            _ => self.values[k * n + i],
+        };
+        (0..n)
+            .map(|i| match self.interpolation {
+                Interpolation::Step => value(key, i),
+                Interpolation::Linear => {
+                    value(key, i) + (value(next, i) - value(key, i)) * t
+                }
+                Interpolation::CubicSpline => {
+                    let out_tangent = self.values[(key * 3 + 2) * n + i];
+                    let in_tangent = self.values[next * 3 * n + i];
+                    let [a, b, c, d] = hermite(t, span);
+                    value(key, i) * a
+                        + out_tangent * b
+                        + value(next, i) * c
+                        + in_tangent * d
+                }
+            })
+            .collect()
+    }
+}
+
+// The keys either side of `time`, how far it is between them from 0 to 1,
+// and the time between them. Both keys are the first or last one outside
+// the keyed range.
+fn locate(times: &[f32], time: f32) -> (usize, usize, f32, f32) {
+    let last = times.len() - 1;
+    match times.iter().position(|&t| t > time) {
+        Some(0) => (0, 0, 0.0, 0.0),
+        Some(next) => {
+            let key = next - 1;
+            let span = times[next] - times[key];
+            (key, next, (time - times[key]) / span, span)
+        }
+        None => (last, last, 0.0, 0.0),
+    }
+}
+
+// Cubic Hermite basis for value, out-tangent, next value and next
+// in-tangent, with the tangents scaled by the key spacing as glTF asks.
+fn hermite(t: f32, span: f32) -> [f32; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [
+        2.0 * t3 - 3.0 * t2 + 1.0,
+        span * (t3 - 2.0 * t2 + t),
+        -2.0 * t3 + 3.0 * t2,
+        span * (t3 - t2),
+    ]
+}
+
+fn quaternion(v: Vector4<f32>) -> Quaternion<f32> {
+    Quaternion::new(v.w, v.x, v.y, v.z)
+}
+
+#[derive(Debug, Clone)]
+pub struct Clip {
+    pub name: String,
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+    pub weights: Option<WeightChannel>,
+}
+
+impl Clip {
+    // The skeleton's rest pose with this clip's channels applied at `time`.
+    pub fn pose(&self, skeleton: &Skeleton, time: f32) -> Vec<Pose> {
+        let mut poses = skeleton
+            .joints
+            .iter()
+            .map(|joint| joint.rest)
+            .collect::<Vec<_>>();
+        for channel in &self.channels {
+            let value = channel.sample(time);
+            let pose = &mut poses[channel.joint];
+            match channel.property {
+                Property::Translation => pose.translation = value.truncate(),
+                Property::Rotation => pose.rotation = quaternion(value),
+                Property::Scale => pose.scale = value.truncate(),
+            }
+        }
+        poses
+    }
+}
+
+// A skinned mesh with the skeleton and clips that animate it.
+pub struct SkinnedModel {
+    pub vertices: Vec<skinpipe::Vertex>,
+    pub skeleton: Skeleton,
+    pub clips: Vec<Clip>,
+    // Position offsets per blend shape, one per vertex each.
+    pub morph_targets: Vec<Vec<[f32; 4]>>,
+    // Blend shape weights when no clip animates them.
+    pub weights: Vec<f32>,
+}
+
+// Plays one clip at a time, advanced by the fixed update step.
+pub struct AnimationPlayer {
+    clips: Vec<Clip>,
+    current: usize,
+    time: f32,
+    pub speed: f32,
+    pub looping: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(clips: Vec<Clip>) -> AnimationPlayer {
+        AnimationPlayer {
+            clips,
+            current: 0,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+
+    pub fn clips(&self) -> &[Clip] {
+        &self.clips
+    }
+
+    // Restarts from the beginning of clip `index`, if there is one.
+    pub fn play(&mut self, index: usize) {
+        if index < self.clips.len() {
+            self.current = index;
+            self.time = 0.0;
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let duration = match self.clips.get(self.current) {
+            Some(clip) => clip.duration,
+            None => return,
+        };
+        self.time += dt * self.speed;
+        if self.looping && duration > 0.0 {
+            self.time = self.time.rem_euclid(duration);
+        } else {
+            self.time = self.time.min(duration).max(0.0);
+        }
+    }
+
+    // Blend shape weights for the current time; `defaults` when the clip
+    // doesn't animate them.
+    pub fn weights(&self, defaults: &[f32]) -> Vec<f32> {
+        self.clips
+            .get(self.current)
+            .and_then(|clip| clip.weights.as_ref())
+            .map_or_else(|| defaults.to_vec(), |w| w.sample(self.time))
+    }
+
+    // Skinning matrices for the current time; the rest pose without clips.
+    pub fn palette(&self, skeleton: &Skeleton) -> Vec<Matrix4<f32>> {
+        let poses = match self.clips.get(self.current) {
+            Some(clip) => clip.pose(skeleton, self.time),
+            None => skeleton.joints.iter().map(|joint| joint.rest).collect(),
+        };
+        if poses.is_empty() {
+            return vec![Matrix4::identity()];
+        }
+        skeleton.palette(&poses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(interpolation: Interpolation, values: Vec<[f32; 4]>) -> Channel {
+        Channel {
+            joint: 0,
+            property: Property::Translation,
+            interpolation,
+            times: vec![1.0, 3.0],
+            values,
+        }
+    }
+
+    fn linear() -> Channel {
+        channel(
+            Interpolation::Linear,
+            vec![[0.0, 0.0, 0.0, 0.0], [4.0, 2.0, 0.0, 0.0]],
+        )
+    }
+
+    #[test]
+    fn linear_interpolates_between_keys() {
+        assert_eq!(linear().sample(2.0), Vector4::new(2.0, 1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn times_outside_the_keys_hold_the_end_values() {
+        let channel = linear();
+        assert_eq!(channel.sample(-5.0), Vector4::new(0.0, 0.0, 0.0, 0.0));
+        assert_eq!(channel.sample(3.0), Vector4::new(4.0, 2.0, 0.0, 0.0));
+        assert_eq!(channel.sample(10.0), Vector4::new(4.0, 2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn step_holds_until_the_next_key() {
+        let channel = channel(
+            Interpolation::Step,
+            vec![[1.0, 0.0, 0.0, 0.0], [2.0, 0.0, 0.0, 0.0]],
+        );
+        assert_eq!(channel.sample(2.9).x, 1.0);
+        assert_eq!(channel.sample(3.0).x, 2.0);
+    }
+
+    #[test]
+    fn cubic_spline_passes_through_its_keys() {
+        // In-tangent, value, out-tangent per key.
+        let channel = channel(
+            Interpolation::CubicSpline,
+            vec![
+                [9.0, 0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0, 0.0],
+                [5.0, 0.0, 0.0, 0.0],
+                [-5.0, 0.0, 0.0, 0.0],
+                [3.0, 0.0, 0.0, 0.0],
+                [9.0, 0.0, 0.0, 0.0],
+            ],
+        );
+        assert_eq!(channel.sample(1.0).x, 1.0);
+        assert!((channel.sample(3.0 - 1e-6).x - 3.0).abs() < 1e-4);
+        assert_eq!(channel.sample(0.0).x, 1.0);
+        assert_eq!(channel.sample(4.0).x, 3.0);
+    }
+
+    #[test]
+    fn rotations_stay_normalized() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let channel = Channel {
+            property: Property::Rotation,
+            ..channel(
+                Interpolation::Linear,
+                vec![[0.0, 0.0, 0.0, 1.0], [0.0, half, 0.0, half]],
+            )
+        };
+        let sampled = channel.sample(2.0);
+        assert!((sampled.magnitude() - 1.0).abs() < 1e-5);
+        assert!(sampled.y > 0.0 && sampled.y < half);
+    }
+
+    #[test]
+    fn weights_outside_the_keys_hold_the_end_values() {
+        let weights = WeightChannel {
+            targets: 2,
+            interpolation: Interpolation::Linear,
+            times: vec![0.0, 1.0],
+            values: vec![0.0, 1.0, 1.0, 0.0],
+        };
+        assert_eq!(weights.sample(-1.0), vec![0.0, 1.0]);
+        assert_eq!(weights.sample(0.5), vec![0.5, 0.5]);
+        assert_eq!(weights.sample(2.0), vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn player_clamps_unless_looping() {
+        let clip = Clip {
+            name: "clip".to_owned(),
+            duration: 2.0,
+            channels: Vec::new(),
+            weights: Some(WeightChannel {
+                targets: 1,
+                interpolation: Interpolation::Linear,
+                times: vec![0.0, 2.0],
+                values: vec![0.0, 2.0],
+            }),
+        };
+        let mut player = AnimationPlayer::new(vec![clip]);
+        player.update(3.0);
+        assert_eq!(player.weights(&[]), vec![1.0]);
+        player.looping = false;
+        player.update(3.0);
+        assert_eq!(player.weights(&[]), vec![2.0]);
+        player.speed = -1.0;
+        player.update(5.0);
+        assert_eq!(player.weights(&[]), vec![0.0]);
+    }
+}
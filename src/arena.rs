@@ -0,0 +1,214 @@
+use crate::transfer::Uploader;
+use std::ops::Range;
+use std::sync::Arc;
+use vulkano::buffer::BufferSlice;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::buffer::DeviceLocalBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::sync::GpuFuture;
+
+// Element range of an arena, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocation {
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl Allocation {
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.len
+    }
+}
+
+// First-fit list of the free element ranges, kept sorted and merged.
+#[derive(Debug, Clone)]
+struct FreeList {
+    ranges: Vec<Range<usize>>,
+    capacity: usize,
+}
+
+impl FreeList {
+    fn new(capacity: usize) -> FreeList {
+        FreeList {
+            ranges: vec![0..capacity],
+            capacity,
+        }
+    }
+
+    fn allocate(&mut self, len: usize) -> Option<Allocation> {
+        let index = self.ranges.iter().position(|range| range.len() >= len)?;
+        let offset = self.ranges[index].start;
+        self.ranges[index].start += len;
+        if self.ranges[index].is_empty() {
+            self.ranges.remove(index);
+        }
+        Some(Allocation { offset, len })
+    }
+
+    // Returns the range to the list, merging it with its neighbours.
+    fn free(&mut self, allocation: Allocation) {
+        let range = allocation.range();
+        let index = self
+            .ranges
+            .iter()
+            .position(|free| free.start > range.start)
+            .unwrap_or(self.ranges.len());
+        self.ranges.insert(index, range);
+        if index + 1 < self.ranges.len()
+            && self.ranges[index].end == self.ranges[index + 1].start
+        {
+            self.ranges[index].end = self.ranges.remove(index + 1).end;
+        }
+        if index > 0 && self.ranges[index - 1].end == self.ranges[index].start {
+            self.ranges[index - 1].end = self.ranges.remove(index).end;
+        }
+    }
+
+    fn used(&self) -> usize {
+        self.capacity
+            - self.ranges.iter().map(|range| range.len()).sum::<usize>()
+    }
+}
+
+// Sub-allocates many meshes out of one device-local buffer with a
+// first-fit free list, so meshes share a single allocation and binding.
+pub struct Arena<T> {
+    pub buffer: Arc<DeviceLocalBuffer<[T]>>,
+    free: FreeList,
+}
+
+impl<T> Arena<T>
+where
+    T: Send + Sync + 'static,
+{
+    pub fn new(
+        uploader: &Uploader,
+        capacity: usize,
+        usage: BufferUsage,
+    ) -> Arena<T> {
+        let buffer = DeviceLocalBuffer::array(
+            uploader.transfer.device().clone(),
+            capacity,
+            BufferUsage {
+                transfer_destination: true,
+                ..usage
+            },
+            uploader.families(),
+        )
+        .unwrap();
+        Arena {
+            buffer,
+            free: FreeList::new(capacity),
+        }
+    }
+
+    pub fn allocate(&mut self, len: usize) -> Option<Allocation> {
+        self.free.allocate(len)
+    }
+
+    pub fn free(&mut self, allocation: Allocation) {
+        self.free.free(allocation)
+    }
+
+    pub fn used(&self) -> usize {
+        self.free.used()
+    }
+
+    pub fn slice(
+        &self,
+        allocation: Allocation,
+    ) -> BufferSlice<[T], Arc<DeviceLocalBuffer<[T]>>> {
+        BufferSlice::from_typed_buffer_access(self.buffer.clone())
+            .slice(allocation.range())
+            .unwrap()
+    }
+
+    // Allocates room for `data` and copies it in on the transfer queue.
+    // None when the arena has no free range large enough.
+    pub fn upload(
+        &mut self,
+        uploader: &Uploader,
+        data: Vec<T>,
+    ) -> Option<(Allocation, Box<dyn GpuFuture>)> {
+        let allocation = self.allocate(data.len())?;
+        let device = uploader.transfer.device().clone();
+        let source = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_source(),
+            data.into_iter(),
+        )
+        .unwrap();
+
+        let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
+            device,
+            uploader.transfer.family(),
+        )
+        .unwrap()
+        .copy_buffer(source, self.slice(allocation))
+        .unwrap()
+        .build()
+        .unwrap();
+
+        Some((allocation, uploader.finish(command_buffer)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take(list: &mut FreeList, len: usize) -> Allocation {
+        list.allocate(len).unwrap()
+    }
+
+    #[test]
+    fn allocate_is_first_fit() {
+        let mut list = FreeList::new(10);
+        let a = take(&mut list, 4);
+        let b = take(&mut list, 4);
+        assert_eq!(a, Allocation { offset: 0, len: 4 });
+        assert_eq!(b, Allocation { offset: 4, len: 4 });
+        assert_eq!(list.allocate(3), None);
+        list.free(a);
+        assert_eq!(take(&mut list, 2), Allocation { offset: 0, len: 2 });
+        assert_eq!(list.used(), 6);
+    }
+
+    #[test]
+    fn free_between_two_free_ranges_merges_all_three() {
+        let mut list = FreeList::new(12);
+        let a = take(&mut list, 4);
+        let b = take(&mut list, 4);
+        let c = take(&mut list, 4);
+        list.free(a);
+        list.free(c);
+        assert_eq!(list.ranges, vec![0..4, 8..12]);
+        list.free(b);
+        assert_eq!(list.ranges, vec![0..12]);
+        assert_eq!(list.used(), 0);
+    }
+
+    #[test]
+    fn free_merges_with_one_neighbour() {
+        let mut list = FreeList::new(12);
+        let a = take(&mut list, 4);
+        let b = take(&mut list, 4);
+        list.free(b);
+        assert_eq!(list.ranges, vec![4..12]);
+        list.free(a);
+        assert_eq!(list.ranges, vec![0..12]);
+    }
+
+    #[test]
+    fn free_keeps_separate_ranges_apart() {
+        let mut list = FreeList::new(12);
+        let a = take(&mut list, 4);
+        let _b = take(&mut list, 4);
+        let c = take(&mut list, 4);
+        list.free(c);
+        list.free(a);
+        assert_eq!(list.ranges, vec![0..4, 8..12]);
+        assert_eq!(list.used(), 4);
+    }
+}
@@ -0,0 +1,675 @@
+use crate::arena::Allocation;
+use crate::arena::Arena;
+use crate::dbgpipe;
+use crate::error::Error;
+use crate::error::Result;
+#[cfg(feature = "hot-reload")]
+use crate::hotreload::AssetWatcher;
+use crate::reflect::Reflection;
+use crate::spirv;
+use crate::transfer::Uploader;
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use tracing::debug;
+use tracing::error;
+use tracing::info;
+#[cfg(feature = "hot-reload")]
+use tracing::warn;
+use vulkano::buffer::BufferSlice;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::DeviceLocalBuffer;
+use vulkano::format::Format;
+use vulkano::image::Dimensions;
+use vulkano::image::ImmutableImage;
+use vulkano::pipeline::shader::ShaderModule;
+use vulkano::sync::GpuFuture;
+
+// Threads decoding files for the async loads.
+const WORKERS: usize = 2;
+
+pub type MeshSlice =
+    BufferSlice<[dbgpipe::Vertex], Arc<DeviceLocalBuffer<[dbgpipe::Vertex]>>>;
+
+#[derive(Clone)]
+pub struct Texture {
+    pub image: Arc<ImmutableImage<Format>>,
+    pub dimensions: [u32; 2],
+}
+
+// Vertices in the manager's mesh arena.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mesh {
+    pub allocation: Allocation,
+}
+
+pub struct Shader {
+    pub module: Arc<ShaderModule>,
+    pub reflection: Reflection,
+}
+
+// A counted reference to a loaded asset. The asset stays loaded while any
+// clone of its handle is alive.
+pub struct Handle<T> {
+    id: u64,
+    count: Arc<()>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle {
+            id: self.id,
+            count: self.count.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({})", self.id)
+    }
+}
+
+struct Entry<T> {
+    path: PathBuf,
+    value: T,
+    // Still showing a placeholder while a worker decodes the file.
+    loading: bool,
+    // One count is the manager's own; the rest are handles.
+    count: Arc<()>,
+}
+
+struct Storage<T> {
+    entries: HashMap<u64, Entry<T>>,
+    paths: HashMap<PathBuf, u64>,
+}
+
+impl<T> Storage<T> {
+    fn new() -> Storage<T> {
+        Storage {
+            entries: HashMap::new(),
+            paths: HashMap::new(),
+        }
+    }
+
+    fn find(&self, path: &Path) -> Option<Handle<T>> {
+        let id = *self.paths.get(path)?;
+        Some(handle(id, &self.entries[&id].count))
+    }
+
+    fn insert(
+        &mut self,
+        id: u64,
+        path: PathBuf,
+        value: T,
+        loading: bool,
+    ) -> Handle<T> {
+        let count = Arc::new(());
+        let handle = handle(id, &count);
+        self.paths.insert(path.clone(), id);
+        self.entries.insert(
+            id,
+            Entry {
+                path,
+                value,
+                loading,
+                count,
+            },
+        );
+        handle
+    }
+
+    fn get(&self, handle: &Handle<T>) -> &T {
+        &self.entries[&handle.id].value
+    }
+
+    fn loading(&self, handle: &Handle<T>) -> bool {
+        self.entries[&handle.id].loading
+    }
+
+    fn id(&self, path: &Path) -> Option<u64> {
+        self.paths.get(path).cloned()
+    }
+
+    // Swaps a finished load in for the placeholder or previous version and
+    // hands back whichever value is no longer needed: the replaced one, or
+    // the new one when the asset was retired while it loaded.
+    fn finish(&mut self, id: u64, value: Option<T>) -> Option<T> {
+        let entry = match self.entries.get_mut(&id) {
+            Some(entry) => entry,
+            None => return value,
+        };
+        entry.loading = false;
+        value.map(|value| std::mem::replace(&mut entry.value, value))
+    }
+
+    // Removes and returns the assets no handle refers to any more.
+    fn unreferenced(&mut self) -> Vec<(PathBuf, T)> {
+        let ids = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| Arc::strong_count(&entry.count) == 1)
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+        ids.into_iter()
+            .map(|id| {
+                let entry = self.entries.remove(&id).unwrap();
+                self.paths.remove(&entry.path);
+                (entry.path, entry.value)
+            })
+            .collect()
+    }
+}
+
+fn handle<T>(id: u64, count: &Arc<()>) -> Handle<T> {
+    Handle {
+        id,
+        count: count.clone(),
+        marker: PhantomData,
+    }
+}
+
+enum Job {
+    Texture(u64, PathBuf),
+    Mesh(u64, PathBuf),
+}
+
+enum Decoded {
+    Texture(RgbaImage),
+    Mesh(Vec<[f32; 4]>),
+}
+
+enum Retired {
+    Texture(Texture),
+    Mesh(Mesh),
+    Shader(Shader),
+}
+
+// Loads textures, meshes and shaders once per path and hands out counted
+// handles to them. Assets whose last handle is dropped are retired by
+// `maintain` and only freed `frames` frames later, once every frame that
+// could have drawn with them has been waited on.
+//
+// The `_async` loads decode on worker threads and return at once; the
+// handle shows a checkerboard texture or unit cube until `maintain` has
+// uploaded the real asset. `reload` swaps a new version in the same way,
+// which with the hot-reload feature `watch_files` does for every texture
+// and mesh file written on disk.
+pub struct Assets {
+    uploader: Uploader,
+    frames: u64,
+    frame: u64,
+    next_id: u64,
+    textures: Storage<Texture>,
+    meshes: Storage<Mesh>,
+    shaders: Storage<Shader>,
+    arena: Arena<dbgpipe::Vertex>,
+    retired: VecDeque<(u64, Retired)>,
+    uploads: Vec<Box<dyn GpuFuture>>,
+    placeholder_texture: Texture,
+    placeholder_mesh: Mesh,
+    jobs: Sender<Job>,
+    decoded: Receiver<(u64, Result<Decoded>)>,
+    #[cfg(feature = "hot-reload")]
+    watcher: Option<AssetWatcher>,
+}
+
+impl Assets {
+    // `frames` is the number of frames in flight; `mesh_capacity` is in
+    // vertices, shared by every mesh.
+    pub fn new(
+        uploader: &Uploader,
+        frames: usize,
+        mesh_capacity: usize,
+    ) -> Result<Assets> {
+        let (jobs, queue) = mpsc::channel();
+        let (finished, decoded) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        for i in 0..WORKERS {
+            let queue = queue.clone();
+            let finished = finished.clone();
+            thread::Builder::new()
+                .name(format!("asset-loader-{}", i))
+                .spawn(move || worker(&queue, &finished))
+                .map_err(Error::io("starting an asset loader".to_owned()))?;
+        }
+
+        let mut arena =
+            Arena::new(uploader, mesh_capacity, BufferUsage::vertex_buffer());
+        let (allocation, cube_upload) = arena
+            .upload(
+                uploader,
+                unit_cube()
+                    .into_iter()
+                    .map(|position| dbgpipe::Vertex { position })
+                    .collect(),
+            )
+            .ok_or(Error::ArenaFull("placeholder mesh"))?;
+        let (image, checker_upload) = uploader.image(
+            checkerboard(8),
+            Dimensions::Dim2d {
+                width: 8,
+                height: 8,
+            },
+            Format::R8G8B8A8Srgb,
+        );
+
+        Ok(Assets {
+            uploader: uploader.clone(),
+            frames: frames.max(1) as u64,
+            frame: 0,
+            next_id: 0,
+            textures: Storage::new(),
+            meshes: Storage::new(),
+            shaders: Storage::new(),
+            arena,
+            retired: VecDeque::new(),
+            uploads: vec![cube_upload, checker_upload],
+            placeholder_texture: Texture {
+                image,
+                dimensions: [8, 8],
+            },
+            placeholder_mesh: Mesh { allocation },
+            jobs,
+            decoded,
+            #[cfg(feature = "hot-reload")]
+            watcher: None,
+        })
+    }
+
+    // An sRGB RGBA texture from any format the image crate reads.
+    pub fn load_texture<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<Handle<Texture>> {
+        let path = path.as_ref();
+        if let Some(handle) = self.textures.find(path) {
+            return Ok(handle);
+        }
+        let texture = self.upload_texture(decode_texture(path)?);
+        let id = self.id();
+        self.watch(path);
+        Ok(self.textures.insert(id, path.to_owned(), texture, false))
+    }
+
+    // Like `load_texture`, decoding on a worker thread. A file that fails
+    // to load is logged and leaves the placeholder in place.
+    pub fn load_texture_async<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Handle<Texture> {
+        let path = path.as_ref();
+        if let Some(handle) = self.textures.find(path) {
+            return handle;
+        }
+        let id = self.id();
+        self.watch(path);
+        self.jobs.send(Job::Texture(id, path.to_owned())).unwrap();
+        let placeholder = self.placeholder_texture.clone();
+        self.textures.insert(id, path.to_owned(), placeholder, true)
+    }
+
+    // A JSON array of vertex positions, laid out like a snapshot's scene.
+    pub fn load_mesh<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<Handle<Mesh>> {
+        let path = path.as_ref();
+        if let Some(handle) = self.meshes.find(path) {
+            return Ok(handle);
+        }
+        let mesh = self.upload_mesh(decode_mesh(path)?)?;
+        let id = self.id();
+        self.watch(path);
+        Ok(self.meshes.insert(id, path.to_owned(), mesh, false))
+    }
+
+    // Like `load_mesh`, decoding on a worker thread.
+    pub fn load_mesh_async<P: AsRef<Path>>(&mut self, path: P) -> Handle<Mesh> {
+        let path = path.as_ref();
+        if let Some(handle) = self.meshes.find(path) {
+            return handle;
+        }
+        let id = self.id();
+        self.watch(path);
+        self.jobs.send(Job::Mesh(id, path.to_owned())).unwrap();
+        let placeholder = self.placeholder_mesh;
+        self.meshes.insert(id, path.to_owned(), placeholder, true)
+    }
+
+    // A compiled SPIR-V module, see `spirv::load_reflected`.
+    pub fn load_shader<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<Handle<Shader>> {
+        let path = path.as_ref();
+        if let Some(handle) = self.shaders.find(path) {
+            return Ok(handle);
+        }
+        info!(path = %path.display(), "loading shader");
+        let (module, reflection) = spirv::load_reflected(
+            self.uploader.transfer.device().clone(),
+            path,
+        )?;
+        let id = self.id();
+        Ok(self.shaders.insert(
+            id,
+            path.to_owned(),
+            Shader { module, reflection },
+            false,
+        ))
+    }
+
+    pub fn texture(&self, handle: &Handle<Texture>) -> &Texture {
+        self.textures.get(handle)
+    }
+
+    pub fn mesh(&self, handle: &Handle<Mesh>) -> &Mesh {
+        self.meshes.get(handle)
+    }
+
+    pub fn shader(&self, handle: &Handle<Shader>) -> &Shader {
+        self.shaders.get(handle)
+    }
+
+    pub fn vertices(&self, handle: &Handle<Mesh>) -> MeshSlice {
+        self.arena.slice(self.mesh(handle).allocation)
+    }
+
+    // Decodes a loaded texture or mesh file again on a worker thread; its
+    // handles keep the current version until `maintain` swaps the new one
+    // in. Does nothing for paths that aren't loaded.
+    pub fn reload<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        if let Some(id) = self.textures.id(path) {
+            self.jobs.send(Job::Texture(id, path.to_owned())).unwrap();
+        }
+        if let Some(id) = self.meshes.id(path) {
+            self.jobs.send(Job::Mesh(id, path.to_owned())).unwrap();
+        }
+    }
+
+    // Starts reloading texture and mesh files as they change on disk,
+    // including those already loaded.
+    #[cfg(feature = "hot-reload")]
+    pub fn watch_files(&mut self) -> Result<()> {
+        let mut watcher = AssetWatcher::new()?;
+        let textures = self.textures.paths.keys();
+        for path in textures.chain(self.meshes.paths.keys()) {
+            watcher.add(path)?;
+        }
+        self.watcher = Some(watcher);
+        Ok(())
+    }
+
+    // False while an async load is still showing its placeholder.
+    pub fn texture_ready(&self, handle: &Handle<Texture>) -> bool {
+        !self.textures.loading(handle)
+    }
+
+    pub fn mesh_ready(&self, handle: &Handle<Mesh>) -> bool {
+        !self.meshes.loading(handle)
+    }
+
+    // Everything loaded since the last call, to join into the next frame's
+    // future. None when nothing was uploaded.
+    pub fn take_uploads(&mut self) -> Option<Box<dyn GpuFuture>> {
+        self.uploads.drain(..).fold(None, |joined, upload| {
+            Some(match joined {
+                Some(joined) => {
+                    Box::new(joined.join(upload)) as Box<dyn GpuFuture>
+                }
+                None => upload,
+            })
+        })
+    }
+
+    // Call once per frame: uploads what the workers finished decoding,
+    // retires assets that lost their last handle and frees those retired
+    // `frames` frames ago.
+    pub fn maintain(&mut self) {
+        #[cfg(feature = "hot-reload")]
+        {
+            let changed = self
+                .watcher
+                .as_ref()
+                .map_or_else(Vec::new, AssetWatcher::changed);
+            for path in changed {
+                self.reload(path);
+            }
+        }
+        while let Ok((id, decoded)) = self.decoded.try_recv() {
+            self.finish_load(id, decoded);
+        }
+
+        self.frame += 1;
+        let frame = self.frame;
+        let retired = self
+            .textures
+            .unreferenced()
+            .into_iter()
+            .map(|(path, texture)| (path, Retired::Texture(texture)))
+            .chain(
+                self.meshes
+                    .unreferenced()
+                    .into_iter()
+                    .map(|(path, mesh)| (path, Retired::Mesh(mesh))),
+            )
+            .chain(
+                self.shaders
+                    .unreferenced()
+                    .into_iter()
+                    .map(|(path, shader)| (path, Retired::Shader(shader))),
+            )
+            .collect::<Vec<_>>();
+        for (path, asset) in retired {
+            debug!(path = %path.display(), "asset retired");
+            self.retired.push_back((frame, asset));
+        }
+
+        while self
+            .retired
+            .front()
+            .map_or(false, |entry| frame - entry.0 >= self.frames)
+        {
+            // Textures and shaders go with their last Arc; arena ranges
+            // have to be handed back.
+            match self.retired.pop_front().unwrap().1 {
+                Retired::Texture(texture) => drop(texture),
+                Retired::Mesh(mesh) => self.free_mesh(mesh),
+                Retired::Shader(shader) => drop(shader),
+            }
+        }
+    }
+
+    fn finish_load(&mut self, id: u64, decoded: Result<Decoded>) {
+        match decoded {
+            Ok(Decoded::Texture(image)) => {
+                let texture = self.upload_texture(image);
+                if let Some(old) = self.textures.finish(id, Some(texture)) {
+                    self.retire(Retired::Texture(old));
+                }
+            }
+            Ok(Decoded::Mesh(positions)) => match self.upload_mesh(positions) {
+                Ok(mesh) => {
+                    if let Some(old) = self.meshes.finish(id, Some(mesh)) {
+                        self.retire(Retired::Mesh(old));
+                    }
+                }
+                Err(e) => {
+                    error!(error = %e, "mesh failed to upload");
+                    self.meshes.finish(id, None);
+                }
+            },
+            // A failed reload keeps the version already loaded.
+            Err(e) => {
+                error!(error = %e, "asset failed to load");
+                self.textures.finish(id, None);
+                self.meshes.finish(id, None);
+            }
+        }
+    }
+
+    // Frames still in flight may draw with `asset`.
+    fn retire(&mut self, asset: Retired) {
+        self.retired.push_back((self.frame, asset));
+    }
+
+    fn upload_texture(&mut self, image: RgbaImage) -> Texture {
+        let (width, height) = image.dimensions();
+        let (image, upload) = self.uploader.image(
+            image.into_raw(),
+            Dimensions::Dim2d { width, height },
+            Format::R8G8B8A8Srgb,
+        );
+        self.uploads.push(upload);
+        Texture {
+            image,
+            dimensions: [width, height],
+        }
+    }
+
+    fn upload_mesh(&mut self, positions: Vec<[f32; 4]>) -> Result<Mesh> {
+        let (allocation, upload) = self
+            .arena
+            .upload(
+                &self.uploader,
+                positions
+                    .into_iter()
+                    .map(|position| dbgpipe::Vertex { position })
+                    .collect(),
+            )
+            .ok_or(Error::ArenaFull("mesh asset"))?;
+        self.uploads.push(upload);
+        Ok(Mesh { allocation })
+    }
+
+    // The placeholder's range is shared by every loading mesh.
+    fn free_mesh(&mut self, mesh: Mesh) {
+        if mesh != self.placeholder_mesh {
+            self.arena.free(mesh.allocation);
+        }
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn watch(&mut self, path: &Path) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            if let Err(e) = watcher.add(path) {
+                warn!(path = %path.display(), error = %e, "not watching asset");
+            }
+        }
+    }
+
+    #[cfg(not(feature = "hot-reload"))]
+    fn watch(&mut self, _path: &Path) {}
+
+    fn id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+fn worker(
+    queue: &Mutex<Receiver<Job>>,
+    finished: &Sender<(u64, Result<Decoded>)>,
+) {
+    loop {
+        // Ends once the manager, and with it the job sender, is dropped.
+        let job = match queue.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        let result = match job {
+            Job::Texture(id, path) => {
+                (id, decode_texture(&path).map(Decoded::Texture))
+            }
+            Job::Mesh(id, path) => (id, decode_mesh(&path).map(Decoded::Mesh)),
+        };
+        if finished.send(result).is_err() {
+            return;
+        }
+    }
+}
+
+fn decode_texture(path: &Path) -> Result<RgbaImage> {
+    info!(path = %path.display(), "loading texture");
+    image::open(path)
+        .map(|image| image.to_rgba())
+        .map_err(|source| Error::Texture {
+            path: path.display().to_string(),
+            source,
+        })
+}
+
+fn decode_mesh(path: &Path) -> Result<Vec<[f32; 4]>> {
+    info!(path = %path.display(), "loading mesh");
+    File::open(path)
+        .and_then(|file| {
+            serde_json::from_reader(BufReader::new(file))
+                .map_err(io::Error::from)
+        })
+        .map_err(Error::io(format!("loading {}", path.display())))
+}
+
+// Magenta and black squares one pixel across, `size` pixels square.
+fn checkerboard(size: u32) -> Vec<u8> {
+    (0..size * size)
+        .flat_map(|i| {
+            if (i % size + i / size) % 2 == 0 {
+                vec![255, 0, 255, 255]
+            } else {
+                vec![0, 0, 0, 255]
+            }
+        })
+        .collect()
+}
+
+// Two triangles per face, centered on the origin with unit sides.
+fn unit_cube() -> Vec<[f32; 4]> {
+    let quad = [
+        (-0.5, -0.5),
+        (0.5, -0.5),
+        (0.5, 0.5),
+        (-0.5, -0.5),
+        (0.5, 0.5),
+        (-0.5, 0.5),
+    ];
+    let mut vertices = Vec::with_capacity(36);
+    for axis in 0..3 {
+        for &side in &[-0.5, 0.5] {
+            for &(u, v) in &quad {
+                // Mirrored on the negative side to keep the winding outward.
+                let (u, v) = if side < 0.0 { (v, u) } else { (u, v) };
+                let mut position = [0.0, 0.0, 0.0, 1.0];
+                position[axis] = side;
+                position[(axis + 1) % 3] = u;
+                position[(axis + 2) % 3] = v;
+                vertices.push(position);
+            }
+        }
+    }
+    vertices
+}
@@ -0,0 +1,215 @@
+use crate::camera::Camera;
+use crate::profiler::Timing;
+use crate::profiler::Track;
+use crate::telemetry;
+use crate::telemetry::FrameSample;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+// Frames rendered before measuring, so pipeline creation and first-use
+// uploads don't skew the percentiles.
+pub const WARMUP: usize = 30;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PassReport {
+    pub name: &'static str,
+    pub gpu: bool,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub frames: usize,
+    pub elapsed_s: f64,
+    pub fps: f64,
+    pub frame_p50_ms: f64,
+    pub frame_p90_ms: f64,
+    pub frame_p95_ms: f64,
+    pub frame_p99_ms: f64,
+    pub frame_max_ms: f64,
+    pub record_p50_ms: f64,
+    pub record_p95_ms: f64,
+    pub gpu_wait_p50_ms: f64,
+    pub gpu_wait_p95_ms: f64,
+    pub draws_mean: f64,
+    pub draws_max: u32,
+    pub passes: Vec<PassReport>,
+}
+
+// Renders a fixed number of frames along a scripted camera path and
+// summarizes them, for comparing builds on the same machine. Pass timings
+// come from the profiler, GPU passes from their timestamp queries.
+pub struct Benchmark {
+    frames: usize,
+    frame: usize,
+    start: Option<Instant>,
+    samples: Vec<FrameSample>,
+    passes: BTreeMap<(&'static str, bool), Vec<f64>>,
+}
+
+impl Benchmark {
+    pub fn new(frames: usize) -> Benchmark {
+        Benchmark {
+            frames,
+            frame: 0,
+            start: None,
+            samples: Vec::with_capacity(frames),
+            passes: BTreeMap::new(),
+        }
+    }
+
+    // One slow orbit of the view center with a zoom in and out, the same
+    // for every run and independent of frame rate.
+    pub fn camera(&self, base: &Camera) -> Camera {
+        let t = self.frame as f32 / (WARMUP + self.frames).max(1) as f32;
+        let angle = 2.0 * PI * t;
+        let zoom = 1.0 + 0.5 * (2.0 * angle).sin();
+        let width = (base.right - base.left) * zoom;
+        let height = (base.top - base.bottom) * zoom;
+        let center = [
+            (base.left + base.right) / 2.0 + width * 0.25 * angle.cos(),
+            (base.bottom + base.top) / 2.0 + height * 0.25 * angle.sin(),
+        ];
+        Camera {
+            left: center[0] - width / 2.0,
+            right: center[0] + width / 2.0,
+            bottom: center[1] - height / 2.0,
+            top: center[1] + height / 2.0,
+            ..*base
+        }
+    }
+
+    // Call once per presented frame with the profiler's last frame.
+    pub fn frame(&mut self, sample: FrameSample, timings: &[Timing]) {
+        self.frame += 1;
+        if self.frame <= WARMUP {
+            return;
+        }
+        self.start.get_or_insert_with(Instant::now);
+        self.samples.push(sample);
+        for timing in timings {
+            self.passes
+                .entry((timing.name, timing.track == Track::Gpu))
+                .or_insert_with(Vec::new)
+                .push(timing.duration_ms);
+        }
+    }
+
+    pub fn done(&self) -> bool {
+        self.samples.len() >= self.frames
+    }
+
+    pub fn report(&self) -> Report {
+        let sorted = |value: fn(&FrameSample) -> f64| {
+            let mut values = self.samples.iter().map(value).collect::<Vec<_>>();
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            values
+        };
+        let frame_ms = sorted(|sample| sample.frame_ms);
+        let record_ms = sorted(|sample| sample.record_ms);
+        let gpu_wait_ms = sorted(|sample| sample.gpu_wait_ms);
+        let frames = self.samples.len();
+        let elapsed_s = self
+            .start
+            .map_or(0.0, |start| start.elapsed().as_secs_f64());
+        let passes = self
+            .passes
+            .iter()
+            .map(|(&(name, gpu), durations)| {
+                let mut durations = durations.clone();
+                durations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                PassReport {
+                    name,
+                    gpu,
+                    mean_ms: durations.iter().sum::<f64>()
+                        / durations.len().max(1) as f64,
+                    p95_ms: telemetry::percentile(&durations, 0.95),
+                }
+            })
+            .collect();
+
+        Report {
+            frames,
+            elapsed_s,
+            fps: frames as f64 / elapsed_s.max(1e-9),
+            frame_p50_ms: telemetry::percentile(&frame_ms, 0.50),
+            frame_p90_ms: telemetry::percentile(&frame_ms, 0.90),
+            frame_p95_ms: telemetry::percentile(&frame_ms, 0.95),
+            frame_p99_ms: telemetry::percentile(&frame_ms, 0.99),
+            frame_max_ms: frame_ms.last().cloned().unwrap_or(0.0),
+            record_p50_ms: telemetry::percentile(&record_ms, 0.50),
+            record_p95_ms: telemetry::percentile(&record_ms, 0.95),
+            gpu_wait_p50_ms: telemetry::percentile(&gpu_wait_ms, 0.50),
+            gpu_wait_p95_ms: telemetry::percentile(&gpu_wait_ms, 0.95),
+            draws_mean: self
+                .samples
+                .iter()
+                .map(|sample| sample.draws as f64)
+                .sum::<f64>()
+                / frames.max(1) as f64,
+            draws_max: self
+                .samples
+                .iter()
+                .map(|sample| sample.draws)
+                .max()
+                .unwrap_or(0),
+            passes,
+        }
+    }
+}
+
+impl Report {
+    // JSON for a .json path, otherwise CSV with one `metric,value` row per
+    // figure and a `pass.<name>.<cpu|gpu>.<mean|p95>_ms` row per pass.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let json =
+            path.as_ref().extension().and_then(|e| e.to_str()) == Some("json");
+        let mut writer = BufWriter::new(File::create(path)?);
+        if json {
+            serde_json::to_writer_pretty(&mut writer, self)?;
+            writeln!(writer)?;
+            return writer.flush();
+        }
+        writeln!(writer, "metric,value")?;
+        let rows = [
+            ("frames", self.frames as f64),
+            ("elapsed_s", self.elapsed_s),
+            ("fps", self.fps),
+            ("frame_p50_ms", self.frame_p50_ms),
+            ("frame_p90_ms", self.frame_p90_ms),
+            ("frame_p95_ms", self.frame_p95_ms),
+            ("frame_p99_ms", self.frame_p99_ms),
+            ("frame_max_ms", self.frame_max_ms),
+            ("record_p50_ms", self.record_p50_ms),
+            ("record_p95_ms", self.record_p95_ms),
+            ("gpu_wait_p50_ms", self.gpu_wait_p50_ms),
+            ("gpu_wait_p95_ms", self.gpu_wait_p95_ms),
+            ("draws_mean", self.draws_mean),
+            ("draws_max", f64::from(self.draws_max)),
+        ];
+        for (name, value) in &rows {
+            writeln!(writer, "{},{}", name, value)?;
+        }
+        for pass in &self.passes {
+            let track = if pass.gpu { "gpu" } else { "cpu" };
+            writeln!(
+                writer,
+                "pass.{}.{}.mean_ms,{}",
+                pass.name, track, pass.mean_ms
+            )?;
+            writeln!(
+                writer,
+                "pass.{}.{}.p95_ms,{}",
+                pass.name, track, pass.p95_ms
+            )?;
+        }
+        writer.flush()
+    }
+}
@@ -0,0 +1,249 @@
+use crate::compat;
+use crate::dbgpipe;
+use crate::error::Result;
+use crate::texarray;
+use crate::texarray::Batch;
+use crate::texarray::Table;
+use cgmath::Matrix4;
+use serde::{Deserialize, Serialize};
+use std::mem;
+use std::sync::Arc;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::device::Device;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::depth_stencil::Compare;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+// Every corner of a billboard carries its center; the vertex shader
+// places the corner from the camera's axes.
+#[derive(Debug, Clone, Default)]
+pub struct Vertex {
+    pub center: [f32; 3],
+    // Offset from the center in the billboard's plane, in world units.
+    pub corner: [f32; 2],
+    pub uv: [f32; 2],
+    // `Orientation` as a number.
+    pub mode: u32,
+}
+
+vulkano::impl_vertex!(Vertex, center, corner, uv, mode);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Orientation {
+    // Faces the camera fully, for light sprites and labels.
+    Spherical,
+    // Only turns about world y, for impostors of upright objects like
+    // trees.
+    Cylindrical,
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Orientation::Spherical
+    }
+}
+
+// A camera-facing quad showing one of the sprite textures, named by its
+// path or given by index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Billboard {
+    pub position: [f32; 3],
+    pub size: [f32; 2],
+    #[serde(default)]
+    pub orientation: Orientation,
+    // Index into the sprite textures when there's no `texture` name.
+    #[serde(default)]
+    pub layer: u32,
+    #[serde(default)]
+    pub texture: Option<String>,
+}
+
+pub mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+layout (location = 0) in vec3 center;
+layout (location = 1) in vec2 corner;
+layout (location = 2) in vec2 uv;
+layout (location = 3) in uint mode;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+layout (push_constant) uniform Push {
+    mat4 view;
+    uint index;
+} push;
+
+layout (location = 0) out vec2 out_uv;
+
+void main() {
+    // The view's rows are the camera's axes in world space.
+    vec3 right = vec3(push.view[0][0], push.view[1][0], push.view[2][0]);
+    vec3 up = vec3(push.view[0][1], push.view[1][1], push.view[2][1]);
+    if (mode == 1) {
+        up = vec3(0.0, 1.0, 0.0);
+        right = normalize(right - up * dot(right, up));
+    }
+    vec3 position = center + right * corner.x + up * corner.y;
+    gl_Position = vp_inst.vp * vec4(position, 1.0);
+    out_uv = uv;
+}"
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+layout (location = 0) in vec2 uv;
+
+// texarray::CAPACITY
+layout (set = 1, binding = 0) uniform sampler2D textures[8];
+
+layout (push_constant) uniform Push {
+    mat4 view;
+    uint index;
+} push;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    f_color = texture(textures[push.index], uv);
+}
+"
+    }
+}
+
+pub struct Pipeline {
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+pub fn interface() -> compat::Interface {
+    compat::Interface {
+        bindings: vec![
+            compat::Binding {
+                set: compat::VIEW_SET,
+                binding: 0,
+                kind: compat::Kind::UniformBuffer,
+            },
+            compat::Binding {
+                set: compat::MATERIAL_SET,
+                binding: 0,
+                kind: compat::Kind::CombinedImageSampler,
+            },
+        ],
+        push_constants: mem::size_of::<vs::ty::Push>(),
+        blocks: vec![compat::Block::of::<dbgpipe::ViewBlock>(
+            compat::VIEW_SET,
+            0,
+        )],
+    }
+}
+
+// What the shaders declare. The SPIR-V isn't kept, so the view block's size
+// comes from the struct vulkano generated for it.
+pub fn shader_interface(pipeline: &Pipeline) -> compat::Interface {
+    compat::Interface {
+        blocks: vec![compat::Block::of::<vs::ty::VP_BLOCK>(
+            compat::VIEW_SET,
+            0,
+        )],
+        ..compat::reflect(&*pipeline.pipeline)
+    }
+}
+
+// Draws in the forward scene pass, blended and depth tested against opaque
+// geometry without hiding what's behind.
+pub fn build(device: Arc<Device>, scene: &dbgpipe::Pipeline) -> Pipeline {
+    let vs = vs::Shader::load(device.clone()).unwrap();
+    let fs = fs::Shader::load(device.clone()).unwrap();
+
+    let pipeline = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Vertex>()
+            .vertex_shader(vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .depth_stencil(DepthStencil {
+                depth_write: false,
+                depth_compare: Compare::Less,
+                ..DepthStencil::simple_depth_test()
+            })
+            .blend_alpha_blending()
+            .fragment_shader(fs.main_entry_point(), ())
+            .render_pass(Subpass::from(scene.render_pass.clone(), 0).unwrap())
+            .build(device)
+            .unwrap(),
+    );
+
+    Pipeline { pipeline }
+}
+
+// The billboards' vertices, grouped by texture.
+pub fn quads(
+    billboards: &[Billboard],
+    table: &Table,
+) -> Result<(Vec<Vertex>, Vec<texarray::Run>)> {
+    texarray::runs(
+        billboards,
+        |billboard| {
+            table.resolve(
+                billboard.texture.as_ref().map(String::as_str),
+                billboard.layer,
+            )
+        },
+        |billboard| {
+            let mode = billboard.orientation as u32;
+            let [w, h] = [billboard.size[0] * 0.5, billboard.size[1] * 0.5];
+            let corner = |dx: f32, dy: f32, u: f32, v: f32| Vertex {
+                center: billboard.position,
+                corner: [dx, dy],
+                uv: [u, v],
+                mode,
+            };
+            vec![
+                corner(-w, -h, 0.0, 0.0),
+                corner(w, -h, 1.0, 0.0),
+                corner(w, h, 1.0, 1.0),
+                corner(-w, -h, 0.0, 0.0),
+                corner(w, h, 1.0, 1.0),
+                corner(-w, h, 0.0, 1.0),
+            ]
+        },
+    )
+}
+
+// One draw per texture, like `spritepipe::draw`.
+pub fn draw(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &Pipeline,
+    dynamic_state: &DynamicState,
+    batches: &[Batch],
+    view_set: Arc<dyn DescriptorSet + Send + Sync>,
+    table_set: Arc<dyn DescriptorSet + Send + Sync>,
+    view: Matrix4<f32>,
+) -> AutoCommandBufferBuilder {
+    batches.iter().fold(builder, |builder, batch| {
+        builder
+            .draw(
+                pipeline.pipeline.clone(),
+                dynamic_state,
+                vec![batch.vertices.clone()],
+                vec![view_set.clone(), table_set.clone()],
+                vs::ty::Push {
+                    view: view.into(),
+                    index: batch.texture,
+                },
+            )
+            .unwrap()
+    })
+}
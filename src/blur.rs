@@ -0,0 +1,166 @@
+use std::sync::Arc;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::Dimensions;
+use vulkano::image::StorageImage;
+use vulkano::pipeline::ComputePipeline;
+use vulkano::pipeline::ComputePipelineAbstract;
+
+pub const LOCAL_SIZE: u32 = 256;
+pub const MAX_RADIUS: u32 = 32;
+pub const FORMAT: Format = Format::R16G16B16A16Sfloat;
+
+pub mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: "
+#version 450
+
+layout (local_size_x = 256) in;
+
+layout (set = 0, binding = 0, rgba16f) uniform readonly image2D source;
+layout (set = 0, binding = 1, rgba16f) uniform writeonly image2D destination;
+
+// direction: (1, 0) for rows, (0, 1) for columns
+layout (push_constant) uniform Pass {
+    ivec2 direction;
+    int radius;
+    float sigma;
+} pass;
+
+const int MAX_RADIUS = 32;
+shared vec4 tile[256 + 2 * MAX_RADIUS];
+
+void main() {
+    ivec2 along = pass.direction;
+    ivec2 across = ivec2(1) - along;
+    int extent = int(dot(imageSize(source), along));
+    int line = int(gl_WorkGroupID.y);
+    int local = int(gl_LocalInvocationID.x);
+
+    // Each group loads its span of the line plus the apron on both sides,
+    // clamping at the edges, so every tap reads shared memory.
+    int start = int(gl_WorkGroupID.x) * 256 - MAX_RADIUS;
+    for (int i = local; i < 256 + 2 * MAX_RADIUS; i += 256) {
+        int t = clamp(start + i, 0, extent - 1);
+        tile[i] = imageLoad(source, along * t + across * line);
+    }
+    barrier();
+
+    int position = int(gl_GlobalInvocationID.x);
+    if (position >= extent) {
+        return;
+    }
+
+    int radius = min(pass.radius, MAX_RADIUS);
+    vec4 sum = vec4(0.0);
+    float total = 0.0;
+    for (int offset = -radius; offset <= radius; offset++) {
+        float weight = exp(-float(offset * offset)
+            / (2.0 * pass.sigma * pass.sigma));
+        sum += tile[local + MAX_RADIUS + offset] * weight;
+        total += weight;
+    }
+    imageStore(destination, along * position + across * line, sum / total);
+}"
+    }
+}
+
+// Separable Gaussian blur of a FORMAT storage image, in place through a
+// scratch image of the same size.
+pub struct Blur {
+    pub pipeline: Arc<dyn ComputePipelineAbstract + Send + Sync>,
+}
+
+pub struct Targets {
+    pub image: Arc<StorageImage<Format>>,
+    pub dimensions: [u32; 2],
+    horizontal: Arc<dyn DescriptorSet + Send + Sync>,
+    vertical: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl Blur {
+    pub fn new(device: Arc<Device>) -> Blur {
+        let cs = cs::Shader::load(device.clone()).unwrap();
+        let pipeline = Arc::new(
+            ComputePipeline::new(device, &cs.main_entry_point(), &()).unwrap(),
+        );
+        Blur { pipeline }
+    }
+
+    pub fn targets(
+        &self,
+        device: Arc<Device>,
+        queue: &Queue,
+        image: Arc<StorageImage<Format>>,
+        dimensions: [u32; 2],
+    ) -> Targets {
+        let scratch = StorageImage::new(
+            device,
+            Dimensions::Dim2d {
+                width: dimensions[0],
+                height: dimensions[1],
+            },
+            FORMAT,
+            Some(queue.family()),
+        )
+        .unwrap();
+        let set = |source, destination| {
+            Arc::new(
+                PersistentDescriptorSet::start(self.pipeline.clone(), 0)
+                    .add_image(source)
+                    .unwrap()
+                    .add_image(destination)
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            ) as Arc<dyn DescriptorSet + Send + Sync>
+        };
+        Targets {
+            horizontal: set(image.clone(), scratch.clone()),
+            vertical: set(scratch, image.clone()),
+            image,
+            dimensions,
+        }
+    }
+
+    // `radius` is clamped to MAX_RADIUS taps on each side.
+    pub fn record(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        targets: &Targets,
+        radius: u32,
+        sigma: f32,
+    ) -> AutoCommandBufferBuilder {
+        let [width, height] = targets.dimensions;
+        let groups = |extent: u32| (extent + LOCAL_SIZE - 1) / LOCAL_SIZE;
+        let radius = radius.min(MAX_RADIUS) as i32;
+        builder
+            .dispatch(
+                [groups(width), height, 1],
+                self.pipeline.clone(),
+                targets.horizontal.clone(),
+                cs::ty::Pass {
+                    direction: [1, 0],
+                    radius,
+                    sigma,
+                },
+            )
+            .unwrap()
+            .dispatch(
+                [groups(height), width, 1],
+                self.pipeline.clone(),
+                targets.vertical.clone(),
+                cs::ty::Pass {
+                    direction: [0, 1],
+                    radius,
+                    sigma,
+                },
+            )
+            .unwrap()
+    }
+}
@@ -0,0 +1,132 @@
+use crate::bmptxtpipe::Vertex;
+use image::Rgba;
+use image::RgbaImage;
+
+// Glyph cell in atlas texels; glyphs are 3x5 with one texel of padding.
+pub const CELL: [u32; 2] = [4, 6];
+
+// Each row is three bits, most significant bit on the left.
+const GLYPHS: &[(char, [u8; 5])] = &[
+    ('?', [7, 1, 2, 0, 2]),
+    (' ', [0, 0, 0, 0, 0]),
+    ('0', [7, 5, 5, 5, 7]),
+    ('1', [2, 6, 2, 2, 7]),
+    ('2', [7, 1, 7, 4, 7]),
+    ('3', [7, 1, 7, 1, 7]),
+    ('4', [5, 5, 7, 1, 1]),
+    ('5', [7, 4, 7, 1, 7]),
+    ('6', [7, 4, 7, 5, 7]),
+    ('7', [7, 1, 1, 2, 2]),
+    ('8', [7, 5, 7, 5, 7]),
+    ('9', [7, 5, 7, 1, 7]),
+    ('A', [2, 5, 7, 5, 5]),
+    ('B', [6, 5, 6, 5, 6]),
+    ('C', [3, 4, 4, 4, 3]),
+    ('D', [6, 5, 5, 5, 6]),
+    ('E', [7, 4, 6, 4, 7]),
+    ('F', [7, 4, 6, 4, 4]),
+    ('G', [3, 4, 5, 5, 3]),
+    ('H', [5, 5, 7, 5, 5]),
+    ('I', [7, 2, 2, 2, 7]),
+    ('J', [1, 1, 1, 5, 2]),
+    ('K', [5, 5, 6, 5, 5]),
+    ('L', [4, 4, 4, 4, 7]),
+    ('M', [5, 7, 7, 5, 5]),
+    ('N', [6, 5, 5, 5, 5]),
+    ('O', [2, 5, 5, 5, 2]),
+    ('P', [6, 5, 6, 4, 4]),
+    ('Q', [2, 5, 5, 6, 3]),
+    ('R', [6, 5, 6, 5, 5]),
+    ('S', [3, 4, 2, 1, 6]),
+    ('T', [7, 2, 2, 2, 2]),
+    ('U', [5, 5, 5, 5, 7]),
+    ('V', [5, 5, 5, 5, 2]),
+    ('W', [5, 5, 7, 7, 5]),
+    ('X', [5, 5, 2, 5, 5]),
+    ('Y', [5, 5, 2, 2, 2]),
+    ('Z', [7, 1, 2, 4, 7]),
+    ('.', [0, 0, 0, 0, 2]),
+    (',', [0, 0, 0, 2, 4]),
+    (':', [0, 2, 0, 2, 0]),
+    ('-', [0, 0, 7, 0, 0]),
+    ('+', [0, 2, 7, 2, 0]),
+    ('=', [0, 7, 0, 7, 0]),
+    ('/', [1, 1, 2, 4, 4]),
+    ('%', [5, 1, 2, 4, 5]),
+    ('(', [1, 2, 2, 2, 1]),
+    (')', [4, 2, 2, 2, 4]),
+    ('<', [1, 2, 4, 2, 1]),
+    ('>', [4, 2, 1, 2, 4]),
+    ('_', [0, 0, 0, 0, 7]),
+];
+
+fn glyph_index(c: char) -> usize {
+    let c = c.to_ascii_uppercase();
+    GLYPHS.iter().position(|&(g, _)| g == c).unwrap_or(0)
+}
+
+// One row of white glyphs on a transparent background.
+pub fn atlas() -> RgbaImage {
+    let mut image = RgbaImage::new(GLYPHS.len() as u32 * CELL[0], CELL[1]);
+    for (index, (_, rows)) in GLYPHS.iter().enumerate() {
+        for (y, row) in rows.iter().enumerate() {
+            for x in 0..3 {
+                if row & (4 >> x) != 0 {
+                    image.put_pixel(
+                        index as u32 * CELL[0] + x,
+                        y as u32,
+                        Rgba([255, 255, 255, 255]),
+                    );
+                }
+            }
+        }
+    }
+    image
+}
+
+// Two triangles per character, laid out from `origin` with `scale` units
+// per atlas texel. Newlines start a new row below.
+pub fn quads(text: &str, origin: [f32; 2], scale: f32) -> Vec<Vertex> {
+    let advance = [CELL[0] as f32 * scale, CELL[1] as f32 * scale];
+    let width = 1.0 / GLYPHS.len() as f32;
+    let mut vertices = Vec::with_capacity(text.len() * 6);
+    let mut pen = origin;
+    for c in text.chars() {
+        if c == '\n' {
+            pen = [origin[0], pen[1] + advance[1]];
+            continue;
+        }
+        let u = glyph_index(c) as f32 * width;
+        let (x0, y0) = (pen[0], pen[1]);
+        let (x1, y1) = (x0 + advance[0], y0 + advance[1]);
+        let (u0, u1) = (u, u + width);
+        vertices.extend_from_slice(&[
+            Vertex {
+                position: [x0, y0],
+                uv: [u0, 0.0],
+            },
+            Vertex {
+                position: [x1, y0],
+                uv: [u1, 0.0],
+            },
+            Vertex {
+                position: [x1, y1],
+                uv: [u1, 1.0],
+            },
+            Vertex {
+                position: [x0, y0],
+                uv: [u0, 0.0],
+            },
+            Vertex {
+                position: [x1, y1],
+                uv: [u1, 1.0],
+            },
+            Vertex {
+                position: [x0, y1],
+                uv: [u0, 1.0],
+            },
+        ]);
+        pen[0] += advance[0];
+    }
+    vertices
+}
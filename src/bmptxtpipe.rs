@@ -1,9 +1,24 @@
+use crate::compat;
+use crate::entrypoint;
+use crate::error::Error;
+use crate::error::Result;
+#[cfg(feature = "hot-reload")]
+use crate::hotreload::ShaderWatcher;
+use crate::spirv;
 use std::sync::Arc;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
 use vulkano::device::Device;
 use vulkano::framebuffer::RenderPassAbstract;
 use vulkano::framebuffer::Subpass;
+use vulkano::image::ImageViewAccess;
+use vulkano::pipeline::shader::GraphicsShaderType;
+use vulkano::pipeline::shader::ShaderModule;
 use vulkano::pipeline::GraphicsPipeline;
 use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::sampler::Sampler;
 use vulkano::swapchain::Swapchain;
 use winit::window::Window;
 
@@ -18,40 +33,52 @@ vulkano::impl_vertex!(Vertex, position, uv);
 pub mod vs {
     vulkano_shaders::shader! {
         ty: "vertex",
-        src: "
-#version 450
-
-layout (location = 0) in vec2 position;
-layout (location = 1) in vec2 uv;
-
-layout (set = 0, binding = 0) uniform MVP_BLOCK {
-    mat4 mvp;
-} mvp_inst;
-
-layout (location = 0) out vec2 out_uv;
-
-void main() {
-    gl_Position = mvp_inst.mvp * vec4(position, 0, 1);
-    out_uv = uv;
-}"
+        path: "shaders/text.vert"
     }
 }
 
 pub mod fs {
     vulkano_shaders::shader! {
         ty: "fragment",
-        src: "
-#version 450
-layout (location = 0) in vec2 uv;
-
-layout (set = 1, binding = 1) uniform sampler2D bitmap;
+        path: "shaders/text.frag"
+    }
+}
 
-layout (location = 0) out vec4 f_color;
+// Files under `shaders/` that the pipeline is built from.
+pub const SOURCES: [&str; 2] = ["text.vert", "text.frag"];
 
-void main() {
-    f_color = texture(bitmap, uv); 
+// The SPIR-V for each stage, either built with the crate or recompiled
+// from `SOURCES` while running.
+pub struct Modules {
+    pub vs: Arc<ShaderModule>,
+    pub fs: Arc<ShaderModule>,
 }
-"
+
+impl Modules {
+    pub fn load(device: Arc<Device>) -> Result<Modules> {
+        Ok(Modules {
+            vs: spirv::module(
+                device.clone(),
+                "bitmap text vertex",
+                &spirv::TEXT_VERT,
+            )?,
+            fs: spirv::module(
+                device,
+                "bitmap text fragment",
+                &spirv::TEXT_FRAG,
+            )?,
+        })
+    }
+
+    #[cfg(feature = "hot-reload")]
+    pub fn compile(
+        device: Arc<Device>,
+        watcher: &mut ShaderWatcher,
+    ) -> Result<Modules> {
+        Ok(Modules {
+            vs: watcher.load(device.clone(), SOURCES[0])?,
+            fs: watcher.load(device, SOURCES[1])?,
+        })
     }
 }
 
@@ -60,13 +87,38 @@ pub struct Pipeline {
     pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
 }
 
+pub fn interface() -> compat::Interface {
+    compat::Interface {
+        bindings: vec![
+            compat::Binding {
+                set: compat::VIEW_SET,
+                binding: 0,
+                kind: compat::Kind::UniformBuffer,
+            },
+            compat::Binding {
+                set: compat::MATERIAL_SET,
+                binding: 1,
+                kind: compat::Kind::CombinedImageSampler,
+            },
+        ],
+        push_constants: 0,
+        blocks: vec![compat::Block::of::<vs::ty::MVP_BLOCK>(
+            compat::VIEW_SET,
+            0,
+        )],
+    }
+}
+
+// What the built-in SPIR-V declares.
+pub fn shader_interface() -> Result<compat::Interface> {
+    compat::reflect_spirv("bmptxtpipe", &[&spirv::TEXT_VERT, &spirv::TEXT_FRAG])
+}
+
 pub fn build(
     device: Arc<Device>,
     swapchain: Arc<Swapchain<Window>>,
-) -> Pipeline {
-    let vs = vs::Shader::load(device.clone()).unwrap();
-    let fs = fs::Shader::load(device.clone()).unwrap();
-
+) -> Result<Pipeline> {
+    let modules = Modules::load(device.clone())?;
     let render_pass = Arc::new(
         vulkano::single_pass_renderpass!(
             device.clone(),
@@ -83,23 +135,100 @@ pub fn build(
                 depth_stencil: {}
             }
         )
-        .unwrap(),
+        .map_err(Error::render_pass("bitmap text"))?,
     );
+    pipeline(device, render_pass, &modules)
+}
+
+impl Pipeline {
+    // As `dbgpipe::Pipeline::reload`.
+    pub fn reload(
+        &mut self,
+        device: Arc<Device>,
+        modules: &Modules,
+    ) -> Result<()> {
+        *self = pipeline(device, self.render_pass.clone(), modules)?;
+        Ok(())
+    }
+}
+
+fn pipeline(
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    modules: &Modules,
+) -> Result<Pipeline> {
+    let (vs, fs) = unsafe {
+        (
+            entrypoint::graphics(
+                &modules.vs,
+                vs::MainInput,
+                vs::MainOutput,
+                vs::Layout(entrypoint::vertex_stage()),
+                GraphicsShaderType::Vertex,
+            ),
+            entrypoint::graphics(
+                &modules.fs,
+                fs::MainInput,
+                fs::MainOutput,
+                fs::Layout(entrypoint::fragment_stage()),
+                GraphicsShaderType::Fragment,
+            ),
+        )
+    };
 
     let pipeline = Arc::new(
         GraphicsPipeline::start()
             .vertex_input_single_buffer::<Vertex>()
-            .vertex_shader(vs.main_entry_point(), ())
+            .vertex_shader(vs, ())
             .triangle_list()
             .viewports_dynamic_scissors_irrelevant(1)
-            .fragment_shader(fs.main_entry_point(), ())
+            .fragment_shader(fs, ())
             .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
             .build(device.clone())
-            .unwrap(),
+            .map_err(Error::pipeline("bitmap text"))?,
     );
 
-    Pipeline {
+    Ok(Pipeline {
         render_pass,
         pipeline,
-    }
+    })
+}
+
+pub fn mvp_set(
+    device: Arc<Device>,
+    pipeline: &Pipeline,
+    mvp: [[f32; 4]; 4],
+) -> Arc<dyn DescriptorSet + Send + Sync> {
+    let buffer = CpuAccessibleBuffer::from_data(
+        device,
+        BufferUsage::uniform_buffer(),
+        vs::ty::MVP_BLOCK { mvp },
+    )
+    .unwrap();
+    Arc::new(
+        PersistentDescriptorSet::start(pipeline.pipeline.clone(), 0)
+            .add_buffer(buffer)
+            .unwrap()
+            .build()
+            .unwrap(),
+    )
+}
+
+pub fn bitmap_set<I>(
+    pipeline: &Pipeline,
+    bitmap: I,
+    sampler: Arc<Sampler>,
+) -> Arc<dyn DescriptorSet + Send + Sync>
+where
+    I: ImageViewAccess + Send + Sync + 'static,
+{
+    Arc::new(
+        PersistentDescriptorSet::start(pipeline.pipeline.clone(), 1)
+            .add_empty()
+            .unwrap()
+            .add_sampled_image(bitmap, sampler)
+            .unwrap()
+            .build()
+            .unwrap(),
+    )
 }
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use tracing::info;
+use tracing::warn;
+
+pub struct Entry {
+    pub budget_ms: f64,
+    pub last_ms: f64,
+    pub over_frames: u32,
+}
+
+pub struct Budgets {
+    entries: HashMap<String, Entry>,
+    threshold: u32,
+}
+
+impl Budgets {
+    pub fn new(threshold: u32) -> Self {
+        Budgets {
+            entries: HashMap::new(),
+            threshold,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, budget_ms: f64) {
+        self.entries.insert(
+            name.to_owned(),
+            Entry {
+                budget_ms,
+                last_ms: 0.0,
+                over_frames: 0,
+            },
+        );
+    }
+
+    pub fn record(&mut self, name: &str, measured_ms: f64) {
+        let threshold = self.threshold;
+        let entry = match self.entries.get_mut(name) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        entry.last_ms = measured_ms;
+        if measured_ms <= entry.budget_ms {
+            if entry.over_frames >= threshold {
+                info!(name, measured_ms, "back within budget");
+            }
+            entry.over_frames = 0;
+            return;
+        }
+
+        entry.over_frames += 1;
+        if entry.over_frames == threshold {
+            warn!(
+                name,
+                frames = threshold,
+                measured_ms,
+                budget_ms = entry.budget_ms,
+                "over budget"
+            );
+        }
+    }
+
+    pub fn last(&self, name: &str) -> Option<f64> {
+        self.entries.get(name).map(|entry| entry.last_ms)
+    }
+
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.over_frames >= self.threshold)
+            .map(|(name, entry)| {
+                format!(
+                    "{} {:.2}/{:.2} ms",
+                    name, entry.last_ms, entry.budget_ms
+                )
+            })
+            .collect::<Vec<_>>();
+        warnings.sort();
+        warnings
+    }
+}
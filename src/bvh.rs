@@ -0,0 +1,458 @@
+use cgmath::ElementWise;
+use cgmath::InnerSpace;
+use cgmath::Vector3;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    // xyz: center, w: radius, as in `culling::Object`.
+    pub fn from_sphere(sphere: [f32; 4]) -> Aabb {
+        let [x, y, z, radius] = sphere;
+        let center = Vector3::new(x, y, z);
+        let extent = Vector3::new(radius, radius, radius);
+        Aabb {
+            min: center - extent,
+            max: center + extent,
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    // Distance along the ray to where it enters the box, 0 when it starts
+    // inside. `inverse` is one over each direction component.
+    pub fn ray(
+        &self,
+        origin: Vector3<f32>,
+        inverse: Vector3<f32>,
+    ) -> Option<f32> {
+        let a = (self.min - origin).mul_element_wise(inverse);
+        let b = (self.max - origin).mul_element_wise(inverse);
+        let near = a.x.min(b.x).max(a.y.min(b.y)).max(a.z.min(b.z));
+        let far = a.x.max(b.x).min(a.y.max(b.y)).min(a.z.max(b.z));
+        if far >= near.max(0.0) {
+            Some(near.max(0.0))
+        } else {
+            None
+        }
+    }
+
+    fn plane_side(&self, plane: &[f32; 4]) -> Side {
+        let [nx, ny, nz, d] = *plane;
+        let corner = |positive: bool| {
+            let pick = |n: f32, low: f32, high: f32| {
+                if (n >= 0.0) == positive {
+                    high
+                } else {
+                    low
+                }
+            };
+            nx * pick(nx, self.min.x, self.max.x)
+                + ny * pick(ny, self.min.y, self.max.y)
+                + nz * pick(nz, self.min.z, self.max.z)
+                + d
+        };
+        if corner(true) < 0.0 {
+            Side::Outside
+        } else if corner(false) >= 0.0 {
+            Side::Inside
+        } else {
+            Side::Straddles
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Inside,
+    Outside,
+    Straddles,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Contents {
+    Leaf(usize),
+    Branch(usize, usize),
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: Aabb,
+    parent: Option<usize>,
+    contents: Contents,
+}
+
+// Bounding volume hierarchy over items identified by their index in the
+// slice it was built from. Moving items are refit in place, which keeps
+// the tree valid but looser; build again after large changes.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    // The leaf node of each item.
+    leaves: Vec<usize>,
+}
+
+impl Bvh {
+    // Top-down, splitting each node at the median centroid along its
+    // longest axis.
+    pub fn build(bounds: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            leaves: vec![0; bounds.len()],
+        };
+        let mut items = (0..bounds.len()).collect::<Vec<_>>();
+        if !items.is_empty() {
+            bvh.build_node(&mut items, bounds, None);
+        }
+        bvh
+    }
+
+    fn build_node(
+        &mut self,
+        items: &mut [usize],
+        bounds: &[Aabb],
+        parent: Option<usize>,
+    ) -> usize {
+        let index = self.nodes.len();
+        if items.len() == 1 {
+            let item = items[0];
+            self.nodes.push(Node {
+                bounds: bounds[item],
+                parent,
+                contents: Contents::Leaf(item),
+            });
+            self.leaves[item] = index;
+            return index;
+        }
+        // Filled in once the children are built.
+        self.nodes.push(Node {
+            bounds: bounds[items[0]],
+            parent,
+            contents: Contents::Leaf(items[0]),
+        });
+
+        let centers = items
+            .iter()
+            .map(|&i| {
+                let c = bounds[i].center();
+                Aabb { min: c, max: c }
+            })
+            .fold(None, |all: Option<Aabb>, c| {
+                Some(all.map_or(c, |all| all.union(&c)))
+            })
+            .unwrap();
+        let size = centers.max - centers.min;
+        let axis = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+        items.sort_by(|&a, &b| {
+            bounds[a].center()[axis]
+                .partial_cmp(&bounds[b].center()[axis])
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let (left, right) = items.split_at_mut(items.len() / 2);
+        let left = self.build_node(left, bounds, Some(index));
+        let right = self.build_node(right, bounds, Some(index));
+        self.nodes[index].bounds =
+            self.nodes[left].bounds.union(&self.nodes[right].bounds);
+        self.nodes[index].contents = Contents::Branch(left, right);
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    // Moves `item` to `bounds`, growing or shrinking its ancestors to fit.
+    pub fn refit(&mut self, item: usize, bounds: Aabb) {
+        let mut index = self.leaves[item];
+        self.nodes[index].bounds = bounds;
+        while let Some(parent) = self.nodes[index].parent {
+            if let Contents::Branch(left, right) = self.nodes[parent].contents {
+                self.nodes[parent].bounds =
+                    self.nodes[left].bounds.union(&self.nodes[right].bounds);
+            }
+            index = parent;
+        }
+    }
+
+    // Items whose bounds are at least partly inside `planes`, given as
+    // `dot(n, p) + d >= 0` inside like `culling::frustum_planes`.
+    pub fn frustum(&self, planes: &[[f32; 4]; 6], out: &mut Vec<usize>) {
+        if !self.nodes.is_empty() {
+            self.frustum_node(0, planes, out);
+        }
+    }
+
+    fn frustum_node(
+        &self,
+        index: usize,
+        planes: &[[f32; 4]; 6],
+        out: &mut Vec<usize>,
+    ) {
+        let node = &self.nodes[index];
+        let mut inside = true;
+        for plane in planes {
+            match node.bounds.plane_side(plane) {
+                Side::Outside => return,
+                Side::Straddles => inside = false,
+                Side::Inside => (),
+            }
+        }
+        match node.contents {
+            _ if inside => self.collect(index, out),
+            Contents::Leaf(item) => out.push(item),
+            Contents::Branch(left, right) => {
+                self.frustum_node(left, planes, out);
+                self.frustum_node(right, planes, out);
+            }
+        }
+    }
+
+    // Every item under `index`, without testing.
+    fn collect(&self, index: usize, out: &mut Vec<usize>) {
+        match self.nodes[index].contents {
+            Contents::Leaf(item) => out.push(item),
+            Contents::Branch(left, right) => {
+                self.collect(left, out);
+                self.collect(right, out);
+            }
+        }
+    }
+
+    // Items whose bounds overlap `bounds`.
+    pub fn overlapping(&self, bounds: &Aabb, out: &mut Vec<usize>) {
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds.overlaps(bounds) {
+                continue;
+            }
+            match node.contents {
+                Contents::Leaf(item) => out.push(item),
+                Contents::Branch(left, right) => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+    }
+
+    // The nearest item `hit` reports a distance for, among those whose
+    // bounds the ray crosses. Nodes farther than the best hit so far are
+    // skipped, so `hit` should do the exact test.
+    pub fn raycast<F>(
+        &self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        mut hit: F,
+    ) -> Option<(usize, f32)>
+    where
+        F: FnMut(usize) -> Option<f32>,
+    {
+        let inverse = Vector3::new(
+            1.0 / direction.x,
+            1.0 / direction.y,
+            1.0 / direction.z,
+        );
+        let mut nearest: Option<(usize, f32)> = None;
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let entry = match node.bounds.ray(origin, inverse) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if nearest.map_or(false, |(_, best)| entry > best) {
+                continue;
+            }
+            match node.contents {
+                Contents::Leaf(item) => {
+                    if let Some(distance) = hit(item) {
+                        if nearest.map_or(true, |(_, best)| distance < best) {
+                            nearest = Some((item, distance));
+                        }
+                    }
+                }
+                Contents::Branch(left, right) => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        nearest
+    }
+}
+
+// Möller-Trumbore; the distance along the ray to where it crosses the
+// triangle from either side.
+pub fn ray_triangle(
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+    triangle: &[Vector3<f32>; 3],
+) -> Option<f32> {
+    let [a, b, c] = *triangle;
+    let (ab, ac) = (b - a, c - a);
+    let p = direction.cross(ac);
+    let determinant = ab.dot(p);
+    if determinant.abs() < 1e-8 {
+        return None;
+    }
+    let t = origin - a;
+    let u = t.dot(p) / determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = t.cross(ab);
+    let v = direction.dot(q) / determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = ac.dot(q) / determinant;
+    if distance >= 0.0 {
+        Some(distance)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box(x: f32) -> Aabb {
+        Aabb::from_sphere([x, 0.0, 0.0, 0.5])
+    }
+
+    #[test]
+    fn empty_tree_hits_nothing() {
+        let bvh = Bvh::build(&[]);
+        assert!(bvh.is_empty());
+        let hit = bvh.raycast(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            |_| panic!("no items to test"),
+        );
+        assert_eq!(hit, None);
+        let mut out = Vec::new();
+        bvh.overlapping(&unit_box(0.0), &mut out);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn raycast_finds_the_nearest_item() {
+        let bounds =
+            (0..8).map(|i| unit_box(i as f32 * 2.0)).collect::<Vec<_>>();
+        let bvh = Bvh::build(&bounds);
+        let origin = Vector3::new(-10.0, 0.0, 0.0);
+        let inverse = Vector3::new(1.0, f32::INFINITY, f32::INFINITY);
+        let hit = bvh.raycast(origin, Vector3::new(1.0, 0.0, 0.0), |item| {
+            bounds[item].ray(origin, inverse)
+        });
+        assert_eq!(hit, Some((0, 9.5)));
+    }
+
+    #[test]
+    fn raycast_skips_items_the_exact_test_misses() {
+        let bounds =
+            (0..4).map(|i| unit_box(i as f32 * 2.0)).collect::<Vec<_>>();
+        let bvh = Bvh::build(&bounds);
+        let origin = Vector3::new(-10.0, 0.0, 0.0);
+        let inverse = Vector3::new(1.0, f32::INFINITY, f32::INFINITY);
+        // The first two boxes are crossed but their contents missed.
+        let hit = bvh.raycast(origin, Vector3::new(1.0, 0.0, 0.0), |item| {
+            if item < 2 {
+                None
+            } else {
+                bounds[item].ray(origin, inverse)
+            }
+        });
+        assert_eq!(hit, Some((2, 13.5)));
+    }
+
+    #[test]
+    fn raycast_misses_behind_the_origin() {
+        let bvh = Bvh::build(&[unit_box(0.0)]);
+        let hit = bvh.raycast(
+            Vector3::new(5.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            |_| Some(0.0),
+        );
+        assert_eq!(hit, None);
+    }
+
+    #[test]
+    fn refit_moves_an_item() {
+        let bounds =
+            (0..4).map(|i| unit_box(i as f32 * 2.0)).collect::<Vec<_>>();
+        let mut bvh = Bvh::build(&bounds);
+        bvh.refit(3, unit_box(-20.0));
+        let mut out = Vec::new();
+        bvh.overlapping(&unit_box(-20.0), &mut out);
+        assert_eq!(out, vec![3]);
+    }
+
+    #[test]
+    fn ray_triangle_hits_from_either_side() {
+        let triangle = [
+            Vector3::new(-1.0, -1.0, 0.0),
+            Vector3::new(1.0, -1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        let forward = Vector3::new(0.0, 0.0, 1.0);
+        let front =
+            ray_triangle(Vector3::new(0.0, 0.0, -2.0), forward, &triangle);
+        let back =
+            ray_triangle(Vector3::new(0.0, 0.0, 2.0), -forward, &triangle);
+        assert_eq!(front, Some(2.0));
+        assert_eq!(back, Some(2.0));
+        let beside =
+            ray_triangle(Vector3::new(5.0, 0.0, -2.0), forward, &triangle);
+        assert_eq!(beside, None);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::layers;
+use cgmath::{
+    InnerSpace, Matrix4, Quaternion, Rad, SquareMatrix, Vector3, Vector4,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Camera {
+    pub left: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub top: f32,
+    pub near: f32,
+    pub far: f32,
+    // The eye in world space; the bounds above are relative to it.
+    #[serde(default)]
+    pub position: [f32; 3],
+    // xyzw quaternion, as in scene files
+    #[serde(default = "identity_rotation")]
+    pub rotation: [f32; 4],
+    #[serde(default = "layers::default_mask")]
+    pub cull_mask: u32,
+    // When set, `near` and `far` are distances in front of the eye and the
+    // orthographic bounds are unused.
+    #[serde(default)]
+    pub perspective: Option<Perspective>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Perspective {
+    // Vertical field of view, in radians.
+    pub yfov: f32,
+    // Width over height.
+    pub aspect: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            left: -5.0,
+            right: 5.0,
+            bottom: 5.0,
+            top: -5.0,
+            near: -1.0,
+            far: 1.0,
+            position: [0.0; 3],
+            rotation: identity_rotation(),
+            cull_mask: layers::ALL,
+            perspective: None,
+        }
+    }
+}
+
+fn identity_rotation() -> [f32; 4] {
+    [0.0, 0.0, 0.0, 1.0]
+}
+
+impl Camera {
+    // Inverse of the eye's transform, so the camera looks down its own -z.
+    pub fn view(&self) -> Matrix4<f32> {
+        let [x, y, z, w] = self.rotation;
+        let rotation = Quaternion::new(w, x, y, z).normalize();
+        Matrix4::from(rotation.conjugate())
+            * Matrix4::from_translation(-Vector3::from(self.position))
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.projection() * self.view()
+    }
+
+    pub fn projection(&self) -> Matrix4<f32> {
+        match self.perspective {
+            // cgmath's projection is OpenGL's: flip y and map depth to 0..1.
+            Some(perspective) => {
+                #[rustfmt::skip]
+                let clip = Matrix4::new(
+                    1.0, 0.0, 0.0, 0.0,
+                    0.0, -1.0, 0.0, 0.0,
+                    0.0, 0.0, 0.5, 0.0,
+                    0.0, 0.0, 0.5, 1.0,
+                );
+                clip * cgmath::perspective(
+                    Rad(perspective.yfov),
+                    perspective.aspect,
+                    self.near,
+                    self.far,
+                )
+            }
+            None => cgmath::ortho(
+                self.left,
+                self.right,
+                self.bottom,
+                self.top,
+                self.near,
+                self.far,
+            ),
+        }
+    }
+
+    // Normalized device depth of the near plane. Orthographic cameras keep
+    // cgmath's -1.
+    fn near_depth(&self) -> f32 {
+        if self.perspective.is_some() {
+            0.0
+        } else {
+            -1.0
+        }
+    }
+
+    // World space box around the view volume.
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        let inverse = self.view_projection().invert().unwrap();
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for corner in 0..8 {
+            let ndc = Vector4::new(
+                if corner & 1 == 0 { -1.0 } else { 1.0 },
+                if corner & 2 == 0 { -1.0 } else { 1.0 },
+                if corner & 4 == 0 {
+                    self.near_depth()
+                } else {
+                    1.0
+                },
+                1.0,
+            );
+            let point = inverse * ndc;
+            let point = point.truncate() / point.w;
+            for axis in 0..3 {
+                min[axis] = min[axis].min(point[axis]);
+                max[axis] = max[axis].max(point[axis]);
+            }
+        }
+        (min, max)
+    }
+
+    // World space origin and unit direction of the ray through `ndc`,
+    // starting on the near plane.
+    pub fn ray(&self, ndc: [f32; 2]) -> (Vector3<f32>, Vector3<f32>) {
+        let inverse = self.view_projection().invert().unwrap();
+        let unproject = |z: f32| {
+            let point = inverse * Vector4::new(ndc[0], ndc[1], z, 1.0);
+            point.truncate() / point.w
+        };
+        let near = unproject(self.near_depth());
+        let far = unproject(1.0);
+        (near, (far - near).normalize())
+    }
+
+    // Offsets the projection by a subpixel amount; see `jitter`.
+    pub fn jittered_view_projection(&self, jitter: [f32; 2]) -> Matrix4<f32> {
+        Matrix4::from_translation(Vector3::new(jitter[0], jitter[1], 0.0))
+            * self.view_projection()
+    }
+}
+
+pub const JITTER_PHASES: u64 = 8;
+
+fn halton(mut index: u64, base: u64) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+// Halton(2, 3) subpixel offset for a frame, in normalized device coordinates.
+pub fn jitter(frame: u64, dimensions: [u32; 2]) -> [f32; 2] {
+    let index = frame % JITTER_PHASES + 1;
+    [
+        (halton(index, 2) - 0.5) * 2.0 / dimensions[0] as f32,
+        (halton(index, 3) - 0.5) * 2.0 / dimensions[1] as f32,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector4<f32>, b: Vector4<f32>) -> bool {
+        (a - b).magnitude() < 1e-5
+    }
+
+    #[test]
+    fn view_moves_the_eye_to_the_origin() {
+        let camera = Camera {
+            position: [1.0, 2.0, 3.0],
+            ..Camera::default()
+        };
+        let eye = camera.view() * Vector4::new(1.0, 2.0, 3.0, 1.0);
+        assert!(close(eye, Vector4::new(0.0, 0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn view_undoes_the_eye_rotation() {
+        // A quarter turn about +y looks down world -x.
+        let half = std::f32::consts::FRAC_PI_4;
+        let camera = Camera {
+            rotation: [0.0, half.sin(), 0.0, half.cos()],
+            ..Camera::default()
+        };
+        let ahead = camera.view() * Vector4::new(-1.0, 0.0, 0.0, 1.0);
+        assert!(close(ahead, Vector4::new(0.0, 0.0, -1.0, 1.0)));
+    }
+
+    #[test]
+    fn bounds_follow_the_eye() {
+        let camera = Camera {
+            position: [10.0, 0.0, 0.0],
+            ..Camera::default()
+        };
+        let (min, max) = camera.bounds();
+        assert!((min[0] - 5.0).abs() < 1e-4);
+        assert!((max[0] - 15.0).abs() < 1e-4);
+        assert!((min[1] + 5.0).abs() < 1e-4);
+        assert!((max[1] - 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn perspective_depth_runs_from_near_to_far() {
+        let camera = Camera {
+            near: 0.5,
+            far: 10.0,
+            perspective: Some(Perspective {
+                yfov: 1.0,
+                aspect: 1.5,
+            }),
+            ..Camera::default()
+        };
+        let depth = |z: f32| {
+            let clip = camera.projection() * Vector4::new(0.0, 0.0, z, 1.0);
+            clip.z / clip.w
+        };
+        assert!(depth(-0.5).abs() < 1e-5);
+        assert!((depth(-10.0) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn perspective_ray_starts_on_the_near_plane() {
+        let camera = Camera {
+            near: 0.5,
+            far: 10.0,
+            perspective: Some(Perspective {
+                yfov: 1.0,
+                aspect: 1.0,
+            }),
+            ..Camera::default()
+        };
+        let (origin, direction) = camera.ray([0.0, 0.0]);
+        assert!((origin - Vector3::new(0.0, 0.0, -0.5)).magnitude() < 1e-4);
+        assert!((direction - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-4);
+    }
+}
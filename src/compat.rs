@@ -0,0 +1,215 @@
+use crate::error::Error;
+use crate::reflect;
+use std::fmt;
+use std::mem;
+use vulkano::descriptor::descriptor::DescriptorDescTy;
+use vulkano::descriptor::pipeline_layout::PipelineLayoutDesc;
+
+pub const VIEW_SET: usize = 0;
+pub const MATERIAL_SET: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    UniformBuffer,
+    StorageBuffer,
+    CombinedImageSampler,
+    SampledImage,
+    StorageImage,
+    Sampler,
+    InputAttachment,
+    TexelBuffer,
+}
+
+impl Kind {
+    fn of(ty: &DescriptorDescTy) -> Kind {
+        match ty {
+            DescriptorDescTy::Sampler => Kind::Sampler,
+            DescriptorDescTy::CombinedImageSampler(_) => {
+                Kind::CombinedImageSampler
+            }
+            DescriptorDescTy::Image(desc) if desc.sampled => Kind::SampledImage,
+            DescriptorDescTy::Image(_) => Kind::StorageImage,
+            DescriptorDescTy::TexelBuffer { .. } => Kind::TexelBuffer,
+            DescriptorDescTy::InputAttachment { .. } => Kind::InputAttachment,
+            DescriptorDescTy::Buffer(desc) if desc.storage => {
+                Kind::StorageBuffer
+            }
+            DescriptorDescTy::Buffer(_) => Kind::UniformBuffer,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    pub set: usize,
+    pub binding: usize,
+    pub kind: Kind,
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "set {} binding {}: {:?}",
+            self.set, self.binding, self.kind
+        )
+    }
+}
+
+// A uniform block's size, either as a shader declares it or as the
+// renderer uploads it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    pub set: usize,
+    pub binding: usize,
+    pub size: usize,
+}
+
+impl Block {
+    // The block at `set` and `binding` is uploaded from a `T`.
+    pub fn of<T>(set: usize, binding: usize) -> Block {
+        Block {
+            set,
+            binding,
+            size: mem::size_of::<T>(),
+        }
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "set {} binding {}: {} byte block",
+            self.set, self.binding, self.size
+        )
+    }
+}
+
+// Pipeline layouts don't record block sizes, so `blocks` only lists those
+// known from SPIR-V or, on the expected side, from Rust structs.
+pub struct Interface {
+    pub bindings: Vec<Binding>,
+    pub push_constants: usize,
+    pub blocks: Vec<Block>,
+}
+
+pub fn reflect<L>(layout: &L) -> Interface
+where
+    L: PipelineLayoutDesc + ?Sized,
+{
+    let mut bindings = Vec::new();
+    for set in 0..layout.num_sets() {
+        let count = layout.num_bindings_in_set(set).unwrap_or(0);
+        for binding in 0..count {
+            if let Some(desc) = layout.descriptor(set, binding) {
+                bindings.push(Binding {
+                    set,
+                    binding,
+                    kind: Kind::of(&desc.ty),
+                });
+            }
+        }
+    }
+
+    let push_constants = (0..layout.num_push_constants_ranges())
+        .filter_map(|num| layout.push_constants_range(num))
+        .map(|range| range.offset + range.size)
+        .max()
+        .unwrap_or(0);
+
+    Interface {
+        bindings,
+        push_constants,
+        blocks: Vec::new(),
+    }
+}
+
+// The combined interface of a pipeline's stages, uniform block sizes
+// included, straight from their SPIR-V.
+pub fn reflect_spirv(
+    name: &str,
+    stages: &[&[u32]],
+) -> Result<Interface, Error> {
+    let mut interface = Interface {
+        bindings: Vec::new(),
+        push_constants: 0,
+        blocks: Vec::new(),
+    };
+    for words in stages {
+        let reflection =
+            reflect::reflect(words).map_err(|message| Error::Reflect {
+                name: name.to_owned(),
+                message,
+            })?;
+        let stage = reflect(&reflection.layout);
+        for binding in stage.bindings {
+            if !interface.bindings.contains(&binding) {
+                interface.bindings.push(binding);
+            }
+        }
+        interface.push_constants =
+            interface.push_constants.max(stage.push_constants);
+        for (set, binding, size) in reflection.uniform_blocks {
+            let block = Block { set, binding, size };
+            if !interface.blocks.contains(&block) {
+                interface.blocks.push(block);
+            }
+        }
+    }
+    Ok(interface)
+}
+
+// Lists what differs between what the shaders declare and what the
+// renderer binds and uploads. Expected blocks are compared with the found
+// block at the same binding.
+pub fn check(found: &Interface, expected: &Interface) -> Result<(), String> {
+    let mut diff = Vec::new();
+
+    for binding in &expected.bindings {
+        if !found.bindings.contains(binding) {
+            diff.push(format!("- {}", binding));
+        }
+    }
+    for binding in &found.bindings {
+        if !expected.bindings.contains(binding) {
+            diff.push(format!("+ {}", binding));
+        }
+    }
+    if found.push_constants != expected.push_constants {
+        diff.push(format!(
+            "- push constants: {} bytes",
+            expected.push_constants
+        ));
+        diff.push(format!("+ push constants: {} bytes", found.push_constants));
+    }
+    for block in &expected.blocks {
+        let shader = found.blocks.iter().find(|found| {
+            (found.set, found.binding) == (block.set, block.binding)
+        });
+        if shader != Some(block) {
+            diff.push(format!("- {}", block));
+            if let Some(shader) = shader {
+                diff.push(format!("+ {}", shader));
+            }
+        }
+    }
+
+    if diff.is_empty() {
+        Ok(())
+    } else {
+        Err(diff.join("\n"))
+    }
+}
+
+// `check` as an error naming the pipeline, for setup code.
+pub fn verify(
+    name: &str,
+    found: &Interface,
+    expected: &Interface,
+) -> Result<(), Error> {
+    check(found, expected).map_err(|diff| Error::Interface {
+        name: name.to_owned(),
+        diff,
+    })
+}
@@ -0,0 +1,150 @@
+use crate::error::Error;
+use crate::error::Result;
+use std::sync::Arc;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::device::Queue;
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::ComputePipeline;
+use vulkano::pipeline::ComputePipelineAbstract;
+use vulkano::sampler::Sampler;
+use vulkano::sync::GpuFuture;
+
+pub const LOCAL_SIZE: u32 = 16;
+pub const BINS: usize = 64;
+
+pub mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: "
+#version 450
+
+layout (local_size_x = 16, local_size_y = 16) in;
+
+layout (set = 0, binding = 0) uniform sampler2D source;
+
+layout (set = 0, binding = 1) buffer Histogram {
+    uint bins[64];
+} histogram;
+
+void main() {
+    ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+    if (any(greaterThanEqual(pixel, textureSize(source, 0)))) {
+        return;
+    }
+
+    vec3 color = texelFetch(source, pixel, 0).rgb;
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    uint bin = uint(clamp(luminance, 0.0, 1.0) * 63.0 + 0.5);
+    atomicAdd(histogram.bins[bin], 1);
+}"
+    }
+}
+
+pub struct Pipeline {
+    pub pipeline: Arc<dyn ComputePipelineAbstract + Send + Sync>,
+}
+
+pub fn build(device: Arc<Device>) -> Result<Pipeline> {
+    let cs = cs::Shader::load(device.clone())
+        .map_err(Error::shader("histogram compute"))?;
+    let pipeline = Arc::new(
+        ComputePipeline::new(device, &cs.main_entry_point(), &())
+            .map_err(Error::compute_pipeline("histogram"))?,
+    );
+    Ok(Pipeline { pipeline })
+}
+
+pub fn histogram_buffer(
+    device: Arc<Device>,
+) -> Result<Arc<CpuAccessibleBuffer<[u32]>>> {
+    let buffer = CpuAccessibleBuffer::from_iter(
+        device,
+        BufferUsage {
+            storage_buffer: true,
+            transfer_destination: true,
+            ..BufferUsage::none()
+        },
+        (0..BINS).map(|_| 0u32),
+    )
+    .map_err(Error::allocation("histogram"))?;
+    Ok(buffer)
+}
+
+pub fn descriptor_set(
+    pipeline: &Pipeline,
+    source: Arc<AttachmentImage>,
+    sampler: Arc<Sampler>,
+    histogram: Arc<CpuAccessibleBuffer<[u32]>>,
+) -> Result<Arc<dyn DescriptorSet + Send + Sync>> {
+    Ok(Arc::new(
+        PersistentDescriptorSet::start(pipeline.pipeline.clone(), 0)
+            .add_sampled_image(source, sampler)?
+            .add_buffer(histogram)?
+            .build()?,
+    ))
+}
+
+pub fn dispatch(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &Pipeline,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    histogram: Arc<CpuAccessibleBuffer<[u32]>>,
+    dimensions: [u32; 2],
+) -> Result<AutoCommandBufferBuilder> {
+    let groups = [
+        (dimensions[0] + LOCAL_SIZE - 1) / LOCAL_SIZE,
+        (dimensions[1] + LOCAL_SIZE - 1) / LOCAL_SIZE,
+        1,
+    ];
+    let builder = builder.fill_buffer(histogram, 0)?.dispatch(
+        groups,
+        pipeline.pipeline.clone(),
+        set,
+        (),
+    )?;
+    Ok(builder)
+}
+
+// Chains a compute command buffer after `previous`, through a semaphore
+// when `queue` is not the queue `previous` was submitted to.
+pub fn then_execute<F, C>(
+    previous: F,
+    queue: Arc<Queue>,
+    command_buffer: C,
+) -> Result<Box<dyn GpuFuture>>
+where
+    F: GpuFuture + 'static,
+    C: CommandBuffer + 'static,
+{
+    let same_queue = previous
+        .queue()
+        .map_or(true, |previous_queue| previous_queue.is_same(&queue));
+    if same_queue {
+        Ok(Box::new(previous.then_execute(queue, command_buffer)?))
+    } else {
+        Ok(Box::new(
+            previous
+                .then_signal_semaphore()
+                .then_execute(queue, command_buffer)?,
+        ))
+    }
+}
+
+pub fn average_luminance(histogram: &CpuAccessibleBuffer<[u32]>) -> f32 {
+    let bins = histogram.read().unwrap();
+    let total = bins.iter().map(|&count| count as f32).sum::<f32>();
+    if total == 0.0 {
+        return 0.0;
+    }
+    bins.iter()
+        .enumerate()
+        .map(|(bin, &count)| bin as f32 / (BINS - 1) as f32 * count as f32)
+        .sum::<f32>()
+        / total
+}
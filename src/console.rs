@@ -0,0 +1,217 @@
+use winit::event::VirtualKeyCode;
+
+// Output lines kept for scrolling back through.
+const SCROLLBACK: usize = 256;
+const HISTORY: usize = 64;
+
+// A drop-down command line. It only edits text: submitted lines are
+// collected for the app to run, and the app prints the results back.
+// Commands registered with `register` are offered by tab completion.
+#[derive(Debug, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    output: Vec<String>,
+    history: Vec<String>,
+    // Position while browsing history with the arrow keys.
+    recalled: Option<usize>,
+    commands: Vec<String>,
+    submitted: Vec<String>,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console::default()
+    }
+
+    // A command, or a command and its first words, e.g. "toggle grid".
+    pub fn register(&mut self, command: &str) {
+        if !self.commands.iter().any(|known| known == command) {
+            self.commands.push(command.to_owned());
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn print(&mut self, text: &str) {
+        self.output.extend(text.lines().map(str::to_owned));
+        if self.output.len() > SCROLLBACK {
+            let excess = self.output.len() - SCROLLBACK;
+            self.output.drain(..excess);
+        }
+    }
+
+    // Typed text. The toggle key's character is dropped so opening the
+    // console doesn't also type it.
+    pub fn character(&mut self, c: char) {
+        if self.open && c != '`' && !c.is_control() {
+            self.input.push(c);
+        }
+    }
+
+    // Editing keys; others are ignored.
+    pub fn key(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::Back => {
+                self.input.pop();
+            }
+            VirtualKeyCode::Return => self.submit(),
+            VirtualKeyCode::Tab => self.complete(),
+            VirtualKeyCode::Up => self.recall(true),
+            VirtualKeyCode::Down => self.recall(false),
+            VirtualKeyCode::Escape => self.open = false,
+            _ => (),
+        }
+    }
+
+    // Lines submitted since the last call, oldest first.
+    pub fn take_submitted(&mut self) -> Vec<String> {
+        std::mem::replace(&mut self.submitted, Vec::new())
+    }
+
+    // The last `rows` output lines followed by the prompt.
+    pub fn text(&self, rows: usize) -> String {
+        let start = self.output.len().saturating_sub(rows);
+        let mut text = String::new();
+        for line in &self.output[start..] {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text.push_str("> ");
+        text.push_str(&self.input);
+        text.push('_');
+        text
+    }
+
+    fn submit(&mut self) {
+        let line = self.input.trim().to_owned();
+        self.input.clear();
+        self.recalled = None;
+        if line.is_empty() {
+            return;
+        }
+        self.print(&format!("> {}", line));
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > HISTORY {
+                self.history.remove(0);
+            }
+        }
+        self.submitted.push(line);
+    }
+
+    fn recall(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let last = self.history.len() - 1;
+        self.recalled = match (self.recalled, older) {
+            (None, true) => Some(last),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index < last => Some(index + 1),
+            (Some(_), false) => None,
+        };
+        self.input = match self.recalled {
+            Some(index) => self.history[index].clone(),
+            None => String::new(),
+        };
+    }
+
+    // Completes to the longest prefix all matching commands share, and
+    // lists them when that doesn't add anything.
+    fn complete(&mut self) {
+        let matches = self
+            .commands
+            .iter()
+            .filter(|command| command.starts_with(self.input.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        let prefix = match matches.split_first() {
+            Some((first, rest)) => rest.iter().fold(first.clone(), |a, b| {
+                a.chars()
+                    .zip(b.chars())
+                    .take_while(|(x, y)| x == y)
+                    .map(|(x, _)| x)
+                    .collect()
+            }),
+            None => return,
+        };
+        if matches.len() == 1 {
+            self.input = prefix + " ";
+        } else if prefix.len() > self.input.len() {
+            self.input = prefix;
+        } else {
+            self.print(&matches.join("  "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn console(input: &str) -> Console {
+        let mut console = Console::new();
+        console.open = true;
+        for command in &["toggle grid", "toggle probes", "timescale", "quit"] {
+            console.register(command);
+        }
+        input.chars().for_each(|c| console.character(c));
+        console
+    }
+
+    #[test]
+    fn unique_prefix_completes_the_command() {
+        let mut console = console("q");
+        console.key(VirtualKeyCode::Tab);
+        assert_eq!(console.input, "quit ");
+    }
+
+    #[test]
+    fn ambiguous_prefix_extends_to_the_shared_part() {
+        let mut console = console("to");
+        console.key(VirtualKeyCode::Tab);
+        assert_eq!(console.input, "toggle ");
+        assert!(console.output.is_empty());
+    }
+
+    #[test]
+    fn ambiguous_prefix_lists_the_matches_once_nothing_is_shared() {
+        let mut console = console("t");
+        console.key(VirtualKeyCode::Tab);
+        assert_eq!(console.input, "t");
+        assert_eq!(
+            console.output,
+            vec!["toggle grid  toggle probes  timescale".to_owned()]
+        );
+    }
+
+    #[test]
+    fn unknown_prefix_is_left_alone() {
+        let mut console = console("x");
+        console.key(VirtualKeyCode::Tab);
+        assert_eq!(console.input, "x");
+        assert!(console.output.is_empty());
+    }
+
+    #[test]
+    fn history_skips_repeats_and_walks_back() {
+        let mut console = console("");
+        for line in &["quit", "quit", "timescale 2"] {
+            line.chars().for_each(|c| console.character(c));
+            console.key(VirtualKeyCode::Return);
+        }
+        assert_eq!(console.take_submitted().len(), 3);
+        console.key(VirtualKeyCode::Up);
+        assert_eq!(console.input, "timescale 2");
+        console.key(VirtualKeyCode::Up);
+        console.key(VirtualKeyCode::Up);
+        assert_eq!(console.input, "quit");
+        console.key(VirtualKeyCode::Down);
+        console.key(VirtualKeyCode::Down);
+        assert_eq!(console.input, "");
+    }
+}
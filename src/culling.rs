@@ -0,0 +1,388 @@
+use crate::arena::Allocation;
+use crate::arena::Arena;
+use crate::bvh::Aabb;
+use crate::bvh::Bvh;
+use crate::dbgpipe;
+use crate::dbgpipe::Vertex;
+use crate::indirect::Bucket;
+use crate::indirect::CountMode;
+use cgmath::InnerSpace;
+use cgmath::Matrix4;
+use cgmath::SquareMatrix;
+use cgmath::Vector4;
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::buffer::DeviceLocalBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::pipeline::ComputePipeline;
+use vulkano::pipeline::ComputePipelineAbstract;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+pub const LOCAL_SIZE: u32 = 64;
+
+// Matches `Object` in the culling shader (std430).
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct Object {
+    // xyz: center, w: radius
+    pub sphere: [f32; 4],
+    pub first_vertex: u32,
+    pub vertex_count: u32,
+    pub _pad: [u32; 2],
+}
+
+pub mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: "
+#version 450
+
+layout (local_size_x = 64) in;
+
+struct Object {
+    vec4 sphere;
+    uint first_vertex;
+    uint vertex_count;
+    uvec2 pad;
+};
+
+struct Command {
+    uint vertex_count;
+    uint instance_count;
+    uint first_vertex;
+    uint first_instance;
+};
+
+layout (set = 0, binding = 0) readonly buffer Objects {
+    Object objects[];
+} scene;
+
+layout (set = 0, binding = 1) writeonly buffer Commands {
+    Command commands[];
+} draws;
+
+layout (set = 0, binding = 2) buffer Count {
+    uint visible;
+} count;
+
+layout (push_constant) uniform Frustum {
+    vec4 planes[6];
+    uint object_count;
+    // Pack the visible commands at the front instead of zeroing the rest.
+    uint compact;
+} frustum;
+
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+    if (i >= frustum.object_count) {
+        return;
+    }
+
+    Object object = scene.objects[i];
+    bool visible = true;
+    for (int p = 0; p < 6; p++) {
+        vec4 plane = frustum.planes[p];
+        if (dot(plane.xyz, object.sphere.xyz) + plane.w < -object.sphere.w) {
+            visible = false;
+        }
+    }
+
+    if (frustum.compact != 0) {
+        if (visible) {
+            uint slot = atomicAdd(count.visible, 1);
+            draws.commands[slot] = Command(
+                object.vertex_count,
+                1,
+                object.first_vertex,
+                0);
+        }
+        return;
+    }
+    draws.commands[i] = Command(
+        object.vertex_count,
+        visible ? 1 : 0,
+        object.first_vertex,
+        0);
+    if (visible) {
+        atomicAdd(count.visible, 1);
+    }
+}"
+    }
+}
+
+// One object per triangle of a triangle list, bounded by a sphere around
+// its centroid.
+pub fn objects(scene: &[[f32; 4]]) -> Vec<Object> {
+    scene
+        .chunks_exact(3)
+        .enumerate()
+        .map(|(index, triangle)| {
+            let mut center = [0.0; 3];
+            for vertex in triangle {
+                for (c, v) in center.iter_mut().zip(vertex.iter()) {
+                    *c += v / 3.0;
+                }
+            }
+            let radius = triangle
+                .iter()
+                .map(|vertex| {
+                    center
+                        .iter()
+                        .zip(vertex.iter())
+                        .map(|(c, v)| (v - c).powi(2))
+                        .sum::<f32>()
+                        .sqrt()
+                })
+                .fold(0.0, f32::max);
+            Object {
+                sphere: [center[0], center[1], center[2], radius],
+                first_vertex: index as u32 * 3,
+                vertex_count: 3,
+                _pad: [0; 2],
+            }
+        })
+        .collect()
+}
+
+// Normalized clip planes (left, right, bottom, top, near, far) of a
+// view-projection matrix, each as `dot(n, p) + d >= 0` inside.
+pub fn frustum_planes(view_projection: &Matrix4<f32>) -> [[f32; 4]; 6] {
+    let m = view_projection;
+    let row = |i: usize| Vector4::new(m.x[i], m.y[i], m.z[i], m.w[i]);
+    let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+    let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r3 + r2, r3 - r2];
+
+    let mut normalized = [[0.0; 4]; 6];
+    for (out, plane) in normalized.iter_mut().zip(planes.iter()) {
+        let length = plane.truncate().magnitude();
+        *out = (plane / length).into();
+    }
+    normalized
+}
+
+// The CPU version of the culling shader's test.
+pub fn sphere_visible(sphere: [f32; 4], planes: &[[f32; 4]; 6]) -> bool {
+    let [x, y, z, radius] = sphere;
+    planes
+        .iter()
+        .all(|p| p[0] * x + p[1] * y + p[2] * z + p[3] >= -radius)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CullStats {
+    pub objects: u32,
+    pub culled: u32,
+    pub draws: u32,
+}
+
+// Frustum culls objects on the CPU while recording, for when the GPU
+// culling path isn't used. Objects are found through a BVH rather than
+// tested one by one.
+pub struct CpuCuller {
+    objects: Vec<Object>,
+    bvh: Bvh,
+}
+
+// Vertex ranges of a mesh left after culling. Neighbouring visible objects
+// are merged into one draw.
+pub struct Visible {
+    pub ranges: Vec<Allocation>,
+    pub stats: CullStats,
+}
+
+impl CpuCuller {
+    pub fn new(objects: Vec<Object>) -> CpuCuller {
+        let bounds = objects
+            .iter()
+            .map(|object| Aabb::from_sphere(object.sphere))
+            .collect::<Vec<_>>();
+        CpuCuller {
+            bvh: Bvh::build(&bounds),
+            objects,
+        }
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    pub fn bvh(&self) -> &Bvh {
+        &self.bvh
+    }
+
+    // For an object that moved; its vertices are the caller's to update.
+    pub fn set_sphere(&mut self, index: usize, sphere: [f32; 4]) {
+        self.objects[index].sphere = sphere;
+        self.bvh.refit(index, Aabb::from_sphere(sphere));
+    }
+
+    // `mesh` is where the objects' vertices live in the arena.
+    pub fn cull(
+        &self,
+        mesh: Allocation,
+        view_projection: &Matrix4<f32>,
+    ) -> Visible {
+        let planes = frustum_planes(view_projection);
+        let mut visible = Vec::new();
+        self.bvh.frustum(&planes, &mut visible);
+        // The tree tests boxes around the spheres; test the spheres too.
+        visible.retain(|&i| sphere_visible(self.objects[i].sphere, &planes));
+        visible.sort_unstable();
+
+        let mut ranges: Vec<Allocation> = Vec::new();
+        for object in visible.iter().map(|&i| &self.objects[i]) {
+            let offset = mesh.offset + object.first_vertex as usize;
+            let len = object.vertex_count as usize;
+            match ranges.last_mut() {
+                Some(last) if last.offset + last.len == offset => {
+                    last.len += len
+                }
+                _ => ranges.push(Allocation { offset, len }),
+            }
+        }
+        Visible {
+            stats: CullStats {
+                objects: self.objects.len() as u32,
+                culled: (self.objects.len() - visible.len()) as u32,
+                draws: ranges.len() as u32,
+            },
+            ranges,
+        }
+    }
+}
+
+impl Visible {
+    pub fn draw(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        dynamic_state: &DynamicState,
+        arena: &Arena<Vertex>,
+        view_set: Arc<dyn DescriptorSet + Send + Sync>,
+    ) -> AutoCommandBufferBuilder {
+        draw_ranges(
+            builder,
+            pipeline,
+            dynamic_state,
+            arena,
+            &self.ranges,
+            view_set,
+        )
+    }
+}
+
+// One untransformed draw per range of `arena`.
+pub fn draw_ranges(
+    mut builder: AutoCommandBufferBuilder,
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    dynamic_state: &DynamicState,
+    arena: &Arena<Vertex>,
+    ranges: &[Allocation],
+    view_set: Arc<dyn DescriptorSet + Send + Sync>,
+) -> AutoCommandBufferBuilder {
+    for &range in ranges {
+        builder = builder
+            .draw(
+                pipeline.clone(),
+                dynamic_state,
+                vec![Arc::new(arena.slice(range))
+                    as Arc<dyn BufferAccess + Send + Sync>],
+                vec![view_set.clone()],
+                dbgpipe::vs::ty::Push {
+                    model: Matrix4::identity().into(),
+                },
+            )
+            .unwrap();
+    }
+    builder
+}
+
+pub struct Culler {
+    pub pipeline: Arc<dyn ComputePipelineAbstract + Send + Sync>,
+    pub objects: Arc<CpuAccessibleBuffer<[Object]>>,
+    pub count: Arc<DeviceLocalBuffer<u32>>,
+    pub set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub object_count: u32,
+    pub compact: bool,
+}
+
+impl Culler {
+    // Writes the commands into `bucket` and the visible tally into its
+    // `count`. In GpuCount mode only the visible commands are written,
+    // packed at the front; otherwise there's one per object and culled
+    // ones have no instances.
+    pub fn new(
+        device: Arc<Device>,
+        objects: Vec<Object>,
+        bucket: &Bucket,
+    ) -> Culler {
+        assert!(objects.len() as u32 <= bucket.capacity);
+        let cs = cs::Shader::load(device.clone()).unwrap();
+        let pipeline = Arc::new(
+            ComputePipeline::new(device.clone(), &cs.main_entry_point(), &())
+                .unwrap(),
+        );
+
+        let object_count = objects.len() as u32;
+        let objects = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage {
+                storage_buffer: true,
+                ..BufferUsage::none()
+            },
+            objects.into_iter(),
+        )
+        .unwrap();
+        let count = bucket.count.clone();
+
+        let set = Arc::new(
+            PersistentDescriptorSet::start(pipeline.clone(), 0)
+                .add_buffer(objects.clone())
+                .unwrap()
+                .add_buffer(bucket.commands.clone())
+                .unwrap()
+                .add_buffer(count.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        Culler {
+            pipeline,
+            objects,
+            count,
+            set,
+            object_count,
+            compact: bucket.mode == CountMode::GpuCount,
+        }
+    }
+
+    // Must be recorded outside a render pass, before the indirect draw.
+    pub fn record(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        view_projection: &Matrix4<f32>,
+    ) -> AutoCommandBufferBuilder {
+        let groups = (self.object_count + LOCAL_SIZE - 1) / LOCAL_SIZE;
+        builder
+            .fill_buffer(self.count.clone(), 0)
+            .unwrap()
+            .dispatch(
+                [groups, 1, 1],
+                self.pipeline.clone(),
+                self.set.clone(),
+                cs::ty::Frustum {
+                    planes: frustum_planes(view_projection),
+                    object_count: self.object_count,
+                    compact: self.compact as u32,
+                },
+            )
+            .unwrap()
+    }
+}
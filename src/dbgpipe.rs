@@ -1,7 +1,24 @@
+use crate::compat;
+use crate::entrypoint;
+use crate::error::Error;
+use crate::error::Result;
+#[cfg(feature = "hot-reload")]
+use crate::hotreload::ShaderWatcher;
+use crate::spirv;
+use std::mem;
 use std::sync::Arc;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
 use vulkano::device::Device;
+use vulkano::format::Format;
 use vulkano::framebuffer::RenderPassAbstract;
 use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::depth_stencil::Compare;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::shader::GraphicsShaderType;
+use vulkano::pipeline::shader::ShaderModule;
 use vulkano::pipeline::GraphicsPipeline;
 use vulkano::pipeline::GraphicsPipelineAbstract;
 use vulkano::swapchain::Swapchain;
@@ -14,55 +31,186 @@ pub struct Vertex {
 
 vulkano::impl_vertex!(Vertex, position);
 
+// World-space line endpoint for the `lines` pipeline.
+#[derive(Debug, Clone, Default)]
+pub struct LineVertex {
+    pub position: [f32; 4],
+    pub color: [f32; 4],
+}
+
+vulkano::impl_vertex!(LineVertex, position, color);
+
+pub const DEPTH_FORMAT: Format = Format::D16Unorm;
+
+// The view uniform block every scene pipeline reads from set 0, binding 0.
+// The debug shaders' generated struct isn't used so that each pipeline's
+// declaration gets checked against what is actually uploaded.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ViewBlock {
+    pub vp: [[f32; 4]; 4],
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TransparentPush {
+    pub model: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
 pub mod vs {
     vulkano_shaders::shader! {
         ty: "vertex",
-        src: "
-#version 450
-
-layout (location = 0) in vec4 position;
+        path: "shaders/debug.vert"
+    }
+}
 
-layout (set = 0, binding = 0) uniform VP_BLOCK {
-    mat4 vp;
-} vp_inst;
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/debug.frag"
+    }
+}
 
-layout (push_constant) uniform Push {
-    mat4 model;
-} push;
+pub mod transparent_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/transparent.frag"
+    }
+}
 
-void main() {
-    gl_Position = vp_inst.vp * position;
-}"
+pub mod line_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/line.vert"
     }
 }
 
-pub mod fs {
+pub mod line_fs {
     vulkano_shaders::shader! {
         ty: "fragment",
-        src: "
-#version 450
+        path: "shaders/line.frag"
+    }
+}
 
-layout (location = 0) out vec4 f_color;
+// Files under `shaders/` that the pipelines are built from.
+pub const SOURCES: [&str; 5] = [
+    "debug.vert",
+    "debug.frag",
+    "transparent.frag",
+    "line.vert",
+    "line.frag",
+];
 
-void main() {
-    f_color = vec4(1.0, 0.0, 0.0, 1.0);
+// The SPIR-V for each stage, either built with the crate or recompiled
+// from `SOURCES` while running.
+pub struct Modules {
+    pub vs: Arc<ShaderModule>,
+    pub fs: Arc<ShaderModule>,
+    pub transparent_fs: Arc<ShaderModule>,
+    pub line_vs: Arc<ShaderModule>,
+    pub line_fs: Arc<ShaderModule>,
 }
-"
+
+impl Modules {
+    pub fn load(device: Arc<Device>) -> Result<Modules> {
+        Ok(Modules {
+            vs: spirv::module(
+                device.clone(),
+                "debug vertex",
+                &spirv::DEBUG_VERT,
+            )?,
+            fs: spirv::module(
+                device.clone(),
+                "debug fragment",
+                &spirv::DEBUG_FRAG,
+            )?,
+            transparent_fs: spirv::module(
+                device.clone(),
+                "transparent fragment",
+                &spirv::TRANSPARENT_FRAG,
+            )?,
+            line_vs: spirv::module(
+                device.clone(),
+                "line vertex",
+                &spirv::LINE_VERT,
+            )?,
+            line_fs: spirv::module(device, "line fragment", &spirv::LINE_FRAG)?,
+        })
+    }
+
+    #[cfg(feature = "hot-reload")]
+    pub fn compile(
+        device: Arc<Device>,
+        watcher: &mut ShaderWatcher,
+    ) -> Result<Modules> {
+        Ok(Modules {
+            vs: watcher.load(device.clone(), SOURCES[0])?,
+            fs: watcher.load(device.clone(), SOURCES[1])?,
+            transparent_fs: watcher.load(device.clone(), SOURCES[2])?,
+            line_vs: watcher.load(device.clone(), SOURCES[3])?,
+            line_fs: watcher.load(device, SOURCES[4])?,
+        })
     }
 }
 
 pub struct Pipeline {
     pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
     pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pub transparent: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pub wireframe: Option<Arc<dyn GraphicsPipelineAbstract + Send + Sync>>,
+    // Line lists of `LineVertex`, with the same view set as `pipeline`.
+    pub lines: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    // `lines` without depth testing or writes, drawn over everything.
+    pub lines_on_top: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+pub fn interface() -> compat::Interface {
+    compat::Interface {
+        bindings: vec![compat::Binding {
+            set: compat::VIEW_SET,
+            binding: 0,
+            kind: compat::Kind::UniformBuffer,
+        }],
+        push_constants: mem::size_of::<vs::ty::Push>(),
+        blocks: vec![compat::Block::of::<ViewBlock>(compat::VIEW_SET, 0)],
+    }
+}
+
+// The transparent pipeline pushes the model and color together.
+pub fn transparent_interface() -> compat::Interface {
+    compat::Interface {
+        push_constants: mem::size_of::<TransparentPush>(),
+        ..interface()
+    }
+}
+
+// What the built-in SPIR-V declares.
+pub fn shader_interface() -> Result<compat::Interface> {
+    compat::reflect_spirv("dbgpipe", &[&spirv::DEBUG_VERT, &spirv::DEBUG_FRAG])
+}
+
+pub fn transparent_shader_interface() -> Result<compat::Interface> {
+    compat::reflect_spirv(
+        "dbgpipe transparent",
+        &[&spirv::DEBUG_VERT, &spirv::TRANSPARENT_FRAG],
+    )
 }
 
 pub fn build(
     device: Arc<Device>,
     swapchain: Arc<Swapchain<Window>>,
-) -> Pipeline {
-    let vs = vs::Shader::load(device.clone()).unwrap();
-    let fs = fs::Shader::load(device.clone()).unwrap();
+) -> Result<Pipeline> {
+    build_for_format(device, swapchain.format())
+}
 
+// Same pipelines for a color target that isn't a swapchain image, such as
+// an offscreen render.
+pub fn build_for_format(
+    device: Arc<Device>,
+    format: Format,
+) -> Result<Pipeline> {
+    let modules = Modules::load(device.clone())?;
     let render_pass = Arc::new(
         vulkano::single_pass_renderpass!(
             device.clone(),
@@ -70,32 +218,187 @@ pub fn build(
                 color: {
                     load: Clear,
                     store: Store,
-                    format: swapchain.format(),
+                    format: format,
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: Store,
+                    format: DEPTH_FORMAT,
                     samples: 1,
                 }
             },
             pass: {
                 color: [color],
-                depth_stencil: {}
+                depth_stencil: {depth}
             }
         )
-        .unwrap(),
+        .map_err(Error::render_pass("debug"))?,
     );
+    pipelines(device, render_pass, &modules)
+}
+
+impl Pipeline {
+    // Swaps in pipelines built from `modules`. The render pass is kept, so
+    // framebuffers and descriptor sets made for the old pipelines still
+    // work, as long as the shaders' interfaces haven't changed.
+    pub fn reload(
+        &mut self,
+        device: Arc<Device>,
+        modules: &Modules,
+    ) -> Result<()> {
+        *self = pipelines(device, self.render_pass.clone(), modules)?;
+        Ok(())
+    }
+}
+
+fn pipelines(
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    modules: &Modules,
+) -> Result<Pipeline> {
+    let (vs, fs, transparent_fs, line_vs, line_fs) = unsafe {
+        (
+            entrypoint::graphics(
+                &modules.vs,
+                vs::MainInput,
+                vs::MainOutput,
+                vs::Layout(entrypoint::vertex_stage()),
+                GraphicsShaderType::Vertex,
+            ),
+            entrypoint::graphics(
+                &modules.fs,
+                fs::MainInput,
+                fs::MainOutput,
+                fs::Layout(entrypoint::fragment_stage()),
+                GraphicsShaderType::Fragment,
+            ),
+            entrypoint::graphics(
+                &modules.transparent_fs,
+                transparent_fs::MainInput,
+                transparent_fs::MainOutput,
+                transparent_fs::Layout(entrypoint::fragment_stage()),
+                GraphicsShaderType::Fragment,
+            ),
+            entrypoint::graphics(
+                &modules.line_vs,
+                line_vs::MainInput,
+                line_vs::MainOutput,
+                line_vs::Layout(entrypoint::vertex_stage()),
+                GraphicsShaderType::Vertex,
+            ),
+            entrypoint::graphics(
+                &modules.line_fs,
+                line_fs::MainInput,
+                line_fs::MainOutput,
+                line_fs::Layout(entrypoint::fragment_stage()),
+                GraphicsShaderType::Fragment,
+            ),
+        )
+    };
 
     let pipeline = Arc::new(
         GraphicsPipeline::start()
             .vertex_input_single_buffer::<Vertex>()
-            .vertex_shader(vs.main_entry_point(), ())
+            .vertex_shader(vs.clone(), ())
             .triangle_list()
             .viewports_dynamic_scissors_irrelevant(1)
-            .fragment_shader(fs.main_entry_point(), ())
+            .depth_stencil_simple_depth()
+            .fragment_shader(fs.clone(), ())
             .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
             .build(device.clone())
-            .unwrap(),
+            .map_err(Error::pipeline("debug"))?,
     );
 
-    Pipeline {
+    let transparent = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Vertex>()
+            .vertex_shader(vs.clone(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .depth_stencil(DepthStencil {
+                depth_write: false,
+                depth_compare: Compare::Less,
+                ..DepthStencil::simple_depth_test()
+            })
+            .blend_alpha_blending()
+            .fragment_shader(transparent_fs, ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("transparent"))?,
+    );
+
+    let wireframe = if device.enabled_features().fill_mode_non_solid {
+        Some(Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(vs, ())
+                .triangle_list()
+                .polygon_mode_line()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil_simple_depth()
+                .fragment_shader(fs, ())
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .map_err(Error::pipeline("wireframe"))?,
+        ) as Arc<dyn GraphicsPipelineAbstract + Send + Sync>)
+    } else {
+        None
+    };
+
+    let lines = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<LineVertex>()
+            .vertex_shader(line_vs.clone(), ())
+            .line_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .depth_stencil_simple_depth()
+            .fragment_shader(line_fs.clone(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("debug lines"))?,
+    );
+
+    let lines_on_top = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<LineVertex>()
+            .vertex_shader(line_vs, ())
+            .line_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .depth_stencil_disabled()
+            .fragment_shader(line_fs, ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("debug lines on top"))?,
+    );
+
+    Ok(Pipeline {
         render_pass,
         pipeline,
-    }
+        transparent,
+        wireframe,
+        lines,
+        lines_on_top,
+    })
+}
+
+// Fixed view-projection set, for callers that don't stream one per frame.
+pub fn view_set(
+    device: Arc<Device>,
+    pipeline: &Pipeline,
+    vp: [[f32; 4]; 4],
+) -> Arc<dyn DescriptorSet + Send + Sync> {
+    let buffer = CpuAccessibleBuffer::from_data(
+        device,
+        BufferUsage::uniform_buffer(),
+        ViewBlock { vp },
+    )
+    .unwrap();
+    Arc::new(
+        PersistentDescriptorSet::start(pipeline.pipeline.clone(), 0)
+            .add_buffer(buffer)
+            .unwrap()
+            .build()
+            .unwrap(),
+    )
 }
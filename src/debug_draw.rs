@@ -0,0 +1,272 @@
+use crate::dbgpipe;
+use crate::dbgpipe::LineVertex;
+use crate::ring::Ring;
+use crate::ring::Slice;
+use cgmath::InnerSpace;
+use cgmath::Matrix4;
+use cgmath::Point3;
+use cgmath::SquareMatrix;
+use cgmath::Vector3;
+use cgmath::Vector4;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use tracing::warn;
+use vulkano::buffer::BufferUsage;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::device::Device;
+
+// Segments per circle of a `sphere`.
+const SPHERE_SEGMENTS: usize = 24;
+
+// Immediate-mode lines: call the shape functions any time during a frame,
+// then `flush` once to upload them and `draw` them with dbgpipe's `lines`
+// pipeline. Nothing is retained between frames.
+pub struct DebugDraw {
+    vertices: Vec<LineVertex>,
+    ring: Ring<LineVertex>,
+    capacity: usize,
+}
+
+impl DebugDraw {
+    // Room for `capacity` vertices (half as many lines) in each of
+    // `frames` frames in flight.
+    pub fn new(
+        device: Arc<Device>,
+        capacity: usize,
+        frames: usize,
+    ) -> DebugDraw {
+        DebugDraw {
+            vertices: Vec::new(),
+            ring: Ring::new(
+                device,
+                capacity,
+                frames,
+                BufferUsage::vertex_buffer(),
+            ),
+            capacity,
+        }
+    }
+
+    pub fn line(&mut self, a: Point3<f32>, b: Point3<f32>, color: [f32; 4]) {
+        for point in &[a, b] {
+            self.vertices.push(LineVertex {
+                position: [point.x, point.y, point.z, 1.0],
+                color,
+            });
+        }
+    }
+
+    pub fn aabb(
+        &mut self,
+        min: Point3<f32>,
+        max: Point3<f32>,
+        color: [f32; 4],
+    ) {
+        let corner = |i: usize| {
+            Point3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        self.box_edges(corner, color);
+    }
+
+    // Three great circles, one around each axis.
+    pub fn sphere(
+        &mut self,
+        center: Point3<f32>,
+        radius: f32,
+        color: [f32; 4],
+    ) {
+        let point = |axis: usize, angle: f32| {
+            let (sin, cos) = (angle.sin() * radius, angle.cos() * radius);
+            match axis {
+                0 => center + cgmath::vec3(0.0, cos, sin),
+                1 => center + cgmath::vec3(sin, 0.0, cos),
+                _ => center + cgmath::vec3(cos, sin, 0.0),
+            }
+        };
+        let step = 2.0 * PI / SPHERE_SEGMENTS as f32;
+        for axis in 0..3 {
+            for i in 0..SPHERE_SEGMENTS {
+                let a = point(axis, i as f32 * step);
+                let b = point(axis, (i + 1) as f32 * step);
+                self.line(a, b, color);
+            }
+        }
+    }
+
+    // A circle of `radius` around `center` facing along `normal`.
+    pub fn circle(
+        &mut self,
+        center: Point3<f32>,
+        normal: Vector3<f32>,
+        radius: f32,
+        color: [f32; 4],
+    ) {
+        let normal = normal.normalize();
+        let helper = if normal.x.abs() < 0.9 {
+            Vector3::unit_x()
+        } else {
+            Vector3::unit_y()
+        };
+        let u = normal.cross(helper).normalize() * radius;
+        let v = normal.cross(u);
+        let point = |angle: f32| center + u * angle.cos() + v * angle.sin();
+        let step = 2.0 * PI / SPHERE_SEGMENTS as f32;
+        for i in 0..SPHERE_SEGMENTS {
+            let a = point(i as f32 * step);
+            let b = point((i + 1) as f32 * step);
+            self.line(a, b, color);
+        }
+    }
+
+    // The volume `matrix` maps to clip space, e.g. a camera's
+    // view-projection. Depth spans -1 to 1, as in `culling::frustum_planes`.
+    pub fn frustum(&mut self, matrix: Matrix4<f32>, color: [f32; 4]) {
+        let inverse = match matrix.invert() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let corner = |i: usize| {
+            let clip = Vector4::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+                1.0,
+            );
+            let world = inverse * clip;
+            Point3::from_homogeneous(world)
+        };
+        self.box_edges(corner, color);
+    }
+
+    // Lines every `spacing` units across the z = 0 plane, covering the
+    // rectangle from `min` to `max` snapped outwards to the spacing. Lines
+    // at equal depth lose to those added earlier, so add `axes` first.
+    pub fn grid(
+        &mut self,
+        min: [f32; 2],
+        max: [f32; 2],
+        spacing: f32,
+        color: [f32; 4],
+    ) {
+        let first = |v: f32| (v / spacing).floor() as i32;
+        let last = |v: f32| (v / spacing).ceil() as i32;
+        let (x0, x1) = (first(min[0]), last(max[0]));
+        let (y0, y1) = (first(min[1]), last(max[1]));
+        let (x0f, x1f) = (x0 as f32 * spacing, x1 as f32 * spacing);
+        let (y0f, y1f) = (y0 as f32 * spacing, y1 as f32 * spacing);
+        for i in x0..=x1 {
+            let x = i as f32 * spacing;
+            self.line(
+                Point3::new(x, y0f, 0.0),
+                Point3::new(x, y1f, 0.0),
+                color,
+            );
+        }
+        for i in y0..=y1 {
+            let y = i as f32 * spacing;
+            self.line(
+                Point3::new(x0f, y, 0.0),
+                Point3::new(x1f, y, 0.0),
+                color,
+            );
+        }
+    }
+
+    // World axes from `origin`: x red, y green, z blue.
+    pub fn axes(&mut self, origin: Point3<f32>, length: f32) {
+        let axes = [
+            (cgmath::vec3(length, 0.0, 0.0), [1.0, 0.0, 0.0, 1.0]),
+            (cgmath::vec3(0.0, length, 0.0), [0.0, 1.0, 0.0, 1.0]),
+            (cgmath::vec3(0.0, 0.0, length), [0.0, 0.0, 1.0, 1.0]),
+        ];
+        for &(axis, color) in &axes {
+            self.line(origin, origin + axis, color);
+        }
+    }
+
+    // Uploads this frame's lines and starts collecting the next frame's.
+    // None when there is nothing to draw. Lines past the capacity are
+    // dropped.
+    pub fn flush(&mut self) -> Option<Slice<LineVertex>> {
+        self.ring.begin_frame();
+        if self.vertices.len() > self.capacity {
+            warn!(
+                vertices = self.vertices.len(),
+                capacity = self.capacity,
+                "debug lines dropped"
+            );
+            self.vertices.truncate(self.capacity);
+        }
+        let lines = if self.vertices.is_empty() {
+            None
+        } else {
+            self.ring.push(&self.vertices)
+        };
+        self.vertices.clear();
+        lines
+    }
+
+    // The 12 edges between corners whose indices differ by one bit.
+    fn box_edges<F>(&mut self, corner: F, color: [f32; 4])
+    where
+        F: Fn(usize) -> Point3<f32>,
+    {
+        for i in 0..8 {
+            for &bit in &[1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+}
+
+// Must be recorded inside dbgpipe's render pass.
+pub fn draw(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &dbgpipe::Pipeline,
+    dynamic_state: &DynamicState,
+    lines: Slice<LineVertex>,
+    view_set: Arc<dyn DescriptorSet + Send + Sync>,
+) -> AutoCommandBufferBuilder {
+    builder
+        .draw(
+            pipeline.lines.clone(),
+            dynamic_state,
+            vec![Arc::new(lines)],
+            vec![view_set],
+            (),
+        )
+        .unwrap()
+}
+
+// A power of ten giving between 10 and 100 grid cells across `extent`, so
+// the grid stays readable and bounded at any zoom.
+pub fn grid_spacing(extent: f32) -> f32 {
+    10f32.powf((extent.abs().max(1e-6) / 10.0).log10().floor())
+}
+
+// Like `draw`, but over everything already in the frame.
+pub fn draw_on_top(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &dbgpipe::Pipeline,
+    dynamic_state: &DynamicState,
+    lines: Slice<LineVertex>,
+    view_set: Arc<dyn DescriptorSet + Send + Sync>,
+) -> AutoCommandBufferBuilder {
+    builder
+        .draw(
+            pipeline.lines_on_top.clone(),
+            dynamic_state,
+            vec![Arc::new(lines)],
+            vec![view_set],
+            (),
+        )
+        .unwrap()
+}
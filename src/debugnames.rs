@@ -0,0 +1,161 @@
+use crate::rawcmd::RawCommands;
+use std::ffi::CString;
+use std::ptr;
+use std::sync::Arc;
+use vk_sys as vk;
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::device::Device;
+use vulkano::device::Queue;
+use vulkano::image::ImageAccess;
+use vulkano::instance::QueueFamily;
+use vulkano::pipeline::ComputePipelineAbstract;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::SynchronizedVulkanObject;
+use vulkano::VulkanObject;
+
+// Names Vulkan objects through VK_EXT_debug_utils so validation messages
+// and capture tools show "scene depth image" instead of a bare handle.
+// Does nothing unless the instance loaded the extension, which
+// `validation::instance` does.
+//
+// Labelled regions are recorded through `RawCommands`, so they can only
+// open and close between render passes.
+pub struct DebugNames {
+    device: Arc<Device>,
+    enabled: bool,
+}
+
+impl DebugNames {
+    pub fn new(device: Arc<Device>) -> DebugNames {
+        let enabled = device.instance().loaded_extensions().ext_debug_utils;
+        DebugNames { device, enabled }
+    }
+
+    pub fn device(&self, name: &str) {
+        let handle = self.device.internal_object() as u64;
+        self.set(vk::OBJECT_TYPE_DEVICE, handle, name);
+    }
+
+    pub fn queue(&self, queue: &Queue, name: &str) {
+        let handle = *queue.internal_object_guard() as u64;
+        self.set(vk::OBJECT_TYPE_QUEUE, handle, name);
+    }
+
+    pub fn image<I: ImageAccess + ?Sized>(&self, image: &I, name: &str) {
+        let handle = image.inner().image.internal_object();
+        self.set(vk::OBJECT_TYPE_IMAGE, handle, name);
+    }
+
+    pub fn buffer<B: BufferAccess + ?Sized>(&self, buffer: &B, name: &str) {
+        let handle = buffer.inner().buffer.internal_object();
+        self.set(vk::OBJECT_TYPE_BUFFER, handle, name);
+    }
+
+    pub fn pipeline(
+        &self,
+        pipeline: &(dyn GraphicsPipelineAbstract + Send + Sync),
+        name: &str,
+    ) {
+        let handle = pipeline.inner().internal_object();
+        self.set(vk::OBJECT_TYPE_PIPELINE, handle, name);
+    }
+
+    pub fn compute_pipeline(
+        &self,
+        pipeline: &(dyn ComputePipelineAbstract + Send + Sync),
+        name: &str,
+    ) {
+        let handle = pipeline.inner().internal_object();
+        self.set(vk::OBJECT_TYPE_PIPELINE, handle, name);
+    }
+
+    // A single labelled point in the queue's timeline, e.g. one per frame.
+    pub fn label(&self, queue: &Queue, name: &str) {
+        if !self.enabled {
+            return;
+        }
+        let name = CString::new(name).unwrap();
+        let info = vk::DebugUtilsLabelEXT {
+            sType: vk::STRUCTURE_TYPE_DEBUG_UTILS_LABEL_EXT,
+            pNext: ptr::null(),
+            pLabelName: name.as_ptr(),
+            color: [0.0; 4],
+        };
+        let queue = queue.internal_object_guard();
+        unsafe {
+            self.device
+                .instance()
+                .pointers()
+                .QueueInsertDebugUtilsLabelEXT(*queue, &info);
+        }
+    }
+
+    // Opens a labelled region, e.g. around a pass. `builder` must be
+    // outside a render pass.
+    pub fn begin_label(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        family: QueueFamily,
+        name: &str,
+    ) -> AutoCommandBufferBuilder {
+        if !self.enabled {
+            return builder;
+        }
+        let name = CString::new(name).unwrap();
+        let info = vk::DebugUtilsLabelEXT {
+            sType: vk::STRUCTURE_TYPE_DEBUG_UTILS_LABEL_EXT,
+            pNext: ptr::null(),
+            pLabelName: name.as_ptr(),
+            color: [0.0; 4],
+        };
+        let instance = self.device.instance();
+        let commands = unsafe {
+            RawCommands::record(self.device.clone(), family, |_, cmd| {
+                instance.pointers().CmdBeginDebugUtilsLabelEXT(cmd, &info);
+            })
+        };
+        commands.execute(builder)
+    }
+
+    // Closes the innermost region opened by `begin_label`.
+    pub fn end_label(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        family: QueueFamily,
+    ) -> AutoCommandBufferBuilder {
+        if !self.enabled {
+            return builder;
+        }
+        let instance = self.device.instance();
+        let commands = unsafe {
+            RawCommands::record(self.device.clone(), family, |_, cmd| {
+                instance.pointers().CmdEndDebugUtilsLabelEXT(cmd);
+            })
+        };
+        commands.execute(builder)
+    }
+
+    fn set(&self, object_type: vk::ObjectType, handle: u64, name: &str) {
+        if !self.enabled {
+            return;
+        }
+        let name = CString::new(name).unwrap();
+        let info = vk::DebugUtilsObjectNameInfoEXT {
+            sType: vk::STRUCTURE_TYPE_DEBUG_UTILS_OBJECT_NAME_INFO_EXT,
+            pNext: ptr::null(),
+            objectType: object_type,
+            objectHandle: handle,
+            pObjectName: name.as_ptr(),
+        };
+        unsafe {
+            self.device
+                .instance()
+                .pointers()
+                .SetDebugUtilsObjectNameEXT(
+                    self.device.internal_object(),
+                    &info,
+                );
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tracing::warn;
+
+pub const HELP: &str = "commands: \
+                        toggle <wireframe|probes|grid|fog|debug-layer>, \
+                        set <name> <value>, set vsync <on|off>, \
+                        load <path>, view next, screenshot [path], \
+                        stats, memory, profile, help";
+
+// Every command with its fixed leading words, for completion.
+pub const COMMANDS: &[&str] = &[
+    "toggle wireframe",
+    "toggle vsync",
+    "toggle probes",
+    "toggle grid",
+    "toggle normals",
+    "toggle fog",
+    "toggle debug-layer",
+    "set vsync on",
+    "set vsync off",
+    "set",
+    "load",
+    "view next",
+    "screenshot",
+    "stats",
+    "memory",
+    "profile",
+    "help",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Toggle(String),
+    Set(String, f32),
+    Vsync(bool),
+    Load(String),
+    NextView,
+    Screenshot(Option<String>),
+    Stats,
+    Memory,
+    Profile,
+    Help,
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        ["toggle", name] => Ok(Command::Toggle((*name).to_owned())),
+        ["set", "vsync", "on"] => Ok(Command::Vsync(true)),
+        ["set", "vsync", "off"] => Ok(Command::Vsync(false)),
+        ["set", name, value] => value
+            .parse()
+            .map(|value| Command::Set((*name).to_owned(), value))
+            .map_err(|_| format!("not a number: {}", value)),
+        ["load", path] => Ok(Command::Load((*path).to_owned())),
+        ["view", "next"] => Ok(Command::NextView),
+        ["screenshot"] => Ok(Command::Screenshot(None)),
+        ["screenshot", path] => {
+            Ok(Command::Screenshot(Some((*path).to_owned())))
+        }
+        ["stats"] => Ok(Command::Stats),
+        ["memory"] => Ok(Command::Memory),
+        ["profile"] => Ok(Command::Profile),
+        ["help"] => Ok(Command::Help),
+        _ => Err(format!("unknown command: {}", line.trim())),
+    }
+}
+
+pub struct Request {
+    pub client: u64,
+    pub command: Result<Command, String>,
+}
+
+struct Client {
+    id: u64,
+    stream: TcpStream,
+    pending: Vec<u8>,
+    closed: bool,
+}
+
+// Line-based TCP server polled once per frame; never blocks the render loop.
+pub struct Server {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    next_id: u64,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Server> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Server {
+            listener,
+            clients: Vec::new(),
+            next_id: 0,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn poll(&mut self) -> Vec<Request> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_err() {
+                        continue;
+                    }
+                    self.clients.push(Client {
+                        id: self.next_id,
+                        stream,
+                        pending: Vec::new(),
+                        closed: false,
+                    });
+                    self.next_id += 1;
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!(error = ?e, "debug server accept failed");
+                    break;
+                }
+            }
+        }
+
+        let mut requests = Vec::new();
+        for client in &mut self.clients {
+            let mut chunk = [0u8; 1024];
+            loop {
+                match client.stream.read(&mut chunk) {
+                    Ok(0) => {
+                        client.closed = true;
+                        break;
+                    }
+                    Ok(read) => {
+                        client.pending.extend_from_slice(&chunk[..read])
+                    }
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(_) => {
+                        client.closed = true;
+                        break;
+                    }
+                }
+            }
+
+            while let Some(end) =
+                client.pending.iter().position(|&b| b == b'\n')
+            {
+                let line = client.pending.drain(..=end).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                requests.push(Request {
+                    client: client.id,
+                    command: parse(&line),
+                });
+            }
+        }
+        self.clients.retain(|client| !client.closed);
+        requests
+    }
+
+    pub fn reply(&mut self, client: u64, text: &str) {
+        if let Some(client) = self.clients.iter_mut().find(|c| c.id == client) {
+            let result = client
+                .stream
+                .write_all(text.as_bytes())
+                .and_then(|_| client.stream.write_all(b"\n"));
+            if result.is_err() {
+                client.closed = true;
+            }
+        }
+    }
+}
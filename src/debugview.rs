@@ -0,0 +1,30 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugView {
+    Final,
+    Depth,
+    Normals,
+    Albedo,
+    Ao,
+    Overdraw,
+}
+
+impl DebugView {
+    pub fn next(self) -> DebugView {
+        match self {
+            DebugView::Final => DebugView::Depth,
+            DebugView::Depth => DebugView::Normals,
+            DebugView::Normals => DebugView::Albedo,
+            DebugView::Albedo => DebugView::Ao,
+            DebugView::Ao => DebugView::Overdraw,
+            DebugView::Overdraw => DebugView::Final,
+        }
+    }
+
+    pub fn mode(self) -> u32 {
+        self as u32
+    }
+
+    pub fn bit(self) -> u32 {
+        1 << self.mode()
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::pipeline_layout::PipelineLayoutAbstract;
+
+// Identifies a set by its layout and the identity of every bound resource.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key {
+    layout: usize,
+    resources: Vec<usize>,
+}
+
+impl Key {
+    pub fn new<L>(pipeline: &L, set: usize) -> Key
+    where
+        L: PipelineLayoutAbstract + ?Sized,
+    {
+        let layout = pipeline.descriptor_set_layout(set).unwrap();
+        Key {
+            layout: Arc::as_ptr(layout) as usize,
+            resources: Vec::new(),
+        }
+    }
+
+    pub fn with<T: ?Sized>(mut self, resource: &Arc<T>) -> Key {
+        self.resources
+            .push(Arc::as_ptr(resource) as *const () as usize);
+        self
+    }
+}
+
+struct Entry {
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    last_used: u64,
+}
+
+// Reuses descriptor sets across frames. Keys hold raw pointers, so each
+// entry keeps its resources alive through the set until it is evicted.
+pub struct DescriptorCache {
+    sets: HashMap<Key, Entry>,
+    frame: u64,
+    max_age: u64,
+}
+
+impl DescriptorCache {
+    pub fn new(max_age: u64) -> DescriptorCache {
+        DescriptorCache {
+            sets: HashMap::new(),
+            frame: 0,
+            max_age,
+        }
+    }
+
+    pub fn get<F>(
+        &mut self,
+        key: Key,
+        build: F,
+    ) -> Arc<dyn DescriptorSet + Send + Sync>
+    where
+        F: FnOnce() -> Arc<dyn DescriptorSet + Send + Sync>,
+    {
+        let frame = self.frame;
+        let entry = self.sets.entry(key).or_insert_with(|| Entry {
+            set: build(),
+            last_used: frame,
+        });
+        entry.last_used = frame;
+        entry.set.clone()
+    }
+
+    // Drops sets that weren't used in the last `max_age` frames, e.g. ones
+    // referencing targets from before a resize.
+    pub fn end_frame(&mut self) {
+        let (frame, max_age) = (self.frame, self.max_age);
+        self.sets
+            .retain(|_, entry| frame - entry.last_used <= max_age);
+        self.frame += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+}
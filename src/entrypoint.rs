@@ -0,0 +1,200 @@
+use crate::compat;
+use std::ffi::CStr;
+use vulkano::descriptor::descriptor::DescriptorBufferDesc;
+use vulkano::descriptor::descriptor::DescriptorDesc;
+use vulkano::descriptor::descriptor::DescriptorDescTy;
+use vulkano::descriptor::descriptor::DescriptorImageDesc;
+use vulkano::descriptor::descriptor::DescriptorImageDescArray;
+use vulkano::descriptor::descriptor::DescriptorImageDescDimensions;
+use vulkano::descriptor::descriptor::ShaderStages;
+use vulkano::descriptor::pipeline_layout::PipelineLayoutDesc;
+use vulkano::descriptor::pipeline_layout::PipelineLayoutDescPcRange;
+use vulkano::format::Format;
+use vulkano::pipeline::shader::GraphicsEntryPoint;
+use vulkano::pipeline::shader::GraphicsShaderType;
+use vulkano::pipeline::shader::ShaderInterfaceDef;
+use vulkano::pipeline::shader::ShaderInterfaceDefEntry;
+use vulkano::pipeline::shader::ShaderModule;
+
+// The `main` function of `module`, described either by the interface types
+// that `vulkano_shaders::shader!` generated for the same source or by a
+// `StageInterface` and `LayoutDesc` written by hand. Lets pipelines be
+// built from modules compiled or loaded at runtime.
+//
+// Unsafe because nothing checks that the module's inputs, outputs,
+// descriptors and push constants match the description.
+pub unsafe fn graphics<S, I, O, L>(
+    module: &ShaderModule,
+    input: I,
+    output: O,
+    layout: L,
+    ty: GraphicsShaderType,
+) -> GraphicsEntryPoint<'_, S, I, O, L> {
+    let name = CStr::from_bytes_with_nul_unchecked(b"main\0");
+    module.graphics_entry_point(name, input, output, layout, ty)
+}
+
+pub fn vertex_stage() -> ShaderStages {
+    ShaderStages {
+        vertex: true,
+        ..ShaderStages::none()
+    }
+}
+
+pub fn fragment_stage() -> ShaderStages {
+    ShaderStages {
+        fragment: true,
+        ..ShaderStages::none()
+    }
+}
+
+// A stage's inputs or outputs, one entry per location.
+#[derive(Debug, Clone, Default)]
+pub struct StageInterface {
+    entries: Vec<ShaderInterfaceDefEntry>,
+}
+
+impl StageInterface {
+    pub fn new() -> StageInterface {
+        StageInterface::default()
+    }
+
+    // Formats that span several locations, such as matrices, take one
+    // entry per column.
+    pub fn with(mut self, location: u32, format: Format) -> StageInterface {
+        self.entries.push(ShaderInterfaceDefEntry {
+            location: location..location + 1,
+            format,
+            name: None,
+        });
+        self
+    }
+}
+
+unsafe impl ShaderInterfaceDef for StageInterface {
+    type Iter = std::vec::IntoIter<ShaderInterfaceDefEntry>;
+
+    fn elements(&self) -> Self::Iter {
+        self.entries.clone().into_iter()
+    }
+}
+
+// The descriptor sets and push constants one stage uses.
+#[derive(Debug, Clone, Default)]
+pub struct LayoutDesc {
+    sets: Vec<Vec<Option<DescriptorDesc>>>,
+    push_constants: Option<PipelineLayoutDescPcRange>,
+}
+
+impl LayoutDesc {
+    // The same bindings `compat` checks the built-in pipelines against.
+    // Images are taken to be 2D and not arrayed, and push constants to
+    // start at offset 0.
+    pub fn new(
+        interface: &compat::Interface,
+        stages: ShaderStages,
+    ) -> LayoutDesc {
+        let mut layout = LayoutDesc::default();
+        for binding in &interface.bindings {
+            layout.set_descriptor(
+                binding.set,
+                binding.binding,
+                DescriptorDesc {
+                    ty: descriptor_ty(binding.kind),
+                    array_count: 1,
+                    stages,
+                    readonly: binding.kind != compat::Kind::StorageBuffer
+                        && binding.kind != compat::Kind::StorageImage,
+                },
+            );
+        }
+        layout.set_push_constants(interface.push_constants, stages);
+        layout
+    }
+
+    pub fn set_descriptor(
+        &mut self,
+        set: usize,
+        binding: usize,
+        desc: DescriptorDesc,
+    ) {
+        if self.sets.len() <= set {
+            self.sets.resize(set + 1, Vec::new());
+        }
+        let set = &mut self.sets[set];
+        if set.len() <= binding {
+            set.resize(binding + 1, None);
+        }
+        set[binding] = Some(desc);
+    }
+
+    // One range from offset 0; a size of 0 means no push constants.
+    pub fn set_push_constants(&mut self, size: usize, stages: ShaderStages) {
+        self.push_constants = if size > 0 {
+            Some(PipelineLayoutDescPcRange {
+                offset: 0,
+                size,
+                stages,
+            })
+        } else {
+            None
+        };
+    }
+}
+
+unsafe impl PipelineLayoutDesc for LayoutDesc {
+    fn num_sets(&self) -> usize {
+        self.sets.len()
+    }
+
+    fn num_bindings_in_set(&self, set: usize) -> Option<usize> {
+        self.sets.get(set).map(|set| set.len())
+    }
+
+    fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
+        self.sets.get(set)?.get(binding)?.clone()
+    }
+
+    fn num_push_constants_ranges(&self) -> usize {
+        self.push_constants.iter().count()
+    }
+
+    fn push_constants_range(
+        &self,
+        num: usize,
+    ) -> Option<PipelineLayoutDescPcRange> {
+        self.push_constants.as_ref().filter(|_| num == 0).cloned()
+    }
+}
+
+fn descriptor_ty(kind: compat::Kind) -> DescriptorDescTy {
+    let image = |sampled| DescriptorImageDesc {
+        sampled,
+        dimensions: DescriptorImageDescDimensions::TwoDimensional,
+        format: None,
+        multisampled: false,
+        array_layers: DescriptorImageDescArray::NonArrayed,
+    };
+    let buffer = |storage| DescriptorBufferDesc {
+        dynamic: Some(false),
+        storage,
+    };
+    match kind {
+        compat::Kind::UniformBuffer => DescriptorDescTy::Buffer(buffer(false)),
+        compat::Kind::StorageBuffer => DescriptorDescTy::Buffer(buffer(true)),
+        compat::Kind::CombinedImageSampler => {
+            DescriptorDescTy::CombinedImageSampler(image(true))
+        }
+        compat::Kind::SampledImage => DescriptorDescTy::Image(image(true)),
+        compat::Kind::StorageImage => DescriptorDescTy::Image(image(false)),
+        compat::Kind::Sampler => DescriptorDescTy::Sampler,
+        compat::Kind::InputAttachment => DescriptorDescTy::InputAttachment {
+            multisampled: false,
+            array_layers: DescriptorImageDescArray::NonArrayed,
+        },
+        compat::Kind::TexelBuffer => DescriptorDescTy::TexelBuffer {
+            storage: false,
+            format: None,
+        },
+    }
+}
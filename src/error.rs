@@ -0,0 +1,202 @@
+use std::io;
+use thiserror::Error;
+use vulkano::command_buffer::AutoCommandBufferBuilderContextError;
+use vulkano::command_buffer::BeginRenderPassError;
+use vulkano::command_buffer::BuildError;
+use vulkano::command_buffer::CommandBufferExecError;
+use vulkano::command_buffer::CopyBufferImageError;
+use vulkano::command_buffer::CopyImageError;
+use vulkano::command_buffer::DispatchError;
+use vulkano::command_buffer::DrawError;
+use vulkano::command_buffer::ExecuteCommandsError;
+use vulkano::command_buffer::FillBufferError;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSetBuildError;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSetError;
+use vulkano::device::DeviceCreationError;
+use vulkano::framebuffer::FramebufferCreationError;
+use vulkano::framebuffer::RenderPassCreationError;
+use vulkano::image::ImageCreationError;
+use vulkano::instance::InstanceCreationError;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::ComputePipelineCreationError;
+use vulkano::pipeline::GraphicsPipelineCreationError;
+use vulkano::sampler::SamplerCreationError;
+use vulkano::swapchain::AcquireError;
+use vulkano::swapchain::CapabilitiesError;
+use vulkano::swapchain::SwapchainCreationError;
+use vulkano::sync::FlushError;
+use vulkano::OomError;
+use vulkano_win::CreationError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// Failures during setup and rendering. Variants that name what was being
+// created come from the constructors below, e.g.
+// `.map_err(Error::pipeline("scene"))?`.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{context}: {source}")]
+    Io { context: String, source: io::Error },
+    #[error("invalid value {value:?} for {name}")]
+    Argument { name: String, value: String },
+    #[error("loading {path}: {source}")]
+    Texture {
+        path: String,
+        source: image::ImageError,
+    },
+    #[error("{count} textures don't fit a table of 1 to {capacity}")]
+    TextureTable { count: usize, capacity: usize },
+    #[error("no texture {0} in the texture table")]
+    UnknownTexture(String),
+    #[error("importing {path}: {message}")]
+    Import { path: String, message: String },
+    #[error("creating the Vulkan instance: {0}")]
+    Instance(#[from] InstanceCreationError),
+    #[error("creating the window: {0}")]
+    Window(#[from] CreationError),
+    #[error("no Vulkan device {0}")]
+    NoDevice(&'static str),
+    #[error("creating the device: {0}")]
+    Device(#[from] DeviceCreationError),
+    #[error("the device has no queue family {0}")]
+    QueueFamily(u32),
+    #[error("querying surface capabilities: {0}")]
+    Capabilities(#[from] CapabilitiesError),
+    #[error(
+        "{name} does not match the renderer's interface \
+         (- expected, + shader):\n{diff}"
+    )]
+    Interface { name: String, diff: String },
+    #[error("{pass} needs the {feature} device feature")]
+    MissingFeature {
+        pass: &'static str,
+        feature: &'static str,
+    },
+    #[error("{0} does not fit in the mesh arena")]
+    ArenaFull(&'static str),
+    #[error("loading the {name} shader: {source}")]
+    Shader {
+        name: &'static str,
+        source: OomError,
+    },
+    #[error("creating a shader module from {name}: {source}")]
+    Module { name: String, source: OomError },
+    #[error("{0} is not a SPIR-V module")]
+    InvalidSpirv(String),
+    #[error("reflecting {name}: {message}")]
+    Reflect { name: String, message: String },
+    #[error("compiling {name}: {message}")]
+    Compile { name: String, message: String },
+    #[error("the shader compiler is unavailable")]
+    Compiler,
+    #[error("watching shader sources: {0}")]
+    Watch(String),
+    #[error("creating the {name} render pass: {source}")]
+    RenderPass {
+        name: &'static str,
+        source: RenderPassCreationError,
+    },
+    #[error("building the {name} pipeline: {source}")]
+    Pipeline {
+        name: &'static str,
+        source: GraphicsPipelineCreationError,
+    },
+    #[error("building the {name} compute pipeline: {source}")]
+    ComputePipeline {
+        name: &'static str,
+        source: ComputePipelineCreationError,
+    },
+    #[error("allocating {name}: {source}")]
+    Allocation {
+        name: &'static str,
+        source: DeviceMemoryAllocError,
+    },
+    #[error("creating the {name} image: {source}")]
+    Image {
+        name: &'static str,
+        source: ImageCreationError,
+    },
+    #[error("creating a sampler: {0}")]
+    Sampler(#[from] SamplerCreationError),
+    #[error("creating a framebuffer: {0}")]
+    Framebuffer(#[from] FramebufferCreationError),
+    #[error("writing a descriptor set: {0}")]
+    DescriptorSet(#[from] PersistentDescriptorSetError),
+    #[error("building a descriptor set: {0}")]
+    DescriptorSetBuild(#[from] PersistentDescriptorSetBuildError),
+    #[error("creating the swapchain: {0}")]
+    Swapchain(#[from] SwapchainCreationError),
+    #[error("acquiring a swapchain image: {0}")]
+    Acquire(#[from] AcquireError),
+    #[error("starting a command buffer: {0}")]
+    CommandBuffer(#[from] OomError),
+    #[error("beginning a render pass: {0}")]
+    BeginRenderPass(#[from] BeginRenderPassError),
+    #[error("recording commands: {0}")]
+    Record(#[from] AutoCommandBufferBuilderContextError),
+    #[error("recording a draw: {0}")]
+    Draw(#[from] DrawError),
+    #[error("recording a dispatch: {0}")]
+    Dispatch(#[from] DispatchError),
+    #[error("recording a buffer fill: {0}")]
+    FillBuffer(#[from] FillBufferError),
+    #[error("recording an image copy: {0}")]
+    CopyImage(#[from] CopyImageError),
+    #[error("recording an image readback: {0}")]
+    CopyBufferImage(#[from] CopyBufferImageError),
+    #[error("executing secondary command buffers: {0}")]
+    ExecuteCommands(#[from] ExecuteCommandsError),
+    #[error("building a command buffer: {0}")]
+    Build(#[from] BuildError),
+    #[error("submitting a command buffer: {0}")]
+    Execute(#[from] CommandBufferExecError),
+    #[error("flushing the frame: {0}")]
+    Flush(#[from] FlushError),
+}
+
+impl Error {
+    pub fn io(context: String) -> impl FnOnce(io::Error) -> Error {
+        move |source| Error::Io { context, source }
+    }
+
+    pub fn argument(name: &str, value: &str) -> Error {
+        Error::Argument {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        }
+    }
+
+    pub fn shader(name: &'static str) -> impl FnOnce(OomError) -> Error {
+        move |source| Error::Shader { name, source }
+    }
+
+    pub fn render_pass(
+        name: &'static str,
+    ) -> impl FnOnce(RenderPassCreationError) -> Error {
+        move |source| Error::RenderPass { name, source }
+    }
+
+    pub fn pipeline(
+        name: &'static str,
+    ) -> impl FnOnce(GraphicsPipelineCreationError) -> Error {
+        move |source| Error::Pipeline { name, source }
+    }
+
+    pub fn compute_pipeline(
+        name: &'static str,
+    ) -> impl FnOnce(ComputePipelineCreationError) -> Error {
+        move |source| Error::ComputePipeline { name, source }
+    }
+
+    pub fn allocation(
+        name: &'static str,
+    ) -> impl FnOnce(DeviceMemoryAllocError) -> Error {
+        move |source| Error::Allocation { name, source }
+    }
+
+    pub fn image(
+        name: &'static str,
+    ) -> impl FnOnce(ImageCreationError) -> Error {
+        move |source| Error::Image { name, source }
+    }
+}
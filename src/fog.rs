@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FogMode {
+    Off,
+    Linear,
+    Exponential,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub color: [f32; 3],
+    pub start: f32,
+    pub end: f32,
+    pub density: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Fog {
+            mode: FogMode::Off,
+            color: [0.6, 0.6, 0.7],
+            start: 0.0,
+            end: 2.0,
+            density: 1.0,
+        }
+    }
+}
+
+impl Fog {
+    pub fn cycle_mode(&mut self) {
+        self.mode = match self.mode {
+            FogMode::Off => FogMode::Linear,
+            FogMode::Linear => FogMode::Exponential,
+            FogMode::Exponential => FogMode::Off,
+        };
+    }
+
+    pub fn params(&self) -> [f32; 4] {
+        let mode = match self.mode {
+            FogMode::Off => 0.0,
+            FogMode::Linear => 1.0,
+            FogMode::Exponential => 2.0,
+        };
+        [self.start, self.end, self.density, mode]
+    }
+}
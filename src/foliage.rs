@@ -0,0 +1,443 @@
+use crate::dbgpipe;
+use crate::error::Result;
+use crate::registry;
+use crate::registry::FrameContext;
+use crate::registry::InitContext;
+use crate::snapshot::Snapshot;
+use cgmath::SquareMatrix;
+use image::GrayImage;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::sync::Arc;
+use tracing::info;
+use tracing::warn;
+use vulkano::buffer::BufferAccess;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::device::Device;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::vertex::OneVertexOneInstanceDefinition;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+// Blade and rock dimensions before each instance's scale.
+const BLADE_WIDTH: f32 = 0.03;
+const BLADE_HEIGHT: f32 = 0.3;
+const ROCK_RADIUS: f32 = 0.08;
+const ROCK_HEIGHT: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    // How much wind moves this vertex: 0 at the root, 1 at the tip.
+    pub bend: f32,
+}
+
+vulkano::impl_vertex!(Vertex, position, bend);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Instance {
+    pub offset: [f32; 3],
+    pub scale: f32,
+    // Turn about y in radians.
+    pub yaw: f32,
+    // Offsets this instance's sway so neighbours don't move in lockstep.
+    pub phase: f32,
+    pub color: [f32; 4],
+}
+
+vulkano::impl_vertex!(Instance, offset, scale, yaw, phase, color);
+
+// Grass and rocks scattered over part of the y = 0 ground plane.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Foliage {
+    pub enabled: bool,
+    // Grayscale image stretched over `area`; brighter is denser, and
+    // density is uniform without one.
+    pub density_map: Option<String>,
+    // min x, min z, max x, max z
+    pub area: [f32; 4],
+    // Places tried; the density map rejects some of them.
+    pub count: u32,
+    pub rock_fraction: f32,
+    // Direction and strength on the xz plane.
+    pub wind: [f32; 2],
+    // Distances from the eye where instances start and finish fading out.
+    pub fade: [f32; 2],
+    pub seed: u32,
+}
+
+impl Default for Foliage {
+    fn default() -> Self {
+        Foliage {
+            enabled: false,
+            density_map: None,
+            area: [-5.0, -5.0, 5.0, 5.0],
+            count: 10000,
+            rock_fraction: 0.05,
+            wind: [0.08, 0.03],
+            fade: [6.0, 8.0],
+            seed: 0,
+        }
+    }
+}
+
+pub mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in float bend;
+layout (location = 2) in vec3 offset;
+layout (location = 3) in float scale;
+layout (location = 4) in float yaw;
+layout (location = 5) in float phase;
+layout (location = 6) in vec4 color;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+// eye.w: time in seconds, fade: start and end distance
+layout (push_constant) uniform Wind {
+    vec4 eye;
+    vec2 direction;
+    vec2 fade;
+} wind;
+
+layout (location = 0) out vec4 v_color;
+layout (location = 1) out float v_fade;
+
+void main() {
+    float c = cos(yaw);
+    float s = sin(yaw);
+    vec3 local = position * scale;
+    vec3 world = offset
+        + vec3(c * local.x + s * local.z, local.y, c * local.z - s * local.x);
+
+    // Tips sway furthest; the gust travels across the field.
+    float time = wind.eye.w;
+    float gust = sin(time * 2.0 + phase + dot(offset.xz, vec2(0.7, 0.4)));
+    world.xz += wind.direction * (0.6 + 0.4 * gust) * bend * bend * scale;
+
+    // Faded per instance, so a blade never fades unevenly.
+    v_fade = 1.0
+        - smoothstep(wind.fade.x, wind.fade.y, distance(offset, wind.eye.xyz));
+    v_color = vec4(color.rgb * (0.7 + 0.3 * bend), color.a);
+    gl_Position = vp_inst.vp * vec4(world, 1.0);
+    // Instances that have faded out entirely collapse to a point.
+    if (v_fade <= 0.0) {
+        gl_Position = vec4(0.0, 0.0, 2.0, 1.0);
+    }
+}"
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+
+layout (location = 0) in vec4 v_color;
+layout (location = 1) in float v_fade;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    // Screen-door fade keeps foliage opaque, so it needs no sorting.
+    float noise = fract(
+        52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+    if (v_fade <= noise) {
+        discard;
+    }
+    f_color = v_color;
+}
+"
+    }
+}
+
+// Same hash as the particle shader, to [0, 1].
+fn hash(mut x: u32) -> f32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x as f32 / std::u32::MAX as f32
+}
+
+fn density(map: &GrayImage, u: f32, v: f32) -> f32 {
+    let (width, height) = map.dimensions();
+    let x = ((u * width as f32) as u32).min(width - 1);
+    let y = ((v * height as f32) as u32).min(height - 1);
+    map.get_pixel(x, y)[0] as f32 / 255.0
+}
+
+// Grass and rock instances for `foliage`, in that order. Placement only
+// depends on the settings, so the same seed scatters the same field.
+pub fn scatter(
+    foliage: &Foliage,
+    map: Option<&GrayImage>,
+) -> (Vec<Instance>, Vec<Instance>) {
+    let [x0, z0, x1, z1] = foliage.area;
+    let mut grass = Vec::new();
+    let mut rocks = Vec::new();
+    for i in 0..foliage.count {
+        let h = i
+            .wrapping_mul(747_796_405)
+            .wrapping_add(foliage.seed.wrapping_mul(2_891_336_453));
+        let random = |n: u32| hash(h.wrapping_add(n));
+        let (u, v) = (random(0), random(1));
+        if random(2) > map.map_or(1.0, |map| density(map, u, v)) {
+            continue;
+        }
+        let offset = [x0 + (x1 - x0) * u, 0.0, z0 + (z1 - z0) * v];
+        let yaw = random(3) * 2.0 * PI;
+        let phase = random(4) * 2.0 * PI;
+        let shade = random(5);
+        if random(6) < foliage.rock_fraction {
+            let gray = 0.35 + 0.2 * shade;
+            rocks.push(Instance {
+                offset,
+                scale: 0.5 + random(7),
+                yaw,
+                phase,
+                color: [gray, gray, gray * 0.95, 1.0],
+            });
+        } else {
+            grass.push(Instance {
+                offset,
+                scale: 0.6 + 0.8 * random(7),
+                yaw,
+                phase,
+                color: [0.2 + 0.2 * shade, 0.5 + 0.3 * shade, 0.1, 1.0],
+            });
+        }
+    }
+    (grass, rocks)
+}
+
+// Two crossed tapered blades, so grass has width from any side.
+pub fn blade() -> Vec<Vertex> {
+    let (w, h) = (BLADE_WIDTH, BLADE_HEIGHT);
+    let outline = [
+        (-w, 0.0),
+        (w, 0.0),
+        (-w * 0.6, h * 0.5),
+        (w * 0.6, h * 0.5),
+        (0.0, h),
+    ];
+    let mut vertices = Vec::new();
+    for &crossed in &[false, true] {
+        for &i in &[0, 1, 3, 0, 3, 2, 2, 3, 4] {
+            let (across, up) = outline[i];
+            let position = if crossed {
+                [0.0, up, across]
+            } else {
+                [across, up, 0.0]
+            };
+            vertices.push(Vertex {
+                position,
+                bend: up / h,
+            });
+        }
+    }
+    vertices
+}
+
+// A low four-sided pyramid sitting on the ground; rocks don't sway.
+pub fn rock() -> Vec<Vertex> {
+    let apex = [0.0, ROCK_HEIGHT, 0.0];
+    let r = ROCK_RADIUS;
+    let base = [[r, 0.0, 0.0], [0.0, 0.0, r], [-r, 0.0, 0.0], [0.0, 0.0, -r]];
+    (0..4)
+        .flat_map(|i| vec![base[i], base[(i + 1) % 4], apex])
+        .map(|position| Vertex {
+            position,
+            bend: 0.0,
+        })
+        .collect()
+}
+
+struct Batch {
+    vertices: Arc<CpuAccessibleBuffer<[Vertex]>>,
+    instances: Arc<CpuAccessibleBuffer<[Instance]>>,
+}
+
+impl Batch {
+    // None for no instances, which would be an empty buffer.
+    fn new(
+        device: Arc<Device>,
+        vertices: Vec<Vertex>,
+        instances: Vec<Instance>,
+    ) -> Option<Batch> {
+        if instances.is_empty() {
+            return None;
+        }
+        let usage = BufferUsage::vertex_buffer();
+        Some(Batch {
+            vertices: CpuAccessibleBuffer::from_iter(
+                device.clone(),
+                usage,
+                vertices.into_iter(),
+            )
+            .unwrap(),
+            instances: CpuAccessibleBuffer::from_iter(
+                device,
+                usage,
+                instances.into_iter(),
+            )
+            .unwrap(),
+        })
+    }
+}
+
+pub struct System {
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    batches: Vec<Batch>,
+}
+
+impl System {
+    // Foliage is drawn inside the forward scene pass.
+    pub fn new(
+        device: Arc<Device>,
+        scene: &dbgpipe::Pipeline,
+        foliage: &Foliage,
+    ) -> System {
+        let vs = vs::Shader::load(device.clone()).unwrap();
+        let fs = fs::Shader::load(device.clone()).unwrap();
+
+        let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> =
+            scene.render_pass.clone();
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(
+                    OneVertexOneInstanceDefinition::<Vertex, Instance>::new(),
+                )
+                .vertex_shader(vs.main_entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil_simple_depth()
+                .fragment_shader(fs.main_entry_point(), ())
+                .render_pass(Subpass::from(render_pass, 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+
+        let map =
+            foliage.density_map.as_ref().and_then(|path| {
+                match image::open(path) {
+                    Ok(image) => Some(image.to_luma()),
+                    Err(e) => {
+                        warn!(%path, error = %e, "density map unreadable");
+                        None
+                    }
+                }
+            });
+        let (grass, rocks) = scatter(foliage, map.as_ref());
+        info!(
+            grass = grass.len(),
+            rocks = rocks.len(),
+            "scattered foliage"
+        );
+        let batches = vec![
+            Batch::new(device.clone(), blade(), grass),
+            Batch::new(device, rock(), rocks),
+        ];
+
+        System {
+            pipeline,
+            batches: batches.into_iter().flatten().collect(),
+        }
+    }
+
+    pub fn draw(
+        &self,
+        mut builder: AutoCommandBufferBuilder,
+        dynamic_state: &DynamicState,
+        view_set: Arc<dyn DescriptorSet + Send + Sync>,
+        foliage: &Foliage,
+        eye: [f32; 3],
+        time: f32,
+    ) -> AutoCommandBufferBuilder {
+        for batch in &self.batches {
+            builder = builder
+                .draw(
+                    self.pipeline.clone(),
+                    dynamic_state,
+                    vec![
+                        batch.vertices.clone()
+                            as Arc<dyn BufferAccess + Send + Sync>,
+                        batch.instances.clone(),
+                    ],
+                    vec![view_set.clone()],
+                    vs::ty::Wind {
+                        eye: [eye[0], eye[1], eye[2], time],
+                        direction: foliage.wind,
+                        fade: foliage.fade,
+                    },
+                )
+                .unwrap();
+        }
+        builder
+    }
+}
+
+// Scatters once at init; the instances never change after that.
+#[derive(Default)]
+pub struct Feature {
+    system: Option<System>,
+    time: f32,
+}
+
+impl registry::Feature for Feature {
+    fn name(&self) -> &str {
+        "foliage"
+    }
+
+    fn init(&mut self, context: &InitContext) -> Result<()> {
+        if context.state.foliage.enabled {
+            self.system = Some(System::new(
+                context.device.clone(),
+                context.scene,
+                &context.state.foliage,
+            ));
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, dt: f32, _state: &Snapshot) {
+        self.time += dt;
+    }
+
+    fn draw_scene(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        frame: &FrameContext,
+    ) -> Result<AutoCommandBufferBuilder> {
+        let eye = frame
+            .state
+            .camera
+            .view()
+            .invert()
+            .map_or([0.0; 3], |inverse| inverse.w.truncate().into());
+        Ok(match &self.system {
+            Some(system) => system.draw(
+                builder,
+                frame.dynamic_state,
+                frame.view_set.clone(),
+                &frame.state.foliage,
+                eye,
+                self.time,
+            ),
+            None => builder,
+        })
+    }
+}
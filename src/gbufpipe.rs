@@ -0,0 +1,395 @@
+use crate::camera::Camera;
+use crate::compat;
+use crate::dbgpipe::Vertex;
+use crate::dbgpipe::ViewBlock;
+use crate::entrypoint;
+use crate::error::Error;
+use crate::error::Result;
+use crate::fog::Fog;
+use crate::fullscreen;
+use crate::snapshot::Light;
+use crate::snapshot::LocalLight;
+use crate::spirv;
+use cgmath::{Matrix4, SquareMatrix};
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::ClearValue;
+use vulkano::format::Format;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::shader::GraphicsShaderType;
+use vulkano::pipeline::vertex::BufferlessDefinition;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::swapchain::Swapchain;
+use winit::window::Window;
+
+pub const ALBEDO_FORMAT: Format = Format::R8G8B8A8Unorm;
+pub const NORMAL_FORMAT: Format = Format::R16G16B16A16Sfloat;
+pub const MATERIAL_FORMAT: Format = Format::R8G8B8A8Unorm;
+pub const DEPTH_FORMAT: Format = Format::D16Unorm;
+
+pub mod geometry_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+layout (location = 0) in vec4 position;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+layout (location = 0) out vec3 out_normal;
+
+void main() {
+    gl_Position = vp_inst.vp * position;
+    out_normal = vec3(0.0, 0.0, -1.0);
+}"
+    }
+}
+
+pub mod geometry_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+layout (location = 0) in vec3 normal;
+
+layout (location = 0) out vec4 f_albedo;
+layout (location = 1) out vec4 f_normal;
+layout (location = 2) out vec4 f_material;
+
+void main() {
+    f_albedo = vec4(1.0, 0.0, 0.0, 1.0);
+    f_normal = vec4(normalize(normal), 0.0);
+    // r: ambient occlusion, g: lit flag
+    f_material = vec4(1.0, 1.0, 0.0, 0.0);
+}
+"
+    }
+}
+
+pub mod lighting_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/lighting.frag",
+        include: ["shaders/include"]
+    }
+}
+
+pub struct Pipeline {
+    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub geometry: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pub lighting: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+pub struct Targets {
+    pub framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    pub lighting_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub albedo: Arc<AttachmentImage>,
+    pub normal: Arc<AttachmentImage>,
+    pub material: Arc<AttachmentImage>,
+    pub depth: Arc<AttachmentImage>,
+}
+
+pub fn geometry_interface() -> compat::Interface {
+    compat::Interface {
+        bindings: vec![compat::Binding {
+            set: compat::VIEW_SET,
+            binding: 0,
+            kind: compat::Kind::UniformBuffer,
+        }],
+        push_constants: 0,
+        blocks: vec![compat::Block::of::<ViewBlock>(compat::VIEW_SET, 0)],
+    }
+}
+
+// What the geometry shaders declare. The SPIR-V isn't kept, so the view
+// block's size comes from the struct vulkano generated for it.
+pub fn geometry_shader_interface(pipeline: &Pipeline) -> compat::Interface {
+    compat::Interface {
+        blocks: vec![compat::Block::of::<geometry_vs::ty::VP_BLOCK>(
+            compat::VIEW_SET,
+            0,
+        )],
+        ..compat::reflect(&*pipeline.geometry)
+    }
+}
+
+pub fn build(
+    device: Arc<Device>,
+    swapchain: Arc<Swapchain<Window>>,
+) -> Result<Pipeline> {
+    let geometry_vs = geometry_vs::Shader::load(device.clone())
+        .map_err(Error::shader("gbuffer vertex"))?;
+    let geometry_fs = geometry_fs::Shader::load(device.clone())
+        .map_err(Error::shader("gbuffer fragment"))?;
+    let lighting_vs = fullscreen::vs::Shader::load(device.clone())
+        .map_err(Error::shader("fullscreen vertex"))?;
+    let lighting_fs =
+        spirv::module(device.clone(), "lighting", &spirv::LIGHTING_FRAG)?;
+
+    let render_pass = Arc::new(
+        vulkano::ordered_passes_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: DontCare,
+                    store: Store,
+                    format: swapchain.format(),
+                    samples: 1,
+                },
+                albedo: {
+                    load: Clear,
+                    store: Store,
+                    format: ALBEDO_FORMAT,
+                    samples: 1,
+                },
+                normal: {
+                    load: Clear,
+                    store: Store,
+                    format: NORMAL_FORMAT,
+                    samples: 1,
+                },
+                material: {
+                    load: Clear,
+                    store: Store,
+                    format: MATERIAL_FORMAT,
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: Store,
+                    format: DEPTH_FORMAT,
+                    samples: 1,
+                }
+            },
+            passes: [
+                {
+                    color: [albedo, normal, material],
+                    depth_stencil: {depth},
+                    input: []
+                },
+                {
+                    color: [color],
+                    depth_stencil: {},
+                    input: [albedo, normal, material, depth]
+                }
+            ]
+        )
+        .map_err(Error::render_pass("gbuffer"))?,
+    );
+
+    let geometry = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Vertex>()
+            .vertex_shader(geometry_vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .depth_stencil_simple_depth()
+            .fragment_shader(geometry_fs.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("gbuffer geometry"))?,
+    );
+
+    let lighting = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition)
+            .vertex_shader(lighting_vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(
+                unsafe {
+                    entrypoint::graphics(
+                        &lighting_fs,
+                        lighting_fs::MainInput,
+                        lighting_fs::MainOutput,
+                        lighting_fs::Layout(entrypoint::fragment_stage()),
+                        GraphicsShaderType::Fragment,
+                    )
+                },
+                (),
+            )
+            .render_pass(Subpass::from(render_pass.clone(), 1).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("deferred lighting"))?,
+    );
+
+    Ok(Pipeline {
+        render_pass,
+        geometry,
+        lighting,
+    })
+}
+
+// Point and spot lights past this many are left out of the lighting pass.
+pub const MAX_LOCAL_LIGHTS: usize = 8;
+
+pub fn scene_block(
+    camera: &Camera,
+    light: &Light,
+    local_lights: &[LocalLight],
+    fog: &Fog,
+) -> lighting_fs::ty::SCENE_BLOCK {
+    let inverse_view_projection = camera
+        .view_projection()
+        .invert()
+        .unwrap_or_else(Matrix4::identity);
+    let [x, y, z] = camera.position;
+    let local_lights =
+        &local_lights[..local_lights.len().min(MAX_LOCAL_LIGHTS)];
+    let mut local_position = [[0.0; 4]; MAX_LOCAL_LIGHTS];
+    let mut local_color = [[0.0; 4]; MAX_LOCAL_LIGHTS];
+    let mut local_direction = [[0.0; 4]; MAX_LOCAL_LIGHTS];
+    for (i, local) in local_lights.iter().enumerate() {
+        let [px, py, pz] = local.position;
+        let [r, g, b] = local.color;
+        let [dx, dy, dz] = local.direction;
+        let [inner, outer] = local.cone.unwrap_or([-2.0, -2.0]);
+        local_position[i] = [px, py, pz, local.range.unwrap_or(0.0)];
+        local_color[i] = [r, g, b, inner];
+        local_direction[i] = [dx, dy, dz, outer];
+    }
+    lighting_fs::ty::SCENE_BLOCK {
+        inverse_view_projection: inverse_view_projection.into(),
+        eye: [x, y, z, 1.0],
+        light_direction: light.direction,
+        light_color: light.color,
+        fog_color: [fog.color[0], fog.color[1], fog.color[2], 1.0],
+        fog_params: fog.params(),
+        local_count: [local_lights.len() as f32, 0.0, 0.0, 0.0],
+        local_position,
+        local_color,
+        local_direction,
+    }
+}
+
+pub fn scene_set<B, P>(
+    pipeline: &Pipeline,
+    buffer: B,
+    probes: P,
+) -> Result<Arc<dyn DescriptorSet + Send + Sync>>
+where
+    B: BufferAccess + Send + Sync + 'static,
+    P: BufferAccess + Send + Sync + 'static,
+{
+    Ok(Arc::new(
+        PersistentDescriptorSet::start(pipeline.lighting.clone(), 1)
+            .add_buffer(buffer)?
+            .add_buffer(probes)?
+            .build()?,
+    ))
+}
+
+pub fn targets(
+    pipeline: &Pipeline,
+    device: Arc<Device>,
+    color: Arc<AttachmentImage>,
+) -> Result<Targets> {
+    let dimensions = color.dimensions();
+    let albedo = AttachmentImage::sampled_input_attachment(
+        device.clone(),
+        dimensions,
+        ALBEDO_FORMAT,
+    )
+    .map_err(Error::image("gbuffer albedo"))?;
+    let normal = AttachmentImage::sampled_input_attachment(
+        device.clone(),
+        dimensions,
+        NORMAL_FORMAT,
+    )
+    .map_err(Error::image("gbuffer normal"))?;
+    let material = AttachmentImage::sampled_input_attachment(
+        device.clone(),
+        dimensions,
+        MATERIAL_FORMAT,
+    )
+    .map_err(Error::image("gbuffer material"))?;
+    let depth = AttachmentImage::sampled_input_attachment(
+        device.clone(),
+        dimensions,
+        DEPTH_FORMAT,
+    )
+    .map_err(Error::image("gbuffer depth"))?;
+
+    let framebuffer = Arc::new(
+        Framebuffer::start(pipeline.render_pass.clone())
+            .add(color)?
+            .add(albedo.clone())?
+            .add(normal.clone())?
+            .add(material.clone())?
+            .add(depth.clone())?
+            .build()?,
+    );
+
+    let lighting_set = Arc::new(
+        PersistentDescriptorSet::start(pipeline.lighting.clone(), 0)
+            .add_image(albedo.clone())?
+            .add_image(normal.clone())?
+            .add_image(material.clone())?
+            .add_image(depth.clone())?
+            .build()?,
+    );
+
+    Ok(Targets {
+        framebuffer,
+        lighting_set,
+        albedo,
+        normal,
+        material,
+        depth,
+    })
+}
+
+pub fn draw(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &Pipeline,
+    targets: &Targets,
+    dynamic_state: &DynamicState,
+    vertex_buffers: Vec<Arc<dyn BufferAccess + Send + Sync>>,
+    view_set: Arc<dyn DescriptorSet + Send + Sync>,
+    scene_set: Arc<dyn DescriptorSet + Send + Sync>,
+) -> AutoCommandBufferBuilder {
+    let clear_values = vec![
+        ClearValue::None,
+        [0.0, 0.0, 1.0, 1.0].into(),
+        [0.0, 0.0, 0.0, 0.0].into(),
+        [0.0, 0.0, 0.0, 0.0].into(),
+        1.0f32.into(),
+    ];
+
+    builder
+        .begin_render_pass(targets.framebuffer.clone(), false, clear_values)
+        .unwrap()
+        .draw(
+            pipeline.geometry.clone(),
+            dynamic_state,
+            vertex_buffers,
+            vec![view_set],
+            (),
+        )
+        .unwrap()
+        .next_subpass(false)
+        .unwrap()
+        .draw(
+            pipeline.lighting.clone(),
+            dynamic_state,
+            fullscreen::vertices(),
+            vec![targets.lighting_set.clone(), scene_set],
+            (),
+        )
+        .unwrap()
+        .end_render_pass()
+        .unwrap()
+}
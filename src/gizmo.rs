@@ -0,0 +1,257 @@
+use crate::debug_draw::DebugDraw;
+use crate::world::Transform;
+use cgmath::EuclideanSpace;
+use cgmath::InnerSpace;
+use cgmath::Matrix3;
+use cgmath::Matrix4;
+use cgmath::Point3;
+use cgmath::Quaternion;
+use cgmath::Rad;
+use cgmath::Rotation3;
+use cgmath::SquareMatrix;
+use cgmath::Vector3;
+
+// Handles further than this fraction of the gizmo's size from the ray
+// can't be grabbed.
+const GRAB_TOLERANCE: f32 = 0.1;
+
+const COLORS: [[f32; 4]; 3] = [
+    [1.0, 0.2, 0.2, 1.0],
+    [0.2, 1.0, 0.2, 1.0],
+    [0.3, 0.3, 1.0, 1.0],
+];
+const ACTIVE: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl Mode {
+    pub fn next(self) -> Mode {
+        match self {
+            Mode::Translate => Mode::Rotate,
+            Mode::Rotate => Mode::Scale,
+            Mode::Scale => Mode::Translate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+    axis: usize,
+    start: Transform,
+    // Where along the axis, or at what angle around it, the handle was
+    // grabbed.
+    grab: f32,
+}
+
+// Handles for moving, turning and scaling one transform with the mouse.
+// Translate and rotate handles follow the parent's axes; scale handles
+// follow the transform's own, since that's the space scale applies in.
+// Distances are in world units, assuming the parent isn't scaled.
+pub struct Gizmo {
+    pub mode: Mode,
+    // Length of the arrows and radius of the rings.
+    pub size: f32,
+    drag: Option<Drag>,
+}
+
+impl Gizmo {
+    pub fn new(size: f32) -> Gizmo {
+        Gizmo {
+            mode: Mode::Translate,
+            size,
+            drag: None,
+        }
+    }
+
+    pub fn dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    // `parent` is the world matrix the transform is relative to.
+    pub fn draw(
+        &self,
+        lines: &mut DebugDraw,
+        transform: &Transform,
+        parent: &Matrix4<f32>,
+    ) {
+        let frame = Frame::new(self.mode, transform, parent);
+        let origin = frame.origin;
+        for axis in 0..3 {
+            let color = match self.drag {
+                Some(drag) if drag.axis == axis => ACTIVE,
+                _ => COLORS[axis],
+            };
+            let direction = frame.axes[axis];
+            let tip = origin + direction * self.size;
+            match self.mode {
+                Mode::Translate => {
+                    lines.line(origin, tip, color);
+                    let side = frame.axes[(axis + 1) % 3] * self.size * 0.08;
+                    let back = tip - direction * self.size * 0.2;
+                    lines.line(tip, back + side, color);
+                    lines.line(tip, back - side, color);
+                }
+                Mode::Rotate => {
+                    lines.circle(origin, direction, self.size, color)
+                }
+                Mode::Scale => {
+                    lines.line(origin, tip, color);
+                    let half = self.size * 0.06;
+                    let extent = Vector3::new(half, half, half);
+                    lines.aabb(tip - extent, tip + extent, color);
+                }
+            }
+        }
+    }
+
+    // Grabs the handle nearest the ray, if any is close enough. True when
+    // a drag started, and the click shouldn't do anything else.
+    pub fn begin(
+        &mut self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        transform: &Transform,
+        parent: &Matrix4<f32>,
+    ) -> bool {
+        let frame = Frame::new(self.mode, transform, parent);
+        let tolerance = self.size * GRAB_TOLERANCE;
+        let mut nearest: Option<(f32, Drag)> = None;
+        for axis in 0..3 {
+            let hit = match self.mode {
+                Mode::Translate | Mode::Scale => frame
+                    .along(axis, origin, direction)
+                    .and_then(|(along, distance)| {
+                        if (0.0..=self.size * 1.1).contains(&along) {
+                            Some((distance, along))
+                        } else {
+                            None
+                        }
+                    }),
+                Mode::Rotate => frame
+                    .around(axis, origin, direction)
+                    .map(|(angle, radius)| ((radius - self.size).abs(), angle)),
+            };
+            if let Some((distance, grab)) = hit {
+                let closer = nearest.map_or(true, |(best, _)| distance < best);
+                if distance <= tolerance && closer {
+                    let start = *transform;
+                    nearest = Some((distance, Drag { axis, start, grab }));
+                }
+            }
+        }
+        self.drag = nearest.map(|(_, drag)| drag);
+        self.drag.is_some()
+    }
+
+    // The dragged transform for the ray, None when not dragging or the
+    // ray runs parallel to the handle.
+    pub fn update(
+        &self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        parent: &Matrix4<f32>,
+    ) -> Option<Transform> {
+        let drag = self.drag?;
+        let frame = Frame::new(self.mode, &drag.start, parent);
+        let mut transform = drag.start;
+        match self.mode {
+            Mode::Translate => {
+                let (along, _) = frame.along(drag.axis, origin, direction)?;
+                let moved = frame.axes[drag.axis] * (along - drag.grab);
+                let inverse = parent.invert()?;
+                transform.translation +=
+                    (inverse * moved.extend(0.0)).truncate();
+            }
+            Mode::Rotate => {
+                let (angle, _) = frame.around(drag.axis, origin, direction)?;
+                let mut axis = Vector3::new(0.0, 0.0, 0.0);
+                axis[drag.axis] = 1.0;
+                let turn =
+                    Quaternion::from_axis_angle(axis, Rad(angle - drag.grab));
+                transform.rotation = (turn * drag.start.rotation).normalize();
+            }
+            Mode::Scale => {
+                let (along, _) = frame.along(drag.axis, origin, direction)?;
+                if drag.grab.abs() > 1e-4 {
+                    let factor = (along / drag.grab).max(1e-3);
+                    transform.scale[drag.axis] *= factor;
+                }
+            }
+        }
+        Some(transform)
+    }
+
+    pub fn end(&mut self) {
+        self.drag = None;
+    }
+}
+
+// Where the handles are for one mode, in world space.
+struct Frame {
+    origin: Point3<f32>,
+    axes: [Vector3<f32>; 3],
+}
+
+impl Frame {
+    fn new(mode: Mode, transform: &Transform, parent: &Matrix4<f32>) -> Frame {
+        let local = match mode {
+            Mode::Scale => Matrix3::from(transform.rotation),
+            _ => Matrix3::identity(),
+        };
+        let axis =
+            |i: usize| (parent * local[i].extend(0.0)).truncate().normalize();
+        Frame {
+            origin: Point3::from_homogeneous(
+                parent * transform.translation.extend(1.0),
+            ),
+            axes: [axis(0), axis(1), axis(2)],
+        }
+    }
+
+    // How far along `axis` the ray passes closest to it, and how close it
+    // gets.
+    fn along(
+        &self,
+        axis: usize,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+    ) -> Option<(f32, f32)> {
+        let a = self.axes[axis];
+        let w = origin - self.origin.to_vec();
+        let b = direction.dot(a);
+        let denominator = 1.0 - b * b;
+        if denominator.abs() < 1e-6 {
+            return None;
+        }
+        let t = (b * a.dot(w) - direction.dot(w)) / denominator;
+        let s = a.dot(w) + t * b;
+        let distance = (w + direction * t - a * s).magnitude();
+        Some((s, distance))
+    }
+
+    // Where the ray crosses the plane facing along `axis`, as an angle
+    // around it and a distance from the origin.
+    fn around(
+        &self,
+        axis: usize,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+    ) -> Option<(f32, f32)> {
+        let normal = self.axes[axis];
+        let facing = direction.dot(normal);
+        if facing.abs() < 1e-6 {
+            return None;
+        }
+        let center = self.origin.to_vec();
+        let t = (center - origin).dot(normal) / facing;
+        let offset = origin + direction * t - center;
+        let u = self.axes[(axis + 1) % 3];
+        let v = self.axes[(axis + 2) % 3];
+        Some((offset.dot(v).atan2(offset.dot(u)), offset.magnitude()))
+    }
+}
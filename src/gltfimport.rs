@@ -0,0 +1,443 @@
+use crate::animation::Channel;
+use crate::animation::Clip;
+use crate::animation::Interpolation;
+use crate::animation::Joint;
+use crate::animation::Pose;
+use crate::animation::Property;
+use crate::animation::Skeleton;
+use crate::animation::SkinnedModel;
+use crate::animation::WeightChannel;
+use crate::camera::Camera;
+use crate::camera::Perspective;
+use crate::error::Error;
+use crate::error::Result;
+use crate::skinpipe;
+use crate::snapshot::Light;
+use crate::snapshot::LocalLight;
+use crate::world::Material;
+use crate::world::Mesh;
+use crate::world::Name;
+use crate::world::Parent;
+use crate::world::RenderWorld;
+use crate::world::Transform;
+use cgmath::InnerSpace;
+use cgmath::Matrix3;
+use cgmath::Matrix4;
+use cgmath::Quaternion;
+use cgmath::SquareMatrix;
+use cgmath::Vector4;
+use gltf::animation::util::ReadOutputs;
+use gltf::buffer::Data;
+use gltf::camera::Projection;
+use gltf::khr_lights_punctual::Kind;
+use gltf::mesh::Mode;
+use gltf::Node;
+use hecs::Entity;
+use hecs::World;
+use std::path::Path;
+use tracing::info;
+use tracing::warn;
+
+// Spawns the default scene of a .gltf or .glb file into `world`: one
+// entity per node, parented as in the file, with its mesh, camera and
+// light. Returns the root entities.
+//
+// The renderer can't show everything glTF describes, so the import is
+// lossy: primitives other than triangle lists are skipped, a mesh takes the
+// base color of its first primitive's material, directional light
+// intensity is ignored and point and spot lights only light the deferred
+// path.
+pub fn import<P: AsRef<Path>>(
+    world: &mut RenderWorld,
+    path: P,
+) -> Result<Vec<Entity>> {
+    let path = path.as_ref();
+    info!(path = %path.display(), "importing glTF scene");
+    let (document, buffers, _) =
+        gltf::import(path).map_err(|e| Error::Import {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| Error::Import {
+            path: path.display().to_string(),
+            message: "no scenes".to_owned(),
+        })?;
+    Ok(scene
+        .nodes()
+        .map(|node| {
+            spawn(&mut world.world, &buffers, &node, None, Matrix4::identity())
+        })
+        .collect())
+}
+
+// Spawns `node` and its descendants; `parent_matrix` is the parent's world
+// transform, for placing lights and cameras.
+fn spawn(
+    world: &mut World,
+    buffers: &[Data],
+    node: &Node,
+    parent: Option<Entity>,
+    parent_matrix: Matrix4<f32>,
+) -> Entity {
+    let (translation, rotation, scale) = node.transform().decomposed();
+    let transform = Transform {
+        translation: translation.into(),
+        rotation: quaternion(rotation),
+        scale: scale.into(),
+    };
+    let matrix = parent_matrix * transform.matrix();
+
+    let entity = world.spawn((transform,));
+    if let Some(parent) = parent {
+        world.insert_one(entity, Parent(parent)).unwrap();
+    }
+    if let Some(name) = node.name() {
+        world.insert_one(entity, Name(name.to_owned())).unwrap();
+    }
+    if let Some(mesh) = node.mesh() {
+        let (vertices, material) = mesh_data(buffers, &mesh);
+        world
+            .insert(entity, (Mesh::Static(vertices), material))
+            .unwrap();
+    }
+    if let Some(camera) = node.camera() {
+        let (position, rotation) = eye(&matrix);
+        let camera = match camera.projection() {
+            Projection::Orthographic(ortho) => Camera {
+                left: -ortho.xmag(),
+                right: ortho.xmag(),
+                // y down, as in `Camera::default`
+                bottom: ortho.ymag(),
+                top: -ortho.ymag(),
+                near: ortho.znear(),
+                far: ortho.zfar(),
+                position,
+                rotation,
+                ..Camera::default()
+            },
+            Projection::Perspective(perspective) => Camera {
+                near: perspective.znear(),
+                far: perspective.zfar().unwrap_or(INFINITE_FAR),
+                position,
+                rotation,
+                perspective: Some(Perspective {
+                    yfov: perspective.yfov(),
+                    // Without one, glTF means the viewport's, which isn't
+                    // known here.
+                    aspect: perspective.aspect_ratio().unwrap_or(1.0),
+                }),
+                ..Camera::default()
+            },
+        };
+        world.insert_one(entity, camera).unwrap();
+    }
+    if let Some(light) = node.light() {
+        // glTF lights shine down their node's -z.
+        let direction = matrix * Vector4::new(0.0, 0.0, -1.0, 0.0);
+        let [r, g, b] = light.color();
+        let local = |cone| LocalLight {
+            position: matrix.w.truncate().into(),
+            direction: direction.truncate().into(),
+            color: [
+                r * light.intensity(),
+                g * light.intensity(),
+                b * light.intensity(),
+            ],
+            range: light.range(),
+            cone,
+        };
+        match light.kind() {
+            Kind::Directional => world
+                .insert_one(
+                    entity,
+                    Light {
+                        direction: direction.into(),
+                        color: [r, g, b, 1.0],
+                    },
+                )
+                .unwrap(),
+            Kind::Point => world.insert_one(entity, local(None)).unwrap(),
+            Kind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => world
+                .insert_one(
+                    entity,
+                    local(Some([
+                        inner_cone_angle.cos(),
+                        outer_cone_angle.cos(),
+                    ])),
+                )
+                .unwrap(),
+        }
+    }
+
+    for child in node.children() {
+        spawn(world, buffers, &child, Some(entity), matrix);
+    }
+    entity
+}
+
+// Stands in for a perspective camera's infinite far plane.
+const INFINITE_FAR: f32 = 1000.0;
+
+// Position and xyzw rotation of a world transform, ignoring its scale.
+fn eye(matrix: &Matrix4<f32>) -> ([f32; 3], [f32; 4]) {
+    let axes = Matrix3::from_cols(
+        matrix.x.truncate().normalize(),
+        matrix.y.truncate().normalize(),
+        matrix.z.truncate().normalize(),
+    );
+    let Quaternion { s, v } = Quaternion::from(axes);
+    (matrix.w.truncate().into(), [v.x, v.y, v.z, s])
+}
+
+// glTF stores rotations as x, y, z, w.
+fn quaternion(xyzw: [f32; 4]) -> Quaternion<f32> {
+    Quaternion::new(xyzw[3], xyzw[0], xyzw[1], xyzw[2])
+}
+
+// Every triangle list primitive of `mesh` as one unindexed triangle list.
+fn mesh_data(buffers: &[Data], mesh: &gltf::Mesh) -> (Vec<[f32; 4]>, Material) {
+    let mut vertices = Vec::new();
+    let mut material = None;
+    for primitive in mesh.primitives() {
+        if primitive.mode() != Mode::Triangles {
+            warn!(mesh = mesh.index(), "non-triangle primitive skipped");
+            continue;
+        }
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let positions = match reader.read_positions() {
+            Some(positions) => positions.collect::<Vec<_>>(),
+            None => continue,
+        };
+        let vertex = |i: u32| {
+            let [x, y, z] = positions[i as usize];
+            [x, y, z, 1.0]
+        };
+        match reader.read_indices() {
+            Some(indices) => vertices.extend(indices.into_u32().map(vertex)),
+            None => vertices.extend((0..positions.len() as u32).map(vertex)),
+        }
+        material.get_or_insert_with(|| Material {
+            color: primitive
+                .material()
+                .pbr_metallic_roughness()
+                .base_color_factor(),
+            ..Material::default()
+        });
+    }
+    (vertices, material.unwrap_or_default())
+}
+
+// The first skinned mesh in a glTF file, with its skin's joints, its morph
+// targets and every animation channel that moves them. Joints beyond the
+// first four influences and channels targeting other nodes are dropped,
+// and the mesh is posed in model space, ignoring the nodes above the
+// skeleton.
+pub fn import_skinned<P: AsRef<Path>>(path: P) -> Result<SkinnedModel> {
+    let path = path.as_ref();
+    info!(path = %path.display(), "importing skinned glTF model");
+    let error = |message: String| Error::Import {
+        path: path.display().to_string(),
+        message,
+    };
+    let (document, buffers, _) =
+        gltf::import(path).map_err(|e| error(e.to_string()))?;
+    let (node, skin) = document
+        .nodes()
+        .find_map(|node| Some((node.clone(), node.skin()?)))
+        .ok_or_else(|| error("no skinned mesh".to_owned()))?;
+    let mesh = node
+        .mesh()
+        .ok_or_else(|| error("skinned node has no mesh".to_owned()))?;
+
+    let joint_nodes =
+        skin.joints().map(|joint| joint.index()).collect::<Vec<_>>();
+    let joint_index = |node: usize| joint_nodes.iter().position(|&n| n == node);
+    let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+    let inverse_binds = reader
+        .read_inverse_bind_matrices()
+        .map(|matrices| matrices.map(Matrix4::from).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut joints = skin
+        .joints()
+        .enumerate()
+        .map(|(i, joint)| {
+            let (translation, rotation, scale) = joint.transform().decomposed();
+            Joint {
+                parent: None,
+                rest: Pose {
+                    translation: translation.into(),
+                    rotation: quaternion(rotation),
+                    scale: scale.into(),
+                },
+                inverse_bind: inverse_binds
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(Matrix4::identity),
+            }
+        })
+        .collect::<Vec<_>>();
+    for joint in skin.joints() {
+        let parent = joint_index(joint.index());
+        for child in joint.children() {
+            if let Some(child) = joint_index(child.index()) {
+                joints[child].parent = parent;
+            }
+        }
+    }
+
+    let mut vertices = Vec::new();
+    let mut morph_targets: Vec<Vec<[f32; 4]>> = Vec::new();
+    for primitive in mesh.primitives() {
+        if primitive.mode() != Mode::Triangles {
+            warn!(mesh = mesh.index(), "non-triangle primitive skipped");
+            continue;
+        }
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let (positions, joints, weights) = match (
+            reader.read_positions(),
+            reader.read_joints(0),
+            reader.read_weights(0),
+        ) {
+            (Some(positions), Some(joints), Some(weights)) => (
+                positions.collect::<Vec<_>>(),
+                joints.into_u16().collect::<Vec<_>>(),
+                weights.into_f32().collect::<Vec<_>>(),
+            ),
+            _ => {
+                warn!(mesh = mesh.index(), "unskinned primitive skipped");
+                continue;
+            }
+        };
+        let vertex = |i: u32| {
+            let i = i as usize;
+            let [x, y, z] = positions[i];
+            let [a, b, c, d] = joints[i];
+            skinpipe::Vertex {
+                position: [x, y, z, 1.0],
+                joints: [a.into(), b.into(), c.into(), d.into()],
+                weights: weights[i],
+            }
+        };
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        let targets = reader
+            .read_morph_targets()
+            .map(|(positions, _, _)| {
+                positions.map_or_else(Vec::new, |p| p.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        // Vertices of primitives without a target don't move with it.
+        while morph_targets.len() < targets.len() {
+            morph_targets.push(vec![[0.0; 4]; vertices.len()]);
+        }
+        for (t, deltas) in morph_targets.iter_mut().enumerate() {
+            let target = targets.get(t);
+            deltas.extend(indices.iter().map(|&i| {
+                match target.and_then(|target| target.get(i as usize)) {
+                    Some(&[x, y, z]) => [x, y, z, 0.0],
+                    None => [0.0; 4],
+                }
+            }));
+        }
+        vertices.extend(indices.into_iter().map(vertex));
+    }
+
+    let mut clips = Vec::new();
+    for animation in document.animations() {
+        let mut channels = Vec::new();
+        let mut weights = None;
+        for channel in animation.channels() {
+            let reader =
+                channel.reader(|buffer| Some(&buffers[buffer.index()]));
+            let (times, outputs) =
+                match (reader.read_inputs(), reader.read_outputs()) {
+                    (Some(times), Some(outputs)) => {
+                        (times.collect::<Vec<_>>(), outputs)
+                    }
+                    _ => continue,
+                };
+            if times.is_empty() {
+                continue;
+            }
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => {
+                    Interpolation::CubicSpline
+                }
+            };
+            let target = channel.target().node().index();
+            let (property, values) = match outputs {
+                ReadOutputs::Translations(values) => (
+                    Property::Translation,
+                    values.map(|[x, y, z]| [x, y, z, 0.0]).collect(),
+                ),
+                ReadOutputs::Rotations(values) => {
+                    (Property::Rotation, values.into_f32().collect())
+                }
+                ReadOutputs::Scales(values) => (
+                    Property::Scale,
+                    values.map(|[x, y, z]| [x, y, z, 0.0]).collect(),
+                ),
+                ReadOutputs::MorphTargetWeights(values) => {
+                    if target == node.index() && !morph_targets.is_empty() {
+                        weights = Some(WeightChannel {
+                            targets: morph_targets.len(),
+                            interpolation,
+                            times,
+                            values: values.into_f32().collect(),
+                        });
+                    }
+                    continue;
+                }
+            };
+            if let Some(joint) = joint_index(target) {
+                channels.push(Channel {
+                    joint,
+                    property,
+                    interpolation,
+                    times,
+                    values,
+                });
+            }
+        }
+        let duration = channels
+            .iter()
+            .map(|channel| &channel.times)
+            .chain(weights.iter().map(|weights| &weights.times))
+            .filter_map(|times| times.last().cloned())
+            .fold(0.0, f32::max);
+        clips.push(Clip {
+            name: animation
+                .name()
+                .map(str::to_owned)
+                .unwrap_or_else(|| format!("{}", animation.index())),
+            duration,
+            channels,
+            weights,
+        });
+    }
+    info!(
+        vertices = vertices.len(),
+        joints = joints.len(),
+        morph_targets = morph_targets.len(),
+        clips = ?clips.iter().map(|clip| &clip.name).collect::<Vec<_>>(),
+        "skinned model imported"
+    );
+
+    Ok(SkinnedModel {
+        vertices,
+        skeleton: Skeleton { joints },
+        clips,
+        morph_targets,
+        weights: mesh.weights().map_or_else(Vec::new, <[f32]>::to_vec),
+    })
+}
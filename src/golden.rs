@@ -0,0 +1,323 @@
+use crate::dbgpipe;
+use crate::error::Result;
+use crate::offscreen;
+use crate::offscreen::Offscreen;
+use crate::snapshot::Snapshot;
+use crate::transparent;
+use cgmath::Matrix4;
+use cgmath::SquareMatrix;
+use image::Rgba;
+use image::RgbaImage;
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::device::Device;
+use vulkano::device::Queue;
+
+// Set to rewrite every golden from the current output instead of comparing.
+pub const UPDATE_VAR: &str = "UPDATE_GOLDENS";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    // Pixels with any channel off by more than the tolerance.
+    pub mismatched: usize,
+    pub max_difference: u8,
+}
+
+// Renders a snapshot's forward scene as the headless example does: the
+// opaque triangles, then its transparent instances back to front.
+pub fn render_snapshot(
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    state: &Snapshot,
+    dimensions: [u32; 2],
+) -> Result<RgbaImage> {
+    let target = Offscreen::new(device.clone(), queue, dimensions);
+    let pipeline =
+        dbgpipe::build_for_format(device.clone(), offscreen::FORMAT)?;
+    let framebuffer = target
+        .framebuffer(pipeline.render_pass.clone(), Some(dbgpipe::DEPTH_FORMAT));
+    let vertices = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage::vertex_buffer(),
+        state
+            .scene
+            .iter()
+            .map(|&position| dbgpipe::Vertex { position }),
+    )
+    .unwrap();
+    let set = dbgpipe::view_set(
+        device,
+        &pipeline,
+        state.camera.view_projection().into(),
+    );
+    let dynamic_state = target.dynamic_state();
+    let instances = transparent::draw_list(
+        &state.transparent,
+        &state.camera.view(),
+        state.camera.cull_mask,
+    );
+
+    Ok(target.render(|builder| {
+        let mut builder = builder
+            .begin_render_pass(
+                framebuffer,
+                false,
+                vec![[0.0, 0.0, 1.0, 1.0].into(), 1.0f32.into()],
+            )
+            .unwrap()
+            .draw(
+                pipeline.pipeline.clone(),
+                &dynamic_state,
+                vec![vertices.clone()],
+                vec![set.clone()],
+                dbgpipe::vs::ty::Push {
+                    model: Matrix4::identity().into(),
+                },
+            )
+            .unwrap();
+        for instance in &instances {
+            builder = builder
+                .draw(
+                    pipeline.transparent.clone(),
+                    &dynamic_state,
+                    vec![vertices.clone()],
+                    vec![set.clone()],
+                    dbgpipe::TransparentPush {
+                        model: instance.model().into(),
+                        color: instance.color,
+                    },
+                )
+                .unwrap();
+        }
+        builder.end_render_pass().unwrap()
+    }))
+}
+
+// Errors when the sizes differ, since nothing else can be compared then.
+pub fn compare(
+    actual: &RgbaImage,
+    expected: &RgbaImage,
+    tolerance: u8,
+) -> std::result::Result<Comparison, String> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(format!(
+            "size {:?} differs from the golden's {:?}",
+            actual.dimensions(),
+            expected.dimensions()
+        ));
+    }
+    let mut comparison = Comparison {
+        mismatched: 0,
+        max_difference: 0,
+    };
+    for (a, e) in actual.pixels().zip(expected.pixels()) {
+        let difference = channel_difference(a, e);
+        comparison.max_difference = comparison.max_difference.max(difference);
+        if difference > tolerance {
+            comparison.mismatched += 1;
+        }
+    }
+    Ok(comparison)
+}
+
+// Red where pixels differ by more than the tolerance, a dimmed copy of
+// the golden elsewhere.
+pub fn diff_image(
+    actual: &RgbaImage,
+    expected: &RgbaImage,
+    tolerance: u8,
+) -> RgbaImage {
+    let mut diff = expected.clone();
+    for (x, y, pixel) in diff.enumerate_pixels_mut() {
+        if channel_difference(actual.get_pixel(x, y), pixel) > tolerance {
+            *pixel = Rgba([255, 0, 0, 255]);
+        } else {
+            let Rgba([r, g, b, _]) = *pixel;
+            *pixel = Rgba([r / 4, g / 4, b / 4, 255]);
+        }
+    }
+    diff
+}
+
+// Checks `actual` against `<dir>/<name>.png`, allowing up to
+// `max_mismatched` pixels outside `tolerance`. With UPDATE_GOLDENS set,
+// writes `actual` as the golden instead; otherwise a missing golden is an
+// error. On failure the output and a diff image are saved beside the
+// golden for inspection.
+pub fn check<P: AsRef<Path>>(
+    dir: P,
+    name: &str,
+    actual: &RgbaImage,
+    tolerance: u8,
+    max_mismatched: usize,
+) -> std::result::Result<(), String> {
+    check_or_update(
+        dir.as_ref(),
+        name,
+        actual,
+        tolerance,
+        max_mismatched,
+        env::var_os(UPDATE_VAR).is_some(),
+    )
+}
+
+fn check_or_update(
+    dir: &Path,
+    name: &str,
+    actual: &RgbaImage,
+    tolerance: u8,
+    max_mismatched: usize,
+    update: bool,
+) -> std::result::Result<(), String> {
+    let golden = dir.join(format!("{}.png", name));
+    if update {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        actual.save(&golden).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+    if !golden.exists() {
+        return Err(format!(
+            "{} is missing; run with {}=1 to create it",
+            golden.display(),
+            UPDATE_VAR
+        ));
+    }
+    let expected = image::open(&golden)
+        .map_err(|e| format!("{}: {}", golden.display(), e))?
+        .to_rgba();
+    let comparison = compare(actual, &expected, tolerance)?;
+    if comparison.mismatched <= max_mismatched {
+        return Ok(());
+    }
+    let actual_path = dir.join(format!("{}.actual.png", name));
+    let diff_path = dir.join(format!("{}.diff.png", name));
+    actual.save(&actual_path).map_err(|e| e.to_string())?;
+    diff_image(actual, &expected, tolerance)
+        .save(&diff_path)
+        .map_err(|e| e.to_string())?;
+    Err(format!(
+        "{}: {} pixels differ by more than {} (up to {}); see {} and {}",
+        name,
+        comparison.mismatched,
+        tolerance,
+        comparison.max_difference,
+        actual_path.display(),
+        diff_path.display()
+    ))
+}
+
+fn channel_difference(a: &Rgba<u8>, b: &Rgba<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .map(|(&a, &b)| (i16::from(a) - i16::from(b)).abs() as u8)
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn gray(value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(4, 4, Rgba([value, value, value, 255]))
+    }
+
+    fn with_pixel(mut image: RgbaImage, value: u8) -> RgbaImage {
+        image.put_pixel(1, 2, Rgba([value, value, value, 255]));
+        image
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "golden-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn identical_images_match() {
+        assert_eq!(
+            compare(&gray(100), &gray(100), 0),
+            Ok(Comparison {
+                mismatched: 0,
+                max_difference: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn one_pixel_off() {
+        let actual = with_pixel(gray(100), 110);
+        assert_eq!(
+            compare(&actual, &gray(100), 2),
+            Ok(Comparison {
+                mismatched: 1,
+                max_difference: 10,
+            })
+        );
+    }
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let actual = with_pixel(gray(100), 102);
+        let comparison = compare(&actual, &gray(100), 2).unwrap();
+        assert_eq!(comparison.mismatched, 0);
+        assert_eq!(comparison.max_difference, 2);
+        let comparison = compare(&actual, &gray(100), 1).unwrap();
+        assert_eq!(comparison.mismatched, 1);
+    }
+
+    #[test]
+    fn mismatched_dimensions_are_an_error() {
+        let small = RgbaImage::from_pixel(2, 4, Rgba([100, 100, 100, 255]));
+        assert!(compare(&small, &gray(100), 255).is_err());
+    }
+
+    #[test]
+    fn diff_marks_only_pixels_over_tolerance() {
+        let actual = with_pixel(gray(100), 110);
+        let diff = diff_image(&actual, &gray(100), 2);
+        assert_eq!(*diff.get_pixel(1, 2), Rgba([255, 0, 0, 255]));
+        assert_eq!(*diff.get_pixel(0, 0), Rgba([25, 25, 25, 255]));
+        let diff = diff_image(&actual, &gray(100), 10);
+        assert_eq!(*diff.get_pixel(1, 2), Rgba([25, 25, 25, 255]));
+    }
+
+    #[test]
+    fn check_compares_against_the_golden() {
+        let dir = temp_dir("check");
+        gray(100).save(dir.join("scene.png")).unwrap();
+        let within = with_pixel(gray(100), 101);
+        let over = with_pixel(gray(100), 110);
+        let results = (
+            check_or_update(&dir, "scene", &within, 2, 0, false),
+            check_or_update(&dir, "scene", &over, 2, 1, false),
+            check_or_update(&dir, "scene", &over, 2, 0, false),
+            dir.join("scene.diff.png").exists(),
+            check_or_update(&dir, "missing", &within, 2, 0, false),
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(results.0, Ok(()));
+        assert_eq!(results.1, Ok(()));
+        assert!(results.2.is_err());
+        assert!(results.3);
+        assert!(results.4.is_err());
+    }
+
+    #[test]
+    fn update_writes_the_golden() {
+        let dir = temp_dir("update");
+        let written = check_or_update(&dir, "scene", &gray(7), 0, 0, true);
+        let golden = image::open(dir.join("scene.png")).unwrap().to_rgba();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(written, Ok(()));
+        assert_eq!(golden.into_raw(), gray(7).into_raw());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::error::Error;
+use crate::error::Result;
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::pipeline::ComputePipeline;
+use vulkano::pipeline::ComputePipelineAbstract;
+
+pub const LOCAL_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct Entry {
+    pub key: f32,
+    pub value: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Ascending,
+    Descending,
+}
+
+pub mod cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: "
+#version 450
+
+layout (local_size_x = 256) in;
+
+struct Entry {
+    float key;
+    uint value;
+};
+
+layout (set = 0, binding = 0) buffer Entries {
+    Entry entries[];
+} data;
+
+layout (push_constant) uniform Step {
+    uint j;
+    uint k;
+    uint count;
+    uint descending;
+} step;
+
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+    // Each stage starts by comparing mirrored halves of its blocks, so
+    // every comparison orders the same way. Entries past the end then act
+    // as keys that sort last, and comparisons with them can be skipped.
+    uint partner = step.j == step.k / 2 ? i ^ (step.k - 1) : i ^ step.j;
+    if (partner <= i || partner >= step.count) {
+        return;
+    }
+
+    Entry a = data.entries[i];
+    Entry b = data.entries[partner];
+    if (step.descending != 0 ? a.key < b.key : a.key > b.key) {
+        data.entries[i] = b;
+        data.entries[partner] = a;
+    }
+}"
+    }
+}
+
+pub struct Sorter {
+    pub pipeline: Arc<dyn ComputePipelineAbstract + Send + Sync>,
+}
+
+impl Sorter {
+    pub fn new(device: Arc<Device>) -> Result<Sorter> {
+        let cs = cs::Shader::load(device.clone())
+            .map_err(Error::shader("sort compute"))?;
+        let pipeline = Arc::new(
+            ComputePipeline::new(device, &cs.main_entry_point(), &())
+                .map_err(Error::compute_pipeline("sort"))?,
+        );
+        Ok(Sorter { pipeline })
+    }
+
+    pub fn descriptor_set<B>(
+        &self,
+        entries: B,
+    ) -> Result<Arc<dyn DescriptorSet + Send + Sync>>
+    where
+        B: BufferAccess + Send + Sync + 'static,
+    {
+        Ok(Arc::new(
+            PersistentDescriptorSet::start(self.pipeline.clone(), 0)
+                .add_buffer(entries)?
+                .build()?,
+        ))
+    }
+
+    // Sorts the first `count` entries of the set's buffer. Any count works;
+    // the network is sized to the next power of two.
+    pub fn record(
+        &self,
+        mut builder: AutoCommandBufferBuilder,
+        set: Arc<dyn DescriptorSet + Send + Sync>,
+        count: u32,
+        order: Order,
+    ) -> Result<AutoCommandBufferBuilder> {
+        if count < 2 {
+            return Ok(builder);
+        }
+
+        let size = count.next_power_of_two();
+        let groups = (count + LOCAL_SIZE - 1) / LOCAL_SIZE;
+        let mut k = 2;
+        while k <= size {
+            let mut j = k / 2;
+            while j > 0 {
+                let step = cs::ty::Step {
+                    j,
+                    k,
+                    count,
+                    descending: (order == Order::Descending) as u32,
+                };
+                builder = builder.dispatch(
+                    [groups, 1, 1],
+                    self.pipeline.clone(),
+                    set.clone(),
+                    step,
+                )?;
+                j /= 2;
+            }
+            k *= 2;
+        }
+        Ok(builder)
+    }
+}
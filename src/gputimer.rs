@@ -0,0 +1,356 @@
+use crate::rawcmd::RawCommands;
+use std::sync::Arc;
+use tracing::debug;
+#[cfg(feature = "profiling")]
+use tracy_client::GpuContext;
+#[cfg(feature = "profiling")]
+use tracy_client::GpuContextType;
+#[cfg(feature = "profiling")]
+use tracy_client::GpuSpan;
+use vk_sys as vk;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+#[cfg(feature = "profiling")]
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::device::Device;
+use vulkano::device::Queue;
+use vulkano::instance::QueueFamily;
+use vulkano::query::QueryType;
+use vulkano::query::UnsafeQueryPool;
+#[cfg(feature = "profiling")]
+use vulkano::sync::GpuFuture;
+use vulkano::VulkanObject;
+
+// Frames whose queries can be in flight at once. Each frame's results are
+// read when its slice of the pool comes around again.
+pub const FRAMES: usize = 3;
+// Two queries per scope.
+pub const MAX_SCOPES: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct GpuTiming {
+    pub name: &'static str,
+    // Number of scopes that were open when this one began.
+    pub depth: usize,
+    // Relative to the frame's first timestamp.
+    pub start_ms: f64,
+    pub duration_ms: f64,
+    // Raw timestamps, in device ticks.
+    pub start: u64,
+    pub end: u64,
+}
+
+struct Scope {
+    name: &'static str,
+    depth: usize,
+    ended: bool,
+    #[cfg(feature = "profiling")]
+    span: Option<GpuSpan>,
+}
+
+#[derive(Default)]
+struct Frame {
+    scopes: Vec<Scope>,
+    submitted: bool,
+}
+
+// Timestamp queries written around passes, giving per-pass GPU times.
+// Scopes nest and can be on any graphics or compute queue, but each begin
+// and end must go in the same command buffer. Does nothing on devices
+// that can't write timestamps from every graphics and compute queue.
+//
+// With the `profiling` feature, scopes are also Tracy GPU zones, with the
+// GPU clock calibrated against `queue` on creation.
+pub struct GpuTimer {
+    device: Arc<Device>,
+    pool: Option<UnsafeQueryPool>,
+    period_ns: f64,
+    frames: Vec<Frame>,
+    current: usize,
+    open: Vec<usize>,
+    last: Vec<GpuTiming>,
+    #[cfg(feature = "profiling")]
+    tracy: Option<GpuContext>,
+}
+
+impl GpuTimer {
+    pub fn new(queue: Arc<Queue>) -> GpuTimer {
+        let device = queue.device().clone();
+        let limits = device.physical_device().limits();
+        let pool = if limits.timestamp_compute_and_graphics() != 0 {
+            UnsafeQueryPool::new(
+                device.clone(),
+                QueryType::Timestamp,
+                (FRAMES * MAX_SCOPES * 2) as u32,
+            )
+            .ok()
+        } else {
+            None
+        };
+        #[allow(unused_mut)]
+        let mut timer = GpuTimer {
+            period_ns: f64::from(limits.timestamp_period()),
+            device,
+            pool,
+            frames: (0..FRAMES).map(|_| Frame::default()).collect(),
+            current: 0,
+            open: Vec::new(),
+            last: Vec::new(),
+            #[cfg(feature = "profiling")]
+            tracy: None,
+        };
+        #[cfg(feature = "profiling")]
+        {
+            timer.tracy = timer.gpu_context(queue);
+        }
+        timer
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.pool.is_some()
+    }
+
+    // Moves on to the next frame's queries, first reading back what was
+    // last written there if it has finished. Returns whether `last_frame`
+    // has new timings.
+    pub fn begin_frame(&mut self) -> bool {
+        self.current = (self.current + 1) % FRAMES;
+        let read = self.read(self.current);
+        let frame = &mut self.frames[self.current];
+        frame.scopes.clear();
+        frame.submitted = false;
+        self.open.clear();
+        match read {
+            Some(timings) => {
+                for timing in &timings {
+                    debug!(
+                        pass = timing.name,
+                        ms = timing.duration_ms,
+                        "gpu pass"
+                    );
+                }
+                self.last = timings;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Call once the frame's command buffers are submitted, so frames that
+    // were abandoned while recording aren't read back.
+    pub fn submitted(&mut self) {
+        self.frames[self.current].submitted = true;
+    }
+
+    // `builder` must be outside a render pass and for a queue of `family`.
+    pub fn begin(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        family: QueueFamily,
+        name: &'static str,
+    ) -> AutoCommandBufferBuilder {
+        let frame = &mut self.frames[self.current];
+        if self.pool.is_none() || frame.scopes.len() == MAX_SCOPES {
+            return builder;
+        }
+        let index = frame.scopes.len();
+        frame.scopes.push(Scope {
+            name,
+            depth: self.open.len(),
+            ended: false,
+            #[cfg(feature = "profiling")]
+            span: self.tracy.as_ref().and_then(|context| {
+                context.span_alloc(name, name, file!(), line!()).ok()
+            }),
+        });
+        self.open.push(index);
+        self.write(
+            builder,
+            family,
+            index * 2,
+            vk::PIPELINE_STAGE_TOP_OF_PIPE_BIT,
+        )
+    }
+
+    // Ends the innermost open scope.
+    pub fn end(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        family: QueueFamily,
+    ) -> AutoCommandBufferBuilder {
+        let index = match self.open.pop() {
+            Some(index) => index,
+            None => return builder,
+        };
+        let scope = &mut self.frames[self.current].scopes[index];
+        scope.ended = true;
+        #[cfg(feature = "profiling")]
+        {
+            if let Some(span) = scope.span.as_mut() {
+                span.end_zone();
+            }
+        }
+        self.write(
+            builder,
+            family,
+            index * 2 + 1,
+            vk::PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT,
+        )
+    }
+
+    // The most recent frame that has been read back, a few frames behind.
+    pub fn last_frame(&self) -> &[GpuTiming] {
+        &self.last
+    }
+
+    fn first_query(&self, frame: usize) -> u32 {
+        (frame * MAX_SCOPES * 2) as u32
+    }
+
+    // Resets the query right before writing it, so a scope never depends
+    // on a reset in another queue's command buffer.
+    fn write(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        family: QueueFamily,
+        slot: usize,
+        stage: vk::PipelineStageFlagBits,
+    ) -> AutoCommandBufferBuilder {
+        let pool = self.pool.as_ref().unwrap().internal_object();
+        let query = self.first_query(self.current) + slot as u32;
+        let commands = unsafe {
+            RawCommands::record(self.device.clone(), family, |pointers, cmd| {
+                pointers.CmdResetQueryPool(cmd, pool, query, 1);
+                pointers.CmdWriteTimestamp(cmd, stage, pool, query);
+            })
+        };
+        commands.execute(builder)
+    }
+
+    // None while any of the frame's queries is unavailable.
+    fn read(&self, frame: usize) -> Option<Vec<GpuTiming>> {
+        let pool = self.pool.as_ref()?;
+        let scopes = &self.frames[frame];
+        if !scopes.submitted || scopes.scopes.is_empty() {
+            return None;
+        }
+        // Each query is a timestamp followed by its availability.
+        let count = scopes.scopes.len() * 2;
+        let mut results = vec![0u64; count * 2];
+        let result = unsafe {
+            self.device.pointers().GetQueryPoolResults(
+                self.device.internal_object(),
+                pool.internal_object(),
+                self.first_query(frame),
+                count as u32,
+                results.len() * 8,
+                results.as_mut_ptr() as *mut _,
+                16,
+                vk::QUERY_RESULT_64_BIT
+                    | vk::QUERY_RESULT_WITH_AVAILABILITY_BIT,
+            )
+        };
+        if result != vk::SUCCESS {
+            return None;
+        }
+        let origin = (0..scopes.scopes.len())
+            .map(|index| results[index * 4])
+            .min()
+            .unwrap_or(0);
+        let to_ms = |ticks: u64| ticks as f64 * self.period_ns / 1e6;
+        #[cfg(feature = "profiling")]
+        upload_zones(&scopes.scopes, &results);
+        Some(
+            scopes
+                .scopes
+                .iter()
+                .enumerate()
+                .filter(|(_, scope)| scope.ended)
+                .map(|(index, scope)| {
+                    let start = results[index * 4];
+                    let end = results[index * 4 + 2];
+                    GpuTiming {
+                        name: scope.name,
+                        depth: scope.depth,
+                        start_ms: to_ms(start.wrapping_sub(origin)),
+                        duration_ms: to_ms(end.wrapping_sub(start)),
+                        start,
+                        end,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    // Writes one timestamp and waits for it, to line Tracy's GPU timeline
+    // up with its CPU one.
+    #[cfg(feature = "profiling")]
+    fn gpu_context(&self, queue: Arc<Queue>) -> Option<GpuContext> {
+        let pool = self.pool.as_ref()?;
+        let builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            queue.family(),
+        )
+        .ok()?;
+        let builder = self.write(
+            builder,
+            queue.family(),
+            0,
+            vk::PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT,
+        );
+        builder
+            .build()
+            .ok()?
+            .execute(queue)
+            .ok()?
+            .then_signal_fence_and_flush()
+            .ok()?
+            .wait(None)
+            .ok()?;
+        let mut timestamp = 0u64;
+        let result = unsafe {
+            self.device.pointers().GetQueryPoolResults(
+                self.device.internal_object(),
+                pool.internal_object(),
+                self.first_query(self.current),
+                1,
+                8,
+                &mut timestamp as *mut u64 as *mut _,
+                8,
+                vk::QUERY_RESULT_64_BIT | vk::QUERY_RESULT_WAIT_BIT,
+            )
+        };
+        if result != vk::SUCCESS {
+            return None;
+        }
+        tracy_client::Client::running()?
+            .new_gpu_context(
+                Some("gpu"),
+                GpuContextType::Vulkan,
+                timestamp as i64,
+                self.period_ns as f32,
+            )
+            .ok()
+    }
+}
+
+// Tracy wants every zone's timestamps in increasing order, outer starts
+// before inner ones and inner ends before outer ones.
+#[cfg(feature = "profiling")]
+fn upload_zones(scopes: &[Scope], results: &[u64]) {
+    let mut events = Vec::new();
+    for (index, scope) in scopes.iter().enumerate() {
+        if let (true, Some(span)) = (scope.ended, scope.span.as_ref()) {
+            let depth = scope.depth as i64;
+            events.push((results[index * 4], false, depth, span));
+            events.push((results[index * 4 + 2], true, -depth, span));
+        }
+    }
+    events.sort_by_key(|&(timestamp, end, depth, _)| (timestamp, end, depth));
+    for (timestamp, end, _, span) in events {
+        if end {
+            span.upload_timestamp_end(timestamp as i64);
+        } else {
+            span.upload_timestamp_start(timestamp as i64);
+        }
+    }
+}
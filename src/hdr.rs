@@ -0,0 +1,69 @@
+use tracing::warn;
+use vulkano::format::Format;
+use vulkano::swapchain::ColorSpace;
+
+pub const PAPER_WHITE_NITS: f32 = 200.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Sdr,
+    Scrgb,
+    Hdr10,
+}
+
+impl Output {
+    pub fn encoding(self) -> u32 {
+        self as u32
+    }
+}
+
+// vulkano 0.14's Swapchain::new has no color space parameter and always
+// requests sRGB non-linear, so scRGB and HDR10 pairs can't be presented with
+// the color space they need. An HDR output is only picked when the pair's
+// color space is the one the swapchain will actually get.
+pub const SWAPCHAIN_COLOR_SPACE: ColorSpace = ColorSpace::SrgbNonLinear;
+
+pub fn select(
+    formats: &[(Format, ColorSpace)],
+    force_sdr: bool,
+) -> (Format, ColorSpace, Output) {
+    let candidates = [
+        (
+            Format::R16G16B16A16Sfloat,
+            ColorSpace::ExtendedSrgbLinear,
+            Output::Scrgb,
+        ),
+        (
+            Format::A2B10G10R10UnormPack32,
+            ColorSpace::Hdr10St2084,
+            Output::Hdr10,
+        ),
+    ];
+
+    if !force_sdr {
+        for &(format, color_space, output) in &candidates {
+            if !formats.contains(&(format, color_space)) {
+                continue;
+            }
+            if color_space == SWAPCHAIN_COLOR_SPACE {
+                return (format, color_space, output);
+            }
+            warn!(
+                ?format,
+                ?color_space,
+                "HDR output blocked: the swapchain can't request this color space"
+            );
+        }
+    }
+
+    sdr(formats)
+}
+
+pub fn sdr(formats: &[(Format, ColorSpace)]) -> (Format, ColorSpace, Output) {
+    let (format, color_space) = formats
+        .iter()
+        .cloned()
+        .find(|&(_, color_space)| color_space == ColorSpace::SrgbNonLinear)
+        .unwrap_or(formats[0]);
+    (format, color_space, Output::Sdr)
+}
@@ -0,0 +1,254 @@
+use crate::error::Error;
+use crate::error::Result;
+use crate::shadercache;
+use crate::shadercache::ShaderCache;
+use notify::DebouncedEvent;
+use notify::RecommendedWatcher;
+use notify::RecursiveMode;
+use notify::Watcher;
+use shaderc::CompileOptions;
+use shaderc::Compiler;
+use shaderc::ResolvedInclude;
+use shaderc::ShaderKind;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+use vulkano::device::Device;
+use vulkano::pipeline::shader::ShaderModule;
+
+// Editors often write a file in several steps; wait for them to settle.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+// Watches a directory of GLSL sources and compiles them to SPIR-V on
+// request, so pipelines can be rebuilt from the edited shaders. The stage
+// comes from the extension: .vert, .frag or .comp. Includes resolve as in
+// build.rs, and an edited header reloads every shader that includes it.
+// Compiled SPIR-V goes through the shader cache, so unchanged shaders
+// skip shaderc.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    compiler: Compiler,
+    cache: ShaderCache,
+    events: Receiver<DebouncedEvent>,
+    _watcher: RecommendedWatcher,
+}
+
+impl ShaderWatcher {
+    pub fn new<P: AsRef<Path>>(
+        dir: P,
+        cache: ShaderCache,
+    ) -> Result<ShaderWatcher> {
+        let dir = dir.as_ref().to_owned();
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::watcher(sender, DEBOUNCE)
+            .map_err(|e| Error::Watch(e.to_string()))?;
+        // Recursive to see the include directory too.
+        watcher
+            .watch(&dir, RecursiveMode::Recursive)
+            .map_err(|e| Error::Watch(e.to_string()))?;
+        Ok(ShaderWatcher {
+            dir,
+            compiler: Compiler::new().ok_or(Error::Compiler)?,
+            cache,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    // Shader file names written since the last call, counting a written
+    // header as a change to the shaders that include it.
+    pub fn changed(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for event in self.events.try_iter() {
+            let path = match event {
+                DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => {
+                    path
+                }
+                DebouncedEvent::Rename(_, path) => path,
+                _ => continue,
+            };
+            let affected = if is_stage(&path) {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .map(str::to_owned)
+                    .into_iter()
+                    .collect()
+            } else {
+                self.dependents(&path)
+            };
+            for name in affected {
+                debug!(%name, "shader source changed");
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    fn include_dirs(&self) -> Vec<PathBuf> {
+        vec![self.dir.clone(), self.dir.join("include")]
+    }
+
+    // Shaders in the watched directory that include `header`.
+    fn dependents(&self, header: &Path) -> Vec<String> {
+        let header =
+            fs::canonicalize(header).unwrap_or_else(|_| header.to_owned());
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        let include_dirs = self.include_dirs();
+        entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| is_stage(path))
+            .filter(|path| {
+                let source = match fs::read_to_string(path) {
+                    Ok(source) => source,
+                    Err(_) => return false,
+                };
+                shadercache::includes(&source, &include_dirs)
+                    .iter()
+                    .filter_map(|path| fs::canonicalize(path).ok())
+                    .any(|path| path == header)
+            })
+            .filter_map(|path| {
+                path.file_name().and_then(|n| n.to_str()).map(str::to_owned)
+            })
+            .collect()
+    }
+
+    // Compiles `name` from the watched directory, or takes it from the
+    // cache when it and its headers are unchanged.
+    pub fn load(
+        &mut self,
+        device: Arc<Device>,
+        name: &str,
+    ) -> Result<Arc<ShaderModule>> {
+        let path = self.dir.join(name);
+        let source = fs::read_to_string(&path)
+            .map_err(Error::io(format!("reading {}", path.display())))?;
+        let kind = match path.extension().and_then(|e| e.to_str()) {
+            Some("vert") => ShaderKind::Vertex,
+            Some("frag") => ShaderKind::Fragment,
+            Some("comp") => ShaderKind::Compute,
+            _ => ShaderKind::InferFromSource,
+        };
+        let include_dirs = self.include_dirs();
+        let compiler = &mut self.compiler;
+        let spirv =
+            self.cache.spirv(name, &source, &include_dirs, |source| {
+                let mut options =
+                    CompileOptions::new().ok_or(Error::Compiler)?;
+                options.set_include_callback(|header, _, _, _| {
+                    let path = include_dirs
+                        .iter()
+                        .map(|dir| dir.join(header))
+                        .find(|path| path.is_file())
+                        .ok_or_else(|| format!("{} not found", header))?;
+                    let content = fs::read_to_string(&path)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    Ok(ResolvedInclude {
+                        resolved_name: path.display().to_string(),
+                        content,
+                    })
+                });
+                compiler
+                    .compile_into_spirv(
+                        source,
+                        kind,
+                        name,
+                        "main",
+                        Some(&options),
+                    )
+                    .map(|artifact| artifact.as_binary().to_vec())
+                    .map_err(|e| Error::Compile {
+                        name: name.to_owned(),
+                        message: e.to_string(),
+                    })
+            })?;
+        // Safe as far as the SPIR-V goes, since shaderc produced it. The
+        // caller still has to use it with the interface it was built for.
+        let module = unsafe { ShaderModule::from_words(device, &spirv) };
+        module.map_err(|source| Error::Module {
+            name: name.to_owned(),
+            source,
+        })
+    }
+}
+
+// .vert, .frag or .comp, as opposed to a header.
+fn is_stage(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("vert") | Some("frag") | Some("comp")
+    )
+}
+
+// Watches the directories of individual asset files and reports which of
+// those files were written. Paths come back as they were added, however
+// the watcher spells them.
+pub struct AssetWatcher {
+    events: Receiver<DebouncedEvent>,
+    watcher: RecommendedWatcher,
+    // Canonical path to the path it was added as.
+    files: HashMap<PathBuf, PathBuf>,
+    dirs: Vec<PathBuf>,
+}
+
+impl AssetWatcher {
+    pub fn new() -> Result<AssetWatcher> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::watcher(sender, DEBOUNCE)
+            .map_err(|e| Error::Watch(e.to_string()))?;
+        Ok(AssetWatcher {
+            events,
+            watcher,
+            files: HashMap::new(),
+            dirs: Vec::new(),
+        })
+    }
+
+    pub fn add(&mut self, path: &Path) -> Result<()> {
+        let canonical = fs::canonicalize(path)
+            .map_err(Error::io(format!("resolving {}", path.display())))?;
+        if let Some(dir) = canonical.parent() {
+            if !self.dirs.iter().any(|known| known == dir) {
+                self.watcher
+                    .watch(dir, RecursiveMode::NonRecursive)
+                    .map_err(|e| Error::Watch(e.to_string()))?;
+                self.dirs.push(dir.to_owned());
+            }
+        }
+        self.files.insert(canonical, path.to_owned());
+        Ok(())
+    }
+
+    // Added files written since the last call.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for event in self.events.try_iter() {
+            let path = match event {
+                DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => {
+                    path
+                }
+                DebouncedEvent::Rename(_, path) => path,
+                _ => continue,
+            };
+            let path = fs::canonicalize(&path).unwrap_or(path);
+            if let Some(added) = self.files.get(&path) {
+                debug!(path = %added.display(), "asset changed");
+                if !paths.contains(added) {
+                    paths.push(added.clone());
+                }
+            }
+        }
+        paths
+    }
+}
@@ -0,0 +1,293 @@
+use crate::dbgpipe;
+use crate::error::Error;
+use crate::error::Result;
+use cgmath::{Matrix4, Vector3};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::error;
+use tracing::info;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::command_buffer::AutoCommandBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::image::AttachmentImage;
+use vulkano::image::ImageUsage;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::sync::GpuFuture;
+
+pub struct Capture {
+    path: PathBuf,
+    size: [u32; 2],
+    scale: u32,
+    tiles: [u32; 2],
+    tile_size: [u32; 2],
+    next: u32,
+    pixels: Vec<f32>,
+}
+
+impl Capture {
+    pub fn new(
+        path: PathBuf,
+        size: [u32; 2],
+        scale: u32,
+        max_dimension: u32,
+    ) -> Capture {
+        let tiles = [
+            (size[0] * scale + max_dimension - 1) / max_dimension,
+            (size[1] * scale + max_dimension - 1) / max_dimension,
+        ];
+        let tile_size = [
+            (size[0] + tiles[0] - 1) / tiles[0],
+            (size[1] + tiles[1] - 1) / tiles[1],
+        ];
+        info!(
+            ?size,
+            supersampling = scale * scale,
+            tiles = tiles[0] * tiles[1],
+            "capturing"
+        );
+
+        Capture {
+            path,
+            size,
+            scale,
+            tiles,
+            tile_size,
+            next: 0,
+            pixels: vec![0.0; (size[0] * size[1] * 4) as usize],
+        }
+    }
+
+    pub fn done(&self) -> bool {
+        self.next >= self.tiles[0] * self.tiles[1]
+    }
+
+    fn crop(&self, tile: [u32; 2]) -> Matrix4<f32> {
+        let extent = [
+            2.0 * self.tile_size[0] as f32 / self.size[0] as f32,
+            2.0 * self.tile_size[1] as f32 / self.size[1] as f32,
+        ];
+        let center = [
+            -1.0 + extent[0] * (tile[0] as f32 + 0.5),
+            -1.0 + extent[1] * (tile[1] as f32 + 0.5),
+        ];
+        Matrix4::from_nonuniform_scale(2.0 / extent[0], 2.0 / extent[1], 1.0)
+            * Matrix4::from_translation(Vector3::new(
+                -center[0], -center[1], 0.0,
+            ))
+    }
+
+    pub fn step<F>(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        pipeline: &dbgpipe::Pipeline,
+        format: Format,
+        view_projection: Matrix4<f32>,
+        record: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(
+            AutoCommandBufferBuilder,
+            &DynamicState,
+            Arc<dyn DescriptorSet + Send + Sync>,
+        ) -> Result<AutoCommandBufferBuilder>,
+    {
+        if self.done() {
+            return Ok(());
+        }
+
+        let tile = [self.next % self.tiles[0], self.next / self.tiles[0]];
+        self.next += 1;
+
+        let dimensions = [
+            self.tile_size[0] * self.scale,
+            self.tile_size[1] * self.scale,
+        ];
+        let color = AttachmentImage::with_usage(
+            device.clone(),
+            dimensions,
+            format,
+            ImageUsage {
+                color_attachment: true,
+                transfer_source: true,
+                ..ImageUsage::none()
+            },
+        )
+        .map_err(Error::image("capture tile"))?;
+        let depth = AttachmentImage::transient(
+            device.clone(),
+            dimensions,
+            dbgpipe::DEPTH_FORMAT,
+        )
+        .map_err(Error::image("capture depth"))?;
+        let framebuffer = Arc::new(
+            Framebuffer::start(pipeline.render_pass.clone())
+                .add(color.clone())?
+                .add(depth)?
+                .build()?,
+        );
+
+        let vp = CpuAccessibleBuffer::from_data(
+            device.clone(),
+            BufferUsage::uniform_buffer(),
+            dbgpipe::ViewBlock {
+                vp: (self.crop(tile) * view_projection).into(),
+            },
+        )
+        .map_err(Error::allocation("capture view"))?;
+        let set = Arc::new(
+            PersistentDescriptorSet::start(pipeline.pipeline.clone(), 0)
+                .add_buffer(vp)?
+                .build()?,
+        );
+
+        let readback = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_destination(),
+            (0..dimensions[0] * dimensions[1] * 4).map(|_| 0u8),
+        )
+        .map_err(Error::allocation("capture readback"))?;
+
+        let dynamic_state = DynamicState {
+            line_width: None,
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                depth_range: 0.0..1.0,
+            }]),
+            scissors: None,
+        };
+
+        let builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            device.clone(),
+            queue.family(),
+        )?
+        .begin_render_pass(
+            framebuffer,
+            false,
+            vec![[0.0, 0.0, 1.0, 1.0].into(), 1.0f32.into()],
+        )?;
+        let command_buffer: AutoCommandBuffer =
+            record(builder, &dynamic_state, set)?
+                .end_render_pass()?
+                .copy_image_to_buffer(color, readback.clone())?
+                .build()?;
+
+        command_buffer
+            .execute(queue)?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+
+        let data = readback.read().unwrap();
+        self.resolve(tile, dimensions, &data, format);
+
+        if self.done() {
+            self.save();
+        }
+        Ok(())
+    }
+
+    fn resolve(
+        &mut self,
+        tile: [u32; 2],
+        dimensions: [u32; 2],
+        data: &[u8],
+        format: Format,
+    ) {
+        let bgra =
+            matches!(format, Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb);
+        let srgb =
+            matches!(format, Format::B8G8R8A8Srgb | Format::R8G8B8A8Srgb);
+        let weight = 1.0 / (self.scale * self.scale) as f32;
+
+        for y in 0..self.tile_size[1] {
+            let out_y = tile[1] * self.tile_size[1] + y;
+            if out_y >= self.size[1] {
+                break;
+            }
+            for x in 0..self.tile_size[0] {
+                let out_x = tile[0] * self.tile_size[0] + x;
+                if out_x >= self.size[0] {
+                    break;
+                }
+
+                let mut sum = [0.0f32; 4];
+                for sy in 0..self.scale {
+                    for sx in 0..self.scale {
+                        let px = x * self.scale + sx;
+                        let py = y * self.scale + sy;
+                        let i = ((py * dimensions[0] + px) * 4) as usize;
+                        let mut texel = [
+                            data[i] as f32 / 255.0,
+                            data[i + 1] as f32 / 255.0,
+                            data[i + 2] as f32 / 255.0,
+                            data[i + 3] as f32 / 255.0,
+                        ];
+                        if bgra {
+                            texel.swap(0, 2);
+                        }
+                        if srgb {
+                            for c in &mut texel[..3] {
+                                *c = to_linear(*c);
+                            }
+                        }
+                        for (total, value) in sum.iter_mut().zip(&texel) {
+                            *total += value * weight;
+                        }
+                    }
+                }
+
+                let o = ((out_y * self.size[0] + out_x) * 4) as usize;
+                self.pixels[o..o + 4].copy_from_slice(&sum);
+                if srgb {
+                    for c in &mut self.pixels[o..o + 3] {
+                        *c = to_srgb(*c);
+                    }
+                }
+            }
+        }
+    }
+
+    fn save(&self) {
+        let bytes = self
+            .pixels
+            .iter()
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect::<Vec<_>>();
+        match image::save_buffer(
+            &self.path,
+            &bytes,
+            self.size[0],
+            self.size[1],
+            image::ColorType::RGBA(8),
+        ) {
+            Ok(()) => info!(path = %self.path.display(), "saved capture"),
+            Err(e) => error!(error = ?e, "failed to save capture"),
+        }
+    }
+}
+
+fn to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
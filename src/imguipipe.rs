@@ -0,0 +1,406 @@
+use crate::error::Error;
+use crate::error::Result;
+use crate::ring::Ring;
+use crate::transfer::Uploader;
+use imgui::Context;
+use imgui::DrawCmd;
+use imgui::DrawCmdParams;
+use imgui::Key;
+use imgui::Ui;
+use std::sync::Arc;
+use std::time::Instant;
+use vulkano::buffer::BufferUsage;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::image::Dimensions;
+use vulkano::pipeline::viewport::Scissor;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::sampler::Filter;
+use vulkano::sampler::MipmapMode;
+use vulkano::sampler::Sampler;
+use vulkano::sampler::SamplerAddressMode;
+use vulkano::sync::GpuFuture;
+use winit::event::ElementState;
+use winit::event::Event;
+use winit::event::KeyboardInput;
+use winit::event::MouseButton;
+use winit::event::MouseScrollDelta;
+use winit::event::VirtualKeyCode;
+use winit::event::WindowEvent;
+
+// Per frame in flight; larger UIs are cut off.
+const VERTICES: usize = 1 << 16;
+const INDICES: usize = 1 << 17;
+
+// ImGui's vertex colors are packed bytes; they're unpacked on upload.
+#[derive(Debug, Clone, Default)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+vulkano::impl_vertex!(Vertex, position, uv, color);
+
+pub mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/imgui.vert"
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/imgui.frag"
+    }
+}
+
+// Dear ImGui drawn over the frame in the same subpass as the stats
+// overlay, for apps whose tools are already written against imgui-rs.
+// Only the font atlas is bound, so `Image` widgets with other texture ids
+// draw with the font texture.
+pub struct ImguiPass {
+    pub context: Context,
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    font_set: Arc<dyn DescriptorSet + Send + Sync>,
+    vertices: Ring<Vertex>,
+    indices: Ring<u16>,
+    last_frame: Instant,
+}
+
+impl ImguiPass {
+    // `render_pass` is the overlay's, drawn to the swapchain image.
+    pub fn new(
+        device: Arc<Device>,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+        uploader: &Uploader,
+        frames: usize,
+    ) -> Result<(ImguiPass, Box<dyn GpuFuture>)> {
+        let mut context = Context::create();
+        context.set_ini_filename(None);
+        map_keys(&mut context);
+
+        let vs = vs::Shader::load(device.clone())
+            .map_err(Error::shader("imgui vertex"))?;
+        let fs = fs::Shader::load(device.clone())
+            .map_err(Error::shader("imgui fragment"))?;
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(vs.main_entry_point(), ())
+                .triangle_list()
+                .viewports_scissors_dynamic(1)
+                .blend_alpha_blending()
+                .fragment_shader(fs.main_entry_point(), ())
+                .render_pass(Subpass::from(render_pass, 0).unwrap())
+                .build(device.clone())
+                .map_err(Error::pipeline("imgui"))?,
+        );
+
+        let (font, upload) = {
+            let mut fonts = context.fonts();
+            let texture = fonts.build_rgba32_texture();
+            uploader.image(
+                texture.data.to_vec(),
+                Dimensions::Dim2d {
+                    width: texture.width,
+                    height: texture.height,
+                },
+                Format::R8G8B8A8Unorm,
+            )
+        };
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )?;
+        let font_set = Arc::new(
+            PersistentDescriptorSet::start(pipeline.clone(), 0)
+                .add_sampled_image(font, sampler)?
+                .build()?,
+        );
+
+        let pass = ImguiPass {
+            context,
+            pipeline,
+            font_set,
+            vertices: Ring::new(
+                device.clone(),
+                VERTICES,
+                frames,
+                BufferUsage::vertex_buffer(),
+            ),
+            indices: Ring::new(
+                device,
+                INDICES,
+                frames,
+                BufferUsage::index_buffer(),
+            ),
+            last_frame: Instant::now(),
+        };
+        Ok((pass, upload))
+    }
+
+    // Feeds window input to ImGui. True when ImGui wants the event for
+    // itself, e.g. a click on a window or typing into a text field, and
+    // the app should ignore it.
+    pub fn handle_event<T>(&mut self, event: &Event<T>) -> bool {
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return false,
+        };
+        let io = self.context.io_mut();
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                io.mouse_pos = [position.x as f32, position.y as f32];
+                io.want_capture_mouse
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let index = match button {
+                    MouseButton::Left => 0,
+                    MouseButton::Right => 1,
+                    MouseButton::Middle => 2,
+                    MouseButton::Other(index) => *index as usize,
+                };
+                if index < io.mouse_down.len() {
+                    io.mouse_down[index] = *state == ElementState::Pressed;
+                }
+                io.want_capture_mouse
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        io.mouse_wheel_h += x;
+                        io.mouse_wheel += y;
+                    }
+                    MouseScrollDelta::PixelDelta(position) => {
+                        io.mouse_wheel_h += position.x.signum() as f32;
+                        io.mouse_wheel += position.y.signum() as f32;
+                    }
+                }
+                io.want_capture_mouse
+            }
+            WindowEvent::ReceivedCharacter(character) => {
+                // Control characters arrive as keys.
+                if !character.is_control() {
+                    io.add_input_character(*character);
+                }
+                io.want_capture_keyboard
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode,
+                        modifiers,
+                        ..
+                    },
+                ..
+            } => {
+                io.key_shift = modifiers.shift;
+                io.key_ctrl = modifiers.ctrl;
+                io.key_alt = modifiers.alt;
+                io.key_super = modifiers.logo;
+                if let Some(key) = virtual_keycode {
+                    io.keys_down[*key as usize] =
+                        *state == ElementState::Pressed;
+                }
+                io.want_capture_keyboard
+            }
+            _ => false,
+        }
+    }
+
+    pub fn wants_mouse(&self) -> bool {
+        self.context.io().want_capture_mouse
+    }
+
+    pub fn wants_keyboard(&self) -> bool {
+        self.context.io().want_capture_keyboard
+    }
+
+    // Runs `build` to lay out this frame's UI, then records it. Must be
+    // recorded inside the overlay's subpass; `dimensions` are its
+    // framebuffer's in pixels.
+    pub fn draw<F>(
+        &mut self,
+        mut builder: AutoCommandBufferBuilder,
+        dimensions: [u32; 2],
+        scale_factor: f64,
+        build: F,
+    ) -> AutoCommandBufferBuilder
+    where
+        F: FnOnce(&Ui),
+    {
+        self.vertices.begin_frame();
+        self.indices.begin_frame();
+
+        let now = Instant::now();
+        let io = self.context.io_mut();
+        io.delta_time = (now - self.last_frame).as_secs_f32().max(1e-6);
+        self.last_frame = now;
+        let scale = scale_factor as f32;
+        io.display_framebuffer_scale = [scale, scale];
+        io.display_size =
+            [dimensions[0] as f32 / scale, dimensions[1] as f32 / scale];
+
+        let ui = self.context.frame();
+        build(&ui);
+        let draw_data = ui.render();
+
+        let [width, height] = draw_data.display_size;
+        if width <= 0.0 || height <= 0.0 {
+            return builder;
+        }
+        let scale = [2.0 / width, 2.0 / height];
+        let push = vs::ty::Push {
+            scale,
+            translate: [
+                -1.0 - draw_data.display_pos[0] * scale[0],
+                -1.0 - draw_data.display_pos[1] * scale[1],
+            ],
+        };
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        for list in draw_data.draw_lists() {
+            let vertices = list
+                .vtx_buffer()
+                .iter()
+                .map(|v| Vertex {
+                    position: v.pos,
+                    uv: v.uv,
+                    color: [
+                        f32::from(v.col[0]) / 255.0,
+                        f32::from(v.col[1]) / 255.0,
+                        f32::from(v.col[2]) / 255.0,
+                        f32::from(v.col[3]) / 255.0,
+                    ],
+                })
+                .collect::<Vec<_>>();
+            let (vertices, indices) = match (
+                self.vertices.push(&vertices),
+                self.indices.push(list.idx_buffer()),
+            ) {
+                (Some(vertices), Some(indices)) => (vertices, indices),
+                _ => break,
+            };
+            for command in list.commands() {
+                let (params, count) = match command {
+                    DrawCmd::Elements { cmd_params, count } => {
+                        (cmd_params, count)
+                    }
+                    _ => continue,
+                };
+                let DrawCmdParams {
+                    clip_rect,
+                    vtx_offset,
+                    idx_offset,
+                    ..
+                } = params;
+                let scissor = match scissor(
+                    clip_rect,
+                    draw_data.display_pos,
+                    draw_data.framebuffer_scale,
+                    dimensions,
+                ) {
+                    Some(scissor) => scissor,
+                    None => continue,
+                };
+                let dynamic_state = DynamicState {
+                    viewports: Some(vec![viewport.clone()]),
+                    scissors: Some(vec![scissor]),
+                    ..DynamicState::none()
+                };
+                let vertices = vertices.clone().slice(vtx_offset..).unwrap();
+                let indices = indices
+                    .clone()
+                    .slice(idx_offset..idx_offset + count)
+                    .unwrap();
+                builder = builder
+                    .draw_indexed(
+                        self.pipeline.clone(),
+                        &dynamic_state,
+                        vec![Arc::new(vertices)],
+                        indices,
+                        self.font_set.clone(),
+                        push,
+                    )
+                    .unwrap();
+            }
+        }
+        builder
+    }
+}
+
+// The pixel rectangle a clip rect covers, or None when it's off screen.
+fn scissor(
+    clip_rect: [f32; 4],
+    display_pos: [f32; 2],
+    framebuffer_scale: [f32; 2],
+    dimensions: [u32; 2],
+) -> Option<Scissor> {
+    let x0 = ((clip_rect[0] - display_pos[0]) * framebuffer_scale[0]).max(0.0);
+    let y0 = ((clip_rect[1] - display_pos[1]) * framebuffer_scale[1]).max(0.0);
+    let x1 = ((clip_rect[2] - display_pos[0]) * framebuffer_scale[0])
+        .min(dimensions[0] as f32);
+    let y1 = ((clip_rect[3] - display_pos[1]) * framebuffer_scale[1])
+        .min(dimensions[1] as f32);
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    Some(Scissor {
+        origin: [x0 as i32, y0 as i32],
+        dimensions: [(x1 - x0) as u32, (y1 - y0) as u32],
+    })
+}
+
+fn map_keys(context: &mut Context) {
+    let io = context.io_mut();
+    let keys = [
+        (Key::Tab, VirtualKeyCode::Tab),
+        (Key::LeftArrow, VirtualKeyCode::Left),
+        (Key::RightArrow, VirtualKeyCode::Right),
+        (Key::UpArrow, VirtualKeyCode::Up),
+        (Key::DownArrow, VirtualKeyCode::Down),
+        (Key::PageUp, VirtualKeyCode::PageUp),
+        (Key::PageDown, VirtualKeyCode::PageDown),
+        (Key::Home, VirtualKeyCode::Home),
+        (Key::End, VirtualKeyCode::End),
+        (Key::Insert, VirtualKeyCode::Insert),
+        (Key::Delete, VirtualKeyCode::Delete),
+        (Key::Backspace, VirtualKeyCode::Back),
+        (Key::Space, VirtualKeyCode::Space),
+        (Key::Enter, VirtualKeyCode::Return),
+        (Key::Escape, VirtualKeyCode::Escape),
+        (Key::A, VirtualKeyCode::A),
+        (Key::C, VirtualKeyCode::C),
+        (Key::V, VirtualKeyCode::V),
+        (Key::X, VirtualKeyCode::X),
+        (Key::Y, VirtualKeyCode::Y),
+        (Key::Z, VirtualKeyCode::Z),
+    ];
+    for &(key, code) in &keys {
+        io[key] = code as u32;
+    }
+}
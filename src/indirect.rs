@@ -0,0 +1,338 @@
+use crate::rawcmd::RawCommands;
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+use std::sync::Arc;
+use vk_sys as vk;
+use vulkano::buffer::BufferAccess;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::DeviceLocalBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DrawIndirectCommand;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor::ShaderStages;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::device::Device;
+use vulkano::device::RawDeviceExtensions;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::instance::PhysicalDevice;
+use vulkano::instance::QueueFamily;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::VulkanObject;
+
+pub const DRAW_INDIRECT_COUNT: &str = "VK_KHR_draw_indirect_count";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountMode {
+    // The culling pass packs the visible commands at the front of the
+    // bucket and the draw reads their count from `Bucket::count`.
+    GpuCount,
+    // Culled commands are written with `instance_count = 0` and the whole
+    // bucket is submitted as a multi-draw.
+    ZeroedInstances,
+}
+
+impl CountMode {
+    pub fn select(physical: PhysicalDevice) -> CountMode {
+        let supported = RawDeviceExtensions::supported_by_device(physical)
+            .iter()
+            .any(|ext| ext.to_bytes() == DRAW_INDIRECT_COUNT.as_bytes());
+        if supported {
+            CountMode::GpuCount
+        } else {
+            CountMode::ZeroedInstances
+        }
+    }
+}
+
+impl Default for CountMode {
+    fn default() -> CountMode {
+        CountMode::ZeroedInstances
+    }
+}
+
+type CmdDrawIndirectCount = extern "system" fn(
+    vk::CommandBuffer,
+    vk::Buffer,
+    vk::DeviceSize,
+    vk::Buffer,
+    vk::DeviceSize,
+    u32,
+    u32,
+);
+
+pub struct Bucket {
+    pub commands: Arc<DeviceLocalBuffer<[DrawIndirectCommand]>>,
+    // Visible commands, written by the culling pass.
+    pub count: Arc<DeviceLocalBuffer<u32>>,
+    pub capacity: u32,
+    pub mode: CountMode,
+    // vkCmdDrawIndirectCountKHR, loaded in GpuCount mode. The device must
+    // have been created with DRAW_INDIRECT_COUNT enabled.
+    draw_count: Option<CmdDrawIndirectCount>,
+}
+
+impl Bucket {
+    pub fn new(device: Arc<Device>, mode: CountMode, capacity: u32) -> Bucket {
+        let usage = BufferUsage {
+            indirect_buffer: true,
+            storage_buffer: true,
+            transfer_destination: true,
+            ..BufferUsage::none()
+        };
+        let families = device.active_queue_families().collect::<Vec<_>>();
+
+        let commands = DeviceLocalBuffer::array(
+            device.clone(),
+            capacity as usize,
+            usage,
+            families.iter().cloned(),
+        )
+        .unwrap();
+        let count = DeviceLocalBuffer::new(
+            device.clone(),
+            usage,
+            families.iter().cloned(),
+        )
+        .unwrap();
+        let draw_count = match mode {
+            CountMode::GpuCount => {
+                let name = CString::new("vkCmdDrawIndirectCountKHR").unwrap();
+                let function = unsafe {
+                    device.instance().pointers().GetDeviceProcAddr(
+                        device.internal_object(),
+                        name.as_ptr(),
+                    )
+                };
+                Some(unsafe { mem::transmute(function) })
+            }
+            CountMode::ZeroedInstances => None,
+        };
+
+        Bucket {
+            commands,
+            count,
+            capacity,
+            mode,
+            draw_count,
+        }
+    }
+}
+
+// Records a draw of the bucket's first `count` commands, for `subpass` of
+// a render pass begun with secondary buffers. None outside GpuCount mode.
+// vulkano can't record the draw, so this binds what its own draw would:
+// the pipeline, dynamic viewports, vertex buffers, sets from 0 up and
+// `constants` as push constants.
+pub fn draw_count<Pc>(
+    device: Arc<Device>,
+    family: QueueFamily,
+    subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    dynamic_state: &DynamicState,
+    vertex_buffers: Vec<Arc<dyn BufferAccess + Send + Sync>>,
+    bucket: &Bucket,
+    sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+    constants: Pc,
+) -> Option<RawCommands> {
+    let draw_count = bucket.draw_count?;
+    let handle = pipeline.inner().internal_object();
+    let layout = pipeline.sys().internal_object();
+    let push_stages = pipeline
+        .push_constants_range(0)
+        .map_or(0, |range| stage_flags(range.stages));
+    let viewports = dynamic_state
+        .viewports
+        .iter()
+        .flatten()
+        .map(|viewport| vk::Viewport {
+            x: viewport.origin[0],
+            y: viewport.origin[1],
+            width: viewport.dimensions[0],
+            height: viewport.dimensions[1],
+            minDepth: viewport.depth_range.start,
+            maxDepth: viewport.depth_range.end,
+        })
+        .collect::<Vec<_>>();
+    let raw_sets = sets
+        .iter()
+        .map(|set| set.inner().internal_object())
+        .collect::<Vec<_>>();
+    let (raw_buffers, offsets): (Vec<_>, Vec<_>) = vertex_buffers
+        .iter()
+        .map(|buffer| {
+            let inner = buffer.inner();
+            (
+                inner.buffer.internal_object(),
+                inner.offset as vk::DeviceSize,
+            )
+        })
+        .unzip();
+    let commands = bucket.commands.inner();
+    let count = bucket.count.inner();
+    let (commands, commands_offset) = (
+        commands.buffer.internal_object(),
+        commands.offset as vk::DeviceSize,
+    );
+    let (count, count_offset) = (
+        count.buffer.internal_object(),
+        count.offset as vk::DeviceSize,
+    );
+
+    let mut resources: Vec<Box<dyn Send + Sync>> = vec![
+        Box::new(pipeline),
+        Box::new(bucket.commands.clone()),
+        Box::new(bucket.count.clone()),
+    ];
+    resources.extend(
+        vertex_buffers
+            .into_iter()
+            .map(|buffer| Box::new(buffer) as Box<dyn Send + Sync>),
+    );
+    resources.extend(
+        sets.into_iter()
+            .map(|set| Box::new(set) as Box<dyn Send + Sync>),
+    );
+
+    let raw = unsafe {
+        RawCommands::record_in_subpass(
+            device,
+            family,
+            subpass,
+            resources,
+            |pointers, cmd| {
+                pointers.CmdBindPipeline(
+                    cmd,
+                    vk::PIPELINE_BIND_POINT_GRAPHICS,
+                    handle,
+                );
+                if !viewports.is_empty() {
+                    pointers.CmdSetViewport(
+                        cmd,
+                        0,
+                        viewports.len() as u32,
+                        viewports.as_ptr(),
+                    );
+                }
+                if !raw_sets.is_empty() {
+                    pointers.CmdBindDescriptorSets(
+                        cmd,
+                        vk::PIPELINE_BIND_POINT_GRAPHICS,
+                        layout,
+                        0,
+                        raw_sets.len() as u32,
+                        raw_sets.as_ptr(),
+                        0,
+                        ptr::null(),
+                    );
+                }
+                pointers.CmdBindVertexBuffers(
+                    cmd,
+                    0,
+                    raw_buffers.len() as u32,
+                    raw_buffers.as_ptr(),
+                    offsets.as_ptr(),
+                );
+                if push_stages != 0 {
+                    pointers.CmdPushConstants(
+                        cmd,
+                        layout,
+                        push_stages,
+                        0,
+                        mem::size_of::<Pc>() as u32,
+                        &constants as *const Pc as *const c_void,
+                    );
+                }
+                draw_count(
+                    cmd,
+                    commands,
+                    commands_offset,
+                    count,
+                    count_offset,
+                    bucket.capacity,
+                    mem::size_of::<DrawIndirectCommand>() as u32,
+                );
+            },
+        )
+    };
+    Some(raw)
+}
+
+fn stage_flags(stages: ShaderStages) -> vk::ShaderStageFlags {
+    let mut flags = 0;
+    if stages.vertex {
+        flags |= vk::SHADER_STAGE_VERTEX_BIT;
+    }
+    if stages.tessellation_control {
+        flags |= vk::SHADER_STAGE_TESSELLATION_CONTROL_BIT;
+    }
+    if stages.tessellation_evaluation {
+        flags |= vk::SHADER_STAGE_TESSELLATION_EVALUATION_BIT;
+    }
+    if stages.geometry {
+        flags |= vk::SHADER_STAGE_GEOMETRY_BIT;
+    }
+    if stages.fragment {
+        flags |= vk::SHADER_STAGE_FRAGMENT_BIT;
+    }
+    if stages.compute {
+        flags |= vk::SHADER_STAGE_COMPUTE_BIT;
+    }
+    flags
+}
+
+// Multi-draw of the whole bucket, for ZeroedInstances mode.
+pub fn draw<Pc>(
+    builder: AutoCommandBufferBuilder,
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    dynamic_state: &DynamicState,
+    vertex_buffers: Vec<Arc<dyn BufferAccess + Send + Sync>>,
+    bucket: &Bucket,
+    sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+    constants: Pc,
+) -> AutoCommandBufferBuilder {
+    builder
+        .draw_indirect(
+            pipeline,
+            dynamic_state,
+            vertex_buffers,
+            bucket.commands.clone(),
+            sets,
+            constants,
+        )
+        .unwrap()
+}
+
+#[derive(Clone, Copy)]
+pub enum Draw<'a> {
+    // All vertices of the bound buffers, one instance.
+    Direct,
+    Indirect(&'a Bucket),
+}
+
+pub fn submit<Pc>(
+    builder: AutoCommandBufferBuilder,
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    dynamic_state: &DynamicState,
+    vertex_buffers: Vec<Arc<dyn BufferAccess + Send + Sync>>,
+    draw_call: Draw,
+    sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+    constants: Pc,
+) -> AutoCommandBufferBuilder {
+    match draw_call {
+        Draw::Direct => builder
+            .draw(pipeline, dynamic_state, vertex_buffers, sets, constants)
+            .unwrap(),
+        Draw::Indirect(bucket) => draw(
+            builder,
+            pipeline,
+            dynamic_state,
+            vertex_buffers,
+            bucket,
+            sets,
+            constants,
+        ),
+    }
+}
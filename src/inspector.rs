@@ -0,0 +1,78 @@
+use crate::bmptxtpipe::Vertex;
+use crate::debugview::DebugView;
+use crate::lutpipe::Inputs;
+use std::sync::Arc;
+use vulkano::image::AttachmentImage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomRight,
+    BottomLeft,
+}
+
+impl Corner {
+    pub fn next(self) -> Corner {
+        match self {
+            Corner::TopLeft => Corner::TopRight,
+            Corner::TopRight => Corner::BottomRight,
+            Corner::BottomRight => Corner::BottomLeft,
+            Corner::BottomLeft => Corner::TopLeft,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Inspector {
+    pub view: DebugView,
+    pub corner: Corner,
+    pub scale: f32,
+}
+
+impl Inspector {
+    pub fn new(view: DebugView, corner: Corner) -> Inspector {
+        Inspector {
+            view,
+            corner,
+            scale: 0.3,
+        }
+    }
+
+    pub fn image(&self, inputs: &Inputs) -> Arc<AttachmentImage> {
+        match self.view {
+            DebugView::Depth => inputs.depth.clone(),
+            DebugView::Normals => inputs.normal.clone(),
+            DebugView::Albedo => inputs.albedo.clone(),
+            DebugView::Ao => inputs.material.clone(),
+            DebugView::Overdraw => inputs.overdraw.clone(),
+            DebugView::Final => inputs.scene.clone(),
+        }
+    }
+
+    // Two triangles in normalized device coordinates, inset from the corner.
+    pub fn quad(&self) -> Vec<Vertex> {
+        let margin = 0.02;
+        let size = 2.0 * self.scale;
+        let (x0, y0) = match self.corner {
+            Corner::TopLeft => (-1.0 + margin, -1.0 + margin),
+            Corner::TopRight => (1.0 - margin - size, -1.0 + margin),
+            Corner::BottomRight => (1.0 - margin - size, 1.0 - margin - size),
+            Corner::BottomLeft => (-1.0 + margin, 1.0 - margin - size),
+        };
+        let (x1, y1) = (x0 + size, y0 + size);
+
+        let vertex = |x, y, u, v| Vertex {
+            position: [x, y],
+            uv: [u, v],
+        };
+        vec![
+            vertex(x0, y0, 0.0, 0.0),
+            vertex(x1, y0, 1.0, 0.0),
+            vertex(x1, y1, 1.0, 1.0),
+            vertex(x0, y0, 0.0, 0.0),
+            vertex(x1, y1, 1.0, 1.0),
+            vertex(x0, y1, 0.0, 1.0),
+        ]
+    }
+}
@@ -0,0 +1,20 @@
+pub const DEFAULT: u32 = 1;
+pub const DEBUG: u32 = 1 << 1;
+pub const ALL: u32 = !0;
+
+// Passes that should not pick up debug-only geometry.
+pub const SHADOW_MASK: u32 = ALL & !DEBUG;
+pub const REFLECTION_MASK: u32 = ALL & !DEBUG;
+pub const CAPTURE_MASK: u32 = ALL & !DEBUG;
+
+pub fn visible(layers: u32, mask: u32) -> bool {
+    layers & mask != 0
+}
+
+pub fn default_layers() -> u32 {
+    DEFAULT
+}
+
+pub fn default_mask() -> u32 {
+    ALL
+}
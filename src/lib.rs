@@ -1,2 +1,90 @@
+pub mod animation;
+pub mod arena;
+pub mod assets;
+pub mod benchmark;
+pub mod billboardpipe;
+pub mod blur;
+pub mod bmpfont;
 pub mod bmptxtpipe;
+pub mod budget;
+pub mod bvh;
+pub mod camera;
+pub mod compat;
+pub mod compute;
+pub mod console;
+pub mod culling;
 pub mod dbgpipe;
+pub mod debug_draw;
+pub mod debugnames;
+pub mod debugserver;
+pub mod debugview;
+pub mod descriptors;
+pub mod entrypoint;
+pub mod error;
+pub mod fog;
+pub mod foliage;
+pub mod fullscreen;
+pub mod gbufpipe;
+#[cfg(feature = "ecs")]
+pub mod gizmo;
+#[cfg(feature = "gltf-import")]
+pub mod gltfimport;
+pub mod golden;
+pub mod gpusort;
+pub mod gputimer;
+pub mod hdr;
+#[cfg(feature = "hot-reload")]
+pub mod hotreload;
+pub mod hqcapture;
+#[cfg(feature = "imgui")]
+pub mod imguipipe;
+pub mod indirect;
+pub mod inspector;
+pub mod layers;
+pub mod lightmap;
+pub mod lightmappipe;
+pub mod lod;
+pub mod logger;
+pub mod lut;
+pub mod lutpipe;
+pub mod memory;
+pub mod motionblurpipe;
+pub mod normalpipe;
+pub mod objectpipe;
+pub mod occlusion;
+pub mod offscreen;
+pub mod oitpipe;
+pub mod overdrawpipe;
+pub mod overlay;
+pub mod pacing;
+pub mod particles;
+pub mod passes;
+pub mod probes;
+pub mod profiler;
+pub mod rawcmd;
+pub mod recording;
+pub mod reflect;
+pub mod registry;
+pub mod renderer;
+pub mod replay;
+pub mod ring;
+pub mod scenebuffers;
+pub mod screenshot;
+pub mod secondary;
+pub mod settings;
+pub mod shadercache;
+pub mod skinpipe;
+pub mod snapshot;
+pub mod spirv;
+pub mod spritepipe;
+pub mod taapipe;
+pub mod telemetry;
+pub mod tesspipe;
+pub mod texarray;
+pub mod timestep;
+pub mod trace;
+pub mod transfer;
+pub mod transparent;
+pub mod validation;
+#[cfg(feature = "ecs")]
+pub mod world;
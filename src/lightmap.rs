@@ -0,0 +1,140 @@
+use crate::snapshot::Light;
+use crate::trace;
+use crate::trace::Random;
+use crate::trace::EPSILON;
+use crate::transfer::Uploader;
+use cgmath::{InnerSpace, Vector3};
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+use vulkano::format::Format;
+use vulkano::image::Dimensions;
+use vulkano::image::ImmutableImage;
+use vulkano::sync::GpuFuture;
+
+pub const SIZE: u32 = 256;
+pub const SAMPLES: u32 = 64;
+pub const AMBIENT: f32 = 0.1;
+
+pub fn tiles(triangle_count: usize) -> u32 {
+    (triangle_count as f32).sqrt().ceil().max(1.0) as u32
+}
+
+// Each triangle gets its own square tile in the atlas; the corners map to
+// (0, 0), (1, 0) and (0, 1) of the tile, inset by a texel of padding.
+pub fn uvs(triangle_count: usize, size: u32) -> Vec<[f32; 2]> {
+    let tiles = tiles(triangle_count);
+    let tile = 1.0 / tiles as f32;
+    let pad = 1.0 / size as f32;
+    let mut uvs = Vec::with_capacity(triangle_count * 3);
+    for index in 0..triangle_count as u32 {
+        let x = (index % tiles) as f32 * tile + pad;
+        let y = (index / tiles) as f32 * tile + pad;
+        let span = tile - 2.0 * pad;
+        uvs.push([x, y]);
+        uvs.push([x + span, y]);
+        uvs.push([x, y + span]);
+    }
+    uvs
+}
+
+pub fn bake(
+    scene: &[[f32; 4]],
+    light: &Light,
+    size: u32,
+    samples: u32,
+) -> RgbaImage {
+    let triangles = trace::triangles(scene);
+    let tiles = tiles(triangles.len());
+    let tile_size = size / tiles;
+    let to_light = -Vector3::new(
+        light.direction[0],
+        light.direction[1],
+        light.direction[2],
+    )
+    .normalize();
+    let light_color =
+        Vector3::new(light.color[0], light.color[1], light.color[2]);
+    let mut random = Random(0x9e37_79b9);
+
+    let mut image = RgbaImage::new(size, size);
+    for (index, triangle) in triangles.iter().enumerate() {
+        let tile_x = (index as u32 % tiles) * tile_size;
+        let tile_y = (index as u32 / tiles) * tile_size;
+        let mut normal = triangle.normal();
+        if normal.dot(to_light) < 0.0 {
+            normal = -normal;
+        }
+
+        for y in 0..tile_size {
+            for x in 0..tile_size {
+                let u = (x as f32 + 0.5) / tile_size as f32;
+                let v = (y as f32 + 0.5) / tile_size as f32;
+                // Bleed one texel past the edge so bilinear filtering
+                // never picks up the unlit background.
+                let edge = 1.0 / tile_size as f32;
+                if u + v > 1.0 + edge {
+                    continue;
+                }
+                let (u, v) = if u + v > 1.0 {
+                    (u / (u + v), v / (u + v))
+                } else {
+                    (u, v)
+                };
+                let origin = triangle.point(u, v) + normal * EPSILON;
+
+                let direct = if trace::occluded(&triangles, origin, to_light) {
+                    0.0
+                } else {
+                    normal.dot(to_light).max(0.0)
+                };
+                let open = (0..samples)
+                    .filter(|_| {
+                        let direction = trace::hemisphere(normal, &mut random);
+                        !trace::occluded(&triangles, origin, direction)
+                    })
+                    .count() as f32
+                    / samples.max(1) as f32;
+
+                let irradiance = light_color * direct
+                    + Vector3::new(AMBIENT, AMBIENT, AMBIENT) * open;
+                let encode =
+                    |channel: f32| (channel.min(1.0) * 255.0).round() as u8;
+                image.put_pixel(
+                    tile_x + x,
+                    tile_y + y,
+                    Rgba([
+                        encode(irradiance.x),
+                        encode(irradiance.y),
+                        encode(irradiance.z),
+                        255,
+                    ]),
+                );
+            }
+        }
+    }
+    image
+}
+
+pub fn save<P: AsRef<Path>>(image: &RgbaImage, path: P) {
+    image.save(path).unwrap();
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> RgbaImage {
+    let path = path.as_ref();
+    info!(path = %path.display(), "loading lightmap");
+    image::open(path).unwrap().to_rgba()
+}
+
+pub fn upload(
+    image: RgbaImage,
+    uploader: &Uploader,
+) -> (Arc<ImmutableImage<Format>>, Box<dyn GpuFuture>) {
+    let (width, height) = image.dimensions();
+    uploader.image(
+        image.into_raw(),
+        Dimensions::Dim2d { width, height },
+        Format::R8G8B8A8Unorm,
+    )
+}
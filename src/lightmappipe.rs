@@ -0,0 +1,142 @@
+use crate::compat;
+use crate::dbgpipe;
+use std::mem;
+use std::sync::Arc;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::image::ImmutableImage;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::sampler::Sampler;
+
+#[derive(Debug, Clone, Default)]
+pub struct Vertex {
+    pub position: [f32; 4],
+    pub lightmap_uv: [f32; 2],
+}
+
+vulkano::impl_vertex!(Vertex, position, lightmap_uv);
+
+pub mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+layout (location = 0) in vec4 position;
+layout (location = 1) in vec2 lightmap_uv;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+layout (push_constant) uniform Push {
+    mat4 model;
+} push;
+
+layout (location = 0) out vec2 out_lightmap_uv;
+
+void main() {
+    gl_Position = vp_inst.vp * push.model * position;
+    out_lightmap_uv = lightmap_uv;
+}"
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+layout (location = 0) in vec2 lightmap_uv;
+
+layout (set = 1, binding = 0) uniform sampler2D lightmap;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    vec3 albedo = vec3(1.0, 0.0, 0.0);
+    f_color = vec4(albedo * texture(lightmap, lightmap_uv).rgb, 1.0);
+}
+"
+    }
+}
+
+pub struct Pipeline {
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+pub fn interface() -> compat::Interface {
+    compat::Interface {
+        bindings: vec![
+            compat::Binding {
+                set: compat::VIEW_SET,
+                binding: 0,
+                kind: compat::Kind::UniformBuffer,
+            },
+            compat::Binding {
+                set: compat::MATERIAL_SET,
+                binding: 0,
+                kind: compat::Kind::CombinedImageSampler,
+            },
+        ],
+        push_constants: mem::size_of::<vs::ty::Push>(),
+        blocks: vec![compat::Block::of::<dbgpipe::ViewBlock>(
+            compat::VIEW_SET,
+            0,
+        )],
+    }
+}
+
+// What the shaders declare. The SPIR-V isn't kept, so the view block's size
+// comes from the struct vulkano generated for it.
+pub fn shader_interface(pipeline: &Pipeline) -> compat::Interface {
+    compat::Interface {
+        blocks: vec![compat::Block::of::<vs::ty::VP_BLOCK>(
+            compat::VIEW_SET,
+            0,
+        )],
+        ..compat::reflect(&*pipeline.pipeline)
+    }
+}
+
+// Draws into the forward scene pass, so it shares dbgpipe's render pass.
+pub fn build(device: Arc<Device>, scene: &dbgpipe::Pipeline) -> Pipeline {
+    let vs = vs::Shader::load(device.clone()).unwrap();
+    let fs = fs::Shader::load(device.clone()).unwrap();
+    let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> =
+        scene.render_pass.clone();
+
+    let pipeline = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Vertex>()
+            .vertex_shader(vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .depth_stencil_simple_depth()
+            .fragment_shader(fs.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass, 0).unwrap())
+            .build(device)
+            .unwrap(),
+    );
+
+    Pipeline { pipeline }
+}
+
+pub fn lightmap_set(
+    pipeline: &Pipeline,
+    lightmap: Arc<ImmutableImage<Format>>,
+    sampler: Arc<Sampler>,
+) -> Arc<dyn DescriptorSet + Send + Sync> {
+    Arc::new(
+        PersistentDescriptorSet::start(pipeline.pipeline.clone(), 1)
+            .add_sampled_image(lightmap, sampler)
+            .unwrap()
+            .build()
+            .unwrap(),
+    )
+}
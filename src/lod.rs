@@ -0,0 +1,405 @@
+use crate::arena::Allocation;
+use crate::arena::Arena;
+use crate::dbgpipe::Vertex;
+use crate::transfer::Uploader;
+use cgmath::InnerSpace;
+use cgmath::Matrix4;
+use cgmath::Vector3;
+use cgmath::Vector4;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use vulkano::sync::GpuFuture;
+
+// Levels past this share the last slot in `LodStats`.
+pub const MAX_LEVELS: usize = 8;
+// The scene is cut into up to this many cells along each axis, each its
+// own `LodMesh`, so near and far parts of it pick their levels separately.
+pub const CELLS_PER_AXIS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Metric {
+    // view-space distance from the eye to the bounding sphere
+    Distance,
+    // fraction of the screen height the bounding sphere covers
+    Coverage,
+    // diameter of the bounding sphere on screen, in pixels
+    Pixels,
+}
+
+// thresholds[i] is where level i + 1 takes over from level i; distances
+// increase and coverages and pixel sizes decrease with each level.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lod {
+    pub enabled: bool,
+    pub metric: Metric,
+    pub thresholds: Vec<f32>,
+    // How far past a threshold, as a fraction of it, the metric has to go
+    // before the level changes, so meshes near one don't flicker between
+    // levels.
+    #[serde(default = "default_hysteresis")]
+    pub hysteresis: f32,
+}
+
+fn default_hysteresis() -> f32 {
+    0.1
+}
+
+impl Default for Lod {
+    fn default() -> Self {
+        Lod {
+            enabled: false,
+            metric: Metric::Pixels,
+            thresholds: vec![400.0, 200.0, 80.0],
+            hysteresis: default_hysteresis(),
+        }
+    }
+}
+
+// How many meshes drew at each level this frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LodStats {
+    pub counts: [u32; MAX_LEVELS],
+}
+
+impl LodStats {
+    pub fn record(&mut self, level: usize) {
+        self.counts[level.min(MAX_LEVELS - 1)] += 1;
+    }
+
+    // Counts from level 0 up to the last one used.
+    pub fn text(&self) -> String {
+        let used = self
+            .counts
+            .iter()
+            .rposition(|&count| count > 0)
+            .map_or(1, |last| last + 1);
+        self.counts[..used]
+            .iter()
+            .map(|count| count.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+pub struct LodMesh {
+    pub sphere: [f32; 4],
+    pub levels: Vec<Allocation>,
+    // The level last selected, which the hysteresis favours.
+    current: usize,
+}
+
+impl LodMesh {
+    // One mesh per non-empty cell of `split`, each with up to `count`
+    // levels.
+    pub fn upload_scene(
+        arena: &mut Arena<Vertex>,
+        uploader: &Uploader,
+        triangles: &[[f32; 4]],
+        count: usize,
+    ) -> Option<(Vec<LodMesh>, Box<dyn GpuFuture>)> {
+        let mut meshes = Vec::new();
+        let mut future =
+            Box::new(vulkano::sync::now(uploader.transfer.device().clone()))
+                as Box<dyn GpuFuture>;
+        for cell in split(triangles, CELLS_PER_AXIS) {
+            let (mesh, upload) =
+                LodMesh::upload(arena, uploader, generate(&cell, count))?;
+            meshes.push(mesh);
+            future = Box::new(future.join(upload));
+        }
+        Some((meshes, future))
+    }
+
+    // Uploads each level into the arena; levels[0] is full detail.
+    pub fn upload(
+        arena: &mut Arena<Vertex>,
+        uploader: &Uploader,
+        levels: Vec<Vec<[f32; 4]>>,
+    ) -> Option<(LodMesh, Box<dyn GpuFuture>)> {
+        let sphere = bounds(&levels[0]);
+        let mut allocations = Vec::new();
+        let mut future =
+            Box::new(vulkano::sync::now(uploader.transfer.device().clone()))
+                as Box<dyn GpuFuture>;
+        for level in levels {
+            let (allocation, upload) = arena.upload(
+                uploader,
+                level
+                    .into_iter()
+                    .map(|position| Vertex { position })
+                    .collect(),
+            )?;
+            allocations.push(allocation);
+            future = Box::new(future.join(upload));
+        }
+        Some((
+            LodMesh {
+                sphere,
+                levels: allocations,
+                current: 0,
+            },
+            future,
+        ))
+    }
+
+    // The level last selected, for `LodStats`.
+    pub fn level(&self) -> usize {
+        self.current
+    }
+
+    // `viewport_height` is in pixels, for `Metric::Pixels`. Thresholds the
+    // current level has already crossed are moved back by the hysteresis,
+    // and ones it hasn't are moved further out, so a mesh keeps its level
+    // until the metric clearly leaves its band.
+    pub fn select(
+        &mut self,
+        settings: &Lod,
+        view: &Matrix4<f32>,
+        view_projection: &Matrix4<f32>,
+        viewport_height: f32,
+    ) -> Allocation {
+        let [x, y, z, radius] = self.sphere;
+        let center = Vector4::new(x, y, z, 1.0);
+        let coverage = || {
+            let clip = view_projection * center;
+            let scale = Vector3::new(
+                view_projection.x.y,
+                view_projection.y.y,
+                view_projection.z.y,
+            )
+            .magnitude();
+            radius * scale / clip.w.abs().max(1e-5)
+        };
+        let (value, increasing) = match settings.metric {
+            Metric::Distance => {
+                ((view * center).truncate().magnitude() - radius, true)
+            }
+            Metric::Coverage => (coverage(), false),
+            // coverage is the radius over half the screen height, so it's
+            // also the diameter over the whole height
+            Metric::Pixels => (coverage() * viewport_height, false),
+        };
+        let current = self.current;
+        let passed = settings
+            .thresholds
+            .iter()
+            .enumerate()
+            .filter(|&(i, &t)| {
+                let margin = if i < current {
+                    -settings.hysteresis
+                } else {
+                    settings.hysteresis
+                };
+                if increasing {
+                    value >= t * (1.0 + margin)
+                } else {
+                    value <= t * (1.0 - margin)
+                }
+            })
+            .count();
+        self.current = passed.min(self.levels.len() - 1);
+        self.levels[self.current]
+    }
+}
+
+pub fn bounds(triangles: &[[f32; 4]]) -> [f32; 4] {
+    let mut min = [std::f32::MAX; 3];
+    let mut max = [std::f32::MIN; 3];
+    for vertex in triangles {
+        for ((low, high), &value) in
+            min.iter_mut().zip(max.iter_mut()).zip(vertex.iter())
+        {
+            *low = low.min(value);
+            *high = high.max(value);
+        }
+    }
+    let center = Vector3::new(
+        (min[0] + max[0]) * 0.5,
+        (min[1] + max[1]) * 0.5,
+        (min[2] + max[2]) * 0.5,
+    );
+    let radius = triangles
+        .iter()
+        .map(|v| (Vector3::new(v[0], v[1], v[2]) - center).magnitude())
+        .fold(0.0, f32::max);
+    [center.x, center.y, center.z, radius]
+}
+
+// Vertex clustering: snaps every vertex to the average of its grid cell
+// and drops triangles that collapse.
+pub fn simplify(triangles: &[[f32; 4]], cell: f32) -> Vec<[f32; 4]> {
+    let key = |v: &[f32; 4]| {
+        [
+            (v[0] / cell).floor() as i32,
+            (v[1] / cell).floor() as i32,
+            (v[2] / cell).floor() as i32,
+        ]
+    };
+    let mut clusters: HashMap<[i32; 3], ([f32; 3], f32)> = HashMap::new();
+    for vertex in triangles {
+        let (sum, count) =
+            clusters.entry(key(vertex)).or_insert(([0.0; 3], 0.0));
+        for (total, &value) in sum.iter_mut().zip(vertex.iter()) {
+            *total += value;
+        }
+        *count += 1.0;
+    }
+
+    let mut simplified = Vec::new();
+    for triangle in triangles.chunks(3) {
+        let keys: Vec<_> = triangle.iter().map(key).collect();
+        if keys.len() < 3
+            || keys[0] == keys[1]
+            || keys[1] == keys[2]
+            || keys[0] == keys[2]
+        {
+            continue;
+        }
+        for key in keys {
+            let (sum, count) = clusters[&key];
+            simplified.push([
+                sum[0] / count,
+                sum[1] / count,
+                sum[2] / count,
+                1.0,
+            ]);
+        }
+    }
+    simplified
+}
+
+// Builds up to `count` levels, doubling the cluster size each time. Levels
+// that would drop no triangles, or all of them, are left out, so a mesh too
+// simple to reduce has fewer levels and `LodMesh::select` stops at its
+// last.
+pub fn generate(triangles: &[[f32; 4]], count: usize) -> Vec<Vec<[f32; 4]>> {
+    let diameter = bounds(triangles)[3] * 2.0;
+    let mut levels = vec![triangles.to_vec()];
+    for level in 1..count {
+        let cell = diameter / (64 >> level.min(6)) as f32;
+        let simplified = simplify(triangles, cell);
+        if !simplified.is_empty()
+            && simplified.len() < levels[levels.len() - 1].len()
+        {
+            levels.push(simplified);
+        }
+    }
+    levels
+}
+
+// Groups a triangle list by which of `cells` x `cells` x `cells` boxes over
+// its bounds each triangle's centroid falls in. Empty cells are skipped.
+pub fn split(triangles: &[[f32; 4]], cells: usize) -> Vec<Vec<[f32; 4]>> {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for vertex in triangles {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(vertex[axis]);
+            max[axis] = max[axis].max(vertex[axis]);
+        }
+    }
+    let mut groups: HashMap<[usize; 3], Vec<[f32; 4]>> = HashMap::new();
+    for triangle in triangles.chunks_exact(3) {
+        let mut key = [0; 3];
+        for axis in 0..3 {
+            let centroid = triangle.iter().map(|v| v[axis]).sum::<f32>() / 3.0;
+            let extent = (max[axis] - min[axis]).max(1e-6);
+            key[axis] = (((centroid - min[axis]) / extent * cells as f32)
+                as usize)
+                .min(cells - 1);
+        }
+        groups.entry(key).or_default().extend_from_slice(triangle);
+    }
+    // Sorted so the meshes come out in the same order every run.
+    let mut groups = groups.into_iter().collect::<Vec<_>>();
+    groups.sort_by_key(|&(key, _)| key);
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::SquareMatrix;
+
+    // Three levels swapping at distances 10 and 20.
+    fn settings() -> Lod {
+        Lod {
+            enabled: true,
+            metric: Metric::Distance,
+            thresholds: vec![10.0, 20.0],
+            hysteresis: 0.1,
+        }
+    }
+
+    fn mesh(levels: usize) -> LodMesh {
+        LodMesh {
+            sphere: [0.0, 0.0, 0.0, 0.0],
+            levels: (0..levels)
+                .map(|offset| Allocation { offset, len: 1 })
+                .collect(),
+            current: 0,
+        }
+    }
+
+    // The level picked with the eye `distance` away from the mesh.
+    fn select_at(mesh: &mut LodMesh, settings: &Lod, distance: f32) -> usize {
+        let view = Matrix4::from_translation(Vector3::new(0.0, 0.0, -distance));
+        mesh.select(settings, &view, &Matrix4::identity(), 100.0);
+        mesh.level()
+    }
+
+    #[test]
+    fn crossing_thresholds_changes_level() {
+        let settings = settings();
+        let mut mesh = mesh(3);
+        assert_eq!(select_at(&mut mesh, &settings, 5.0), 0);
+        assert_eq!(select_at(&mut mesh, &settings, 12.0), 1);
+        assert_eq!(select_at(&mut mesh, &settings, 25.0), 2);
+        assert_eq!(select_at(&mut mesh, &settings, 5.0), 0);
+    }
+
+    #[test]
+    fn level_holds_inside_the_hysteresis_band() {
+        let settings = settings();
+        let mut mesh = mesh(3);
+        // Past 10 but not past 11: still level 0.
+        assert_eq!(select_at(&mut mesh, &settings, 10.5), 0);
+        assert_eq!(select_at(&mut mesh, &settings, 11.5), 1);
+        // Back under 10 but not under 9: still level 1.
+        assert_eq!(select_at(&mut mesh, &settings, 9.5), 1);
+        assert_eq!(select_at(&mut mesh, &settings, 8.5), 0);
+    }
+
+    #[test]
+    fn selection_stops_at_the_last_level() {
+        let settings = settings();
+        let mut mesh = mesh(2);
+        assert_eq!(select_at(&mut mesh, &settings, 50.0), 1);
+    }
+
+    #[test]
+    fn generate_skips_levels_that_reduce_nothing() {
+        let triangle = [
+            [0.0, 0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0, 1.0],
+        ];
+        assert_eq!(generate(&triangle, 4).len(), 1);
+    }
+
+    #[test]
+    fn split_groups_triangles_by_cell() {
+        let triangle = |x: f32| {
+            vec![
+                [x, 0.0, 0.0, 1.0],
+                [x + 0.1, 0.0, 0.0, 1.0],
+                [x, 0.1, 0.0, 1.0],
+            ]
+        };
+        let scene = [triangle(0.0), triangle(9.0), triangle(0.2)].concat();
+        let cells = split(&scene, 4);
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0].len(), 6);
+        assert_eq!(cells[1].len(), 3);
+    }
+}
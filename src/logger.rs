@@ -0,0 +1,14 @@
+use std::io;
+use tracing_subscriber::EnvFilter;
+
+// Sends `tracing` events, and `log` records through its bridge, to stderr.
+// RUST_LOG picks what's shown when set, otherwise `default`, e.g. "info"
+// or "vulkano_triangle=debug". Only the first call installs anything.
+pub fn init(default: &str) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(default));
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .try_init();
+}
@@ -0,0 +1,85 @@
+use crate::error::Error;
+use crate::error::Result;
+use crate::transfer::Uploader;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+use vulkano::format::Format;
+use vulkano::image::Dimensions;
+use vulkano::image::ImmutableImage;
+use vulkano::sync::GpuFuture;
+
+pub const SIZE: u32 = 32;
+
+pub fn neutral(size: u32) -> Vec<u8> {
+    let scale = 255.0 / (size - 1) as f32;
+    let mut data = Vec::with_capacity((size * size * size * 4) as usize);
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                data.push((r as f32 * scale).round() as u8);
+                data.push((g as f32 * scale).round() as u8);
+                data.push((b as f32 * scale).round() as u8);
+                data.push(255);
+            }
+        }
+    }
+    data
+}
+
+// Errors with a description when the strip isn't `size` squares of
+// `size` pixels side by side.
+pub fn from_strip(
+    strip: &image::RgbaImage,
+) -> std::result::Result<Vec<u8>, String> {
+    let size = strip.height();
+    if strip.width() != size * size {
+        return Err(format!(
+            "LUT strip must be {} pixels wide for a height of {}",
+            size * size,
+            size
+        ));
+    }
+
+    let mut data = Vec::with_capacity((size * size * size * 4) as usize);
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                data.extend_from_slice(&strip.get_pixel(b * size + r, g).0);
+            }
+        }
+    }
+    Ok(data)
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<(u32, Vec<u8>)> {
+    let path = path.as_ref();
+    info!(path = %path.display(), "loading lut");
+    let strip = image::open(path)
+        .map_err(|source| Error::Texture {
+            path: path.display().to_string(),
+            source,
+        })?
+        .to_rgba();
+    let data = from_strip(&strip).map_err(|message| Error::Import {
+        path: path.display().to_string(),
+        message,
+    })?;
+    Ok((strip.height(), data))
+}
+
+pub fn upload(
+    size: u32,
+    data: Vec<u8>,
+    uploader: &Uploader,
+) -> (Arc<ImmutableImage<Format>>, Box<dyn GpuFuture>) {
+    uploader.image(
+        data,
+        Dimensions::Dim3d {
+            width: size,
+            height: size,
+            depth: size,
+        },
+        Format::R8G8B8A8Unorm,
+    )
+}
@@ -0,0 +1,128 @@
+use crate::entrypoint;
+use crate::error::Error;
+use crate::error::Result;
+use crate::fullscreen;
+use crate::hdr;
+use crate::spirv;
+use std::sync::Arc;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::image::AttachmentImage;
+use vulkano::image::ImmutableImage;
+use vulkano::pipeline::shader::GraphicsShaderType;
+use vulkano::pipeline::vertex::BufferlessDefinition;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::sampler::Sampler;
+use vulkano::swapchain::Swapchain;
+use winit::window::Window;
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/grade.frag",
+        include: ["shaders/include"]
+    }
+}
+
+pub struct Inputs {
+    pub scene: Arc<AttachmentImage>,
+    pub depth: Arc<AttachmentImage>,
+    pub albedo: Arc<AttachmentImage>,
+    pub normal: Arc<AttachmentImage>,
+    pub material: Arc<AttachmentImage>,
+    pub overdraw: Arc<AttachmentImage>,
+}
+
+pub struct Pipeline {
+    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+pub fn specialization(output: hdr::Output) -> fs::SpecializationConstants {
+    fs::SpecializationConstants {
+        encoding: output.encoding(),
+        paper_white: hdr::PAPER_WHITE_NITS,
+    }
+}
+
+pub fn build(
+    device: Arc<Device>,
+    swapchain: Arc<Swapchain<Window>>,
+    constants: fs::SpecializationConstants,
+) -> Result<Pipeline> {
+    let vs = fullscreen::vs::Shader::load(device.clone())
+        .map_err(Error::shader("fullscreen vertex"))?;
+    let fs = spirv::module(device.clone(), "grade", &spirv::GRADE_FRAG)?;
+
+    let render_pass = Arc::new(
+        vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: DontCare,
+                    store: Store,
+                    format: swapchain.format(),
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        )
+        .map_err(Error::render_pass("grade"))?,
+    );
+
+    let pipeline = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition)
+            .vertex_shader(vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(
+                unsafe {
+                    entrypoint::graphics(
+                        &fs,
+                        fs::MainInput,
+                        fs::MainOutput,
+                        fs::Layout(entrypoint::fragment_stage()),
+                        GraphicsShaderType::Fragment,
+                    )
+                },
+                constants,
+            )
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("grade"))?,
+    );
+
+    Ok(Pipeline {
+        render_pass,
+        pipeline,
+    })
+}
+
+pub fn descriptor_set(
+    pipeline: &Pipeline,
+    inputs: &Inputs,
+    lut: Arc<ImmutableImage<Format>>,
+    linear: Arc<Sampler>,
+    nearest: Arc<Sampler>,
+) -> Result<Arc<dyn DescriptorSet + Send + Sync>> {
+    Ok(Arc::new(
+        PersistentDescriptorSet::start(pipeline.pipeline.clone(), 0)
+            .add_sampled_image(inputs.scene.clone(), linear.clone())?
+            .add_sampled_image(lut, linear)?
+            .add_sampled_image(inputs.depth.clone(), nearest.clone())?
+            .add_sampled_image(inputs.albedo.clone(), nearest.clone())?
+            .add_sampled_image(inputs.normal.clone(), nearest.clone())?
+            .add_sampled_image(inputs.material.clone(), nearest.clone())?
+            .add_sampled_image(inputs.overdraw.clone(), nearest)?
+            .build()?,
+    ))
+}
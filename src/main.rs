@@ -1,143 +1,481 @@
-use cgmath;
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, CpuBufferPool};
+#[cfg(feature = "ecs")]
+use cgmath::Vector3;
+use cgmath::{Deg, EuclideanSpace, Point3};
+use tracing::{error, info, warn};
+use vulkano::buffer::{BufferAccess, BufferUsage, CpuBufferPool};
 use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
-use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
-use vulkano::device::{Device, DeviceExtensions};
-use vulkano::framebuffer::{
-    Framebuffer, FramebufferAbstract, RenderPassAbstract,
-};
-use vulkano::image::SwapchainImage;
-use vulkano::instance::{Instance, PhysicalDevice};
-use vulkano::pipeline::viewport::Viewport;
-use vulkano::swapchain;
-use vulkano::swapchain::{
-    AcquireError, PresentMode, SurfaceTransform, Swapchain,
-    SwapchainCreationError,
+use vulkano::descriptor::descriptor_set::{
+    DescriptorSet, PersistentDescriptorSet,
 };
+#[cfg(feature = "hot-reload")]
+use vulkano::device::Device;
+use vulkano::format::ClearValue;
+use vulkano::instance::debug::MessageSeverity;
+use vulkano::instance::PhysicalDevice;
 use vulkano::sync;
 use vulkano::sync::{FlushError, GpuFuture};
 
-use vulkano_win::VkSurfaceBuild;
-
-use winit::event::{Event, WindowEvent};
+#[cfg(feature = "ecs")]
+use winit::event::MouseButton;
+use winit::event::{
+    ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent,
+};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::{Window, WindowBuilder};
+#[cfg(feature = "ecs")]
+use winit::window::Window;
 
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Instant;
+use vulkano_triangle::animation::{AnimationPlayer, SkinnedModel};
+use vulkano_triangle::arena::Arena;
+use vulkano_triangle::benchmark::Benchmark;
+#[cfg(feature = "hot-reload")]
+use vulkano_triangle::bmptxtpipe;
+use vulkano_triangle::budget::Budgets;
+use vulkano_triangle::camera;
+use vulkano_triangle::compute;
+use vulkano_triangle::console::Console;
 use vulkano_triangle::dbgpipe;
+use vulkano_triangle::debug_draw;
+use vulkano_triangle::debug_draw::DebugDraw;
+use vulkano_triangle::debugnames::DebugNames;
+use vulkano_triangle::debugserver;
+use vulkano_triangle::debugserver::Command;
+use vulkano_triangle::debugview::DebugView;
+use vulkano_triangle::descriptors::DescriptorCache;
+use vulkano_triangle::error::Error;
+use vulkano_triangle::foliage::{self, Foliage};
+#[cfg(feature = "ecs")]
+use vulkano_triangle::gizmo::Gizmo;
+#[cfg(feature = "gltf-import")]
+use vulkano_triangle::gltfimport;
+use vulkano_triangle::gputimer::GpuTimer;
+#[cfg(feature = "hot-reload")]
+use vulkano_triangle::hotreload::ShaderWatcher;
+use vulkano_triangle::hqcapture::Capture;
+#[cfg(feature = "imgui")]
+use vulkano_triangle::imguipipe::ImguiPass;
+use vulkano_triangle::indirect::Draw;
+use vulkano_triangle::inspector::{Corner, Inspector};
+use vulkano_triangle::layers;
+use vulkano_triangle::lightmap;
+use vulkano_triangle::lightmappipe;
+use vulkano_triangle::lod::{Lod, LodStats};
+use vulkano_triangle::logger;
+use vulkano_triangle::memory;
+use vulkano_triangle::memory::Category;
+use vulkano_triangle::memory::Tracker;
+use vulkano_triangle::motionblurpipe;
+use vulkano_triangle::motionblurpipe::MotionBlur;
+use vulkano_triangle::normalpipe;
+use vulkano_triangle::overlay::{self, Stats};
+use vulkano_triangle::pacing::{Limiter, Smoother};
+use vulkano_triangle::particles;
+use vulkano_triangle::particles::Emitter;
+use vulkano_triangle::passes::{
+    begin_pass, draw_inspectors, draw_opaque, draw_overdraw, draw_overlay,
+    draw_transparent_sorted, end_pass, record_deferred, record_forward,
+    record_grade, record_taa, Frame, Opaque, Passes, Scene, SkinMesh, Targets,
+};
+use vulkano_triangle::probes;
+use vulkano_triangle::probes::ProbeGrid;
+use vulkano_triangle::profiler::Profiler;
+use vulkano_triangle::recording::Recording;
+use vulkano_triangle::registry::{FrameContext, InitContext, Registry};
+use vulkano_triangle::renderer::{self, Options, Renderer, WindowMode};
+use vulkano_triangle::replay;
+use vulkano_triangle::replay::Replay;
+use vulkano_triangle::ring::Ring;
+use vulkano_triangle::scenebuffers::{self, SceneBuffers};
+use vulkano_triangle::secondary;
+use vulkano_triangle::settings::Settings;
+#[cfg(feature = "hot-reload")]
+use vulkano_triangle::shadercache::ShaderCache;
+use vulkano_triangle::skinpipe;
+use vulkano_triangle::snapshot::Snapshot;
+use vulkano_triangle::taapipe;
+use vulkano_triangle::telemetry::{FrameSample, Telemetry};
+use vulkano_triangle::timestep::FixedStep;
+use vulkano_triangle::transparent;
+use vulkano_triangle::validation;
+#[cfg(feature = "ecs")]
+use vulkano_triangle::world::{Name, Picker, RenderWorld, Transform};
 
-fn main() {
-    let instance = {
-        let extensions = vulkano_win::required_extensions();
+const MESH_ARENA_CAPACITY: usize = 1 << 16;
+const DESCRIPTOR_MAX_AGE: u64 = 8;
+#[cfg(feature = "hot-reload")]
+const SHADER_CACHE_DIR: &str = "shader-cache";
+#[cfg(feature = "hot-reload")]
+const SHADER_SOURCE_DIR: &str = "shaders";
+const SETTINGS_PATH: &str = "settings.toml";
+const TRANSIENT_VERTICES: usize = 4096;
+const CONSOLE_ROWS: usize = 12;
+const SKIN_STRIP_LENGTH: f32 = 2.0;
+const FRAME_TIME_SMOOTHING: f32 = 0.1;
+const UPDATE_RATE: f32 = 60.0;
+const MAX_UPDATES_PER_FRAME: u32 = 8;
+const WINDOW_SIZE: [u32; 2] = [1280, 720];
+const MIN_WINDOW_SIZE: [u32; 2] = [320, 240];
 
-        Instance::new(None, &extensions, None).unwrap()
+// `?` for the event loop, which can't return an error: logs it and ends
+// the loop instead.
+macro_rules! or_exit {
+    ($result:expr, $control_flow:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(e) => {
+                error!("{}", Error::from(e));
+                *$control_flow = ControlFlow::Exit;
+                return;
+            }
+        }
     };
+}
 
-    let physical = PhysicalDevice::enumerate(&instance).next().unwrap();
-    println!(
-        "Using device: {} (type: {:?})",
-        physical.name(),
-        physical.ty()
-    );
+fn main() {
+    if let Err(e) = run() {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
 
-    let events_loop = EventLoop::new();
-    let surface = WindowBuilder::new()
-        .build_vk_surface(&events_loop, instance.clone())
-        .unwrap();
-    let window = surface.window();
-
-    let queue_family = physical
-        .queue_families()
-        .find(|&q| {
-            q.supports_graphics() && surface.is_supported(q).unwrap_or(false)
+fn run() -> Result<(), Error> {
+    // The logger is installed before a bad --validation-level is reported,
+    // so the error isn't dropped.
+    let validation_level = arg_value("--validation-level")
+        .map(|name| {
+            validation::parse_severity(&name)
+                .ok_or_else(|| Error::argument("--validation-level", &name))
         })
-        .unwrap();
+        .transpose();
+    logger::init(match &validation_level {
+        Ok(Some(severity)) if severity.verbose => "debug",
+        _ => "info",
+    });
+    // --validation on its own reports warnings and errors.
+    let validation = validation_level?.or_else(|| {
+        if std::env::args().any(|arg| arg == "--validation") {
+            Some(MessageSeverity::errors_and_warnings())
+        } else {
+            None
+        }
+    });
 
-    let device_ext = DeviceExtensions {
-        khr_swapchain: true,
-        ..DeviceExtensions::none()
-    };
-    let (device, mut queues) = Device::new(
-        physical,
-        physical.supported_features(),
-        &device_ext,
-        [(queue_family, 0.5)].iter().cloned(),
-    )
-    .unwrap();
-
-    let queue = queues.next().unwrap();
-
-    let (mut swapchain, images) = {
-        let caps = surface.capabilities(physical).unwrap();
-        let usage = caps.supported_usage_flags;
-        let alpha = caps.supported_composite_alpha.iter().next().unwrap();
-        let format = caps.supported_formats[0].0;
-        let initial_dimensions = {
-            let dimensions = window.inner_size();
-            let dimensions: (u32, u32) =
-                dimensions.to_physical(window.hidpi_factor()).into();
-            [dimensions.0, dimensions.1]
-        };
+    let settings_path =
+        arg_value("--settings").unwrap_or_else(|| SETTINGS_PATH.to_owned());
+    let mut settings = Settings::load(&settings_path)
+        .map_err(Error::io(format!("loading {}", settings_path)))?;
+    let graphics = settings.graphics.clone();
 
-        Swapchain::new(
-            device.clone(),
-            surface.clone(),
-            caps.min_image_count,
-            format,
-            initial_dimensions,
-            1,
-            usage,
-            &queue,
-            SurfaceTransform::Identity,
-            alpha,
-            PresentMode::Fifo,
-            true,
-            None,
-        )
-        .unwrap()
+    let mut state = match arg_value("--restore") {
+        Some(path) => Snapshot::load(&path)
+            .map_err(Error::io(format!("restoring {}", path)))?,
+        None => Snapshot {
+            deferred: graphics.deferred
+                || std::env::args().any(|arg| arg == "--deferred"),
+            oit: graphics.oit || std::env::args().any(|arg| arg == "--oit"),
+            lut: arg_value("--lut"),
+            force_sdr: graphics.sdr
+                || std::env::args().any(|arg| arg == "--sdr"),
+            lightmap: arg_value("--lightmap"),
+            taa: graphics.taa || std::env::args().any(|arg| arg == "--taa"),
+            gpu_cull: std::env::args().any(|arg| arg == "--gpu-cull"),
+            cpu_cull: std::env::args().any(|arg| arg == "--cpu-cull"),
+            lod: Lod {
+                enabled: graphics.lod
+                    || std::env::args().any(|arg| arg == "--lod"),
+                ..Lod::default()
+            },
+            occlusion: graphics.occlusion
+                || std::env::args().any(|arg| arg == "--occlusion"),
+            skinning: std::env::args().any(|arg| arg == "--skinning"),
+            tessellation: graphics.tessellation
+                || std::env::args().any(|arg| arg == "--tessellation"),
+            emitter: Emitter {
+                enabled: std::env::args().any(|arg| arg == "--particles"),
+                ..Emitter::default()
+            },
+            foliage: Foliage {
+                enabled: std::env::args().any(|arg| arg == "--foliage"),
+                density_map: arg_value("--density-map"),
+                ..Foliage::default()
+            },
+            motion_blur: MotionBlur {
+                enabled: graphics.motion_blur
+                    || std::env::args().any(|arg| arg == "--motion-blur"),
+                samples: parsed_arg("--motion-blur-samples")?
+                    .or(graphics.motion_blur_samples)
+                    .unwrap_or(MotionBlur::default().samples),
+                shutter: parsed_arg("--shutter-scale")?
+                    .unwrap_or(MotionBlur::default().shutter),
+            },
+            ..Snapshot::default()
+        },
     };
+    // Scene content lives in the world; the frame loop only reads what is
+    // extracted from it into `state`.
+    #[cfg(feature = "ecs")]
+    let mut render_world = RenderWorld::from_snapshot(&state);
+    // An imported scene replaces the snapshot's.
+    #[cfg(feature = "gltf-import")]
+    {
+        if let Some(path) = arg_value("--gltf") {
+            render_world = RenderWorld::new();
+            gltfimport::import(&mut render_world, &path)?;
+        }
+    }
+    #[cfg(feature = "ecs")]
+    {
+        if let Some(path) = arg_value("--scene") {
+            render_world = RenderWorld::load(&path)
+                .map_err(Error::io(format!("loading {}", path)))?;
+        }
+        if let Some(path) = arg_value("--save-scene") {
+            render_world
+                .save(&path)
+                .map_err(Error::io(format!("saving {}", path)))?;
+        }
+        render_world.extract(&mut state);
+    }
+    // Rebuilt with the scene buffers whenever the world's meshes change.
+    #[cfg(feature = "ecs")]
+    let mut picker = Picker::new(&render_world);
+    #[cfg(feature = "ecs")]
+    let mut scene_dirty = false;
 
-    let vertex_buffer = {
-        CpuAccessibleBuffer::from_iter(
-            device.clone(),
-            BufferUsage::all(),
-            [
-                dbgpipe::Vertex {
-                    position: [-0.5, -0.25, 0.0, 1.0],
-                },
-                dbgpipe::Vertex {
-                    position: [0.0, 0.5, 0.0, 1.0],
-                },
-                dbgpipe::Vertex {
-                    position: [0.25, -0.1, 0.0, 1.0],
-                },
-            ]
-            .iter()
-            .cloned(),
-        )
-        .unwrap()
+    let video_mode = arg_value("--video-mode")
+        .map(|text| {
+            renderer::parse_video_mode(&text)
+                .ok_or_else(|| Error::argument("--video-mode", &text))
+        })
+        .transpose()?;
+    let present_mode = arg_value("--present-mode")
+        .map(|name| {
+            renderer::parse_present_mode(&name)
+                .ok_or_else(|| Error::argument("--present-mode", &name))
+        })
+        .transpose()?;
+    let position = arg_value("--window-position")
+        .map(|text| {
+            parse_position(&text)
+                .ok_or_else(|| Error::argument("--window-position", &text))
+        })
+        .transpose()?;
+
+    let events_loop = EventLoop::new();
+    let mut renderer = Renderer::new(
+        &events_loop,
+        &Options {
+            size: Some(settings.window.size.unwrap_or(WINDOW_SIZE)),
+            min_size: Some(MIN_WINDOW_SIZE),
+            icon: Some(include_bytes!("../resources/icon.png")),
+            force_sdr: state.force_sdr,
+            present_mode,
+            image_count: parsed_arg("--images")?,
+            video_mode: video_mode.unwrap_or_default(),
+            validation,
+            monitor: parsed_arg("--monitor")?.or(settings.window.monitor),
+            position,
+            device: arg_value("--device")
+                .or_else(|| settings.device.clone())
+                .map(|text| renderer::parse_device_request(&text)),
+            ..Options::default()
+        },
+    )?;
+    if std::env::args().any(|arg| arg == "--list-video-modes") {
+        for mode in renderer.video_modes() {
+            let (width, height): (u32, u32) = mode.size().into();
+            println!(
+                "{}x{}@{} ({} bpp)",
+                width,
+                height,
+                mode.refresh_rate(),
+                mode.bit_depth()
+            );
+        }
+    }
+    if std::env::args().any(|arg| arg == "--list-monitors") {
+        for (index, monitor) in renderer.monitors().iter().enumerate() {
+            let (width, height): (u32, u32) = monitor.size().into();
+            let (x, y): (i32, i32) = monitor.position().into();
+            println!(
+                "{}: {} {}x{} at {},{} (scale {})",
+                index,
+                monitor.name().unwrap_or_default(),
+                width,
+                height,
+                x,
+                y,
+                monitor.hidpi_factor()
+            );
+        }
+    }
+    if std::env::args().any(|arg| arg == "--list-devices") {
+        let selected = renderer.physical().index();
+        for physical in PhysicalDevice::enumerate(&renderer.instance) {
+            let (_, heap) = renderer::device_rank(physical);
+            println!(
+                "{}: {} ({:?}, {:.0} MB device-local){}",
+                physical.index(),
+                physical.name(),
+                physical.ty(),
+                memory::mb(heap as u64),
+                if physical.index() == selected {
+                    " [selected]"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+    // Asking for a video mode implies exclusive fullscreen.
+    if video_mode.is_some() {
+        renderer.set_window_mode(WindowMode::Exclusive);
+    } else if settings.window.mode != WindowMode::Windowed {
+        renderer.set_window_mode(settings.window.mode);
+    }
+    match settings.vsync {
+        Some(vsync) if present_mode.is_none() && vsync != renderer.vsync() => {
+            renderer.set_vsync(vsync);
+        }
+        _ => (),
+    }
+    let loaded_settings = settings.clone();
+    let device = renderer.device.clone();
+    let queue = renderer.queue.clone();
+    let compute_queue = renderer.compute_queue.clone();
+    // The histogram samples the scene image, which is exclusive to the
+    // graphics family, so it only moves to the compute queue when that queue
+    // is in the same family.
+    let histogram_queue = if compute_queue.family().id() == queue.family().id()
+    {
+        compute_queue.clone()
+    } else {
+        queue.clone()
     };
+    let uploader = renderer.uploader();
+    let physical = renderer.physical();
+    let output = renderer.output;
 
-    let vp_data = dbgpipe::vs::ty::VP_BLOCK {
-        vp: cgmath::ortho(-5.0, 5.0, 5.0, -5.0, -1.0, 1.0).into(),
+    #[cfg(feature = "hot-reload")]
+    let shader_cache = ShaderCache::new(SHADER_CACHE_DIR, physical)
+        .map_err(Error::io(format!("opening {}", SHADER_CACHE_DIR)))?;
+
+    // Scene and probe meshes share one device-local allocation.
+    let mut mesh_arena = Arena::new(
+        &uploader,
+        MESH_ARENA_CAPACITY,
+        BufferUsage::vertex_buffer(),
+    );
+    let features = device.enabled_features().clone();
+    let gpu_cull = state.gpu_cull && features.multi_draw_indirect;
+    if state.gpu_cull && !gpu_cull {
+        warn!("GPU culling needs multiDrawIndirect, which is unsupported");
+    }
+    if state.cpu_cull && gpu_cull {
+        warn!("CPU culling is disabled while GPU culling is on");
+    }
+    let occlusion_supported = features.fragment_stores_and_atomics;
+    if state.occlusion && !occlusion_supported {
+        warn!("occlusion culling needs fragmentStoresAndAtomics");
+    }
+    let scene_options = scenebuffers::Options {
+        gpu_cull,
+        count_mode: renderer.count_mode,
+        cpu_cull: state.cpu_cull && !gpu_cull,
+        occlusion: state.occlusion && occlusion_supported,
+        lod_levels: if state.lod.enabled {
+            Some(state.lod.thresholds.len() + 1)
+        } else {
+            None
+        },
     };
 
-    let vp_buffer = CpuBufferPool::<dbgpipe::vs::ty::VP_BLOCK>::new(
+    let (probe_sphere_allocation, probe_sphere_upload) = mesh_arena
+        .upload(
+            &uploader,
+            probes::sphere_vertices()
+                .into_iter()
+                .map(|position| dbgpipe::Vertex { position })
+                .collect(),
+        )
+        .ok_or(Error::ArenaFull("probe sphere"))?;
+    let probe_sphere_buffer =
+        Arc::new(mesh_arena.slice(probe_sphere_allocation))
+            as Arc<dyn BufferAccess + Send + Sync>;
+    info!(
+        used = mesh_arena.used(),
+        capacity = MESH_ARENA_CAPACITY,
+        "mesh arena vertices"
+    );
+
+    // Refilled every frame from the current camera.
+    let vp_buffer = CpuBufferPool::<dbgpipe::ViewBlock>::new(
         device.clone(),
         BufferUsage::all(),
     );
 
-    let vp_subbuffer = vp_buffer.next(vp_data).unwrap();
+    // A skinned glTF model and its animations stand in for the strip.
+    #[cfg(feature = "gltf-import")]
+    let animated: Option<SkinnedModel> = arg_value("--animation")
+        .map(|path| gltfimport::import_skinned(&path))
+        .transpose()?;
+    #[cfg(not(feature = "gltf-import"))]
+    let animated: Option<SkinnedModel> = None;
+    let (skin_mesh, mut animation) = match animated {
+        Some(model) => (
+            Some(SkinMesh {
+                vertices: model.vertices,
+                morph_targets: model.morph_targets,
+            }),
+            Some((
+                model.skeleton,
+                AnimationPlayer::new(model.clips),
+                model.weights,
+            )),
+        ),
+        None if state.skinning => (
+            Some(SkinMesh {
+                vertices: skinpipe::strip(16, SKIN_STRIP_LENGTH, 0.2),
+                morph_targets: Vec::new(),
+            }),
+            None,
+        ),
+        None => (None, None),
+    };
+    #[allow(unused_mut)]
+    let (mut passes, passes_upload) = Passes::new(
+        device.clone(),
+        renderer.swapchain.clone(),
+        &uploader,
+        output,
+        &state,
+        skin_mesh,
+    )?;
+    // Rebuilt when the world's meshes change.
+    let (mut scene_buffers, scene_upload) = SceneBuffers::new(
+        device.clone(),
+        &uploader,
+        &mut mesh_arena,
+        &passes.debug,
+        &state.scene,
+        scene_options,
+    )?;
+    let mut probe_grid = ProbeGrid::new(&state.camera);
 
-    let debug_pipeline = dbgpipe::build(device.clone(), swapchain.clone());
-
-    let set = Arc::new(
-        PersistentDescriptorSet::start(debug_pipeline.pipeline.clone(), 0)
-            .add_buffer(vp_subbuffer)
-            .unwrap()
-            .build()
-            .unwrap(),
-    );
+    let mut registry = Registry::new();
+    registry.register_feature(particles::Feature::default());
+    registry.register_feature(foliage::Feature::default());
+    registry.init(&InitContext {
+        device: device.clone(),
+        queue: queue.clone(),
+        format: renderer.swapchain.format(),
+        scene: &passes.debug,
+        state: &state,
+    })?;
+    info!(features = ?registry.feature_names(), "render features");
 
     let mut dynamic_state = DynamicState {
         line_width: None,
@@ -145,152 +483,1647 @@ fn main() {
         scissors: None,
     };
 
-    let mut framebuffers = window_size_dependent_setup(
-        &images,
-        debug_pipeline.render_pass.clone(),
+    let mut targets = Targets::new(
+        device.clone(),
+        &renderer.images,
+        &passes,
         &mut dynamic_state,
+    )?;
+
+    let names = DebugNames::new(device.clone());
+    names.device("device");
+    names.queue(&queue, "graphics queue");
+    names.queue(&compute_queue, "compute queue");
+    names.queue(&renderer.transfer_queue, "transfer queue");
+    names.buffer(&*scene_buffers.vertex_buffer, "scene vertices");
+    passes.name(&names);
+    targets.name(&names);
+
+    let memory = Tracker::new();
+    memory.track_buffer(Category::Vertex, "mesh arena", &mesh_arena.buffer);
+    passes.track(&memory);
+    targets.track(&memory);
+
+    let mut recreate_swapchain = renderer.needs_recreate;
+
+    let max_image_dimension = physical.limits().max_image_dimension_2d();
+    let mut capture: Option<Capture> = None;
+    let mut wireframe = false;
+    let mut debug_view = DebugView::Final;
+    let mut inspectors: Vec<Inspector> = Vec::new();
+    let mut show_probes = false;
+    let mut show_grid = true;
+    let mut console = Console::new();
+    for command in debugserver::COMMANDS {
+        console.register(command);
+    }
+    for name in TWEAKABLES {
+        console.register(&format!("set {}", name));
+    }
+    let mut normal_mode = 0;
+    let mut frame_index: u64 = 0;
+    let mut descriptor_cache = DescriptorCache::new(DESCRIPTOR_MAX_AGE);
+    let mut text_ring = Ring::new(
+        device.clone(),
+        TRANSIENT_VERTICES,
+        renderer.image_count(),
+        BufferUsage::vertex_buffer(),
+    );
+    let mut debug_lines = DebugDraw::new(
+        device.clone(),
+        TRANSIENT_VERTICES,
+        renderer.image_count(),
     );
+    // Gizmos draw over the scene, so they get their own lines.
+    #[cfg(feature = "ecs")]
+    let mut gizmo_lines = DebugDraw::new(
+        device.clone(),
+        TRANSIENT_VERTICES,
+        renderer.image_count(),
+    );
+    #[cfg(feature = "ecs")]
+    let mut gizmo = Gizmo::new(1.0);
+    let mut taa_reset = true;
+    let mut previous_view_projection = state.camera.view_projection();
+    if passes.debug.wireframe.is_none() {
+        warn!("fillModeNonSolid unsupported, wireframe mode disabled");
+    }
+
+    // GPU passes by their GpuTimer scope names, plus the CPU's recording.
+    let mut budgets = Budgets::new(30);
+    budgets.set("record", 1.0);
+    budgets.set("frame", 16.7);
+    budgets.set("prepare", 1.0);
+    budgets.set("scene", 8.0);
+    budgets.set("taa", 1.5);
+    budgets.set("motion blur", 1.5);
+    budgets.set("grade", 1.0);
+    budgets.set("histogram", 0.5);
+    let mut shown_warnings = Vec::new();
+
+    let profiler = Profiler::new();
+    let mut gpu_timer = GpuTimer::new(queue.clone());
+    // Frames left to record before the chrome://tracing file is written.
+    let trace_frames: u64 = parsed_arg("--trace-frames")?.unwrap_or(300);
+    let mut trace = arg_value("--trace").map(|path| {
+        profiler.start_trace();
+        (path, trace_frames)
+    });
 
-    let mut recreate_swapchain = false;
+    let mut telemetry = arg_value("--telemetry")
+        .map(|path| {
+            Telemetry::create(&path)
+                .map_err(Error::io(format!("creating {}", path)))
+        })
+        .transpose()?;
+    let mut benchmark = parsed_arg("--benchmark")?.map(Benchmark::new);
+    let benchmark_path = arg_value("--benchmark-out")
+        .unwrap_or_else(|| "benchmark.json".to_owned());
+    // The scripted path moves relative to the starting view.
+    let benchmark_camera = state.camera;
+    if benchmark.is_some() && renderer.vsync() {
+        warn!("benchmarking with vsync on; frame times will be capped");
+    }
+    let mut last_present = Instant::now();
+    let mut last_update = Instant::now();
+    let mut timestep = FixedStep::new(UPDATE_RATE, MAX_UPDATES_PER_FRAME);
+    // Strip bend angles in degrees after the previous and latest updates.
+    let mut skin_angles = (0.0, 0.0);
+    let pause_unfocused =
+        std::env::args().any(|arg| arg == "--pause-unfocused");
+    let mut focused = true;
+    let mut paused = false;
+    let mut limiter = parsed_arg("--fps-limit")?.map(Limiter::new);
+    let mut frame_time = Smoother::new(FRAME_TIME_SMOOTHING);
+    let mut shown_frame_time = Smoother::new(FRAME_TIME_SMOOTHING);
+    let mut show_stats = false;
+    let mut stats = Stats::default();
+    // Logical pixels, as winit reports the cursor.
+    #[cfg(feature = "ecs")]
+    let mut cursor = [0.0; 2];
+    #[cfg(feature = "ecs")]
+    let mut selected = None;
+    let probe_triangles = probes::sphere_vertices().len() as u64 / 3;
+    let record_fps = parsed_arg("--record-fps")?.unwrap_or(60.0);
+    let record_frames = parsed_arg("--record-frames")?;
+    let mut recording = arg_value("--record")
+        .map(|directory| {
+            Recording::new(PathBuf::from(&directory), record_fps, record_frames)
+                .map_err(Error::io(format!("creating {}", directory)))
+        })
+        .transpose()?;
 
-    let mut previous_frame_end =
-        Some(Box::new(sync::now(device.clone())) as Box<dyn GpuFuture>);
+    let mut debug_server = arg_value("--debug-server")
+        .map(|addr| {
+            debugserver::Server::bind(&addr)
+                .map_err(Error::io(format!("binding {}", addr)))
+        })
+        .transpose()?;
+    if let Some(Ok(address)) = debug_server.as_ref().map(|s| s.local_addr()) {
+        info!(%address, "debug server listening");
+    }
+    #[cfg(feature = "hot-reload")]
+    let mut shader_watcher =
+        if std::env::args().any(|arg| arg == "--watch-shaders") {
+            info!(dir = SHADER_SOURCE_DIR, "watching shaders");
+            Some(ShaderWatcher::new(SHADER_SOURCE_DIR, shader_cache)?)
+        } else {
+            None
+        };
+
+    let mut upload_future = Box::new(
+        sync::now(device.clone())
+            .join(passes_upload)
+            .join(scene_upload)
+            .join(probe_sphere_upload),
+    ) as Box<dyn GpuFuture>;
+    let mut lightmap_set = None;
+    if let Some(path) = &state.lightmap {
+        let (image, future) = lightmap::upload(lightmap::load(path), &uploader);
+        lightmap_set = Some(lightmappipe::lightmap_set(
+            &passes.lightmap,
+            image,
+            passes.sampler.clone(),
+        ));
+        upload_future = Box::new(upload_future.join(future));
+    }
+
+    #[cfg(feature = "imgui")]
+    let mut imgui = {
+        let (imgui, future) = ImguiPass::new(
+            device.clone(),
+            passes.grade.render_pass.clone(),
+            &uploader,
+            renderer.image_count(),
+        )?;
+        upload_future = Box::new(upload_future.join(future));
+        imgui
+    };
+
+    let mut input_recorder = arg_value("--record-input")
+        .map(|path| {
+            replay::Recorder::create(&path)
+                .map_err(Error::io(format!("creating {}", path)))
+        })
+        .transpose()?;
+    let mut input_replay = arg_value("--replay-input")
+        .map(|path| {
+            Replay::load(&path).map_err(Error::io(format!("loading {}", path)))
+        })
+        .transpose()?;
+    let input_proxy = events_loop.create_proxy();
+
+    let mut previous_frame_end = Some(upload_future);
 
     events_loop.run(move |ev, _, control_flow| {
         *control_flow = ControlFlow::Poll;
-        let window = surface.window();
 
-        previous_frame_end.as_mut().unwrap().cleanup_finished();
+        // Empty only after a frame failed and the loop is exiting.
+        if let Some(previous) = previous_frame_end.as_mut() {
+            previous.cleanup_finished();
+        }
+        // Replayed input arrives as one user event per recorded event and
+        // stands in for live input until the recording runs out.
+        let ev = match ev {
+            Event::NewEvents(_) => {
+                if let Some(replay) = input_replay.as_mut() {
+                    for _ in 0..replay.advance(frame_index) {
+                        let _ = input_proxy.send_event(());
+                    }
+                }
+                ev
+            }
+            Event::UserEvent(()) => {
+                let event = input_replay.as_mut().and_then(Replay::next);
+                if input_replay.as_ref().map_or(false, Replay::finished) {
+                    info!("input replay finished");
+                    input_replay = None;
+                }
+                match event {
+                    Some(event) => Event::WindowEvent {
+                        window_id: renderer.window().id(),
+                        event,
+                    },
+                    None => return,
+                }
+            }
+            Event::WindowEvent { ref event, .. }
+                if input_replay.is_some()
+                    && replay::Input::from_event(event).is_some() =>
+            {
+                return
+            }
+            ev => ev,
+        };
+        if let (Some(recorder), Event::WindowEvent { event, .. }) =
+            (input_recorder.as_mut(), &ev)
+        {
+            if let Err(e) = recorder.record(frame_index, event) {
+                error!(error = ?e, "input recording stopped");
+                input_recorder = None;
+            }
+        }
+        // Input the UI claims doesn't also trigger the shortcuts below.
+        #[cfg(feature = "imgui")]
+        {
+            if imgui.handle_event(&ev) {
+                return;
+            }
+        }
         match ev {
-    Event::EventsCleared => {
-                window.request_redraw();
+            Event::EventsCleared => {
+                #[cfg(feature = "hot-reload")]
+                reload_shaders(shader_watcher.as_mut(), &device, &mut passes);
+                // Console lines and debug server requests run the same
+                // commands; None marks the console's.
+                let mut commands = console
+                    .take_submitted()
+                    .iter()
+                    .map(|line| (None, debugserver::parse(line)))
+                    .collect::<Vec<_>>();
+                if let Some(server) = debug_server.as_mut() {
+                    commands.extend(server.poll().into_iter().map(|request| {
+                        (Some(request.client), request.command)
+                    }));
+                }
+                for (client, command) in commands {
+                    let reply = match command {
+                        Ok(Command::Toggle(name)) => match name.as_str() {
+                            "wireframe" if passes.debug.wireframe.is_some() => {
+                                wireframe = !wireframe;
+                                format!("wireframe {}", wireframe)
+                            }
+                            "vsync" => {
+                                let mode =
+                                    renderer.set_vsync(!renderer.vsync());
+                                recreate_swapchain = true;
+                                format!("vsync {:?}", mode)
+                            }
+                            "probes" => {
+                                show_probes = !show_probes;
+                                format!("probes {}", show_probes)
+                            }
+                            "grid" => {
+                                show_grid = !show_grid;
+                                format!("grid {}", show_grid)
+                            }
+                            "normals" if passes.normals.is_some() => {
+                                normal_mode =
+                                    normalpipe::next_mode(normal_mode);
+                                format!("normals {}", normal_mode)
+                            }
+                            "fog" => {
+                                state.fog.cycle_mode();
+                                format!("fog {:?}", state.fog.mode)
+                            }
+                            "debug-layer" => {
+                                state.camera.cull_mask ^= layers::DEBUG;
+                                format!(
+                                    "debug-layer {}",
+                                    state.camera.cull_mask & layers::DEBUG != 0
+                                )
+                            }
+                            _ => format!("error: cannot toggle {}", name),
+                        },
+                        Ok(Command::Vsync(on)) => {
+                            let mode = renderer.set_vsync(on);
+                            recreate_swapchain = true;
+                            format!("vsync {:?}", mode)
+                        }
+                        Ok(Command::Load(path)) => match registry.load(&path) {
+                            Some(Ok(_)) => format!("loaded {}", path),
+                            Some(Err(e)) => format!("error: {}", e),
+                            None => format!("error: no loader for {}", path),
+                        },
+                        Ok(Command::Set(name, value)) => {
+                            match set_tweakable(&mut state, &name, value) {
+                                Ok(()) => format!("{} {}", name, value),
+                                Err(e) => format!("error: {}", e),
+                            }
+                        }
+                        Ok(Command::NextView) => {
+                            debug_view = debug_view.next();
+                            format!("view {:?}", debug_view)
+                        }
+                        Ok(Command::Screenshot(path)) if capture.is_none() => {
+                            let path = path
+                                .unwrap_or_else(|| "screenshot.png".to_owned());
+                            capture = Some(Capture::new(
+                                PathBuf::from(&path),
+                                renderer.swapchain.dimensions(),
+                                1,
+                                max_image_dimension,
+                            ));
+                            format!("capturing {}", path)
+                        }
+                        Ok(Command::Screenshot(_)) => {
+                            "error: capture already in progress".to_owned()
+                        }
+                        Ok(Command::Stats) => format!(
+                            "frame {} record_ms {:.3} gpu_frame_ms {:.3} \
+                             descriptor_sets {} over_budget [{}]",
+                            frame_index,
+                            budgets.last("record").unwrap_or(0.0),
+                            budgets.last("frame").unwrap_or(0.0),
+                            descriptor_cache.len(),
+                            budgets.warnings().join(", ")
+                        ),
+                        Ok(Command::Memory) => memory.dump_memory_report(),
+                        Ok(Command::Profile) => profiler.report(),
+                        Ok(Command::Help) => debugserver::HELP.to_owned(),
+                        Err(e) => format!("error: {}", e),
+                    };
+                    match (client, debug_server.as_mut()) {
+                        (Some(client), Some(server)) => {
+                            server.reply(client, &reply)
+                        }
+                        _ => console.print(&reply),
+                    }
+                }
+
+                // Nothing to present to while minimized, and optionally no
+                // point while in the background; keep serving the debug
+                // server but stop simulating and drawing.
+                let pause =
+                    renderer.is_minimized() || (pause_unfocused && !focused);
+                if pause != paused {
+                    paused = pause;
+                    info!(paused, "focus changed");
+                    recreate_swapchain = true;
+                }
+                if paused {
+                    last_update = Instant::now();
+                    *control_flow = ControlFlow::WaitUntil(
+                        Instant::now() + renderer::PAUSED_POLL,
+                    );
+                    return;
+                }
+
+                let dt =
+                    match &recording {
+                        Some(recording) => recording.dt(),
+                        None => frame_time.sample(
+                            (elapsed_ms(last_update) / 1000.0).min(0.1) as f32,
+                        ),
+                    };
+                last_update = Instant::now();
+                timestep.advance(dt);
+                while timestep.tick() {
+                    registry.update(timestep.step, &state);
+                    let seconds = timestep.time();
+                    skin_angles = (skin_angles.1, 45.0 * seconds.sin() as f32);
+                    if let Some((_, player, _)) = animation.as_mut() {
+                        player.update(timestep.step);
+                    }
+                }
+                #[cfg(feature = "ecs")]
+                {
+                    render_world.set_camera(state.camera);
+                    scene_dirty |= render_world.extract(&mut state);
+                }
+
+                renderer.window().request_redraw();
             }
             Event::WindowEvent {
                 event: WindowEvent::RedrawRequested,
                 ..
             } => {
-        if recreate_swapchain {
-            let dimensions = window.inner_size();
-            let dimensions: (u32, u32) =
-                dimensions.to_physical(window.hidpi_factor()).into();
-            let dimensions = [dimensions.0, dimensions.1];
-
-            let (new_swapchain, new_images) = match swapchain
-                .recreate_with_dimension(dimensions)
-            {
-                Ok(r) => r,
-                Err(SwapchainCreationError::UnsupportedDimensions) => return,
-                Err(err) => panic!("{:?}", err),
-            };
-
-            swapchain = new_swapchain;
-            framebuffers = window_size_dependent_setup(
-                &new_images,
-                debug_pipeline.render_pass.clone(),
-                &mut dynamic_state,
-            );
+                // The platform can still ask for redraws while paused.
+                if paused {
+                    return;
+                }
+                if let Some(limiter) = limiter.as_mut() {
+                    limiter.wait();
+                }
+                // The last frame was waited for, so the old buffers' arena
+                // ranges are free to reuse.
+                #[cfg(feature = "ecs")]
+                {
+                    if scene_dirty {
+                        scene_dirty = false;
+                        let (rebuilt, upload) = or_exit!(
+                            SceneBuffers::new(
+                                device.clone(),
+                                &uploader,
+                                &mut mesh_arena,
+                                &passes.debug,
+                                &state.scene,
+                                scene_options,
+                            ),
+                            control_flow
+                        );
+                        std::mem::replace(&mut scene_buffers, rebuilt)
+                            .free(&mut mesh_arena);
+                        names.buffer(
+                            &*scene_buffers.vertex_buffer,
+                            "scene vertices",
+                        );
+                        let future =
+                            previous_frame_end.take().unwrap().join(upload);
+                        previous_frame_end = Some(Box::new(future));
+                        picker = Picker::new(&render_world);
+                    }
+                }
+                let vertex_buffer = scene_buffers.vertex_buffer.clone();
+                let lightmap_vertex_buffer =
+                    scene_buffers.lightmap_vertex_buffer.clone();
+                let scene_allocation = scene_buffers.allocation;
+                let scene_triangles = scene_buffers.triangles;
+                let SceneBuffers {
+                    lods: scene_lods,
+                    gpu_culling,
+                    cpu_culling,
+                    occlusion,
+                    ..
+                } = &mut scene_buffers;
+                profiler.begin_frame();
+                if gpu_timer.begin_frame() {
+                    profiler.gpu(gpu_timer.last_frame());
+                    for timing in gpu_timer.last_frame() {
+                        budgets.record(timing.name, timing.duration_ms);
+                    }
+                }
+                let frame_scope = profiler.scope("frame");
+                if let Some(benchmark) = &benchmark {
+                    state.camera = benchmark.camera(&benchmark_camera);
+                }
 
-            recreate_swapchain = false;
-        }
+                if recreate_swapchain {
+                    if !or_exit!(renderer.recreate(), control_flow) {
+                        return;
+                    }
+                    targets = or_exit!(
+                        Targets::new(
+                            device.clone(),
+                            &renderer.images,
+                            &passes,
+                            &mut dynamic_state,
+                        ),
+                        control_flow
+                    );
+                    targets.name(&names);
+                    targets.track(&memory);
 
-        let (image_num, acquire_future) =
-            match swapchain::acquire_next_image(swapchain.clone(), None) {
-                Ok(r) => r,
-                Err(AcquireError::OutOfDate) => {
-                    recreate_swapchain = true;
-                    return;
+                    recreate_swapchain = false;
+                    taa_reset = true;
                 }
-                Err(err) => panic!("{:?}", err),
-            };
 
-        let clear_values = vec![[0.0, 0.0, 1.0, 1.0].into()];
+                if let Some(hq) = capture.as_mut() {
+                    let vertex_buffer = vertex_buffer.clone();
+                    or_exit!(
+                        hq.step(
+                            device.clone(),
+                            queue.clone(),
+                            &passes.debug,
+                            renderer.swapchain.format(),
+                            state.camera.view_projection(),
+                            |builder, dynamic_state, set| {
+                                let builder = draw_opaque(
+                                    builder,
+                                    &passes.debug,
+                                    dynamic_state,
+                                    vertex_buffer.clone(),
+                                    set.clone(),
+                                    wireframe,
+                                    Draw::Direct,
+                                )?;
+                                draw_transparent_sorted(
+                                    builder,
+                                    &passes.debug,
+                                    dynamic_state,
+                                    vertex_buffer,
+                                    set,
+                                    &state,
+                                    state.camera.cull_mask
+                                        & layers::CAPTURE_MASK,
+                                )
+                            },
+                        ),
+                        control_flow
+                    );
+                    if hq.done() {
+                        capture = None;
+                    }
+                }
 
-        let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
-            device.clone(),
-            queue.family(),
-        )
-        .unwrap()
-        .begin_render_pass(framebuffers[image_num].clone(), false, clear_values)
-        .unwrap()
-        .draw(
-            debug_pipeline.pipeline.clone(),
-            &dynamic_state,
-            vec![vertex_buffer.clone()],
-            vec![set.clone()],
-            ()
-        )
-        .unwrap()
-        .end_render_pass()
-        .unwrap()
-        .build()
-        .unwrap();
-
-        let prev = previous_frame_end.take();
-
-        let future = prev.unwrap()
-            .join(acquire_future)
-            .then_execute(queue.clone(), command_buffer)
-            .unwrap()
-            .then_swapchain_present(queue.clone(), swapchain.clone(), image_num)
-            .then_signal_fence_and_flush();
-
-        match future {
-            Ok(future) => {
-                future.wait(None).unwrap();
-                previous_frame_end = Some(Box::new(future) as Box<_>);
-            }
-            Err(FlushError::OutOfDate) => {
-                recreate_swapchain = true;
-                previous_frame_end =
-                    Some(Box::new(sync::now(device.clone())) as Box<_>);
-            }
-            Err(e) => {
-                eprintln!("{:?}", e);
-                previous_frame_end =
-                    Some(Box::new(sync::now(device.clone())) as Box<_>);
-            }
-        }
+                // The previous frame has finished, so its proxy results are
+                // ready.
+                if let Some(occlusion) = occlusion.as_mut() {
+                    occlusion.read_back();
+                }
+                text_ring.begin_frame();
+                if show_grid {
+                    let (min, max) = state.camera.bounds();
+                    let (min, max) = ([min[0], min[1]], [max[0], max[1]]);
+                    let spacing = debug_draw::grid_spacing(
+                        (max[0] - min[0]).max(max[1] - min[1]),
+                    );
+                    debug_lines.axes(Point3::origin(), spacing * 2.0);
+                    debug_lines.grid(min, max, spacing, [0.3, 0.3, 0.3, 1.0]);
+                }
+                if show_probes {
+                    for index in 0..probes::COUNT {
+                        debug_lines.sphere(
+                            Point3::from_vec(probe_grid.position(index)),
+                            probes::RADIUS,
+                            [1.0, 1.0, 0.0, 1.0],
+                        );
+                    }
+                }
+                let lines = debug_lines.flush();
+                #[cfg(feature = "ecs")]
+                let gizmo_on_top = {
+                    let camera = &state.camera;
+                    gizmo.size = (camera.right - camera.left).abs() * 0.1;
+                    let selected = selected.and_then(|entity| {
+                        let transform =
+                            render_world.world.get::<Transform>(entity).ok()?;
+                        Some((entity, *transform))
+                    });
+                    if let Some((entity, transform)) = selected {
+                        let parent = render_world.parent_matrix(entity);
+                        gizmo.draw(&mut gizmo_lines, &transform, &parent);
+                    }
+                    gizmo_lines.flush()
+                };
+
+                let (image_num, acquire_future) =
+                    match or_exit!(renderer.acquire(), control_flow) {
+                        Some(acquired) => acquired,
+                        None => {
+                            recreate_swapchain = true;
+                            return;
+                        }
+                    };
+
+                probe_grid.update(
+                    &state.scene,
+                    &state.light,
+                    probes::PER_FRAME,
+                );
+
+                let view_projection = state.camera.view_projection();
+                let mut cull_stats = None;
+                let mut lod_stats = None;
+                let jitter = if passes.taa.is_some() {
+                    camera::jitter(frame_index, renderer.swapchain.dimensions())
+                } else {
+                    [0.0, 0.0]
+                };
+                let jittered_view_projection =
+                    state.camera.jittered_view_projection(jitter);
+
+                let record_start = Instant::now();
+                let record_scope = profiler.scope("record");
+                // Culling, LOD and probes read the same camera, so the
+                // draws have to see it too.
+                let view_buffer = or_exit!(
+                    vp_buffer
+                        .next(dbgpipe::ViewBlock {
+                            vp: jittered_view_projection.into(),
+                        })
+                        .map_err(Error::allocation("view uniforms")),
+                    control_flow
+                );
+                let view_buffer = Arc::new(view_buffer)
+                    as Arc<dyn BufferAccess + Send + Sync>;
+                let frame_set = or_exit!(
+                    PersistentDescriptorSet::start(
+                        passes.debug.pipeline.clone(),
+                        0,
+                    )
+                    .add_buffer(view_buffer.clone()),
+                    control_flow
+                );
+                let frame_set =
+                    Arc::new(or_exit!(frame_set.build(), control_flow))
+                        as Arc<dyn DescriptorSet + Send + Sync>;
+                let frame = Frame {
+                    dynamic_state: &dynamic_state,
+                    view_buffer,
+                    view_set: frame_set.clone(),
+                    vertex_buffer: vertex_buffer.clone(),
+                    state: &state,
+                };
+                let builder = or_exit!(
+                    AutoCommandBufferBuilder::primary_one_time_submit(
+                        device.clone(),
+                        queue.family(),
+                    ),
+                    control_flow
+                );
+                let builder = begin_pass(
+                    builder,
+                    &mut gpu_timer,
+                    &names,
+                    queue.family(),
+                    "frame",
+                );
+                let builder = begin_pass(
+                    builder,
+                    &mut gpu_timer,
+                    &names,
+                    queue.family(),
+                    "prepare",
+                );
+
+                let feature_frame = FrameContext {
+                    dynamic_state: &dynamic_state,
+                    view_set: frame_set.clone(),
+                    state: &state,
+                    alpha: timestep.alpha(),
+                    frame: frame_index,
+                };
+                let builder = or_exit!(
+                    registry.prepare(builder, &feature_frame),
+                    control_flow
+                );
+                let builder = match &gpu_culling {
+                    Some((culler, _)) if passes.deferred.is_none() => {
+                        culler.record(builder, &view_projection)
+                    }
+                    _ => builder,
+                };
+                let builder = match &occlusion {
+                    Some(occlusion) if passes.deferred.is_none() => {
+                        occlusion.reset(builder)
+                    }
+                    _ => builder,
+                };
+                let builder =
+                    end_pass(builder, &mut gpu_timer, &names, queue.family());
+
+                let builder = begin_pass(
+                    builder,
+                    &mut gpu_timer,
+                    &names,
+                    queue.family(),
+                    "scene",
+                );
+                let builder = match (&passes.deferred, &targets.deferred) {
+                    (Some(pipeline), Some(deferred_targets)) => or_exit!(
+                        record_deferred(
+                            builder,
+                            &passes,
+                            pipeline,
+                            deferred_targets,
+                            &frame,
+                            &probe_grid,
+                        ),
+                        control_flow
+                    ),
+                    _ => {
+                        // Culling commands index the full-detail mesh.
+                        let lod_ranges = match scene_lods.as_mut() {
+                            Some(lods)
+                                if gpu_culling.is_none()
+                                    && cpu_culling.is_none() =>
+                            {
+                                let mut counts = LodStats::default();
+                                let ranges = lods
+                                    .iter_mut()
+                                    .map(|lod| {
+                                        let level = lod.select(
+                                            &state.lod,
+                                            &state.camera.view(),
+                                            &view_projection,
+                                            renderer.swapchain.dimensions()[1]
+                                                as f32,
+                                        );
+                                        counts.record(lod.level());
+                                        level
+                                    })
+                                    .collect::<Vec<_>>();
+                                lod_stats = Some(counts);
+                                Some(ranges)
+                            }
+                            _ => None,
+                        };
+                        let opaque = match &lightmap_set {
+                            Some(lightmap_set) if !wireframe => {
+                                Opaque::Lightmapped(
+                                    lightmap_vertex_buffer.clone(),
+                                    lightmap_set.clone(),
+                                )
+                            }
+                            _ => match &occlusion {
+                                Some(occlusion) if !wireframe => {
+                                    Opaque::Occluded(
+                                        occlusion,
+                                        scene_allocation,
+                                    )
+                                }
+                                _ => match (&cpu_culling, lod_ranges) {
+                                    (Some(culler), _) => {
+                                        let visible = culler.cull(
+                                            scene_allocation,
+                                            &view_projection,
+                                        );
+                                        cull_stats = Some(visible.stats);
+                                        Opaque::Ranges(visible.ranges)
+                                    }
+                                    (None, Some(ranges)) => {
+                                        Opaque::Ranges(ranges)
+                                    }
+                                    (None, None) => {
+                                        Opaque::Whole(match &gpu_culling {
+                                            Some((_, bucket)) => {
+                                                Draw::Indirect(bucket)
+                                            }
+                                            None => Draw::Direct,
+                                        })
+                                    }
+                                },
+                            },
+                        };
+                        let skin = passes.skin.as_ref().map(|_| {
+                            let weights = match &animation {
+                                Some((_, player, defaults)) => {
+                                    player.weights(defaults)
+                                }
+                                None => Vec::new(),
+                            };
+                            let bones = match &animation {
+                                Some((skeleton, player, _)) => {
+                                    player.palette(skeleton)
+                                }
+                                None => {
+                                    let (previous, latest) = skin_angles;
+                                    let alpha = timestep.alpha();
+                                    skinpipe::bend(
+                                        SKIN_STRIP_LENGTH * 0.5,
+                                        Deg(previous
+                                            + (latest - previous) * alpha),
+                                    )
+                                }
+                            };
+                            (weights, bones)
+                        });
+                        let features = or_exit!(
+                            secondary::builder(
+                                device.clone(),
+                                queue.family(),
+                                passes.debug.render_pass.clone(),
+                                0,
+                            ),
+                            control_flow
+                        );
+                        let features = or_exit!(
+                            registry.draw_scene(features, &feature_frame),
+                            control_flow
+                        );
+                        let features = or_exit!(features.build(), control_flow);
+                        #[cfg(feature = "ecs")]
+                        let on_top = gizmo_on_top;
+                        #[cfg(not(feature = "ecs"))]
+                        let on_top = None;
+                        let scene = Scene {
+                            opaque,
+                            arena: &mesh_arena,
+                            wireframe,
+                            occlusion: occlusion.as_ref(),
+                            probes: if show_probes {
+                                Some((&probe_grid, probe_sphere_buffer.clone()))
+                            } else {
+                                None
+                            },
+                            skin,
+                            normal_mode,
+                            lines,
+                            on_top,
+                            features,
+                        };
+                        or_exit!(
+                            record_forward(
+                                builder,
+                                device.clone(),
+                                queue.family(),
+                                &passes,
+                                &targets,
+                                &frame,
+                                scene,
+                            ),
+                            control_flow
+                        )
+                    }
+                };
+                let builder =
+                    end_pass(builder, &mut gpu_timer, &names, queue.family());
+
+                let builder = match (&passes.taa, &targets.taa) {
+                    (Some(taa), Some(taa_targets)) => {
+                        let builder = begin_pass(
+                            builder,
+                            &mut gpu_timer,
+                            &names,
+                            queue.family(),
+                            "taa",
+                        );
+                        let velocity =
+                            taapipe::velocity_vs::ty::VELOCITY_BLOCK {
+                                jittered_vp: jittered_view_projection.into(),
+                                vp: view_projection.into(),
+                                previous_vp: previous_view_projection.into(),
+                            };
+                        let builder = or_exit!(
+                            record_taa(
+                                builder,
+                                &passes,
+                                taa,
+                                taa_targets,
+                                &frame,
+                                velocity,
+                                taa_reset,
+                            ),
+                            control_flow
+                        );
+                        end_pass(
+                            builder,
+                            &mut gpu_timer,
+                            &names,
+                            queue.family(),
+                        )
+                    }
+                    _ => builder,
+                };
+                let builder = match (&passes.motion_blur, &targets.motion_blur)
+                {
+                    (Some(pipeline), Some(blur_targets)) => {
+                        let builder = begin_pass(
+                            builder,
+                            &mut gpu_timer,
+                            &names,
+                            queue.family(),
+                            "motion blur",
+                        );
+                        let builder = or_exit!(
+                            motionblurpipe::draw(
+                                builder,
+                                pipeline,
+                                blur_targets,
+                                &dynamic_state,
+                                &state.motion_blur,
+                            ),
+                            control_flow
+                        );
+                        end_pass(
+                            builder,
+                            &mut gpu_timer,
+                            &names,
+                            queue.family(),
+                        )
+                    }
+                    _ => builder,
+                };
+                taa_reset = false;
+                previous_view_projection = view_projection;
+                frame_index += 1;
+
+                let overdraw_shown = debug_view == DebugView::Overdraw
+                    || inspectors
+                        .iter()
+                        .any(|inspector| inspector.view == DebugView::Overdraw);
+                let builder = if overdraw_shown {
+                    let builder = begin_pass(
+                        builder,
+                        &mut gpu_timer,
+                        &names,
+                        queue.family(),
+                        "overdraw",
+                    );
+                    let builder = or_exit!(
+                        draw_overdraw(builder, &passes, &targets, &frame),
+                        control_flow
+                    );
+                    end_pass(builder, &mut gpu_timer, &names, queue.family())
+                } else {
+                    builder
+                };
+
+                let grade_scope = profiler.scope("grade");
+                let grade = or_exit!(
+                    record_grade(
+                        device.clone(),
+                        queue.family(),
+                        &passes,
+                        &targets,
+                        &dynamic_state,
+                        debug_view,
+                    ),
+                    control_flow
+                );
+                let grade = or_exit!(
+                    draw_inspectors(
+                        grade,
+                        &mut text_ring,
+                        &passes,
+                        &targets,
+                        &dynamic_state,
+                        &inspectors,
+                        &mut descriptor_cache,
+                    ),
+                    control_flow
+                );
+                // The console drops down over the stats.
+                let overlay_text = if console.open {
+                    Some(console.text(CONSOLE_ROWS))
+                } else {
+                    overlay::text(
+                        show_stats,
+                        &stats,
+                        &profiler.report(),
+                        &shown_warnings,
+                    )
+                };
+                let grade = if let Some(text) = overlay_text {
+                    let scale = overlay::SCALE * renderer.scale_factor() as f32;
+                    or_exit!(
+                        draw_overlay(
+                            grade,
+                            &mut text_ring,
+                            &passes,
+                            &targets,
+                            &dynamic_state,
+                            &overlay::quads(&text, scale),
+                        ),
+                        control_flow
+                    )
+                } else {
+                    grade
+                };
+                #[cfg(feature = "imgui")]
+                let grade = imgui.draw(
+                    grade,
+                    renderer.swapchain.dimensions(),
+                    renderer.scale_factor(),
+                    |ui| {
+                        scene_window(
+                            ui,
+                            &mut show_stats,
+                            &mut show_grid,
+                            &mut show_probes,
+                        )
+                    },
+                );
+                drop(grade_scope);
+
+                let builder = begin_pass(
+                    builder,
+                    &mut gpu_timer,
+                    &names,
+                    queue.family(),
+                    "grade",
+                );
+                let builder = or_exit!(
+                    builder.begin_render_pass(
+                        targets.framebuffers[image_num].clone(),
+                        true,
+                        vec![ClearValue::None],
+                    ),
+                    control_flow
+                );
+                let grade = or_exit!(grade.build(), control_flow);
+                let builder = or_exit!(
+                    secondary::execute(builder, vec![grade]),
+                    control_flow
+                );
+                let builder = or_exit!(builder.end_render_pass(), control_flow);
+                let builder =
+                    end_pass(builder, &mut gpu_timer, &names, queue.family());
+                let builder =
+                    end_pass(builder, &mut gpu_timer, &names, queue.family());
+                let command_buffer = or_exit!(builder.build(), control_flow);
+                descriptor_cache.end_frame();
+                let record_ms = elapsed_ms(record_start);
+                budgets.record("record", record_ms);
+                drop(record_scope);
+
+                let submit_start = Instant::now();
+                let submit_scope = profiler.scope("submit");
+                names.label(&queue, &format!("frame {}", frame_index));
+                let prev = previous_frame_end.take();
+
+                let (histogram_pipeline, histogram) = &passes.histogram;
+                let compute_builder = or_exit!(
+                    AutoCommandBufferBuilder::primary_one_time_submit(
+                        device.clone(),
+                        histogram_queue.family(),
+                    ),
+                    control_flow
+                );
+                let compute_builder = begin_pass(
+                    compute_builder,
+                    &mut gpu_timer,
+                    &names,
+                    histogram_queue.family(),
+                    "histogram",
+                );
+                let compute_builder = or_exit!(
+                    compute::dispatch(
+                        compute_builder,
+                        histogram_pipeline,
+                        targets.histogram_set.clone(),
+                        histogram.clone(),
+                        renderer.swapchain.dimensions(),
+                    ),
+                    control_flow
+                );
+                let compute_command_buffer = or_exit!(
+                    end_pass(
+                        compute_builder,
+                        &mut gpu_timer,
+                        &names,
+                        histogram_queue.family()
+                    )
+                    .build(),
+                    control_flow
+                );
+
+                // Present right after graphics; the histogram runs after it
+                // and doesn't hold up the swapchain.
+                let rendered = or_exit!(
+                    prev.unwrap()
+                        .join(acquire_future)
+                        .then_execute(queue.clone(), command_buffer),
+                    control_flow
+                );
+                if let Some(recording) = &recording {
+                    renderer.capture_next_frame(recording.path());
+                }
+                let (screenshot, rendered) = match renderer
+                    .take_capture(image_num)
+                {
+                    Some((pending, copy)) => (
+                        Some(pending),
+                        Box::new(or_exit!(
+                            rendered.then_execute(queue.clone(), copy),
+                            control_flow
+                        )) as Box<dyn GpuFuture>,
+                    ),
+                    None => (None, Box::new(rendered) as Box<_>),
+                };
+                let future = or_exit!(
+                    compute::then_execute(
+                        rendered.then_swapchain_present(
+                            queue.clone(),
+                            renderer.swapchain.clone(),
+                            image_num,
+                        ),
+                        histogram_queue.clone(),
+                        compute_command_buffer,
+                    ),
+                    control_flow
+                )
+                .then_signal_fence_and_flush();
+                drop(submit_scope);
 
+                let mut gpu_wait_ms = 0.0;
+                match future {
+                    Ok(future) => {
+                        gpu_timer.submitted();
+                        or_exit!(future.wait(None), control_flow);
+                        if let Some(screenshot) = screenshot {
+                            screenshot.save();
+                            if let Some(recording) = recording.as_mut() {
+                                recording.advance();
+                                if recording.done() {
+                                    *control_flow = ControlFlow::Exit;
+                                }
+                            }
+                        }
+                        gpu_wait_ms = elapsed_ms(submit_start);
+                        previous_frame_end = Some(Box::new(future) as Box<_>);
+                    }
+                    Err(FlushError::OutOfDate) => {
+                        recreate_swapchain = true;
+                        previous_frame_end =
+                            Some(Box::new(sync::now(device.clone())) as Box<_>);
+                    }
+                    Err(e) => {
+                        error!(error = ?e, frame = frame_index, "frame failed");
+                        previous_frame_end =
+                            Some(Box::new(sync::now(device.clone())) as Box<_>);
+                    }
+                }
+
+                drop(frame_scope);
+                profiler.end_frame();
+                let trace_done = match trace.as_mut() {
+                    Some((_, frames)) => {
+                        *frames = frames.saturating_sub(1);
+                        *frames == 0
+                    }
+                    None => false,
+                };
+                if trace_done {
+                    let (path, _) = trace.take().unwrap();
+                    match profiler.export_trace(&path) {
+                        Ok(()) => info!(%path, "wrote trace"),
+                        Err(e) => error!(error = ?e, "failed to write trace"),
+                    }
+                }
+
+                let transparent_draws = transparent::draw_list(
+                    &state.transparent,
+                    &state.camera.view(),
+                    state.camera.cull_mask,
+                )
+                .len();
+                let probe_draws = if show_probes { probes::COUNT } else { 0 };
+                let opaque_draws =
+                    cull_stats.map_or(1, |cull| cull.draws as usize);
+                let draws = opaque_draws
+                    + transparent_draws
+                    + probe_draws
+                    + inspectors.len()
+                    + (show_stats || console.open || !shown_warnings.is_empty())
+                        as usize;
+                let frame_ms = elapsed_ms(last_present);
+                // Counts the full-detail scene even when a lower LOD drew.
+                stats = Stats {
+                    fps: 1000.0
+                        / shown_frame_time.sample(frame_ms as f32) as f64,
+                    frame_ms,
+                    record_ms,
+                    gpu_ms: gpu_wait_ms,
+                    gpu_frame_ms: gpu_timer
+                        .last_frame()
+                        .iter()
+                        .find(|timing| timing.name == "frame")
+                        .map(|timing| timing.duration_ms),
+                    draws: draws as u32,
+                    triangles: scene_triangles * (1 + transparent_draws) as u64
+                        + probe_triangles * probe_draws as u64
+                        + 2 * inspectors.len() as u64,
+                    memory_bytes: memory.total_bytes(),
+                    cull: cull_stats,
+                    lod: lod_stats,
+                };
+                let sample = FrameSample {
+                    frame_ms,
+                    record_ms,
+                    gpu_wait_ms,
+                    draws: draws as u32,
+                };
+                if let Some(sink) = telemetry.as_mut() {
+                    if let Err(e) = sink.frame(sample) {
+                        error!(error = ?e, "telemetry disabled");
+                        telemetry = None;
+                    }
+                }
+                if let Some(run) = benchmark.as_mut() {
+                    run.frame(sample, &profiler.last_frame());
+                    if run.done() {
+                        let report = run.report();
+                        match report.save(&benchmark_path) {
+                            Ok(()) => info!(
+                                path = %benchmark_path,
+                                frames = report.frames,
+                                frame_p50_ms = report.frame_p50_ms,
+                                frame_p99_ms = report.frame_p99_ms,
+                                "benchmark finished"
+                            ),
+                            Err(e) => {
+                                error!(error = ?e, "failed to save benchmark")
+                            }
+                        }
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+                last_present = Instant::now();
+
+                let warnings = budgets.warnings();
+                if warnings != shown_warnings {
+                    if warnings.is_empty() {
+                        renderer.window().set_title(&renderer.title);
+                    } else {
+                        renderer.window().set_title(&format!(
+                            "{} [over budget: {}]",
+                            renderer.title,
+                            warnings.join(", ")
+                        ));
+                    }
+                    shown_warnings = warnings;
+                }
             }
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
             } => {
+                settings.remember(&renderer);
+                if settings != loaded_settings {
+                    match settings.save(&settings_path) {
+                        Ok(()) => {
+                            info!(path = %settings_path, "saved settings")
+                        }
+                        Err(e) => error!(error = ?e, "failed to save settings"),
+                    }
+                }
                 *control_flow = ControlFlow::Exit;
             }
             Event::WindowEvent {
                 event: WindowEvent::Resized(_),
                 ..
+            }
+            | Event::WindowEvent {
+                event: WindowEvent::HiDpiFactorChanged(_),
+                ..
             } => recreate_swapchain = true,
+            Event::WindowEvent {
+                event: WindowEvent::Focused(now_focused),
+                ..
+            } => focused = now_focused,
+            #[cfg(feature = "ecs")]
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                cursor = [position.x, position.y];
+                if let (Some(entity), true) = (selected, gizmo.dragging()) {
+                    let (origin, direction) =
+                        cursor_ray(renderer.window(), &state.camera, cursor);
+                    let parent = render_world.parent_matrix(entity);
+                    if let (Some(moved), Ok(mut transform)) = (
+                        gizmo.update(origin, direction, &parent),
+                        render_world.world.get_mut::<Transform>(entity),
+                    ) {
+                        *transform = moved;
+                    }
+                }
+            }
+            // A click on the selection's gizmo starts a drag; anywhere
+            // else picks a new selection.
+            #[cfg(feature = "ecs")]
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => {
+                let (origin, direction) =
+                    cursor_ray(renderer.window(), &state.camera, cursor);
+                let grabbed = selected.map_or(false, |entity| {
+                    let parent = render_world.parent_matrix(entity);
+                    match render_world.world.get::<Transform>(entity) {
+                        Ok(transform) => {
+                            gizmo.begin(origin, direction, &transform, &parent)
+                        }
+                        Err(_) => false,
+                    }
+                });
+                if grabbed {
+                    return;
+                }
+                selected = picker.pick(origin, direction).map(|(e, _)| e);
+                match selected {
+                    Some(entity) => {
+                        let name = render_world
+                            .world
+                            .get::<Name>(entity)
+                            .ok()
+                            .map(|name| name.0.clone());
+                        info!(?entity, ?name, "picked")
+                    }
+                    None => info!("picked nothing"),
+                }
+            }
+            #[cfg(feature = "ecs")]
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state: ElementState::Released,
+                        button: MouseButton::Left,
+                        ..
+                    },
+                ..
+            } if gizmo.dragging() => {
+                gizmo.end();
+            }
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(c),
+                ..
+            } => console.character(c),
+            // While open, the console takes every key.
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } if key == VirtualKeyCode::Grave || console.open => {
+                if key == VirtualKeyCode::Grave {
+                    console.toggle();
+                } else {
+                    console.key(key);
+                }
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Return),
+                                modifiers,
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } if modifiers.alt => {
+                let mode =
+                    renderer.set_window_mode(renderer.window_mode.next());
+                info!(?mode, "window mode");
+                recreate_swapchain = true;
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::F12),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => match state.save("snapshot.json") {
+                Ok(()) => info!("saved snapshot.json"),
+                Err(e) => error!(error = ?e, "failed to save snapshot"),
+            },
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => match key {
+                VirtualKeyCode::F => {
+                    state.fog.cycle_mode();
+                    info!(mode = ?state.fog.mode, "fog");
+                }
+                VirtualKeyCode::F1 => show_stats = !show_stats,
+                #[cfg(feature = "ecs")]
+                VirtualKeyCode::T if !gizmo.dragging() => {
+                    gizmo.mode = gizmo.mode.next();
+                    info!(mode = ?gizmo.mode, "gizmo");
+                }
+                VirtualKeyCode::PageUp => state.fog.density *= 1.25,
+                VirtualKeyCode::PageDown => state.fog.density /= 1.25,
+                VirtualKeyCode::L => state.camera.cull_mask ^= layers::DEBUG,
+                VirtualKeyCode::V => {
+                    debug_view = debug_view.next();
+                    info!(view = ?debug_view, "debug view");
+                }
+                VirtualKeyCode::Insert if inspectors.len() < 4 => {
+                    let mut corner = Corner::TopLeft;
+                    while inspectors.iter().any(|i| i.corner == corner) {
+                        corner = corner.next();
+                    }
+                    inspectors.push(Inspector::new(DebugView::Depth, corner));
+                }
+                VirtualKeyCode::Delete => {
+                    inspectors.pop();
+                }
+                VirtualKeyCode::I => {
+                    if let Some(inspector) = inspectors.last_mut() {
+                        inspector.view = inspector.view.next();
+                        while targets.available_views & inspector.view.bit()
+                            == 0
+                        {
+                            inspector.view = inspector.view.next();
+                        }
+                        info!(
+                            corner = ?inspector.corner,
+                            view = ?inspector.view,
+                            "inspector"
+                        );
+                    }
+                }
+                VirtualKeyCode::O => {
+                    if let Some(inspector) = inspectors.last_mut() {
+                        inspector.corner = inspector.corner.next();
+                    }
+                }
+                VirtualKeyCode::W if passes.debug.wireframe.is_some() => {
+                    wireframe = !wireframe;
+                }
+                VirtualKeyCode::Y => {
+                    let mode = renderer.set_vsync(!renderer.vsync());
+                    info!(?mode, "present mode");
+                    recreate_swapchain = true;
+                }
+                VirtualKeyCode::N if passes.normals.is_some() => {
+                    normal_mode = normalpipe::next_mode(normal_mode);
+                }
+                VirtualKeyCode::H => info!(
+                    luminance = compute::average_luminance(&passes.histogram.1),
+                    "average luminance"
+                ),
+                VirtualKeyCode::X => show_grid = !show_grid,
+                VirtualKeyCode::G => {
+                    show_probes = !show_probes;
+                    if show_probes && state.deferred {
+                        warn!("probe spheres are only drawn in forward mode");
+                    }
+                }
+                VirtualKeyCode::B => {
+                    let start = Instant::now();
+                    let baked = lightmap::bake(
+                        &state.scene,
+                        &state.light,
+                        lightmap::SIZE,
+                        lightmap::SAMPLES,
+                    );
+                    lightmap::save(&baked, "lightmap.png");
+                    info!(ms = elapsed_ms(start), "baked lightmap.png");
+
+                    let (image, future) = lightmap::upload(baked, &uploader);
+                    lightmap_set = Some(lightmappipe::lightmap_set(
+                        &passes.lightmap,
+                        image,
+                        passes.sampler.clone(),
+                    ));
+                    previous_frame_end = Some(Box::new(
+                        previous_frame_end.take().unwrap().join(future),
+                    ) as Box<_>);
+                    state.lightmap = Some("lightmap.png".to_owned());
+                }
+                VirtualKeyCode::M => {
+                    let monitors = renderer.monitors();
+                    let current = renderer.window().current_monitor();
+                    let index = monitors
+                        .iter()
+                        .position(|monitor| monitor.name() == current.name())
+                        .map_or(0, |index| (index + 1) % monitors.len());
+                    if let Some(monitor) = monitors.get(index) {
+                        renderer.move_to_monitor(monitor, None);
+                        info!(index, "moved to monitor");
+                        recreate_swapchain = true;
+                    }
+                }
+                VirtualKeyCode::F10 => {
+                    if renderer.capture_frame() {
+                        info!("renderdoc capturing the next frame");
+                    } else {
+                        warn!("renderdoc unavailable");
+                    }
+                }
+                VirtualKeyCode::P => {
+                    renderer.capture_next_frame("screenshot.png");
+                }
+                VirtualKeyCode::F9 if capture.is_none() => {
+                    capture = Some(Capture::new(
+                        PathBuf::from("capture.png"),
+                        renderer.swapchain.dimensions(),
+                        4,
+                        max_image_dimension,
+                    ));
+                }
+                _ => {
+                    registry.key_pressed(key);
+                }
+            },
             _ => (),
         }
     });
 }
 
-fn window_size_dependent_setup(
-    images: &[Arc<SwapchainImage<Window>>],
-    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
-    dynamic_state: &mut DynamicState,
-) -> Vec<Arc<dyn FramebufferAbstract + Send + Sync>> {
-    let dimensions = images[0].dimensions();
-
-    let viewport = Viewport {
-        origin: [0.0, 0.0],
-        dimensions: [dimensions[0] as f32, dimensions[1] as f32],
-        depth_range: 0.0..1.0,
+// World space ray through the cursor, which winit reports in logical
+// pixels.
+#[cfg(feature = "ecs")]
+fn cursor_ray(
+    window: &Window,
+    camera: &camera::Camera,
+    cursor: [f64; 2],
+) -> (Vector3<f32>, Vector3<f32>) {
+    let size = window.inner_size();
+    camera.ray([
+        (2.0 * cursor[0] / size.width - 1.0) as f32,
+        (2.0 * cursor[1] / size.height - 1.0) as f32,
+    ])
+}
+
+#[cfg(feature = "imgui")]
+fn scene_window(
+    ui: &imgui::Ui,
+    show_stats: &mut bool,
+    show_grid: &mut bool,
+    show_probes: &mut bool,
+) {
+    imgui::Window::new(imgui::im_str!("scene")).build(ui, || {
+        ui.checkbox(imgui::im_str!("stats"), show_stats);
+        ui.checkbox(imgui::im_str!("grid"), show_grid);
+        ui.checkbox(imgui::im_str!("probes"), show_probes);
+    });
+}
+
+// Names `set_tweakable` accepts.
+const TWEAKABLES: &[&str] = &[
+    "fog.start",
+    "fog.end",
+    "fog.density",
+    "motion_blur.samples",
+    "motion_blur.shutter",
+    "emitter.x",
+    "emitter.y",
+    "emitter.z",
+    "emitter.rate",
+    "emitter.lifetime",
+    "emitter.spread",
+    "emitter.size",
+];
+
+fn set_tweakable(
+    state: &mut Snapshot,
+    name: &str,
+    value: f32,
+) -> Result<(), String> {
+    match name {
+        "fog.start" => state.fog.start = value,
+        "fog.end" => state.fog.end = value,
+        "fog.density" => state.fog.density = value,
+        "motion_blur.samples" => state.motion_blur.samples = value as u32,
+        "motion_blur.shutter" => state.motion_blur.shutter = value,
+        "emitter.x" => state.emitter.origin[0] = value,
+        "emitter.y" => state.emitter.origin[1] = value,
+        "emitter.z" => state.emitter.origin[2] = value,
+        "emitter.rate" => state.emitter.rate = value,
+        "emitter.lifetime" => state.emitter.lifetime = value,
+        "emitter.spread" => state.emitter.spread = value,
+        "emitter.size" => state.emitter.size = value,
+        _ => return Err(format!("unknown tweakable {}", name)),
+    }
+    Ok(())
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+fn parsed_arg<T: FromStr>(name: &str) -> Result<Option<T>, Error> {
+    arg_value(name)
+        .map(|value| value.parse().map_err(|_| Error::argument(name, &value)))
+        .transpose()
+}
+
+// "x,y" in screen pixels.
+// Rebuilds the pipelines whose sources changed, between frames. A shader
+// that fails to compile leaves the previous pipelines in place.
+#[cfg(feature = "hot-reload")]
+fn reload_shaders(
+    watcher: Option<&mut ShaderWatcher>,
+    device: &Arc<Device>,
+    passes: &mut Passes,
+) {
+    let watcher = match watcher {
+        Some(watcher) => watcher,
+        None => return,
     };
-    dynamic_state.viewports = Some(vec![viewport]);
-
-    images
-        .iter()
-        .map(|image| {
-            Arc::new(
-                Framebuffer::start(render_pass.clone())
-                    .add(image.clone())
-                    .unwrap()
-                    .build()
-                    .unwrap(),
-            ) as Arc<dyn FramebufferAbstract + Send + Sync>
-        })
-        .collect::<Vec<_>>()
+    let changed = watcher.changed();
+    let touches = |sources: &[&str]| {
+        changed.iter().any(|name| sources.contains(&name.as_str()))
+    };
+    if touches(&dbgpipe::SOURCES) {
+        match dbgpipe::Modules::compile(device.clone(), watcher)
+            .and_then(|modules| passes.debug.reload(device.clone(), &modules))
+        {
+            Ok(()) => info!("reloaded debug shaders"),
+            Err(e) => error!("{}", e),
+        }
+    }
+    if touches(&bmptxtpipe::SOURCES) {
+        match bmptxtpipe::Modules::compile(device.clone(), watcher).and_then(
+            |modules| passes.inspector.0.reload(device.clone(), &modules),
+        ) {
+            Ok(()) => info!("reloaded text shaders"),
+            Err(e) => error!("{}", e),
+        }
+    }
+}
+
+fn parse_position(text: &str) -> Option<[i32; 2]> {
+    let mut coordinates = text.split(',').map(|c| c.trim().parse().ok());
+    Some([coordinates.next()??, coordinates.next()??])
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    let elapsed = start.elapsed();
+    elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_micros() as f64 / 1000.0
 }
@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use vulkano::buffer::BufferAccess;
+use vulkano::format::Format;
+use vulkano::image::ImageAccess;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    Vertex,
+    Texture,
+    Attachment,
+    Uniform,
+    Storage,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Total {
+    pub bytes: u64,
+    pub count: usize,
+}
+
+struct Entry {
+    category: Category,
+    name: String,
+    bytes: u64,
+    alive: Box<dyn Fn() -> bool + Send>,
+}
+
+// Accounts GPU allocations by category. Each tracked resource is held
+// weakly, so its bytes stop counting once the last reference is dropped,
+// e.g. when window-sized attachments are recreated.
+#[derive(Default)]
+pub struct Tracker {
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl Tracker {
+    pub fn new() -> Tracker {
+        Tracker::default()
+    }
+
+    pub fn track<R: Send + Sync + 'static>(
+        &self,
+        category: Category,
+        name: &str,
+        resource: &Arc<R>,
+        bytes: u64,
+    ) {
+        let weak: Weak<R> = Arc::downgrade(resource);
+        self.entries.lock().unwrap().push(Entry {
+            category,
+            name: name.to_owned(),
+            bytes,
+            alive: Box::new(move || weak.strong_count() > 0),
+        });
+    }
+
+    pub fn track_buffer<B: BufferAccess + 'static>(
+        &self,
+        category: Category,
+        name: &str,
+        buffer: &Arc<B>,
+    ) {
+        self.track(category, name, buffer, buffer.size() as u64);
+    }
+
+    pub fn track_image<I: ImageAccess + Send + Sync + 'static>(
+        &self,
+        category: Category,
+        name: &str,
+        image: &Arc<I>,
+    ) {
+        let dimensions = image.dimensions();
+        let bytes = image_bytes(
+            [dimensions.width(), dimensions.height()],
+            dimensions.array_layers() * image.samples(),
+            image.format(),
+        );
+        self.track(category, name, image, bytes);
+    }
+
+    pub fn totals(&self) -> BTreeMap<Category, Total> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|entry| (entry.alive)());
+        let mut totals = BTreeMap::new();
+        for entry in entries.iter() {
+            let total: &mut Total = totals.entry(entry.category).or_default();
+            total.bytes += entry.bytes;
+            total.count += 1;
+        }
+        totals
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.totals().values().map(|total| total.bytes).sum()
+    }
+
+    // Per-category totals followed by every live allocation, largest
+    // first.
+    pub fn dump_memory_report(&self) -> String {
+        let totals = self.totals();
+        let sum: u64 = totals.values().map(|total| total.bytes).sum();
+        let mut report = String::new();
+        for (category, total) in &totals {
+            writeln!(
+                report,
+                "{:?}: {:.2} MB in {}",
+                category,
+                mb(total.bytes),
+                total.count
+            )
+            .unwrap();
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.bytes));
+        for entry in entries.iter() {
+            writeln!(
+                report,
+                "  {:>9.2} MB {:?} {}",
+                mb(entry.bytes),
+                entry.category,
+                entry.name
+            )
+            .unwrap();
+        }
+        write!(report, "total: {:.2} MB", mb(sum)).unwrap();
+        report
+    }
+}
+
+// Tightly packed size, with multisampled images counting each sample as a
+// layer; drivers may pad, so this is a lower bound.
+pub fn image_bytes(dimensions: [u32; 2], layers: u32, format: Format) -> u64 {
+    let texel = format.size().unwrap_or(4) as u64;
+    dimensions[0] as u64 * dimensions[1] as u64 * layers as u64 * texel
+}
+
+pub fn mb(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
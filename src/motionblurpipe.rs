@@ -0,0 +1,189 @@
+use crate::error::Error;
+use crate::error::Result;
+use crate::fullscreen;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::ClearValue;
+use vulkano::format::Format;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::vertex::BufferlessDefinition;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::sampler::Sampler;
+
+pub const MAX_SAMPLES: u32 = 32;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MotionBlur {
+    pub enabled: bool,
+    pub samples: u32,
+    pub shutter: f32,
+}
+
+impl Default for MotionBlur {
+    fn default() -> Self {
+        MotionBlur {
+            enabled: false,
+            samples: 8,
+            shutter: 0.5,
+        }
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+layout (location = 0) in vec2 uv;
+
+layout (set = 0, binding = 0) uniform sampler2D color;
+layout (set = 0, binding = 1) uniform sampler2D velocity;
+
+// shutter: fraction of the frame interval the shutter stays open
+layout (push_constant) uniform Blur {
+    uint samples;
+    float shutter;
+} blur;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    vec2 step = texture(velocity, uv).xy * 0.5 * blur.shutter;
+    if (blur.samples < 2 || dot(step, step) == 0.0) {
+        f_color = texture(color, uv);
+        return;
+    }
+
+    vec4 sum = vec4(0.0);
+    for (uint i = 0; i < blur.samples; i++) {
+        float t = float(i) / float(blur.samples - 1) - 0.5;
+        sum += texture(color, uv + step * t);
+    }
+    f_color = sum / float(blur.samples);
+}
+"
+    }
+}
+
+pub struct Pipeline {
+    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+pub struct Targets {
+    pub framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    pub set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub output: Arc<AttachmentImage>,
+}
+
+pub fn build(device: Arc<Device>, format: Format) -> Result<Pipeline> {
+    let vs = fullscreen::vs::Shader::load(device.clone())
+        .map_err(Error::shader("fullscreen vertex"))?;
+    let fs = fs::Shader::load(device.clone())
+        .map_err(Error::shader("motion blur fragment"))?;
+
+    let render_pass = Arc::new(
+        vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                output: {
+                    load: DontCare,
+                    store: Store,
+                    format: format,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [output],
+                depth_stencil: {}
+            }
+        )
+        .map_err(Error::render_pass("motion blur"))?,
+    );
+
+    let pipeline = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition)
+            .vertex_shader(vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(fs.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("motion blur"))?,
+    );
+
+    Ok(Pipeline {
+        render_pass,
+        pipeline,
+    })
+}
+
+pub fn targets(
+    pipeline: &Pipeline,
+    device: Arc<Device>,
+    color: Arc<AttachmentImage>,
+    velocity: Arc<AttachmentImage>,
+    linear: Arc<Sampler>,
+    nearest: Arc<Sampler>,
+) -> Result<Targets> {
+    let output =
+        AttachmentImage::sampled(device, color.dimensions(), color.format())
+            .map_err(Error::image("motion blur"))?;
+
+    let framebuffer = Arc::new(
+        Framebuffer::start(pipeline.render_pass.clone())
+            .add(output.clone())?
+            .build()?,
+    );
+
+    let set = Arc::new(
+        PersistentDescriptorSet::start(pipeline.pipeline.clone(), 0)
+            .add_sampled_image(color, linear)?
+            .add_sampled_image(velocity, nearest)?
+            .build()?,
+    );
+
+    Ok(Targets {
+        framebuffer,
+        set,
+        output,
+    })
+}
+
+pub fn draw(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &Pipeline,
+    targets: &Targets,
+    dynamic_state: &DynamicState,
+    settings: &MotionBlur,
+) -> Result<AutoCommandBufferBuilder> {
+    let builder = builder
+        .begin_render_pass(
+            targets.framebuffer.clone(),
+            false,
+            vec![ClearValue::None],
+        )?
+        .draw(
+            pipeline.pipeline.clone(),
+            dynamic_state,
+            fullscreen::vertices(),
+            vec![targets.set.clone()],
+            fs::ty::Blur {
+                samples: settings.samples.min(MAX_SAMPLES),
+                shutter: settings.shutter,
+            },
+        )?
+        .end_render_pass()?;
+    Ok(builder)
+}
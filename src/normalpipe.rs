@@ -0,0 +1,167 @@
+use crate::dbgpipe;
+use crate::dbgpipe::Vertex;
+use cgmath::Matrix4;
+use cgmath::SquareMatrix;
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::device::Device;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+pub const FACE: u32 = 1;
+pub const VERTEX: u32 = 2;
+pub const LENGTH: f32 = 0.2;
+
+pub mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+layout (location = 0) in vec4 position;
+
+layout (location = 0) out vec4 v_position;
+
+void main() {
+    v_position = position;
+}"
+    }
+}
+
+// The meshes carry no normal attribute, so vertex normals are the face
+// normal at each corner, as the mesh is shaded.
+pub mod gs {
+    vulkano_shaders::shader! {
+        ty: "geometry",
+        src: "
+#version 450
+
+layout (triangles) in;
+layout (line_strip, max_vertices = 8) out;
+
+layout (location = 0) in vec4 v_position[];
+layout (location = 0) out vec4 g_color;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+// mode: bitmask of normalpipe::FACE and normalpipe::VERTEX
+layout (push_constant) uniform Push {
+    mat4 model;
+    float length;
+    uint mode;
+} push;
+
+void line(vec4 from, vec3 direction, vec4 color) {
+    g_color = color;
+    gl_Position = vp_inst.vp * from;
+    EmitVertex();
+    gl_Position = vp_inst.vp * (from + vec4(direction * push.length, 0.0));
+    EmitVertex();
+    EndPrimitive();
+}
+
+void main() {
+    vec4 p0 = push.model * v_position[0];
+    vec4 p1 = push.model * v_position[1];
+    vec4 p2 = push.model * v_position[2];
+    vec3 normal = normalize(cross(p1.xyz - p0.xyz, p2.xyz - p0.xyz));
+
+    if ((push.mode & 1u) != 0) {
+        line((p0 + p1 + p2) / 3.0, normal, vec4(1.0, 1.0, 0.0, 1.0));
+    }
+    if ((push.mode & 2u) != 0) {
+        line(p0, normal, vec4(0.0, 1.0, 1.0, 1.0));
+        line(p1, normal, vec4(0.0, 1.0, 1.0, 1.0));
+        line(p2, normal, vec4(0.0, 1.0, 1.0, 1.0));
+    }
+}"
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+
+layout (location = 0) in vec4 g_color;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    f_color = g_color;
+}
+"
+    }
+}
+
+pub struct Pipeline {
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+impl Pipeline {
+    // None when the device lacks geometryShader.
+    pub fn new(
+        device: Arc<Device>,
+        scene: &dbgpipe::Pipeline,
+    ) -> Option<Pipeline> {
+        if !device.enabled_features().geometry_shader {
+            return None;
+        }
+        let vs = vs::Shader::load(device.clone()).unwrap();
+        let gs = gs::Shader::load(device.clone()).unwrap();
+        let fs = fs::Shader::load(device.clone()).unwrap();
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(vs.main_entry_point(), ())
+                .geometry_shader(gs.main_entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil_simple_depth()
+                .fragment_shader(fs.main_entry_point(), ())
+                .render_pass(
+                    Subpass::from(scene.render_pass.clone(), 0).unwrap(),
+                )
+                .build(device)
+                .unwrap(),
+        );
+
+        Some(Pipeline { pipeline })
+    }
+
+    pub fn draw(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        dynamic_state: &DynamicState,
+        vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+        view_set: Arc<dyn DescriptorSet + Send + Sync>,
+        mode: u32,
+    ) -> AutoCommandBufferBuilder {
+        builder
+            .draw(
+                self.pipeline.clone(),
+                dynamic_state,
+                vec![vertex_buffer],
+                vec![view_set],
+                gs::ty::Push {
+                    model: Matrix4::identity().into(),
+                    length: LENGTH,
+                    mode,
+                },
+            )
+            .unwrap()
+    }
+}
+
+// Off, face, vertex, both, off, ...
+pub fn next_mode(mode: u32) -> u32 {
+    (mode + 1) % ((FACE | VERTEX) + 1)
+}
@@ -0,0 +1,165 @@
+use crate::dbgpipe;
+use crate::dbgpipe::Vertex;
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::buffer::CpuBufferPool;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::depth_stencil::Compare;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+// Must match the array sizes in OBJECT_BLOCK; 128 objects stay under the
+// 16 KiB maxUniformBufferRange every implementation guarantees.
+pub const MAX_OBJECTS: usize = 128;
+
+pub mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+layout (location = 0) in vec4 position;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+layout (set = 1, binding = 0) uniform OBJECT_BLOCK {
+    mat4 model[128];
+    vec4 color[128];
+} objects;
+
+layout (push_constant) uniform Push {
+    uint index;
+} push;
+
+layout (location = 0) out vec4 v_color;
+
+void main() {
+    v_color = objects.color[push.index];
+    gl_Position = vp_inst.vp * objects.model[push.index] * position;
+}"
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+
+layout (location = 0) in vec4 v_color;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    f_color = v_color;
+}
+"
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Object {
+    pub model: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
+// Draws many objects that share a vertex buffer with a single per-frame
+// descriptor set; each draw selects its slot in the packed uniform block
+// by index instead of binding its own set.
+pub struct Pipeline {
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pool: CpuBufferPool<vs::ty::OBJECT_BLOCK>,
+}
+
+impl Pipeline {
+    pub fn new(device: Arc<Device>, scene: &dbgpipe::Pipeline) -> Pipeline {
+        let vs = vs::Shader::load(device.clone()).unwrap();
+        let fs = fs::Shader::load(device.clone()).unwrap();
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(vs.main_entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil(DepthStencil {
+                    depth_write: false,
+                    depth_compare: Compare::Less,
+                    ..DepthStencil::simple_depth_test()
+                })
+                .blend_alpha_blending()
+                .fragment_shader(fs.main_entry_point(), ())
+                .render_pass(
+                    Subpass::from(scene.render_pass.clone(), 0).unwrap(),
+                )
+                .build(device.clone())
+                .unwrap(),
+        );
+
+        Pipeline {
+            pipeline,
+            pool: CpuBufferPool::uniform_buffer(device),
+        }
+    }
+
+    // Packs this frame's objects into one uniform buffer chunk. Objects
+    // past MAX_OBJECTS are dropped.
+    pub fn frame_set(
+        &self,
+        objects: &[Object],
+    ) -> Arc<dyn DescriptorSet + Send + Sync> {
+        let mut block = vs::ty::OBJECT_BLOCK {
+            model: [[[0.0; 4]; 4]; MAX_OBJECTS],
+            color: [[0.0; 4]; MAX_OBJECTS],
+        };
+        for ((model, color), object) in block
+            .model
+            .iter_mut()
+            .zip(block.color.iter_mut())
+            .zip(objects.iter())
+        {
+            *model = object.model;
+            *color = object.color;
+        }
+        Arc::new(
+            PersistentDescriptorSet::start(self.pipeline.clone(), 1)
+                .add_buffer(self.pool.next(block).unwrap())
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+    }
+
+    pub fn draw(
+        &self,
+        mut builder: AutoCommandBufferBuilder,
+        dynamic_state: &DynamicState,
+        vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+        view_set: Arc<dyn DescriptorSet + Send + Sync>,
+        object_set: Arc<dyn DescriptorSet + Send + Sync>,
+        count: usize,
+    ) -> AutoCommandBufferBuilder {
+        for index in 0..count.min(MAX_OBJECTS) {
+            builder = builder
+                .draw(
+                    self.pipeline.clone(),
+                    dynamic_state,
+                    vec![vertex_buffer.clone()],
+                    vec![view_set.clone(), object_set.clone()],
+                    vs::ty::Push {
+                        index: index as u32,
+                    },
+                )
+                .unwrap();
+        }
+        builder
+    }
+}
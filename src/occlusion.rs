@@ -0,0 +1,241 @@
+use crate::arena::Allocation;
+use crate::arena::Arena;
+use crate::culling::Object;
+use crate::dbgpipe;
+use crate::dbgpipe::Vertex;
+use cgmath::Matrix4;
+use cgmath::SquareMatrix;
+use cgmath::Vector3;
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::blend::AttachmentBlend;
+use vulkano::pipeline::depth_stencil::Compare;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ProxyPush {
+    pub model: [[f32; 4]; 4],
+    pub index: u32,
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+
+layout (early_fragment_tests) in;
+
+layout (push_constant) uniform Push {
+    layout (offset = 64) uint index;
+} push;
+
+layout (set = 1, binding = 0) buffer Visibility {
+    uint visible[];
+} visibility;
+
+void main() {
+    visibility.visible[push.index] = 1;
+}
+"
+    }
+}
+
+// Unit cube as a triangle list, scaled per object to its bounding sphere.
+fn cube() -> Vec<Vertex> {
+    let corner = |i: usize| Vertex {
+        position: [
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { -1.0 } else { 1.0 },
+            1.0,
+        ],
+    };
+    let faces = [
+        [0, 1, 3, 2],
+        [4, 6, 7, 5],
+        [0, 4, 5, 1],
+        [2, 3, 7, 6],
+        [0, 2, 6, 4],
+        [1, 5, 7, 3],
+    ];
+    faces
+        .iter()
+        .flat_map(|f| vec![f[0], f[1], f[2], f[0], f[2], f[3]])
+        .map(corner)
+        .collect()
+}
+
+// Proxy boxes are drawn after the opaque geometry with depth testing and
+// no writes; any fragment that survives marks its object visible. Results
+// are read back on the next frame, so visibility lags by one frame.
+pub struct Occlusion {
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pub proxy: Arc<CpuAccessibleBuffer<[Vertex]>>,
+    pub visibility: Arc<CpuAccessibleBuffer<[u32]>>,
+    pub visibility_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub objects: Vec<Object>,
+    visible: Vec<bool>,
+}
+
+impl Occlusion {
+    pub fn new(
+        device: Arc<Device>,
+        scene: &dbgpipe::Pipeline,
+        objects: Vec<Object>,
+    ) -> Occlusion {
+        let vs = dbgpipe::vs::Shader::load(device.clone()).unwrap();
+        let fs = fs::Shader::load(device.clone()).unwrap();
+
+        let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> =
+            scene.render_pass.clone();
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(vs.main_entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil(DepthStencil {
+                    depth_write: false,
+                    depth_compare: Compare::LessOrEqual,
+                    ..DepthStencil::simple_depth_test()
+                })
+                .blend_collective(AttachmentBlend::ignore_source())
+                .fragment_shader(fs.main_entry_point(), ())
+                .render_pass(Subpass::from(render_pass, 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+
+        let proxy = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::vertex_buffer(),
+            cube().into_iter(),
+        )
+        .unwrap();
+        let visibility = CpuAccessibleBuffer::from_iter(
+            device,
+            BufferUsage {
+                storage_buffer: true,
+                transfer_destination: true,
+                ..BufferUsage::none()
+            },
+            objects.iter().map(|_| 1u32),
+        )
+        .unwrap();
+        let visibility_set = Arc::new(
+            PersistentDescriptorSet::start(pipeline.clone(), 1)
+                .add_buffer(visibility.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        Occlusion {
+            pipeline,
+            proxy,
+            visibility,
+            visibility_set,
+            visible: vec![true; objects.len()],
+            objects,
+        }
+    }
+
+    // Call once the frame that wrote the buffer has finished.
+    pub fn read_back(&mut self) {
+        if let Ok(results) = self.visibility.read() {
+            for (visible, &result) in
+                self.visible.iter_mut().zip(results.iter())
+            {
+                *visible = result != 0;
+            }
+        }
+    }
+
+    pub fn visible_count(&self) -> usize {
+        self.visible.iter().filter(|&&visible| visible).count()
+    }
+
+    // Must be recorded outside a render pass, before `draw_proxies`.
+    pub fn reset(
+        &self,
+        builder: AutoCommandBufferBuilder,
+    ) -> AutoCommandBufferBuilder {
+        builder.fill_buffer(self.visibility.clone(), 0).unwrap()
+    }
+
+    pub fn draw_proxies(
+        &self,
+        mut builder: AutoCommandBufferBuilder,
+        dynamic_state: &DynamicState,
+        view_set: Arc<dyn DescriptorSet + Send + Sync>,
+    ) -> AutoCommandBufferBuilder {
+        for (index, object) in self.objects.iter().enumerate() {
+            let [x, y, z, radius] = object.sphere;
+            let model = Matrix4::from_translation(Vector3::new(x, y, z))
+                * Matrix4::from_scale(radius.max(1e-4));
+            builder = builder
+                .draw(
+                    self.pipeline.clone(),
+                    dynamic_state,
+                    vec![self.proxy.clone()],
+                    (view_set.clone(), self.visibility_set.clone()),
+                    ProxyPush {
+                        model: model.into(),
+                        index: index as u32,
+                    },
+                )
+                .unwrap();
+        }
+        builder
+    }
+
+    // Draws the objects that passed last frame's test, each from its range
+    // of the mesh stored at `mesh` in `arena`.
+    pub fn draw_visible(
+        &self,
+        mut builder: AutoCommandBufferBuilder,
+        pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        dynamic_state: &DynamicState,
+        arena: &Arena<Vertex>,
+        mesh: Allocation,
+        view_set: Arc<dyn DescriptorSet + Send + Sync>,
+    ) -> AutoCommandBufferBuilder {
+        let visible_objects = self
+            .objects
+            .iter()
+            .zip(self.visible.iter())
+            .filter(|&(_, &visible)| visible);
+        for (object, _) in visible_objects {
+            let range = Allocation {
+                offset: mesh.offset + object.first_vertex as usize,
+                len: object.vertex_count as usize,
+            };
+            builder = builder
+                .draw(
+                    pipeline.clone(),
+                    dynamic_state,
+                    vec![Arc::new(arena.slice(range))
+                        as Arc<dyn BufferAccess + Send + Sync>],
+                    vec![view_set.clone()],
+                    dbgpipe::vs::ty::Push {
+                        model: Matrix4::identity().into(),
+                    },
+                )
+                .unwrap();
+        }
+        builder
+    }
+}
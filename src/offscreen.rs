@@ -0,0 +1,133 @@
+use image::RgbaImage;
+use std::sync::Arc;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::command_buffer::DynamicState;
+use vulkano::device::Device;
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::image::AttachmentImage;
+use vulkano::image::ImageUsage;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::sync::GpuFuture;
+
+pub const FORMAT: Format = Format::R8G8B8A8Srgb;
+
+// A color target in place of the swapchain, with a host-visible buffer the
+// finished frame is copied into. Needs no surface, so it works in CI and
+// for rendering straight to an image file.
+pub struct Offscreen {
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+    pub image: Arc<AttachmentImage>,
+    pub dimensions: [u32; 2],
+    readback: Arc<CpuAccessibleBuffer<[u8]>>,
+}
+
+impl Offscreen {
+    pub fn new(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        dimensions: [u32; 2],
+    ) -> Offscreen {
+        let image = AttachmentImage::with_usage(
+            device.clone(),
+            dimensions,
+            FORMAT,
+            ImageUsage {
+                color_attachment: true,
+                transfer_source: true,
+                ..ImageUsage::none()
+            },
+        )
+        .unwrap();
+        let readback = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_destination(),
+            (0..dimensions[0] * dimensions[1] * 4).map(|_| 0u8),
+        )
+        .unwrap();
+        Offscreen {
+            device,
+            queue,
+            image,
+            dimensions,
+            readback,
+        }
+    }
+
+    // The color image as the first attachment, with an optional depth
+    // attachment as the second, matching `Renderer::framebuffers`.
+    pub fn framebuffer(
+        &self,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+        depth_format: Option<Format>,
+    ) -> Arc<dyn FramebufferAbstract + Send + Sync> {
+        let framebuffer = Framebuffer::start(render_pass)
+            .add(self.image.clone())
+            .unwrap();
+        match depth_format {
+            Some(format) => {
+                let depth = AttachmentImage::transient(
+                    self.device.clone(),
+                    self.dimensions,
+                    format,
+                )
+                .unwrap();
+                Arc::new(framebuffer.add(depth).unwrap().build().unwrap())
+            }
+            None => Arc::new(framebuffer.build().unwrap()),
+        }
+    }
+
+    pub fn dynamic_state(&self) -> DynamicState {
+        DynamicState {
+            line_width: None,
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [
+                    self.dimensions[0] as f32,
+                    self.dimensions[1] as f32,
+                ],
+                depth_range: 0.0..1.0,
+            }]),
+            scissors: None,
+        }
+    }
+
+    // Records the frame through `record`, copies the color image back and
+    // waits for it. The image is already RGBA; sRGB encoding is kept as
+    // stored, which is what PNG expects.
+    pub fn render<F>(&self, record: F) -> RgbaImage
+    where
+        F: FnOnce(AutoCommandBufferBuilder) -> AutoCommandBufferBuilder,
+    {
+        let builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            self.queue.family(),
+        )
+        .unwrap();
+        let command_buffer = record(builder)
+            .copy_image_to_buffer(self.image.clone(), self.readback.clone())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        command_buffer
+            .execute(self.queue.clone())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let data = self.readback.read().unwrap().to_vec();
+        RgbaImage::from_raw(self.dimensions[0], self.dimensions[1], data)
+            .unwrap()
+    }
+}
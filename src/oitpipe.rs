@@ -0,0 +1,307 @@
+use crate::dbgpipe;
+use crate::dbgpipe::TransparentPush;
+use crate::dbgpipe::Vertex;
+use crate::error::Error;
+use crate::error::Result;
+use crate::fullscreen;
+use crate::transparent::Instance;
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::ClearValue;
+use vulkano::format::Format;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::blend::AttachmentBlend;
+use vulkano::pipeline::blend::BlendFactor;
+use vulkano::pipeline::blend::BlendOp;
+use vulkano::pipeline::depth_stencil::Compare;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::vertex::BufferlessDefinition;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::swapchain::Swapchain;
+use winit::window::Window;
+
+pub const ACCUM_FORMAT: Format = Format::R16G16B16A16Sfloat;
+pub const REVEAL_FORMAT: Format = Format::R8Unorm;
+
+pub mod accum_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+
+layout (push_constant) uniform Push {
+    layout (offset = 64) vec4 color;
+} push;
+
+layout (location = 0) out vec4 f_accum;
+layout (location = 1) out float f_reveal;
+
+void main() {
+    vec4 color = push.color;
+    float depth = 1.0 - gl_FragCoord.z * 0.9;
+    float weight = clamp(
+        pow(min(1.0, color.a * 10.0) + 0.01, 3.0) * 1e8 * pow(depth, 3.0),
+        1e-2,
+        3e3);
+
+    f_accum = vec4(color.rgb * color.a, color.a) * weight;
+    f_reveal = color.a;
+}
+"
+    }
+}
+
+pub mod composite_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+layout (location = 0) in vec2 uv;
+
+layout (input_attachment_index = 0, set = 0, binding = 0)
+    uniform subpassInput u_accum;
+layout (input_attachment_index = 1, set = 0, binding = 1)
+    uniform subpassInput u_reveal;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    float reveal = subpassLoad(u_reveal).r;
+    if (reveal >= 1.0) {
+        discard;
+    }
+
+    vec4 accum = subpassLoad(u_accum);
+    f_color = vec4(accum.rgb / max(accum.a, 1e-5), 1.0 - reveal);
+}
+"
+    }
+}
+
+pub struct Pipeline {
+    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub accumulate: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pub composite: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+pub struct Targets {
+    pub framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    pub composite_set: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+fn blend(source: BlendFactor, destination: BlendFactor) -> AttachmentBlend {
+    AttachmentBlend {
+        enabled: true,
+        color_op: BlendOp::Add,
+        color_source: source,
+        color_destination: destination,
+        alpha_op: BlendOp::Add,
+        alpha_source: source,
+        alpha_destination: destination,
+        mask_red: true,
+        mask_green: true,
+        mask_blue: true,
+        mask_alpha: true,
+    }
+}
+
+pub fn build(
+    device: Arc<Device>,
+    swapchain: Arc<Swapchain<Window>>,
+) -> Result<Pipeline> {
+    let vs = dbgpipe::vs::Shader::load(device.clone())
+        .map_err(Error::shader("scene vertex"))?;
+    let accum_fs = accum_fs::Shader::load(device.clone())
+        .map_err(Error::shader("OIT accumulate fragment"))?;
+    let composite_vs = fullscreen::vs::Shader::load(device.clone())
+        .map_err(Error::shader("fullscreen vertex"))?;
+    let composite_fs = composite_fs::Shader::load(device.clone())
+        .map_err(Error::shader("OIT composite fragment"))?;
+
+    let render_pass = Arc::new(
+        vulkano::ordered_passes_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Load,
+                    store: Store,
+                    format: swapchain.format(),
+                    samples: 1,
+                },
+                depth: {
+                    load: Load,
+                    store: DontCare,
+                    format: dbgpipe::DEPTH_FORMAT,
+                    samples: 1,
+                },
+                accum: {
+                    load: Clear,
+                    store: DontCare,
+                    format: ACCUM_FORMAT,
+                    samples: 1,
+                },
+                reveal: {
+                    load: Clear,
+                    store: DontCare,
+                    format: REVEAL_FORMAT,
+                    samples: 1,
+                }
+            },
+            passes: [
+                {
+                    color: [accum, reveal],
+                    depth_stencil: {depth},
+                    input: []
+                },
+                {
+                    color: [color],
+                    depth_stencil: {},
+                    input: [accum, reveal]
+                }
+            ]
+        )
+        .map_err(Error::render_pass("OIT"))?,
+    );
+
+    let accumulate = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Vertex>()
+            .vertex_shader(vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .depth_stencil(DepthStencil {
+                depth_write: false,
+                depth_compare: Compare::Less,
+                ..DepthStencil::simple_depth_test()
+            })
+            .blend_individual(
+                vec![
+                    blend(BlendFactor::One, BlendFactor::One),
+                    blend(BlendFactor::Zero, BlendFactor::OneMinusSrcColor),
+                ]
+                .into_iter(),
+            )
+            .fragment_shader(accum_fs.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("OIT accumulate"))?,
+    );
+
+    let composite = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition)
+            .vertex_shader(composite_vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .blend_alpha_blending()
+            .fragment_shader(composite_fs.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 1).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("OIT composite"))?,
+    );
+
+    Ok(Pipeline {
+        render_pass,
+        accumulate,
+        composite,
+    })
+}
+
+pub fn targets(
+    pipeline: &Pipeline,
+    device: Arc<Device>,
+    color: Arc<AttachmentImage>,
+    depth: Arc<AttachmentImage>,
+) -> Result<Targets> {
+    let dimensions = color.dimensions();
+    let accum = AttachmentImage::transient_input_attachment(
+        device.clone(),
+        dimensions,
+        ACCUM_FORMAT,
+    )
+    .map_err(Error::image("OIT accumulation"))?;
+    let reveal = AttachmentImage::transient_input_attachment(
+        device.clone(),
+        dimensions,
+        REVEAL_FORMAT,
+    )
+    .map_err(Error::image("OIT revealage"))?;
+
+    let framebuffer = Arc::new(
+        Framebuffer::start(pipeline.render_pass.clone())
+            .add(color)?
+            .add(depth)?
+            .add(accum.clone())?
+            .add(reveal.clone())?
+            .build()?,
+    );
+
+    let composite_set = Arc::new(
+        PersistentDescriptorSet::start(pipeline.composite.clone(), 0)
+            .add_image(accum)?
+            .add_image(reveal)?
+            .build()?,
+    );
+
+    Ok(Targets {
+        framebuffer,
+        composite_set,
+    })
+}
+
+pub fn draw(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &Pipeline,
+    targets: &Targets,
+    dynamic_state: &DynamicState,
+    vertex_buffers: Vec<Arc<dyn BufferAccess + Send + Sync>>,
+    view_set: Arc<dyn DescriptorSet + Send + Sync>,
+    instances: &[Instance],
+) -> Result<AutoCommandBufferBuilder> {
+    let clear_values = vec![
+        ClearValue::None,
+        ClearValue::None,
+        [0.0, 0.0, 0.0, 0.0].into(),
+        [1.0, 0.0, 0.0, 0.0].into(),
+    ];
+
+    let mut builder = builder.begin_render_pass(
+        targets.framebuffer.clone(),
+        false,
+        clear_values,
+    )?;
+    for instance in instances {
+        builder = builder.draw(
+            pipeline.accumulate.clone(),
+            dynamic_state,
+            vertex_buffers.clone(),
+            vec![view_set.clone()],
+            TransparentPush {
+                model: instance.model().into(),
+                color: instance.color,
+            },
+        )?;
+    }
+
+    let builder = builder
+        .next_subpass(false)?
+        .draw(
+            pipeline.composite.clone(),
+            dynamic_state,
+            fullscreen::vertices(),
+            vec![targets.composite_set.clone()],
+            (),
+        )?
+        .end_render_pass()?;
+    Ok(builder)
+}
@@ -0,0 +1,88 @@
+use crate::dbgpipe;
+use crate::dbgpipe::Vertex;
+use std::sync::Arc;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::blend::AttachmentBlend;
+use vulkano::pipeline::blend::BlendFactor;
+use vulkano::pipeline::blend::BlendOp;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+pub const FORMAT: Format = Format::R16Sfloat;
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+
+layout (location = 0) out float f_count;
+
+void main() {
+    f_count = 1.0;
+}
+"
+    }
+}
+
+pub struct Pipeline {
+    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+pub fn build(device: Arc<Device>) -> Pipeline {
+    let vs = dbgpipe::vs::Shader::load(device.clone()).unwrap();
+    let fs = fs::Shader::load(device.clone()).unwrap();
+
+    let render_pass = Arc::new(
+        vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                count: {
+                    load: Clear,
+                    store: Store,
+                    format: FORMAT,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [count],
+                depth_stencil: {}
+            }
+        )
+        .unwrap(),
+    );
+
+    let pipeline = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Vertex>()
+            .vertex_shader(vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .blend_collective(AttachmentBlend {
+                enabled: true,
+                color_op: BlendOp::Add,
+                color_source: BlendFactor::One,
+                color_destination: BlendFactor::One,
+                alpha_op: BlendOp::Add,
+                alpha_source: BlendFactor::One,
+                alpha_destination: BlendFactor::One,
+                mask_red: true,
+                mask_green: true,
+                mask_blue: true,
+                mask_alpha: true,
+            })
+            .fragment_shader(fs.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .unwrap(),
+    );
+
+    Pipeline {
+        render_pass,
+        pipeline,
+    }
+}
@@ -0,0 +1,90 @@
+use crate::bmpfont;
+use crate::bmptxtpipe::Vertex;
+use crate::culling::CullStats;
+use crate::lod::LodStats;
+use crate::memory;
+
+// Pixels per font atlas texel before the window's scale factor.
+pub const SCALE: f32 = 3.0;
+
+// Figures from the most recently finished frame. Per-pass GPU times are
+// drawn from the profiler's report beside these.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    pub fps: f64,
+    pub frame_ms: f64,
+    pub record_ms: f64,
+    // The CPU's wait on the frame's fence.
+    pub gpu_ms: f64,
+    // The frame's GPU time from timestamp queries, a few frames behind.
+    // None where the device can't write timestamps.
+    pub gpu_frame_ms: Option<f64>,
+    pub draws: u32,
+    pub triangles: u64,
+    pub memory_bytes: u64,
+    pub cull: Option<CullStats>,
+    // Meshes drawn at each level of detail, from full detail down.
+    pub lod: Option<LodStats>,
+}
+
+impl Stats {
+    pub fn text(&self) -> String {
+        let mut text = format!(
+            "fps: {:.1}\nframe: {:.2} ms\ncpu record: {:.2} ms\n\
+             gpu wait: {:.2} ms\ndraws: {}\ntriangles: {}\nmemory: {:.1} mb",
+            self.fps,
+            self.frame_ms,
+            self.record_ms,
+            self.gpu_ms,
+            self.draws,
+            self.triangles,
+            memory::mb(self.memory_bytes)
+        );
+        if let Some(gpu_frame_ms) = self.gpu_frame_ms {
+            text += &format!("\ngpu frame: {:.2} ms", gpu_frame_ms);
+        }
+        if let Some(cull) = self.cull {
+            text += &format!(
+                "\nculled: {}/{} ({} draws)",
+                cull.culled, cull.objects, cull.draws
+            );
+        }
+        if let Some(lod) = self.lod {
+            text += &format!("\nlod: {}", lod.text());
+        }
+        text
+    }
+}
+
+// The stats when they're shown, then any budget warnings, which are shown
+// regardless. None when there's nothing to draw.
+pub fn text(
+    show_stats: bool,
+    stats: &Stats,
+    report: &str,
+    warnings: &[String],
+) -> Option<String> {
+    let mut lines = Vec::new();
+    if show_stats {
+        lines.push(stats.text());
+        lines.push(report.trim_end().to_owned());
+    }
+    if !warnings.is_empty() {
+        lines.push(format!("over budget: {}", warnings.join(", ")));
+    }
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+// Pixel coordinates with the origin in the top left corner.
+pub fn mvp(dimensions: [u32; 2]) -> [[f32; 4]; 4] {
+    let [width, height] = dimensions;
+    cgmath::ortho(0.0, width as f32, 0.0, height as f32, -1.0, 1.0).into()
+}
+
+pub fn quads(text: &str, scale: f32) -> Vec<Vertex> {
+    bmpfont::quads(text, [2.0 * scale; 2], scale)
+}
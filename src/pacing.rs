@@ -0,0 +1,63 @@
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+// OS sleeps overshoot by up to a scheduler tick, so the last stretch before
+// a deadline is spent yielding instead.
+const SPIN: Duration = Duration::from_millis(2);
+
+// Caps the frame rate by waiting out the rest of each frame's slot. Keeps
+// Mailbox and Immediate from spinning a core at hundreds of frames a
+// second when nobody needs them.
+pub struct Limiter {
+    interval: Duration,
+    next: Instant,
+}
+
+impl Limiter {
+    pub fn new(fps: f64) -> Limiter {
+        Limiter {
+            interval: Duration::from_secs_f64(1.0 / fps),
+            next: Instant::now(),
+        }
+    }
+
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if self.next > now + SPIN {
+            thread::sleep(self.next - now - SPIN);
+        }
+        while Instant::now() < self.next {
+            thread::yield_now();
+        }
+        // A frame that ran long starts the next slot from now rather than
+        // rushing to catch up.
+        self.next = (self.next + self.interval).max(Instant::now());
+    }
+}
+
+// Exponential moving average of frame times, so animation steps don't
+// jitter with every hitch in presentation.
+pub struct Smoother {
+    weight: f32,
+    average: Option<f32>,
+}
+
+impl Smoother {
+    // `weight` is how much each new sample counts, from 0 to 1.
+    pub fn new(weight: f32) -> Smoother {
+        Smoother {
+            weight,
+            average: None,
+        }
+    }
+
+    pub fn sample(&mut self, seconds: f32) -> f32 {
+        let average = match self.average {
+            Some(average) => average + (seconds - average) * self.weight,
+            None => seconds,
+        };
+        self.average = Some(average);
+        average
+    }
+}
@@ -0,0 +1,541 @@
+use crate::dbgpipe;
+use crate::error::Error;
+use crate::error::Result;
+use crate::gpusort;
+use crate::gpusort::Order;
+use crate::gpusort::Sorter;
+use crate::registry;
+use crate::registry::FrameContext;
+use crate::registry::InitContext;
+use crate::snapshot::Snapshot;
+use cgmath::Matrix4;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::buffer::DeviceLocalBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::depth_stencil::Compare;
+use vulkano::pipeline::depth_stencil::DepthStencil;
+use vulkano::pipeline::vertex::BufferlessDefinition;
+use vulkano::pipeline::vertex::BufferlessVertices;
+use vulkano::pipeline::ComputePipeline;
+use vulkano::pipeline::ComputePipelineAbstract;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+pub const LOCAL_SIZE: u32 = 256;
+
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct Particle {
+    // w: remaining lifetime in seconds, dead when <= 0
+    pub position: [f32; 4],
+    pub velocity: [f32; 4],
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Emitter {
+    pub enabled: bool,
+    pub count: u32,
+    pub origin: [f32; 3],
+    pub velocity: [f32; 3],
+    pub spread: f32,
+    pub gravity: [f32; 3],
+    pub lifetime: f32,
+    pub rate: f32,
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Emitter {
+            enabled: false,
+            count: 4096,
+            origin: [0.0, 2.0, 0.0],
+            velocity: [0.0, -3.0, 0.0],
+            spread: 1.0,
+            gravity: [0.0, 2.0, 0.0],
+            lifetime: 2.0,
+            rate: 1024.0,
+            size: 4.0,
+            color: [1.0, 0.6, 0.1, 1.0],
+        }
+    }
+}
+
+pub mod update_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: "
+#version 450
+
+layout (local_size_x = 256) in;
+
+struct Particle {
+    vec4 position;
+    vec4 velocity;
+};
+
+layout (set = 0, binding = 0) buffer Particles {
+    Particle particles[];
+} data;
+
+// origin.w: spread, velocity.w: lifetime, gravity.w: delta time,
+// spawn_chance: probability that a dead particle respawns this step
+layout (push_constant) uniform Update {
+    vec4 origin;
+    vec4 velocity;
+    vec4 gravity;
+    uint count;
+    uint seed;
+    float spawn_chance;
+} update;
+
+float hash(uint x) {
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return float(x) / 4294967295.0;
+}
+
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+    if (i >= update.count) {
+        return;
+    }
+
+    Particle p = data.particles[i];
+    float dt = update.gravity.w;
+    if (p.position.w <= 0.0) {
+        uint h = i * 747796405u + update.seed * 2891336453u;
+        if (hash(h) > update.spawn_chance) {
+            return;
+        }
+        vec3 jitter = vec3(hash(h + 1u), hash(h + 2u), hash(h + 3u)) * 2.0
+            - 1.0;
+        float life = update.velocity.w * (0.5 + 0.5 * hash(h + 4u));
+        p.position = vec4(update.origin.xyz, life);
+        p.velocity = vec4(update.velocity.xyz + jitter * update.origin.w, 0.0);
+    } else {
+        p.velocity.xyz += update.gravity.xyz * dt;
+        p.position.xyz += p.velocity.xyz * dt;
+        p.position.w -= dt;
+    }
+    data.particles[i] = p;
+}"
+    }
+}
+
+// Writes each particle's view-space depth as a sort key. Dead particles
+// and the padding up to a power of two sort after every live one.
+pub mod keys_cs {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: "
+#version 450
+
+layout (local_size_x = 256) in;
+
+struct Particle {
+    vec4 position;
+    vec4 velocity;
+};
+
+struct Entry {
+    float key;
+    uint value;
+};
+
+layout (set = 0, binding = 0) readonly buffer Particles {
+    Particle particles[];
+} data;
+
+layout (set = 0, binding = 1) writeonly buffer Entries {
+    Entry entries[];
+} order;
+
+layout (push_constant) uniform Keys {
+    mat4 view;
+    uint count;
+    uint padded;
+} keys;
+
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+    if (i >= keys.padded) {
+        return;
+    }
+
+    float last = uintBitsToFloat(0x7f800000u);
+    if (i >= keys.count) {
+        order.entries[i] = Entry(last, 0xffffffffu);
+        return;
+    }
+    vec4 position = data.particles[i].position;
+    float depth = (keys.view * vec4(position.xyz, 1.0)).z;
+    order.entries[i] = Entry(position.w <= 0.0 ? last : depth, i);
+}"
+    }
+}
+
+pub mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+struct Particle {
+    vec4 position;
+    vec4 velocity;
+};
+
+struct Entry {
+    float key;
+    uint value;
+};
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+layout (set = 1, binding = 0) readonly buffer Particles {
+    Particle particles[];
+} data;
+
+// Sorted back to front, so vertex i draws the i-th farthest particle.
+layout (set = 1, binding = 1) readonly buffer Entries {
+    Entry entries[];
+} order;
+
+layout (push_constant) uniform Style {
+    vec4 color;
+    float size;
+    float lifetime;
+} style;
+
+layout (location = 0) out float out_life;
+
+void main() {
+    uint index = order.entries[gl_VertexIndex].value;
+    vec4 position = index < data.particles.length()
+        ? data.particles[index].position
+        : vec4(0.0);
+    if (position.w <= 0.0) {
+        gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+        gl_PointSize = 1.0;
+    } else {
+        gl_Position = vp_inst.vp * vec4(position.xyz, 1.0);
+        gl_PointSize = style.size;
+    }
+    out_life = clamp(position.w / style.lifetime, 0.0, 1.0);
+}"
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+layout (location = 0) in float life;
+
+layout (push_constant) uniform Style {
+    vec4 color;
+    float size;
+    float lifetime;
+} style;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    vec2 offset = gl_PointCoord * 2.0 - 1.0;
+    float falloff = 1.0 - dot(offset, offset);
+    if (falloff <= 0.0) {
+        discard;
+    }
+    f_color = vec4(style.color.rgb, style.color.a * life * falloff);
+}
+"
+    }
+}
+
+pub struct System {
+    pub update: Arc<dyn ComputePipelineAbstract + Send + Sync>,
+    pub keys: Arc<dyn ComputePipelineAbstract + Send + Sync>,
+    pub render: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pub particles: Arc<CpuAccessibleBuffer<[Particle]>>,
+    // Particle indices sorted by view depth, padded to a power of two.
+    pub order: Arc<DeviceLocalBuffer<[gpusort::Entry]>>,
+    pub update_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub keys_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub sort_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub render_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub sorter: Sorter,
+    pub count: u32,
+}
+
+impl System {
+    // Particles are drawn inside the forward scene pass.
+    pub fn new(
+        device: Arc<Device>,
+        scene: &dbgpipe::Pipeline,
+        count: u32,
+    ) -> Result<System> {
+        let update_cs = update_cs::Shader::load(device.clone())
+            .map_err(Error::shader("particle update compute"))?;
+        let keys_cs = keys_cs::Shader::load(device.clone())
+            .map_err(Error::shader("particle keys compute"))?;
+        let vs = vs::Shader::load(device.clone())
+            .map_err(Error::shader("particle vertex"))?;
+        let fs = fs::Shader::load(device.clone())
+            .map_err(Error::shader("particle fragment"))?;
+
+        let update = Arc::new(
+            ComputePipeline::new(
+                device.clone(),
+                &update_cs.main_entry_point(),
+                &(),
+            )
+            .map_err(Error::compute_pipeline("particle update"))?,
+        );
+        let keys = Arc::new(
+            ComputePipeline::new(
+                device.clone(),
+                &keys_cs.main_entry_point(),
+                &(),
+            )
+            .map_err(Error::compute_pipeline("particle keys"))?,
+        );
+
+        let render_pass: Arc<dyn RenderPassAbstract + Send + Sync> =
+            scene.render_pass.clone();
+        let render = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition)
+                .vertex_shader(vs.main_entry_point(), ())
+                .point_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil(DepthStencil {
+                    depth_write: false,
+                    depth_compare: Compare::Less,
+                    ..DepthStencil::simple_depth_test()
+                })
+                .blend_alpha_blending()
+                .fragment_shader(fs.main_entry_point(), ())
+                .render_pass(Subpass::from(render_pass, 0).unwrap())
+                .build(device.clone())
+                .map_err(Error::pipeline("particles"))?,
+        );
+
+        let storage = BufferUsage {
+            storage_buffer: true,
+            ..BufferUsage::none()
+        };
+        let particles = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            storage,
+            (0..count).map(|_| Particle::default()),
+        )
+        .map_err(Error::allocation("particles"))?;
+        let order = DeviceLocalBuffer::array(
+            device.clone(),
+            count.next_power_of_two() as usize,
+            storage,
+            device.active_queue_families(),
+        )
+        .map_err(Error::allocation("particle order"))?;
+
+        let update_set = Arc::new(
+            PersistentDescriptorSet::start(update.clone(), 0)
+                .add_buffer(particles.clone())?
+                .build()?,
+        );
+        let keys_set = Arc::new(
+            PersistentDescriptorSet::start(keys.clone(), 0)
+                .add_buffer(particles.clone())?
+                .add_buffer(order.clone())?
+                .build()?,
+        );
+        let render_set = Arc::new(
+            PersistentDescriptorSet::start(render.clone(), 1)
+                .add_buffer(particles.clone())?
+                .add_buffer(order.clone())?
+                .build()?,
+        );
+        let sorter = Sorter::new(device)?;
+        let sort_set = sorter.descriptor_set(order.clone())?;
+
+        Ok(System {
+            update,
+            keys,
+            render,
+            particles,
+            order,
+            update_set,
+            keys_set,
+            sort_set,
+            render_set,
+            sorter,
+            count,
+        })
+    }
+
+    // Must be recorded outside a render pass.
+    pub fn update(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        emitter: &Emitter,
+        dt: f32,
+        seed: u32,
+    ) -> Result<AutoCommandBufferBuilder> {
+        let dead_fraction = emitter.lifetime * emitter.rate / self.count as f32;
+        let spawn_chance = (dt * emitter.rate / self.count as f32)
+            / (1.0 - dead_fraction.min(0.99)).max(0.01);
+        let groups = (self.count + LOCAL_SIZE - 1) / LOCAL_SIZE;
+        let o = emitter.origin;
+        let v = emitter.velocity;
+        let g = emitter.gravity;
+        let builder = builder.dispatch(
+            [groups, 1, 1],
+            self.update.clone(),
+            self.update_set.clone(),
+            update_cs::ty::Update {
+                origin: [o[0], o[1], o[2], emitter.spread],
+                velocity: [v[0], v[1], v[2], emitter.lifetime],
+                gravity: [g[0], g[1], g[2], dt],
+                count: self.count,
+                seed,
+                spawn_chance: spawn_chance.min(1.0),
+            },
+        )?;
+        Ok(builder)
+    }
+
+    // Sorts the particles back to front for `view` on the GPU. Must be
+    // recorded outside a render pass, after this frame's updates.
+    pub fn sort(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        view: &Matrix4<f32>,
+    ) -> Result<AutoCommandBufferBuilder> {
+        let padded = self.count.next_power_of_two();
+        let groups = (padded + LOCAL_SIZE - 1) / LOCAL_SIZE;
+        let builder = builder.dispatch(
+            [groups, 1, 1],
+            self.keys.clone(),
+            self.keys_set.clone(),
+            keys_cs::ty::Keys {
+                view: (*view).into(),
+                count: self.count,
+                padded,
+            },
+        )?;
+        self.sorter.record(
+            builder,
+            self.sort_set.clone(),
+            padded,
+            Order::Ascending,
+        )
+    }
+
+    pub fn draw(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        dynamic_state: &DynamicState,
+        view_set: Arc<dyn DescriptorSet + Send + Sync>,
+        emitter: &Emitter,
+    ) -> Result<AutoCommandBufferBuilder> {
+        let builder = builder.draw(
+            self.render.clone(),
+            dynamic_state,
+            BufferlessVertices {
+                vertices: self.count as usize,
+                instances: 1,
+            },
+            vec![view_set, self.render_set.clone()],
+            vs::ty::Style {
+                color: emitter.color,
+                size: emitter.size,
+                lifetime: emitter.lifetime,
+            },
+        )?;
+        Ok(builder)
+    }
+}
+
+// Each fixed update queues a simulation step; `prepare` records them all.
+#[derive(Default)]
+pub struct Feature {
+    system: Option<System>,
+    steps: Vec<f32>,
+    seed: u32,
+}
+
+impl registry::Feature for Feature {
+    fn name(&self) -> &str {
+        "particles"
+    }
+
+    fn init(&mut self, context: &InitContext) -> Result<()> {
+        if context.state.emitter.enabled {
+            self.system = Some(System::new(
+                context.device.clone(),
+                context.scene,
+                context.state.emitter.count,
+            )?);
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, dt: f32, _state: &Snapshot) {
+        if self.system.is_some() {
+            self.steps.push(dt);
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        mut builder: AutoCommandBufferBuilder,
+        frame: &FrameContext,
+    ) -> Result<AutoCommandBufferBuilder> {
+        if let Some(system) = &self.system {
+            for dt in self.steps.drain(..) {
+                builder = system.update(
+                    builder,
+                    &frame.state.emitter,
+                    dt,
+                    self.seed,
+                )?;
+                self.seed = self.seed.wrapping_add(1);
+            }
+            builder = system.sort(builder, &frame.state.camera.view())?;
+        }
+        Ok(builder)
+    }
+
+    fn draw_scene(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        frame: &FrameContext,
+    ) -> Result<AutoCommandBufferBuilder> {
+        match &self.system {
+            Some(system) => system.draw(
+                builder,
+                frame.dynamic_state,
+                frame.view_set.clone(),
+                &frame.state.emitter,
+            ),
+            None => Ok(builder),
+        }
+    }
+}
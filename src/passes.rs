@@ -0,0 +1,1280 @@
+use crate::arena::Allocation;
+use crate::arena::Arena;
+use crate::billboardpipe;
+use crate::bmpfont;
+use crate::bmptxtpipe;
+use crate::compat;
+use crate::compute;
+use crate::culling;
+use crate::dbgpipe;
+use crate::dbgpipe::LineVertex;
+use crate::debug_draw;
+use crate::debugnames::DebugNames;
+use crate::debugview::DebugView;
+use crate::descriptors::DescriptorCache;
+use crate::descriptors::Key;
+use crate::error::Error;
+use crate::error::Result;
+use crate::fullscreen;
+use crate::gbufpipe;
+use crate::gputimer::GpuTimer;
+use crate::hdr;
+use crate::indirect;
+use crate::indirect::Draw;
+use crate::inspector::Inspector;
+use crate::layers;
+use crate::lightmappipe;
+use crate::lut;
+use crate::lutpipe;
+use crate::memory::Category;
+use crate::memory::Tracker;
+use crate::motionblurpipe;
+use crate::normalpipe;
+use crate::objectpipe;
+use crate::occlusion::Occlusion;
+use crate::oitpipe;
+use crate::overdrawpipe;
+use crate::overlay;
+use crate::probes;
+use crate::probes::ProbeGrid;
+use crate::ring::Ring;
+use crate::ring::Slice;
+use crate::secondary;
+use crate::skinpipe;
+use crate::snapshot::Snapshot;
+use crate::spritepipe;
+use crate::taapipe;
+use crate::tesspipe;
+use crate::texarray;
+use crate::transfer::Uploader;
+use crate::transparent;
+use cgmath::Matrix4;
+use cgmath::SquareMatrix;
+use std::sync::Arc;
+use tracing::warn;
+use vulkano::buffer::BufferAccess;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::buffer::CpuBufferPool;
+use vulkano::buffer::DeviceLocalBuffer;
+use vulkano::command_buffer::AutoCommandBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::image::AttachmentImage;
+use vulkano::image::Dimensions;
+use vulkano::image::ImmutableImage;
+use vulkano::image::SwapchainImage;
+use vulkano::instance::QueueFamily;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::sampler::Filter;
+use vulkano::sampler::MipmapMode;
+use vulkano::sampler::Sampler;
+use vulkano::sampler::SamplerAddressMode;
+use vulkano::swapchain::Swapchain;
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+use winit::window::Window;
+
+// Every pipeline the frame loop draws with, and the resources they share.
+// Built once; only hot-reloaded shaders replace pipelines afterwards.
+pub struct Passes {
+    pub debug: dbgpipe::Pipeline,
+    pub objects: objectpipe::Pipeline,
+    pub skin: Option<(
+        skinpipe::Pipeline,
+        Arc<CpuAccessibleBuffer<[skinpipe::Vertex]>>,
+        skinpipe::MorphTargets,
+    )>,
+    pub terrain: Option<tesspipe::Pipeline>,
+    pub normals: Option<normalpipe::Pipeline>,
+    pub sprites: Option<(
+        spritepipe::Pipeline,
+        Arc<dyn DescriptorSet + Send + Sync>,
+        Vec<texarray::Batch>,
+    )>,
+    pub billboards: Option<(
+        billboardpipe::Pipeline,
+        Arc<dyn DescriptorSet + Send + Sync>,
+        Vec<texarray::Batch>,
+    )>,
+    pub lightmap: lightmappipe::Pipeline,
+    pub deferred: Option<gbufpipe::Pipeline>,
+    pub oit: Option<oitpipe::Pipeline>,
+    pub overdraw: overdrawpipe::Pipeline,
+    pub taa: Option<taapipe::Pipeline>,
+    pub motion_blur: Option<motionblurpipe::Pipeline>,
+    pub histogram: (compute::Pipeline, Arc<CpuAccessibleBuffer<[u32]>>),
+    pub grade: lutpipe::Pipeline,
+    pub inspector: (bmptxtpipe::Pipeline, Arc<dyn DescriptorSet + Send + Sync>),
+    pub font_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub lut_image: Arc<ImmutableImage<Format>>,
+    pub sampler: Arc<Sampler>,
+    pub nearest_sampler: Arc<Sampler>,
+    pub scene_pool: CpuBufferPool<gbufpipe::lighting_fs::ty::SCENE_BLOCK>,
+    pub probe_pool: CpuBufferPool<gbufpipe::lighting_fs::ty::PROBE_BLOCK>,
+    pub velocity_pool: CpuBufferPool<taapipe::velocity_vs::ty::VELOCITY_BLOCK>,
+}
+
+// The skinned mesh to draw, with one list of position offsets per blend
+// shape.
+pub struct SkinMesh {
+    pub vertices: Vec<skinpipe::Vertex>,
+    pub morph_targets: Vec<Vec<[f32; 4]>>,
+}
+
+impl Passes {
+    // Builds the pipelines `state` asks for and starts uploading the
+    // grading LUT, font and sprite textures.
+    pub fn new(
+        device: Arc<Device>,
+        swapchain: Arc<Swapchain<Window>>,
+        uploader: &Uploader,
+        output: hdr::Output,
+        state: &Snapshot,
+        skin: Option<SkinMesh>,
+    ) -> Result<(Passes, Box<dyn GpuFuture>)> {
+        check_features(&device, state)?;
+        let debug = dbgpipe::build(device.clone(), swapchain.clone())?;
+        compat::verify(
+            "dbgpipe",
+            &dbgpipe::shader_interface()?,
+            &dbgpipe::interface(),
+        )?;
+        compat::verify(
+            "dbgpipe transparent",
+            &dbgpipe::transparent_shader_interface()?,
+            &dbgpipe::transparent_interface(),
+        )?;
+
+        let lightmap = lightmappipe::build(device.clone(), &debug);
+        compat::verify(
+            "lightmappipe",
+            &lightmappipe::shader_interface(&lightmap),
+            &lightmappipe::interface(),
+        )?;
+
+        let deferred = if state.deferred {
+            let pipeline = gbufpipe::build(device.clone(), swapchain.clone())?;
+            compat::verify(
+                "gbufpipe geometry",
+                &gbufpipe::geometry_shader_interface(&pipeline),
+                &gbufpipe::geometry_interface(),
+            )?;
+            Some(pipeline)
+        } else {
+            None
+        };
+
+        let grade = lutpipe::build(
+            device.clone(),
+            swapchain.clone(),
+            lutpipe::specialization(output),
+        )?;
+
+        let (lut_size, lut_data) = match &state.lut {
+            Some(path) => lut::load(path)?,
+            None => (lut::SIZE, lut::neutral(lut::SIZE)),
+        };
+        let (lut_image, lut_upload) = lut::upload(lut_size, lut_data, uploader);
+
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )?;
+        let nearest_sampler = Sampler::new(
+            device.clone(),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )?;
+
+        let oit = if state.oit {
+            Some(oitpipe::build(device.clone(), swapchain.clone())?)
+        } else {
+            None
+        };
+
+        let overdraw = overdrawpipe::build(device.clone());
+
+        let inspector = bmptxtpipe::build(device.clone(), swapchain.clone())?;
+        compat::verify(
+            "bmptxtpipe",
+            &bmptxtpipe::shader_interface()?,
+            &bmptxtpipe::interface(),
+        )?;
+        let inspector_set = bmptxtpipe::mvp_set(
+            device.clone(),
+            &inspector,
+            Matrix4::<f32>::identity().into(),
+        );
+        let atlas = bmpfont::atlas();
+        let (width, height) = atlas.dimensions();
+        let (font, font_upload) = uploader.image(
+            atlas.into_raw(),
+            Dimensions::Dim2d { width, height },
+            Format::R8G8B8A8Unorm,
+        );
+        let font_set =
+            bmptxtpipe::bitmap_set(&inspector, font, nearest_sampler.clone());
+
+        // Motion blur reuses the TAA velocity buffer.
+        let taa = if state.taa || state.motion_blur.enabled {
+            Some(taapipe::build(device.clone(), swapchain.format())?)
+        } else {
+            None
+        };
+        let motion_blur = if state.motion_blur.enabled {
+            Some(motionblurpipe::build(device.clone(), swapchain.format())?)
+        } else {
+            None
+        };
+
+        let histogram = (
+            compute::build(device.clone())?,
+            compute::histogram_buffer(device.clone())?,
+        );
+
+        let objects = objectpipe::Pipeline::new(device.clone(), &debug);
+        let skin = match skin {
+            Some(mesh) => {
+                let morph = skinpipe::MorphTargets::new(
+                    device.clone(),
+                    mesh.vertices.len(),
+                    &mesh.morph_targets,
+                )
+                .map_err(Error::allocation("morph targets"))?;
+                let strip = CpuAccessibleBuffer::from_iter(
+                    device.clone(),
+                    BufferUsage::vertex_buffer(),
+                    mesh.vertices.into_iter(),
+                )
+                .map_err(Error::allocation("skin strip"))?;
+                Some((
+                    skinpipe::Pipeline::new(device.clone(), &debug),
+                    strip,
+                    morph,
+                ))
+            }
+            None => None,
+        };
+        let terrain = if state.tessellation {
+            let terrain = tesspipe::Pipeline::new(device.clone(), &debug);
+            if terrain.is_none() {
+                warn!("tessellationShader unsupported, terrain disabled");
+            }
+            terrain
+        } else {
+            None
+        };
+        let normals = normalpipe::Pipeline::new(device.clone(), &debug);
+        if normals.is_none() {
+            warn!("geometryShader unsupported, normal display disabled");
+        }
+        let (sprites, billboards, sprite_upload) = if state
+            .sprite_textures
+            .is_empty()
+        {
+            (
+                None,
+                None,
+                Box::new(sync::now(device.clone())) as Box<dyn GpuFuture>,
+            )
+        } else {
+            let pipeline = spritepipe::build(device.clone(), &debug);
+            compat::verify(
+                "spritepipe",
+                &spritepipe::shader_interface(&pipeline),
+                &spritepipe::interface(),
+            )?;
+            let (table, table_upload) =
+                texarray::Table::load(&state.sprite_textures, uploader)?;
+            let (vertices, runs) = spritepipe::quads(&state.sprites, &table)?;
+            let (quads, quad_upload) =
+                uploader.buffer(vertices, BufferUsage::vertex_buffer());
+            let set = table.set(pipeline.pipeline.clone(), sampler.clone())?;
+            let (billboards, billboard_upload) = if state.billboards.is_empty()
+            {
+                (
+                    None,
+                    Box::new(sync::now(device.clone())) as Box<dyn GpuFuture>,
+                )
+            } else {
+                let pipeline = billboardpipe::build(device.clone(), &debug);
+                compat::verify(
+                    "billboardpipe",
+                    &billboardpipe::shader_interface(&pipeline),
+                    &billboardpipe::interface(),
+                )?;
+                let (vertices, runs) =
+                    billboardpipe::quads(&state.billboards, &table)?;
+                let (quads, upload) =
+                    uploader.buffer(vertices, BufferUsage::vertex_buffer());
+                let set =
+                    table.set(pipeline.pipeline.clone(), sampler.clone())?;
+                (
+                    Some((pipeline, set, texarray::batches(quads, &runs))),
+                    Box::new(upload) as Box<dyn GpuFuture>,
+                )
+            };
+            (
+                Some((pipeline, set, texarray::batches(quads, &runs))),
+                billboards,
+                Box::new(table_upload.join(quad_upload).join(billboard_upload))
+                    as Box<dyn GpuFuture>,
+            )
+        };
+
+        let passes = Passes {
+            debug,
+            objects,
+            skin,
+            terrain,
+            normals,
+            sprites,
+            billboards,
+            lightmap,
+            deferred,
+            oit,
+            overdraw,
+            taa,
+            motion_blur,
+            histogram,
+            grade,
+            inspector: (inspector, inspector_set),
+            font_set,
+            lut_image,
+            sampler,
+            nearest_sampler,
+            scene_pool: CpuBufferPool::uniform_buffer(device.clone()),
+            probe_pool: CpuBufferPool::uniform_buffer(device.clone()),
+            velocity_pool: CpuBufferPool::uniform_buffer(device),
+        };
+        let future = lut_upload.join(font_upload).join(sprite_upload);
+        Ok((passes, Box::new(future)))
+    }
+
+    pub fn name(&self, names: &DebugNames) {
+        names.pipeline(&*self.debug.pipeline, "scene pipeline");
+        names.pipeline(&*self.debug.transparent, "transparent pipeline");
+        if let Some(wireframe) = &self.debug.wireframe {
+            names.pipeline(&**wireframe, "wireframe pipeline");
+        }
+        names.pipeline(&*self.lightmap.pipeline, "lightmap pipeline");
+        names.pipeline(&*self.grade.pipeline, "grade pipeline");
+        names.compute_pipeline(
+            &*self.histogram.0.pipeline,
+            "histogram pipeline",
+        );
+        names.buffer(&*self.histogram.1, "histogram bins");
+        names.image(&*self.lut_image, "grading lut");
+    }
+
+    pub fn track(&self, memory: &Tracker) {
+        memory.track_buffer(
+            Category::Storage,
+            "histogram bins",
+            &self.histogram.1,
+        );
+        memory.track_image(Category::Texture, "grading lut", &self.lut_image);
+        if let Some((_, strip, morph)) = &self.skin {
+            memory.track_buffer(Category::Vertex, "skin strip", strip);
+            memory.track_buffer(
+                Category::Storage,
+                "morph targets",
+                &morph.deltas,
+            );
+        }
+    }
+}
+
+// Errors for a requested pass the device can't run. Passes with a fallback,
+// such as wireframe or GPU culling, check `enabled_features` themselves.
+pub fn check_features(device: &Device, state: &Snapshot) -> Result<()> {
+    let features = device.enabled_features();
+    let required = [
+        (
+            "OIT",
+            "independentBlend",
+            state.oit,
+            features.independent_blend,
+        ),
+        (
+            "particles",
+            "largePoints",
+            state.emitter.enabled,
+            features.large_points,
+        ),
+        (
+            "sprites",
+            "shaderSampledImageArrayDynamicIndexing",
+            !state.sprite_textures.is_empty(),
+            features.shader_sampled_image_array_dynamic_indexing,
+        ),
+    ];
+    for &(pass, feature, wanted, enabled) in &required {
+        if wanted && !enabled {
+            return Err(Error::MissingFeature { pass, feature });
+        }
+    }
+    Ok(())
+}
+
+// The attachments and framebuffers sized to the swapchain, rebuilt with it.
+pub struct Targets {
+    pub scene_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    pub deferred: Option<gbufpipe::Targets>,
+    pub oit: Option<oitpipe::Targets>,
+    pub taa: Option<taapipe::Targets>,
+    pub motion_blur: Option<motionblurpipe::Targets>,
+    pub overdraw_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    pub framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    pub grade_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub histogram_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub overlay_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub inputs: lutpipe::Inputs,
+    pub available_views: u32,
+}
+
+impl Targets {
+    // Also points `dynamic_state`'s viewport at the new size.
+    pub fn new(
+        device: Arc<Device>,
+        images: &[Arc<SwapchainImage<Window>>],
+        passes: &Passes,
+        dynamic_state: &mut DynamicState,
+    ) -> Result<Targets> {
+        let dimensions = images[0].dimensions();
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        };
+        dynamic_state.viewports = Some(vec![viewport]);
+
+        let scene = AttachmentImage::sampled(
+            device.clone(),
+            dimensions,
+            images[0].swapchain().format(),
+        )
+        .map_err(Error::image("scene color"))?;
+
+        let depth = AttachmentImage::sampled(
+            device.clone(),
+            dimensions,
+            dbgpipe::DEPTH_FORMAT,
+        )
+        .map_err(Error::image("scene depth"))?;
+
+        let scene_framebuffer = Arc::new(
+            Framebuffer::start(passes.debug.render_pass.clone())
+                .add(scene.clone())?
+                .add(depth.clone())?
+                .build()?,
+        )
+            as Arc<dyn FramebufferAbstract + Send + Sync>;
+
+        let deferred = passes
+            .deferred
+            .as_ref()
+            .map(|pipeline| {
+                gbufpipe::targets(pipeline, device.clone(), scene.clone())
+            })
+            .transpose()?;
+
+        let oit = passes
+            .oit
+            .as_ref()
+            .map(|pipeline| {
+                oitpipe::targets(
+                    pipeline,
+                    device.clone(),
+                    scene.clone(),
+                    depth.clone(),
+                )
+            })
+            .transpose()?;
+
+        let taa = passes
+            .taa
+            .as_ref()
+            .map(|pipeline| {
+                taapipe::targets(
+                    pipeline,
+                    device.clone(),
+                    scene.clone(),
+                    passes.sampler.clone(),
+                    passes.nearest_sampler.clone(),
+                )
+            })
+            .transpose()?;
+        let scene = match &taa {
+            Some(taa) => taa.resolved.clone(),
+            None => scene,
+        };
+
+        let motion_blur = match (&passes.motion_blur, &taa) {
+            (Some(pipeline), Some(taa)) => Some(motionblurpipe::targets(
+                pipeline,
+                device.clone(),
+                scene.clone(),
+                taa.velocity.clone(),
+                passes.sampler.clone(),
+                passes.nearest_sampler.clone(),
+            )?),
+            _ => None,
+        };
+        let scene = match &motion_blur {
+            Some(motion_blur) => motion_blur.output.clone(),
+            None => scene,
+        };
+
+        let overdraw = AttachmentImage::sampled(
+            device.clone(),
+            dimensions,
+            overdrawpipe::FORMAT,
+        )
+        .map_err(Error::image("overdraw"))?;
+        let overdraw_framebuffer = Arc::new(
+            Framebuffer::start(passes.overdraw.render_pass.clone())
+                .add(overdraw.clone())?
+                .build()?,
+        )
+            as Arc<dyn FramebufferAbstract + Send + Sync>;
+
+        let framebuffers = images
+            .iter()
+            .map(|image| {
+                let framebuffer =
+                    Framebuffer::start(passes.grade.render_pass.clone())
+                        .add(image.clone())?
+                        .build()?;
+                Ok(Arc::new(framebuffer)
+                    as Arc<dyn FramebufferAbstract + Send + Sync>)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // The forward path has no G-buffer; its albedo, normal and material
+        // inputs are stand-ins bound to the lit scene.
+        let mut available_views = DebugView::Final.bit()
+            | DebugView::Depth.bit()
+            | DebugView::Overdraw.bit();
+        let inputs = match &deferred {
+            Some(gbuffer) => {
+                available_views |= DebugView::Albedo.bit()
+                    | DebugView::Normals.bit()
+                    | DebugView::Ao.bit();
+                lutpipe::Inputs {
+                    scene,
+                    depth: gbuffer.depth.clone(),
+                    albedo: gbuffer.albedo.clone(),
+                    normal: gbuffer.normal.clone(),
+                    material: gbuffer.material.clone(),
+                    overdraw,
+                }
+            }
+            None => lutpipe::Inputs {
+                scene: scene.clone(),
+                depth,
+                albedo: scene.clone(),
+                normal: scene.clone(),
+                material: scene,
+                overdraw,
+            },
+        };
+
+        let grade_set = lutpipe::descriptor_set(
+            &passes.grade,
+            &inputs,
+            passes.lut_image.clone(),
+            passes.sampler.clone(),
+            passes.nearest_sampler.clone(),
+        )?;
+
+        let histogram_set = compute::descriptor_set(
+            &passes.histogram.0,
+            inputs.scene.clone(),
+            passes.nearest_sampler.clone(),
+            passes.histogram.1.clone(),
+        )?;
+
+        let overlay_set = bmptxtpipe::mvp_set(
+            device,
+            &passes.inspector.0,
+            overlay::mvp(dimensions),
+        );
+
+        Ok(Targets {
+            scene_framebuffer,
+            deferred,
+            oit,
+            taa,
+            motion_blur,
+            overdraw_framebuffer,
+            framebuffers,
+            grade_set,
+            histogram_set,
+            overlay_set,
+            inputs,
+            available_views,
+        })
+    }
+
+    pub fn name(&self, names: &DebugNames) {
+        let inputs = &self.inputs;
+        names.image(&*inputs.scene, "scene color image");
+        names.image(&*inputs.depth, "scene depth image");
+        names.image(&*inputs.overdraw, "overdraw image");
+        if self.deferred.is_some() {
+            names.image(&*inputs.albedo, "gbuffer albedo image");
+            names.image(&*inputs.normal, "gbuffer normal image");
+            names.image(&*inputs.material, "gbuffer material image");
+        }
+    }
+
+    // Replaced targets drop out of the totals on their own once released.
+    pub fn track(&self, memory: &Tracker) {
+        let inputs = &self.inputs;
+        memory.track_image(Category::Attachment, "scene color", &inputs.scene);
+        memory.track_image(Category::Attachment, "scene depth", &inputs.depth);
+        memory.track_image(Category::Attachment, "overdraw", &inputs.overdraw);
+        memory.track_image(
+            Category::Attachment,
+            "gbuffer albedo",
+            &inputs.albedo,
+        );
+        memory.track_image(
+            Category::Attachment,
+            "gbuffer normal",
+            &inputs.normal,
+        );
+        memory.track_image(
+            Category::Attachment,
+            "gbuffer material",
+            &inputs.material,
+        );
+    }
+}
+
+// What every pass of one frame draws with.
+pub struct Frame<'a> {
+    pub dynamic_state: &'a DynamicState,
+    // The jittered view-projection, for pipelines that build their own set.
+    pub view_buffer: Arc<dyn BufferAccess + Send + Sync>,
+    // `view_buffer` bound for dbgpipe and the pipelines sharing its layout.
+    pub view_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+    pub state: &'a Snapshot,
+}
+
+// How the forward pass draws the opaque scene.
+pub enum Opaque<'a> {
+    // With baked lighting, from the lightmapped copy of the scene.
+    Lightmapped(
+        Arc<DeviceLocalBuffer<[lightmappipe::Vertex]>>,
+        Arc<dyn DescriptorSet + Send + Sync>,
+    ),
+    // The objects whose proxies passed last frame's occlusion test.
+    Occluded(&'a Occlusion, Allocation),
+    // Ranges of the mesh arena, from CPU culling or LOD selection.
+    Ranges(Vec<Allocation>),
+    // The whole scene buffer, directly or from GPU culling's commands.
+    Whole(Draw<'a>),
+}
+
+// The forward pass's draws on top of the opaque scene.
+pub struct Scene<'a> {
+    pub opaque: Opaque<'a>,
+    pub arena: &'a Arena<dbgpipe::Vertex>,
+    pub wireframe: bool,
+    pub occlusion: Option<&'a Occlusion>,
+    // The grid and the sphere mesh drawn at each probe.
+    pub probes: Option<(&'a ProbeGrid, Arc<dyn BufferAccess + Send + Sync>)>,
+    // Blend shape weights and bone matrices.
+    pub skin: Option<(Vec<f32>, Vec<Matrix4<f32>>)>,
+    pub normal_mode: u32,
+    pub lines: Option<Slice<LineVertex>>,
+    pub on_top: Option<Slice<LineVertex>>,
+    // Features aren't Send, so the caller records them; they run between
+    // the opaque and the probe/transparent draws.
+    pub features: AutoCommandBuffer,
+}
+
+// Opens a timed, labelled region for a pass, outside any render pass.
+pub fn begin_pass(
+    builder: AutoCommandBufferBuilder,
+    timer: &mut GpuTimer,
+    names: &DebugNames,
+    family: QueueFamily,
+    name: &'static str,
+) -> AutoCommandBufferBuilder {
+    let builder = names.begin_label(builder, family, name);
+    timer.begin(builder, family, name)
+}
+
+pub fn end_pass(
+    builder: AutoCommandBufferBuilder,
+    timer: &mut GpuTimer,
+    names: &DebugNames,
+    family: QueueFamily,
+) -> AutoCommandBufferBuilder {
+    let builder = timer.end(builder, family);
+    names.end_label(builder, family)
+}
+
+// Geometry into the G-buffer, then lighting from it and the probes.
+pub fn record_deferred(
+    builder: AutoCommandBufferBuilder,
+    passes: &Passes,
+    pipeline: &gbufpipe::Pipeline,
+    targets: &gbufpipe::Targets,
+    frame: &Frame,
+    probe_grid: &ProbeGrid,
+) -> Result<AutoCommandBufferBuilder> {
+    let state = frame.state;
+    let view_set = Arc::new(
+        PersistentDescriptorSet::start(pipeline.geometry.clone(), 0)
+            .add_buffer(frame.view_buffer.clone())?
+            .build()?,
+    ) as Arc<dyn DescriptorSet + Send + Sync>;
+    let scene_buffer = passes
+        .scene_pool
+        .next(gbufpipe::scene_block(
+            &state.camera,
+            &state.light,
+            &state.local_lights,
+            &state.fog,
+        ))
+        .map_err(Error::allocation("scene uniforms"))?;
+    let probe_buffer = passes
+        .probe_pool
+        .next(probe_grid.block())
+        .map_err(Error::allocation("probe uniforms"))?;
+    let scene_set = gbufpipe::scene_set(pipeline, scene_buffer, probe_buffer)?;
+    Ok(gbufpipe::draw(
+        builder,
+        pipeline,
+        targets,
+        frame.dynamic_state,
+        vec![frame.vertex_buffer.clone()],
+        view_set,
+        scene_set,
+    ))
+}
+
+// The forward scene pass, its draws recorded on worker threads, followed
+// by OIT's transparency when that's on.
+pub fn record_forward(
+    builder: AutoCommandBufferBuilder,
+    device: Arc<Device>,
+    family: QueueFamily,
+    passes: &Passes,
+    targets: &Targets,
+    frame: &Frame,
+    scene: Scene,
+) -> Result<AutoCommandBufferBuilder> {
+    let Scene {
+        opaque,
+        arena,
+        wireframe,
+        occlusion,
+        probes: probe_spheres,
+        skin: skin_pose,
+        normal_mode,
+        lines,
+        on_top,
+        features,
+    } = scene;
+    let debug = &passes.debug;
+    let dynamic_state = frame.dynamic_state;
+    let view_set = &frame.view_set;
+    let vertex_buffer = &frame.vertex_buffer;
+    let state = frame.state;
+    let opaque_variant = match &debug.wireframe {
+        Some(pipeline) if wireframe => pipeline.clone(),
+        _ => debug.pipeline.clone(),
+    };
+
+    let mut jobs: Vec<secondary::Job> = Vec::new();
+    // vulkano can't record a draw that takes its count from the GPU, so
+    // that one is recorded raw and executed ahead of the workers' buffers.
+    let counted = match &opaque {
+        Opaque::Whole(Draw::Indirect(bucket)) => indirect::draw_count(
+            device.clone(),
+            family,
+            Subpass::from(debug.render_pass.clone(), 0).unwrap(),
+            opaque_variant.clone(),
+            dynamic_state,
+            vec![vertex_buffer.clone()],
+            bucket,
+            vec![view_set.clone()],
+            dbgpipe::vs::ty::Push {
+                model: Matrix4::identity().into(),
+            },
+        ),
+        _ => None,
+    };
+    if counted.is_none() {
+        jobs.push(match opaque {
+            Opaque::Lightmapped(vertices, lightmap_set) => {
+                let lightmap = &passes.lightmap;
+                Box::new(move |scene| {
+                    draw_lightmapped(
+                        scene,
+                        lightmap,
+                        dynamic_state,
+                        vertices,
+                        view_set.clone(),
+                        lightmap_set,
+                    )
+                })
+            }
+            Opaque::Occluded(occlusion, mesh) => Box::new(move |scene| {
+                Ok(occlusion.draw_visible(
+                    scene,
+                    debug.pipeline.clone(),
+                    dynamic_state,
+                    arena,
+                    mesh,
+                    view_set.clone(),
+                ))
+            }),
+            Opaque::Ranges(ranges) => Box::new(move |scene| {
+                Ok(culling::draw_ranges(
+                    scene,
+                    opaque_variant,
+                    dynamic_state,
+                    arena,
+                    &ranges,
+                    view_set.clone(),
+                ))
+            }),
+            Opaque::Whole(draw_call) => Box::new(move |scene| {
+                draw_opaque(
+                    scene,
+                    debug,
+                    dynamic_state,
+                    vertex_buffer.clone(),
+                    view_set.clone(),
+                    wireframe,
+                    draw_call,
+                )
+            }),
+        });
+    }
+    if let Some(occlusion) = occlusion {
+        jobs.push(Box::new(move |scene| {
+            Ok(occlusion.draw_proxies(scene, dynamic_state, view_set.clone()))
+        }));
+    }
+    if let Some((probe_grid, sphere)) = probe_spheres {
+        let objects = &passes.objects;
+        let object_set = objects.frame_set(&probe_objects(probe_grid));
+        jobs.push(Box::new(move |scene| {
+            Ok(objects.draw(
+                scene,
+                dynamic_state,
+                sphere,
+                view_set.clone(),
+                object_set,
+                probes::COUNT,
+            ))
+        }));
+    }
+    if let (Some((skin, strip, morph)), Some((weights, bones))) =
+        (&passes.skin, skin_pose)
+    {
+        let morph_set = skin.morph_set(morph, &weights);
+        let bone_set = skin.bone_set(&bones);
+        jobs.push(Box::new(move |scene| {
+            Ok(skin.draw(
+                scene,
+                dynamic_state,
+                strip.clone(),
+                view_set.clone(),
+                bone_set,
+                morph_set,
+            ))
+        }));
+    }
+    match &passes.normals {
+        Some(normals) if normal_mode != 0 => {
+            jobs.push(Box::new(move |scene| {
+                Ok(normals.draw(
+                    scene,
+                    dynamic_state,
+                    vertex_buffer.clone(),
+                    view_set.clone(),
+                    normal_mode,
+                ))
+            }));
+        }
+        _ => {}
+    }
+    if let Some(terrain) = &passes.terrain {
+        jobs.push(Box::new(move |scene| {
+            Ok(terrain.draw(scene, dynamic_state, view_set.clone()))
+        }));
+    }
+    if let Some(lines) = &lines {
+        jobs.push(Box::new(move |scene| {
+            Ok(debug_draw::draw(
+                scene,
+                debug,
+                dynamic_state,
+                lines.clone(),
+                view_set.clone(),
+            ))
+        }));
+    }
+    if let Some((sprites, table_set, batches)) = &passes.sprites {
+        jobs.push(Box::new(move |scene| {
+            Ok(spritepipe::draw(
+                scene,
+                sprites,
+                dynamic_state,
+                batches,
+                view_set.clone(),
+                table_set.clone(),
+            ))
+        }));
+    }
+    if let Some((billboards, table_set, batches)) = &passes.billboards {
+        let view = state.camera.view();
+        jobs.push(Box::new(move |scene| {
+            Ok(billboardpipe::draw(
+                scene,
+                billboards,
+                dynamic_state,
+                batches,
+                view_set.clone(),
+                table_set.clone(),
+                view,
+            ))
+        }));
+    }
+    if passes.oit.is_none() {
+        jobs.push(Box::new(move |scene| {
+            draw_transparent_sorted(
+                scene,
+                debug,
+                dynamic_state,
+                vertex_buffer.clone(),
+                view_set.clone(),
+                state,
+                state.camera.cull_mask,
+            )
+        }));
+    }
+    if let Some(lines) = &on_top {
+        jobs.push(Box::new(move |scene| {
+            Ok(debug_draw::draw_on_top(
+                scene,
+                debug,
+                dynamic_state,
+                lines.clone(),
+                view_set.clone(),
+            ))
+        }));
+    }
+    let mut secondaries = secondary::record_parallel(
+        device,
+        family,
+        debug.render_pass.clone(),
+        0,
+        jobs,
+    )?;
+    // Features draw right after the opaque scene.
+    secondaries.insert(if counted.is_some() { 0 } else { 1 }, features);
+
+    let builder = builder.begin_render_pass(
+        targets.scene_framebuffer.clone(),
+        true,
+        vec![[0.0, 0.0, 1.0, 1.0].into(), 1.0f32.into()],
+    )?;
+    let builder = match counted {
+        Some(raw) => raw.execute(builder),
+        None => builder,
+    };
+    let builder =
+        secondary::execute(builder, secondaries)?.end_render_pass()?;
+
+    match (&passes.oit, &targets.oit) {
+        (Some(oit), Some(oit_targets)) => oitpipe::draw(
+            builder,
+            oit,
+            oit_targets,
+            dynamic_state,
+            vec![vertex_buffer.clone()],
+            view_set.clone(),
+            &transparent::draw_list(
+                &state.transparent,
+                &state.camera.view(),
+                state.camera.cull_mask,
+            ),
+        ),
+        _ => Ok(builder),
+    }
+}
+
+// Per-pixel motion from `velocity`'s matrices, then the jittered frame
+// resolved into the history. `reset` drops the history.
+pub fn record_taa(
+    builder: AutoCommandBufferBuilder,
+    passes: &Passes,
+    pipeline: &taapipe::Pipeline,
+    targets: &taapipe::Targets,
+    frame: &Frame,
+    velocity: taapipe::velocity_vs::ty::VELOCITY_BLOCK,
+    reset: bool,
+) -> Result<AutoCommandBufferBuilder> {
+    let velocity_buffer = passes
+        .velocity_pool
+        .next(velocity)
+        .map_err(Error::allocation("velocity uniforms"))?;
+    let builder = taapipe::draw_velocity(
+        builder,
+        pipeline,
+        targets,
+        frame.dynamic_state,
+        vec![frame.vertex_buffer.clone()],
+        taapipe::velocity_set(pipeline, velocity_buffer)?,
+        &[(Matrix4::identity(), Matrix4::identity())],
+    )?;
+    taapipe::resolve(builder, pipeline, targets, frame.dynamic_state, reset)
+}
+
+// Starts the grading subpass's secondary buffer with the fullscreen pass;
+// inspectors, the overlay and UI are drawn over it before it's executed.
+pub fn record_grade(
+    device: Arc<Device>,
+    family: QueueFamily,
+    passes: &Passes,
+    targets: &Targets,
+    dynamic_state: &DynamicState,
+    view: DebugView,
+) -> Result<AutoCommandBufferBuilder> {
+    let builder = secondary::builder(
+        device,
+        family,
+        passes.grade.render_pass.clone(),
+        0,
+    )?
+    .draw(
+        passes.grade.pipeline.clone(),
+        dynamic_state,
+        fullscreen::vertices(),
+        vec![targets.grade_set.clone()],
+        lutpipe::fs::ty::View {
+            mode: view.mode(),
+            available: targets.available_views,
+        },
+    )?;
+    Ok(builder)
+}
+
+pub fn draw_opaque(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &dbgpipe::Pipeline,
+    dynamic_state: &DynamicState,
+    vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    wireframe: bool,
+    draw_call: Draw,
+) -> Result<AutoCommandBufferBuilder> {
+    let variant = match &pipeline.wireframe {
+        Some(wireframe_pipeline) if wireframe => wireframe_pipeline,
+        _ => &pipeline.pipeline,
+    };
+    Ok(indirect::submit(
+        builder,
+        variant.clone(),
+        dynamic_state,
+        vec![vertex_buffer],
+        draw_call,
+        vec![set],
+        dbgpipe::vs::ty::Push {
+            model: Matrix4::identity().into(),
+        },
+    ))
+}
+
+fn draw_lightmapped(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &lightmappipe::Pipeline,
+    dynamic_state: &DynamicState,
+    vertex_buffer: Arc<DeviceLocalBuffer<[lightmappipe::Vertex]>>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    lightmap_set: Arc<dyn DescriptorSet + Send + Sync>,
+) -> Result<AutoCommandBufferBuilder> {
+    let builder = builder.draw(
+        pipeline.pipeline.clone(),
+        dynamic_state,
+        vec![vertex_buffer],
+        vec![set, lightmap_set],
+        lightmappipe::vs::ty::Push {
+            model: Matrix4::identity().into(),
+        },
+    )?;
+    Ok(builder)
+}
+
+pub fn draw_transparent_sorted(
+    mut builder: AutoCommandBufferBuilder,
+    pipeline: &dbgpipe::Pipeline,
+    dynamic_state: &DynamicState,
+    vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    state: &Snapshot,
+    mask: u32,
+) -> Result<AutoCommandBufferBuilder> {
+    let instances =
+        transparent::draw_list(&state.transparent, &state.camera.view(), mask);
+    for instance in &instances {
+        builder = builder.draw(
+            pipeline.transparent.clone(),
+            dynamic_state,
+            vec![vertex_buffer.clone()],
+            vec![set.clone()],
+            dbgpipe::TransparentPush {
+                model: instance.model().into(),
+                color: instance.color,
+            },
+        )?;
+    }
+    Ok(builder)
+}
+
+fn probe_objects(grid: &ProbeGrid) -> Vec<objectpipe::Object> {
+    (0..probes::COUNT)
+        .map(|index| {
+            let irradiance = grid.average(index);
+            objectpipe::Object {
+                model: Matrix4::from_translation(grid.position(index)).into(),
+                color: [irradiance[0], irradiance[1], irradiance[2], 1.0],
+            }
+        })
+        .collect()
+}
+
+pub fn draw_overdraw(
+    builder: AutoCommandBufferBuilder,
+    passes: &Passes,
+    targets: &Targets,
+    frame: &Frame,
+) -> Result<AutoCommandBufferBuilder> {
+    let pipeline = &passes.overdraw;
+    let state = frame.state;
+    let set = Arc::new(
+        PersistentDescriptorSet::start(pipeline.pipeline.clone(), 0)
+            .add_buffer(frame.view_buffer.clone())?
+            .build()?,
+    ) as Arc<dyn DescriptorSet + Send + Sync>;
+    let models = std::iter::once(Matrix4::identity()).chain(
+        state
+            .transparent
+            .iter()
+            .filter(|instance| {
+                layers::visible(instance.layers, state.camera.cull_mask)
+            })
+            .map(|instance| instance.model()),
+    );
+
+    let mut builder = builder.begin_render_pass(
+        targets.overdraw_framebuffer.clone(),
+        false,
+        vec![[0.0, 0.0, 0.0, 0.0].into()],
+    )?;
+    for model in models {
+        builder = builder.draw(
+            pipeline.pipeline.clone(),
+            frame.dynamic_state,
+            vec![frame.vertex_buffer.clone()],
+            vec![set.clone()],
+            dbgpipe::vs::ty::Push {
+                model: model.into(),
+            },
+        )?;
+    }
+    let builder = builder.end_render_pass()?;
+    Ok(builder)
+}
+
+pub fn draw_inspectors(
+    mut builder: AutoCommandBufferBuilder,
+    ring: &mut Ring<bmptxtpipe::Vertex>,
+    passes: &Passes,
+    targets: &Targets,
+    dynamic_state: &DynamicState,
+    inspectors: &[Inspector],
+    cache: &mut DescriptorCache,
+) -> Result<AutoCommandBufferBuilder> {
+    let (pipeline, mvp_set) = &passes.inspector;
+    for inspector in inspectors {
+        let vertex_buffer = match ring.push(&inspector.quad()) {
+            Some(slice) => {
+                Arc::new(slice) as Arc<dyn BufferAccess + Send + Sync>
+            }
+            None => break,
+        };
+        let image = inspector.image(&targets.inputs);
+        let sampler = passes.nearest_sampler.clone();
+        let key = Key::new(&*pipeline.pipeline, 1).with(&image).with(&sampler);
+        let image_set =
+            cache.get(key, || bmptxtpipe::bitmap_set(pipeline, image, sampler));
+        builder = builder.draw(
+            pipeline.pipeline.clone(),
+            dynamic_state,
+            vec![vertex_buffer],
+            (mvp_set.clone(), image_set),
+            (),
+        )?;
+    }
+    Ok(builder)
+}
+
+pub fn draw_overlay(
+    builder: AutoCommandBufferBuilder,
+    ring: &mut Ring<bmptxtpipe::Vertex>,
+    passes: &Passes,
+    targets: &Targets,
+    dynamic_state: &DynamicState,
+    quads: &[bmptxtpipe::Vertex],
+) -> Result<AutoCommandBufferBuilder> {
+    let vertex_buffer = match ring.push(quads) {
+        Some(slice) => Arc::new(slice) as Arc<dyn BufferAccess + Send + Sync>,
+        None => return Ok(builder),
+    };
+    let (pipeline, _) = &passes.inspector;
+    let builder = builder.draw(
+        pipeline.pipeline.clone(),
+        dynamic_state,
+        vec![vertex_buffer],
+        (targets.overlay_set.clone(), passes.font_set.clone()),
+        (),
+    )?;
+    Ok(builder)
+}
@@ -0,0 +1,184 @@
+use crate::camera::Camera;
+use crate::gbufpipe::lighting_fs::ty::PROBE_BLOCK;
+use crate::snapshot::Light;
+use crate::trace;
+use crate::trace::Random;
+use crate::trace::EPSILON;
+use cgmath::{ElementWise, InnerSpace, Vector3};
+
+pub const DIMS: [usize; 3] = [4, 4, 2];
+pub const COUNT: usize = DIMS[0] * DIMS[1] * DIMS[2];
+pub const RAYS: u32 = 128;
+pub const PER_FRAME: usize = 2;
+pub const SKY: f32 = 0.1;
+pub const ALBEDO: [f32; 3] = [1.0, 0.0, 0.0];
+pub const RADIUS: f32 = 0.15;
+
+const AXES: [[f32; 3]; 6] = [
+    [1.0, 0.0, 0.0],
+    [-1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, -1.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [0.0, 0.0, -1.0],
+];
+
+// Irradiance probes on a regular grid spanning the camera volume, stored as
+// ambient cubes (one color per axis direction, +x -x +y -y +z -z).
+pub struct ProbeGrid {
+    pub min: [f32; 3],
+    pub step: [f32; 3],
+    pub ambient: Vec<[[f32; 3]; 6]>,
+    next: usize,
+    random: Random,
+}
+
+impl ProbeGrid {
+    pub fn new(camera: &Camera) -> ProbeGrid {
+        let (min, max) = camera.bounds();
+        let mut step = [0.0; 3];
+        for (axis, value) in step.iter_mut().enumerate() {
+            *value = (max[axis] - min[axis]) / (DIMS[axis] - 1) as f32;
+        }
+
+        ProbeGrid {
+            min,
+            step,
+            ambient: vec![[[SKY; 3]; 6]; COUNT],
+            next: 0,
+            random: Random(0x2545_f491),
+        }
+    }
+
+    pub fn position(&self, index: usize) -> Vector3<f32> {
+        let cell = [
+            index % DIMS[0],
+            index / DIMS[0] % DIMS[1],
+            index / (DIMS[0] * DIMS[1]),
+        ];
+        Vector3::new(
+            self.min[0] + cell[0] as f32 * self.step[0],
+            self.min[1] + cell[1] as f32 * self.step[1],
+            self.min[2] + cell[2] as f32 * self.step[2],
+        )
+    }
+
+    pub fn average(&self, index: usize) -> [f32; 3] {
+        let mut sum = [0.0; 3];
+        for side in &self.ambient[index] {
+            for (total, channel) in sum.iter_mut().zip(side) {
+                *total += channel / 6.0;
+            }
+        }
+        sum
+    }
+
+    // Relights the next `count` probes round-robin so the whole grid
+    // converges over a few frames without stalling any single one.
+    pub fn update(&mut self, scene: &[[f32; 4]], light: &Light, count: usize) {
+        let triangles = trace::triangles(scene);
+        let to_light = -Vector3::new(
+            light.direction[0],
+            light.direction[1],
+            light.direction[2],
+        )
+        .normalize();
+        let light_color =
+            Vector3::new(light.color[0], light.color[1], light.color[2]);
+        let albedo = Vector3::from(ALBEDO);
+        let sky = Vector3::new(SKY, SKY, SKY);
+
+        for _ in 0..count.min(COUNT) {
+            let index = self.next;
+            self.next = (self.next + 1) % COUNT;
+            let origin = self.position(index);
+
+            let mut sums = [Vector3::new(0.0, 0.0, 0.0); 6];
+            let mut weights = [0.0f32; 6];
+            for _ in 0..RAYS {
+                let direction = trace::sphere(&mut self.random);
+                let radiance =
+                    match trace::closest(&triangles, origin, direction) {
+                        Some((triangle, distance)) => {
+                            let mut normal = triangle.normal();
+                            if normal.dot(direction) > 0.0 {
+                                normal = -normal;
+                            }
+                            let hit = origin
+                                + direction * distance
+                                + normal * EPSILON;
+                            let n_dot_l = normal.dot(to_light).max(0.0);
+                            let direct = if n_dot_l > 0.0
+                                && !trace::occluded(&triangles, hit, to_light)
+                            {
+                                light_color * n_dot_l
+                            } else {
+                                Vector3::new(0.0, 0.0, 0.0)
+                            };
+                            (direct + sky).mul_element_wise(albedo)
+                        }
+                        None => sky,
+                    };
+
+                for (axis, (sum, weight)) in
+                    AXES.iter().zip(sums.iter_mut().zip(weights.iter_mut()))
+                {
+                    let w = direction.dot(Vector3::from(*axis)).max(0.0);
+                    *sum += radiance * w;
+                    *weight += w;
+                }
+            }
+
+            for (side, (sum, weight)) in self.ambient[index]
+                .iter_mut()
+                .zip(sums.iter().zip(weights.iter()))
+            {
+                if *weight > 0.0 {
+                    *side = (sum / *weight).into();
+                }
+            }
+        }
+    }
+
+    pub fn block(&self) -> PROBE_BLOCK {
+        let mut ambient = [[0.0; 4]; COUNT * 6];
+        for (out, side) in ambient
+            .iter_mut()
+            .zip(self.ambient.iter().flat_map(|probe| probe.iter()))
+        {
+            *out = [side[0], side[1], side[2], 1.0];
+        }
+        PROBE_BLOCK {
+            grid_min: [self.min[0], self.min[1], self.min[2], 0.0],
+            grid_step: [self.step[0], self.step[1], self.step[2], 0.0],
+            grid_dims: [DIMS[0] as f32, DIMS[1] as f32, DIMS[2] as f32, 0.0],
+            ambient,
+        }
+    }
+}
+
+pub fn sphere_vertices() -> Vec<[f32; 4]> {
+    let r = RADIUS;
+    let points = [
+        [r, 0.0, 0.0, 1.0],
+        [-r, 0.0, 0.0, 1.0],
+        [0.0, r, 0.0, 1.0],
+        [0.0, -r, 0.0, 1.0],
+        [0.0, 0.0, r, 1.0],
+        [0.0, 0.0, -r, 1.0],
+    ];
+    let faces = [
+        [0, 2, 4],
+        [2, 1, 4],
+        [1, 3, 4],
+        [3, 0, 4],
+        [2, 0, 5],
+        [1, 2, 5],
+        [3, 1, 5],
+        [0, 3, 5],
+    ];
+    faces
+        .iter()
+        .flat_map(|face| face.iter().map(|&corner| points[corner]))
+        .collect()
+}
@@ -0,0 +1,236 @@
+use crate::gputimer::GpuTiming;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Track {
+    Cpu,
+    Gpu,
+}
+
+#[derive(Debug, Clone)]
+pub struct Timing {
+    pub name: &'static str,
+    pub track: Track,
+    // Number of scopes on the same track that were open when this one
+    // began.
+    pub depth: usize,
+    // Relative to the start of the frame: on the CPU, when `begin_frame`
+    // was called, and on the GPU, the frame's first timestamp.
+    pub start_ms: f64,
+    pub duration_ms: f64,
+}
+
+// One complete ("X") event in the chrome://tracing JSON format.
+#[derive(Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+}
+
+struct State {
+    frame: u64,
+    frame_start: Instant,
+    open: Vec<usize>,
+    current: Vec<Timing>,
+    last: Vec<Timing>,
+    trace: Option<Vec<TraceEvent>>,
+}
+
+// Hierarchical per-frame timings. CPU scopes nest by lifetime; GPU scopes
+// are the timestamp queries of a `GpuTimer`, which arrive a few frames
+// late and are filed under the frame they were read back in.
+//
+// With the `profiling` feature, CPU scopes are also Tracy zones and frames
+// are Tracy frame marks; `GpuTimer` sends the GPU scopes as Tracy GPU zones.
+pub struct Profiler {
+    epoch: Instant,
+    #[cfg(feature = "profiling")]
+    tracy: tracy_client::Client,
+    state: RefCell<State>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        let now = Instant::now();
+        Profiler {
+            epoch: now,
+            #[cfg(feature = "profiling")]
+            tracy: tracy_client::Client::start(),
+            state: RefCell::new(State {
+                frame: 0,
+                frame_start: now,
+                open: Vec::new(),
+                current: Vec::new(),
+                last: Vec::new(),
+                trace: None,
+            }),
+        }
+    }
+
+    pub fn begin_frame(&self) {
+        let mut state = self.state.borrow_mut();
+        state.frame += 1;
+        state.frame_start = Instant::now();
+        state.open.clear();
+        state.current.clear();
+    }
+
+    // Keeps this frame's timings for `last_frame` and `report`, and adds
+    // them to the trace if one is being recorded.
+    pub fn end_frame(&self) {
+        let mut state = self.state.borrow_mut();
+        let frame_start = ms_between(self.epoch, state.frame_start);
+        let timings = std::mem::replace(&mut state.current, Vec::new());
+        if let Some(trace) = state.trace.as_mut() {
+            trace.extend(timings.iter().map(|timing| TraceEvent {
+                name: timing.name,
+                ph: "X",
+                ts: (frame_start + timing.start_ms) * 1000.0,
+                dur: timing.duration_ms * 1000.0,
+                pid: 0,
+                tid: match timing.track {
+                    Track::Cpu => 0,
+                    Track::Gpu => 1,
+                },
+            }));
+        }
+        state.last = timings;
+        #[cfg(feature = "profiling")]
+        self.tracy.frame_mark();
+    }
+
+    pub fn scope(&self, name: &'static str) -> Scope<'_> {
+        let mut state = self.state.borrow_mut();
+        let timing = Timing {
+            name,
+            track: Track::Cpu,
+            depth: state.open.len(),
+            start_ms: ms_between(state.frame_start, Instant::now()),
+            duration_ms: 0.0,
+        };
+        let index = state.current.len();
+        state.current.push(timing);
+        state.open.push(index);
+        Scope {
+            profiler: self,
+            frame: state.frame,
+            index,
+            #[cfg(feature = "profiling")]
+            _span: self.tracy.clone().span_alloc(
+                Some(name),
+                name,
+                file!(),
+                line!(),
+                0,
+            ),
+        }
+    }
+
+    // Adds a frame's worth of GPU timings, as read back by a `GpuTimer`.
+    pub fn gpu(&self, timings: &[GpuTiming]) {
+        let mut state = self.state.borrow_mut();
+        for timing in timings {
+            state.current.push(Timing {
+                name: timing.name,
+                track: Track::Gpu,
+                depth: timing.depth,
+                start_ms: timing.start_ms,
+                duration_ms: timing.duration_ms,
+            });
+        }
+    }
+
+    pub fn last_frame(&self) -> Vec<Timing> {
+        self.state.borrow().last.clone()
+    }
+
+    // The previous frame's timings, one per line and indented by depth.
+    pub fn report(&self) -> String {
+        let state = self.state.borrow();
+        let mut report = String::new();
+        for timing in &state.last {
+            let track = match timing.track {
+                Track::Cpu => "cpu",
+                Track::Gpu => "gpu",
+            };
+            writeln!(
+                report,
+                "{}{} {}: {:.2} ms",
+                "  ".repeat(timing.depth),
+                track,
+                timing.name,
+                timing.duration_ms
+            )
+            .unwrap();
+        }
+        report
+    }
+
+    pub fn start_trace(&self) {
+        self.state.borrow_mut().trace = Some(Vec::new());
+    }
+
+    pub fn tracing(&self) -> bool {
+        self.state.borrow().trace.is_some()
+    }
+
+    // Writes the frames recorded since `start_trace` and stops recording.
+    pub fn export_trace<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let events = self.state.borrow_mut().trace.take().unwrap_or_default();
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, &events).map_err(io::Error::from)
+    }
+
+    fn close(&self, frame: u64, index: usize) {
+        let mut state = self.state.borrow_mut();
+        // Scopes that outlive their frame are dropped from the timings.
+        if frame != state.frame {
+            return;
+        }
+        let end = ms_between(state.frame_start, Instant::now());
+        if let Some(position) = state.open.iter().rposition(|&i| i == index) {
+            state.open.truncate(position);
+        }
+        // Empty if the frame has already ended.
+        if let Some(timing) = state.current.get_mut(index) {
+            timing.duration_ms = end - timing.start_ms;
+        }
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler::new()
+    }
+}
+
+// Ends its timing when dropped.
+pub struct Scope<'a> {
+    profiler: &'a Profiler,
+    frame: u64,
+    index: usize,
+    #[cfg(feature = "profiling")]
+    _span: tracy_client::Span,
+}
+
+impl<'a> Drop for Scope<'a> {
+    fn drop(&mut self) {
+        self.profiler.close(self.frame, self.index);
+    }
+}
+
+fn ms_between(start: Instant, end: Instant) -> f64 {
+    let elapsed = end.saturating_duration_since(start);
+    elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1e6
+}
@@ -0,0 +1,167 @@
+use std::sync::Arc;
+use vk_sys as vk;
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::pool::standard::StandardCommandPoolAlloc;
+use vulkano::command_buffer::sys::Flags;
+use vulkano::command_buffer::sys::Kind;
+use vulkano::command_buffer::sys::KindOcclusionQuery;
+use vulkano::command_buffer::sys::KindSecondaryRenderPass;
+use vulkano::command_buffer::sys::UnsafeCommandBuffer;
+use vulkano::command_buffer::sys::UnsafeCommandBufferBuilder;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::command_buffer::CommandBufferExecError;
+use vulkano::device::Device;
+use vulkano::device::DeviceOwned;
+use vulkano::device::Queue;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::image::ImageAccess;
+use vulkano::image::ImageLayout;
+use vulkano::instance::QueueFamily;
+use vulkano::query::QueryPipelineStatisticFlags;
+use vulkano::sync::AccessCheckError;
+use vulkano::sync::AccessFlagBits;
+use vulkano::sync::GpuFuture;
+use vulkano::sync::PipelineStages;
+use vulkano::VulkanObject;
+
+// A secondary command buffer recorded straight through vk-sys, for
+// commands vulkano 0.14's builders don't wrap, such as timestamp queries
+// and debug labels. It's executed from a primary builder between render
+// passes, or inside one for draws. vulkano's synchronization can't see
+// inside it, so whatever the commands touch must already be ready.
+pub struct RawCommands {
+    device: Arc<Device>,
+    inner: UnsafeCommandBuffer<StandardCommandPoolAlloc>,
+    // What the commands use, kept alive until they're dropped.
+    _resources: Vec<Box<dyn Send + Sync>>,
+}
+
+impl RawCommands {
+    // Safety: `record` may only record commands that are valid outside a
+    // render pass and use no buffers or images.
+    pub unsafe fn record<F>(
+        device: Arc<Device>,
+        family: QueueFamily,
+        record: F,
+    ) -> RawCommands
+    where
+        F: FnOnce(&vk::DevicePointers, vk::CommandBuffer),
+    {
+        let pool = Device::standard_command_pool(&device, family);
+        let builder = UnsafeCommandBufferBuilder::new(
+            &pool,
+            Kind::secondary(
+                KindOcclusionQuery::Forbidden,
+                QueryPipelineStatisticFlags::none(),
+            ),
+            Flags::OneTimeSubmit,
+        )
+        .unwrap();
+        record(device.pointers(), builder.internal_object());
+        let inner = builder.build().unwrap();
+        RawCommands {
+            device,
+            inner,
+            _resources: Vec::new(),
+        }
+    }
+
+    // Like `record`, but executed inside `subpass` of a render pass begun
+    // with `secondary: true`, for draws vulkano doesn't wrap.
+    //
+    // Safety: as for `record`, except that the commands may use the
+    // buffers and images in `resources`. Nothing synchronizes them, so
+    // they must already be ready the way vulkano's own secondaries are.
+    pub unsafe fn record_in_subpass<F>(
+        device: Arc<Device>,
+        family: QueueFamily,
+        subpass: Subpass<Arc<dyn RenderPassAbstract + Send + Sync>>,
+        resources: Vec<Box<dyn Send + Sync>>,
+        record: F,
+    ) -> RawCommands
+    where
+        F: FnOnce(&vk::DevicePointers, vk::CommandBuffer),
+    {
+        let pool = Device::standard_command_pool(&device, family);
+        let builder = UnsafeCommandBufferBuilder::new(
+            &pool,
+            Kind::Secondary {
+                render_pass: Some(KindSecondaryRenderPass {
+                    subpass,
+                    framebuffer: None::<
+                        Arc<dyn FramebufferAbstract + Send + Sync>,
+                    >,
+                }),
+                occlusion_query: KindOcclusionQuery::Forbidden,
+                query_statistics_flags: QueryPipelineStatisticFlags::none(),
+            },
+            Flags::OneTimeSubmit,
+        )
+        .unwrap();
+        record(device.pointers(), builder.internal_object());
+        let inner = builder.build().unwrap();
+        RawCommands {
+            device,
+            inner,
+            _resources: resources,
+        }
+    }
+
+    // `builder` must be outside a render pass, or inside the subpass the
+    // commands were recorded for.
+    pub fn execute(
+        self,
+        builder: AutoCommandBufferBuilder,
+    ) -> AutoCommandBufferBuilder {
+        unsafe { builder.execute_commands(self).unwrap() }
+    }
+}
+
+unsafe impl DeviceOwned for RawCommands {
+    fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+}
+
+// Nothing to lock: vulkano can't see what the commands use.
+unsafe impl CommandBuffer for RawCommands {
+    type PoolAlloc = StandardCommandPoolAlloc;
+
+    fn inner(&self) -> &UnsafeCommandBuffer<StandardCommandPoolAlloc> {
+        &self.inner
+    }
+
+    fn lock_submit(
+        &self,
+        _future: &dyn GpuFuture,
+        _queue: &Queue,
+    ) -> Result<(), CommandBufferExecError> {
+        Ok(())
+    }
+
+    unsafe fn unlock(&self) {}
+
+    fn check_buffer_access(
+        &self,
+        _buffer: &dyn BufferAccess,
+        _exclusive: bool,
+        _queue: &Queue,
+    ) -> Result<Option<(PipelineStages, AccessFlagBits)>, AccessCheckError>
+    {
+        Err(AccessCheckError::Unknown)
+    }
+
+    fn check_image_access(
+        &self,
+        _image: &dyn ImageAccess,
+        _layout: ImageLayout,
+        _exclusive: bool,
+        _queue: &Queue,
+    ) -> Result<Option<(PipelineStages, AccessFlagBits)>, AccessCheckError>
+    {
+        Err(AccessCheckError::Unknown)
+    }
+}
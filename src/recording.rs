@@ -0,0 +1,46 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// Writes every frame to a numbered PNG in `directory`, reporting a fixed
+// frame time instead of the wall clock, so the sequence comes out the same
+// however long each frame takes to render.
+pub struct Recording {
+    directory: PathBuf,
+    step: f64,
+    frame: u64,
+    frames: Option<u64>,
+}
+
+impl Recording {
+    pub fn new(
+        directory: PathBuf,
+        fps: f64,
+        frames: Option<u64>,
+    ) -> io::Result<Recording> {
+        fs::create_dir_all(&directory)?;
+        Ok(Recording {
+            directory,
+            step: 1.0 / fps,
+            frame: 0,
+            frames,
+        })
+    }
+
+    pub fn dt(&self) -> f32 {
+        self.step as f32
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.directory.join(format!("frame_{:05}.png", self.frame))
+    }
+
+    // Call once the current frame's image has been written.
+    pub fn advance(&mut self) {
+        self.frame += 1;
+    }
+
+    pub fn done(&self) -> bool {
+        self.frames.map_or(false, |frames| self.frame >= frames)
+    }
+}
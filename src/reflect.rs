@@ -0,0 +1,615 @@
+use crate::entrypoint;
+use crate::entrypoint::LayoutDesc;
+use crate::entrypoint::StageInterface;
+use std::collections::HashMap;
+use vulkano::descriptor::descriptor::DescriptorBufferDesc;
+use vulkano::descriptor::descriptor::DescriptorDesc;
+use vulkano::descriptor::descriptor::DescriptorDescTy;
+use vulkano::descriptor::descriptor::DescriptorImageDesc;
+use vulkano::descriptor::descriptor::DescriptorImageDescArray;
+use vulkano::descriptor::descriptor::DescriptorImageDescDimensions;
+use vulkano::descriptor::descriptor::ShaderStages;
+use vulkano::format::Format;
+use vulkano::pipeline::shader::GraphicsEntryPoint;
+use vulkano::pipeline::shader::GraphicsShaderType;
+use vulkano::pipeline::shader::ShaderModule;
+
+const MAGIC: u32 = 0x0723_0203;
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_OUTPUT: u32 = 3;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_1D: u32 = 0;
+const DIM_3D: u32 = 2;
+const DIM_CUBE: u32 = 3;
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+// What a graphics stage's SPIR-V says about itself: enough to build an
+// entry point without `vulkano_shaders` or a hand-written description.
+#[derive(Debug, Clone)]
+pub struct Reflection {
+    pub ty: GraphicsShaderType,
+    pub stages: ShaderStages,
+    pub inputs: StageInterface,
+    pub outputs: StageInterface,
+    pub layout: LayoutDesc,
+    // Set, binding and size in bytes of each uniform block.
+    pub uniform_blocks: Vec<(usize, usize, usize)>,
+}
+
+impl Reflection {
+    // Unsafe as `entrypoint::graphics` is: `module` has to be the one this
+    // was reflected from.
+    pub unsafe fn entry_point<'a>(
+        &self,
+        module: &'a ShaderModule,
+    ) -> GraphicsEntryPoint<'a, (), StageInterface, StageInterface, LayoutDesc>
+    {
+        entrypoint::graphics(
+            module,
+            self.inputs.clone(),
+            self.outputs.clone(),
+            self.layout.clone(),
+            self.ty,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Scalar {
+        float: bool,
+        signed: bool,
+        width: u32,
+    },
+    Vector {
+        component: u32,
+        count: u32,
+    },
+    Matrix {
+        column: u32,
+        count: u32,
+    },
+    Image {
+        dim: u32,
+        arrayed: bool,
+        multisampled: bool,
+        sampled: u32,
+    },
+    Sampler,
+    SampledImage {
+        image: u32,
+    },
+    Array {
+        element: u32,
+        length: u32,
+    },
+    RuntimeArray {
+        element: u32,
+    },
+    Struct {
+        members: Vec<u32>,
+    },
+    Pointer {
+        pointee: u32,
+    },
+}
+
+#[derive(Debug, Default)]
+struct Decorations {
+    location: Option<u32>,
+    binding: Option<u32>,
+    set: Option<u32>,
+    block: bool,
+    buffer_block: bool,
+    array_stride: Option<u32>,
+}
+
+#[derive(Debug, Default)]
+struct MemberDecorations {
+    offset: u32,
+    matrix_stride: Option<u32>,
+}
+
+struct Variable {
+    id: u32,
+    pointer: u32,
+    storage: u32,
+}
+
+#[derive(Default)]
+struct Module {
+    execution_model: Option<u32>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    members: HashMap<(u32, u32), MemberDecorations>,
+    variables: Vec<Variable>,
+}
+
+// Derives the interface, descriptor bindings and push constant range of
+// the first entry point in `words`. Only vertex, tessellation and fragment
+// stages are handled, and specialization constants are ignored, so array
+// sizes must be literal.
+pub fn reflect(words: &[u32]) -> Result<Reflection, String> {
+    let module = Module::parse(words)?;
+    let (ty, stages) = match module.execution_model {
+        Some(0) => (
+            GraphicsShaderType::Vertex,
+            ShaderStages {
+                vertex: true,
+                ..ShaderStages::none()
+            },
+        ),
+        Some(1) => (
+            GraphicsShaderType::TessellationControl,
+            ShaderStages {
+                tessellation_control: true,
+                ..ShaderStages::none()
+            },
+        ),
+        Some(2) => (
+            GraphicsShaderType::TessellationEvaluation,
+            ShaderStages {
+                tessellation_evaluation: true,
+                ..ShaderStages::none()
+            },
+        ),
+        Some(4) => (
+            GraphicsShaderType::Fragment,
+            ShaderStages {
+                fragment: true,
+                ..ShaderStages::none()
+            },
+        ),
+        Some(model) => {
+            return Err(format!("unsupported execution model {}", model))
+        }
+        None => return Err("no entry point".to_owned()),
+    };
+
+    let mut inputs = StageInterface::new();
+    let mut outputs = StageInterface::new();
+    let mut layout = LayoutDesc::default();
+    let mut uniform_blocks = Vec::new();
+    for variable in &module.variables {
+        let pointee = module.pointee(variable.pointer)?;
+        let decorations = module.decorations.get(&variable.id);
+        match variable.storage {
+            STORAGE_INPUT | STORAGE_OUTPUT => {
+                // Built-ins such as gl_Position have no location.
+                let location = match decorations.and_then(|d| d.location) {
+                    Some(location) => location,
+                    None => continue,
+                };
+                let interface = if variable.storage == STORAGE_INPUT {
+                    &mut inputs
+                } else {
+                    &mut outputs
+                };
+                for (offset, format) in module.locations(pointee)? {
+                    *interface =
+                        interface.clone().with(location + offset, format);
+                }
+            }
+            STORAGE_UNIFORM_CONSTANT
+            | STORAGE_UNIFORM
+            | STORAGE_STORAGE_BUFFER => {
+                let decorations = decorations.ok_or_else(|| {
+                    format!("%{} is undecorated", variable.id)
+                })?;
+                let (set, binding) =
+                    match (decorations.set, decorations.binding) {
+                        (Some(set), Some(binding)) => (set, binding),
+                        _ => {
+                            return Err(format!(
+                                "%{} has no set or binding",
+                                variable.id
+                            ))
+                        }
+                    };
+                let (element, array_count) = match module.types.get(&pointee) {
+                    Some(Type::Array { element, length }) => {
+                        (*element, module.constant(*length)?)
+                    }
+                    Some(Type::RuntimeArray { element }) => (*element, 1),
+                    _ => (pointee, 1),
+                };
+                let ty = module.descriptor_ty(element, variable.storage)?;
+                if let DescriptorDescTy::Buffer(desc) = &ty {
+                    if !desc.storage {
+                        let size = module.size(element, None)?;
+                        uniform_blocks.push((
+                            set as usize,
+                            binding as usize,
+                            size as usize,
+                        ));
+                    }
+                }
+                let readonly = match &ty {
+                    DescriptorDescTy::Buffer(desc) => !desc.storage,
+                    DescriptorDescTy::Image(desc) => desc.sampled,
+                    DescriptorDescTy::TexelBuffer { storage, .. } => !storage,
+                    _ => true,
+                };
+                layout.set_descriptor(
+                    set as usize,
+                    binding as usize,
+                    DescriptorDesc {
+                        ty,
+                        array_count,
+                        stages,
+                        readonly,
+                    },
+                );
+            }
+            STORAGE_PUSH_CONSTANT => {
+                let size = module.size(pointee, None)?;
+                layout.set_push_constants(size as usize, stages);
+            }
+            _ => (),
+        }
+    }
+
+    Ok(Reflection {
+        ty,
+        stages,
+        inputs,
+        outputs,
+        layout,
+        uniform_blocks,
+    })
+}
+
+impl Module {
+    fn parse(words: &[u32]) -> Result<Module, String> {
+        if words.len() < 5 || words[0] != MAGIC {
+            return Err("not a SPIR-V module".to_owned());
+        }
+        let mut module = Module::default();
+        let mut index = 5;
+        while index < words.len() {
+            let count = (words[index] >> 16) as usize;
+            let opcode = words[index] & 0xffff;
+            if count == 0 || index + count > words.len() {
+                return Err(format!("truncated instruction at word {}", index));
+            }
+            module.instruction(opcode, &words[index + 1..index + count]);
+            index += count;
+        }
+        Ok(module)
+    }
+
+    fn instruction(&mut self, opcode: u32, operands: &[u32]) {
+        let operand = |index: usize| operands.get(index).cloned().unwrap_or(0);
+        let ty = match opcode {
+            OP_ENTRY_POINT => {
+                if self.execution_model.is_none() {
+                    self.execution_model = Some(operand(0));
+                }
+                None
+            }
+            OP_TYPE_INT => Some(Type::Scalar {
+                float: false,
+                signed: operand(2) == 1,
+                width: operand(1),
+            }),
+            OP_TYPE_FLOAT => Some(Type::Scalar {
+                float: true,
+                signed: true,
+                width: operand(1),
+            }),
+            OP_TYPE_VECTOR => Some(Type::Vector {
+                component: operand(1),
+                count: operand(2),
+            }),
+            OP_TYPE_MATRIX => Some(Type::Matrix {
+                column: operand(1),
+                count: operand(2),
+            }),
+            OP_TYPE_IMAGE => Some(Type::Image {
+                dim: operand(2),
+                arrayed: operand(4) == 1,
+                multisampled: operand(5) == 1,
+                sampled: operand(6),
+            }),
+            OP_TYPE_SAMPLER => Some(Type::Sampler),
+            OP_TYPE_SAMPLED_IMAGE => {
+                Some(Type::SampledImage { image: operand(1) })
+            }
+            OP_TYPE_ARRAY => Some(Type::Array {
+                element: operand(1),
+                length: operand(2),
+            }),
+            OP_TYPE_RUNTIME_ARRAY => Some(Type::RuntimeArray {
+                element: operand(1),
+            }),
+            OP_TYPE_STRUCT => Some(Type::Struct {
+                members: operands.iter().skip(1).cloned().collect(),
+            }),
+            OP_TYPE_POINTER => Some(Type::Pointer {
+                pointee: operand(2),
+            }),
+            OP_CONSTANT => {
+                self.constants.insert(operand(1), operand(2));
+                None
+            }
+            OP_VARIABLE => {
+                self.variables.push(Variable {
+                    id: operand(1),
+                    pointer: operand(0),
+                    storage: operand(2),
+                });
+                None
+            }
+            OP_DECORATE => {
+                let decorations =
+                    self.decorations.entry(operand(0)).or_default();
+                match operand(1) {
+                    DECORATION_BLOCK => decorations.block = true,
+                    DECORATION_BUFFER_BLOCK => decorations.buffer_block = true,
+                    DECORATION_ARRAY_STRIDE => {
+                        decorations.array_stride = Some(operand(2))
+                    }
+                    DECORATION_LOCATION => {
+                        decorations.location = Some(operand(2))
+                    }
+                    DECORATION_BINDING => {
+                        decorations.binding = Some(operand(2))
+                    }
+                    DECORATION_DESCRIPTOR_SET => {
+                        decorations.set = Some(operand(2))
+                    }
+                    _ => (),
+                }
+                None
+            }
+            OP_MEMBER_DECORATE => {
+                let member =
+                    self.members.entry((operand(0), operand(1))).or_default();
+                match operand(2) {
+                    DECORATION_OFFSET => member.offset = operand(3),
+                    DECORATION_MATRIX_STRIDE => {
+                        member.matrix_stride = Some(operand(3))
+                    }
+                    _ => (),
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some(ty) = ty {
+            self.types.insert(operand(0), ty);
+        }
+    }
+
+    fn pointee(&self, pointer: u32) -> Result<u32, String> {
+        match self.types.get(&pointer) {
+            Some(Type::Pointer { pointee }) => Ok(*pointee),
+            _ => Err(format!("%{} is not a pointer type", pointer)),
+        }
+    }
+
+    fn constant(&self, id: u32) -> Result<u32, String> {
+        self.constants
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| format!("%{} is not a literal constant", id))
+    }
+
+    // Interface formats by location offset; matrices and arrays take one
+    // location per column or element.
+    fn locations(&self, id: u32) -> Result<Vec<(u32, Format)>, String> {
+        match self.types.get(&id) {
+            Some(Type::Matrix { column, count }) => {
+                let format = self.format(*column)?;
+                Ok((0..*count).map(|i| (i, format)).collect())
+            }
+            Some(Type::Array { element, length }) => {
+                let element = self.locations(*element)?;
+                let stride = element.len() as u32;
+                Ok((0..self.constant(*length)?)
+                    .flat_map(|i| {
+                        element
+                            .iter()
+                            .map(move |&(offset, format)| {
+                                (i * stride + offset, format)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect())
+            }
+            _ => Ok(vec![(0, self.format(id)?)]),
+        }
+    }
+
+    fn format(&self, id: u32) -> Result<Format, String> {
+        let (scalar, count) = match self.types.get(&id) {
+            Some(Type::Vector { component, count }) => (*component, *count),
+            _ => (id, 1),
+        };
+        let format = match (self.types.get(&scalar), count) {
+            (
+                Some(Type::Scalar {
+                    float: true,
+                    width: 32,
+                    ..
+                }),
+                count,
+            ) => match count {
+                1 => Some(Format::R32Sfloat),
+                2 => Some(Format::R32G32Sfloat),
+                3 => Some(Format::R32G32B32Sfloat),
+                4 => Some(Format::R32G32B32A32Sfloat),
+                _ => None,
+            },
+            (
+                Some(Type::Scalar {
+                    signed: true,
+                    width: 32,
+                    ..
+                }),
+                count,
+            ) => match count {
+                1 => Some(Format::R32Sint),
+                2 => Some(Format::R32G32Sint),
+                3 => Some(Format::R32G32B32Sint),
+                4 => Some(Format::R32G32B32A32Sint),
+                _ => None,
+            },
+            (Some(Type::Scalar { width: 32, .. }), count) => match count {
+                1 => Some(Format::R32Uint),
+                2 => Some(Format::R32G32Uint),
+                3 => Some(Format::R32G32B32Uint),
+                4 => Some(Format::R32G32B32A32Uint),
+                _ => None,
+            },
+            _ => None,
+        };
+        format.ok_or_else(|| format!("%{} has no interface format", id))
+    }
+
+    fn descriptor_ty(
+        &self,
+        id: u32,
+        storage: u32,
+    ) -> Result<DescriptorDescTy, String> {
+        let image = |image: u32| match self.types.get(&image) {
+            Some(Type::Image {
+                dim,
+                arrayed,
+                multisampled,
+                sampled,
+            }) => Ok((
+                DescriptorImageDesc {
+                    sampled: *sampled == 1,
+                    dimensions: match *dim {
+                        DIM_1D => DescriptorImageDescDimensions::OneDimensional,
+                        DIM_3D => {
+                            DescriptorImageDescDimensions::ThreeDimensional
+                        }
+                        DIM_CUBE => DescriptorImageDescDimensions::Cube,
+                        _ => DescriptorImageDescDimensions::TwoDimensional,
+                    },
+                    format: None,
+                    multisampled: *multisampled,
+                    array_layers: if *arrayed {
+                        DescriptorImageDescArray::Arrayed { max_layers: None }
+                    } else {
+                        DescriptorImageDescArray::NonArrayed
+                    },
+                },
+                *dim,
+            )),
+            _ => Err(format!("%{} is not an image type", image)),
+        };
+        let decorations = self.decorations.get(&id);
+        let buffer = |storage| {
+            DescriptorDescTy::Buffer(DescriptorBufferDesc {
+                dynamic: Some(false),
+                storage,
+            })
+        };
+        match (self.types.get(&id), storage) {
+            (Some(Type::Struct { .. }), STORAGE_STORAGE_BUFFER) => {
+                Ok(buffer(true))
+            }
+            (Some(Type::Struct { .. }), STORAGE_UNIFORM) => match decorations {
+                Some(d) if d.buffer_block => Ok(buffer(true)),
+                Some(d) if d.block => Ok(buffer(false)),
+                _ => Err(format!("%{} is not a block", id)),
+            },
+            (Some(Type::Sampler), _) => Ok(DescriptorDescTy::Sampler),
+            (Some(Type::SampledImage { image: inner }), _) => {
+                let (desc, _) = image(*inner)?;
+                Ok(DescriptorDescTy::CombinedImageSampler(desc))
+            }
+            (Some(Type::Image { .. }), _) => {
+                let (desc, dim) = image(id)?;
+                Ok(match dim {
+                    DIM_BUFFER => DescriptorDescTy::TexelBuffer {
+                        storage: !desc.sampled,
+                        format: None,
+                    },
+                    DIM_SUBPASS_DATA => DescriptorDescTy::InputAttachment {
+                        multisampled: desc.multisampled,
+                        array_layers: desc.array_layers,
+                    },
+                    _ => DescriptorDescTy::Image(desc),
+                })
+            }
+            _ => Err(format!("%{} is not a descriptor type", id)),
+        }
+    }
+
+    // Bytes up to the end of the last member, using the layout's explicit
+    // offsets and strides.
+    fn size(&self, id: u32, matrix_stride: Option<u32>) -> Result<u32, String> {
+        Ok(match self.types.get(&id) {
+            Some(Type::Scalar { width, .. }) => width / 8,
+            Some(Type::Vector { component, count }) => {
+                self.size(*component, None)? * count
+            }
+            Some(Type::Matrix { column, count }) => {
+                let stride = match matrix_stride {
+                    Some(stride) => stride,
+                    None => self.size(*column, None)?,
+                };
+                stride * count
+            }
+            Some(Type::Array { element, length }) => {
+                let stride = match self
+                    .decorations
+                    .get(&id)
+                    .and_then(|d| d.array_stride)
+                {
+                    Some(stride) => stride,
+                    None => self.size(*element, matrix_stride)?,
+                };
+                stride * self.constant(*length)?
+            }
+            Some(Type::Struct { members }) => {
+                let mut end = 0;
+                for (index, &member) in members.iter().enumerate() {
+                    let decorations = self.members.get(&(id, index as u32));
+                    let offset = decorations.map_or(0, |d| d.offset);
+                    let stride = decorations.and_then(|d| d.matrix_stride);
+                    end = end.max(offset + self.size(member, stride)?);
+                }
+                end
+            }
+            _ => return Err(format!("%{} has no size", id)),
+        })
+    }
+}
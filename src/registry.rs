@@ -0,0 +1,163 @@
+use crate::dbgpipe;
+use crate::error::Error;
+use crate::snapshot::Snapshot;
+use std::any::Any;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::device::Device;
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use winit::event::VirtualKeyCode;
+
+pub struct InitContext<'a> {
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+    pub format: Format,
+    pub scene: &'a dbgpipe::Pipeline,
+    pub state: &'a Snapshot,
+}
+
+pub struct FrameContext<'a> {
+    pub dynamic_state: &'a DynamicState,
+    pub view_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub state: &'a Snapshot,
+    // How far this frame falls between the last two fixed updates, 0 to 1.
+    pub alpha: f32,
+    pub frame: u64,
+}
+
+// A pluggable render feature. `update` runs once per fixed simulation step,
+// zero or more times between frames; `prepare` is recorded before any
+// render pass begins (compute, uploads); `draw_scene` is recorded inside
+// the forward scene pass, so its pipelines must use `InitContext::scene`'s
+// render pass.
+pub trait Feature {
+    fn name(&self) -> &str;
+
+    fn init(&mut self, _context: &InitContext) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn update(&mut self, _dt: f32, _state: &Snapshot) {}
+
+    fn prepare(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        _frame: &FrameContext,
+    ) -> Result<AutoCommandBufferBuilder, Error> {
+        Ok(builder)
+    }
+
+    fn draw_scene(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        _frame: &FrameContext,
+    ) -> Result<AutoCommandBufferBuilder, Error> {
+        Ok(builder)
+    }
+
+    fn key_pressed(&mut self, _key: VirtualKeyCode) -> bool {
+        false
+    }
+}
+
+pub type Asset = Box<dyn Any + Send + Sync>;
+
+pub trait AssetLoader {
+    fn extensions(&self) -> &[&str];
+
+    fn load(&self, path: &Path) -> Result<Asset, String>;
+}
+
+pub type InitHook = Box<dyn FnOnce(&InitContext)>;
+
+#[derive(Default)]
+pub struct Registry {
+    features: Vec<Box<dyn Feature>>,
+    loaders: Vec<Box<dyn AssetLoader>>,
+    init_hooks: Vec<InitHook>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    pub fn register_feature<F: Feature + 'static>(&mut self, feature: F) {
+        self.features.push(Box::new(feature));
+    }
+
+    pub fn register_loader<L: AssetLoader + 'static>(&mut self, loader: L) {
+        self.loaders.push(Box::new(loader));
+    }
+
+    pub fn on_init<F: FnOnce(&InitContext) + 'static>(&mut self, hook: F) {
+        self.init_hooks.push(Box::new(hook));
+    }
+
+    pub fn feature_names(&self) -> Vec<&str> {
+        self.features.iter().map(|feature| feature.name()).collect()
+    }
+
+    pub fn init(&mut self, context: &InitContext) -> Result<(), Error> {
+        for hook in self.init_hooks.drain(..) {
+            hook(context);
+        }
+        for feature in &mut self.features {
+            feature.init(context)?;
+        }
+        Ok(())
+    }
+
+    pub fn update(&mut self, dt: f32, state: &Snapshot) {
+        for feature in &mut self.features {
+            feature.update(dt, state);
+        }
+    }
+
+    pub fn prepare(
+        &mut self,
+        mut builder: AutoCommandBufferBuilder,
+        frame: &FrameContext,
+    ) -> Result<AutoCommandBufferBuilder, Error> {
+        for feature in &mut self.features {
+            builder = feature.prepare(builder, frame)?;
+        }
+        Ok(builder)
+    }
+
+    pub fn draw_scene(
+        &mut self,
+        mut builder: AutoCommandBufferBuilder,
+        frame: &FrameContext,
+    ) -> Result<AutoCommandBufferBuilder, Error> {
+        for feature in &mut self.features {
+            builder = feature.draw_scene(builder, frame)?;
+        }
+        Ok(builder)
+    }
+
+    // Offers a key to each feature in registration order until one takes it.
+    pub fn key_pressed(&mut self, key: VirtualKeyCode) -> bool {
+        self.features
+            .iter_mut()
+            .any(|feature| feature.key_pressed(key))
+    }
+
+    pub fn load<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Option<Result<Asset, String>> {
+        let path = path.as_ref();
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        info!(path = %path.display(), "loading asset");
+        self.loaders
+            .iter()
+            .find(|loader| loader.extensions().contains(&extension.as_str()))
+            .map(|loader| loader.load(path))
+    }
+}
@@ -0,0 +1,1063 @@
+use crate::error::Error;
+use crate::error::Result;
+use crate::hdr;
+use crate::indirect;
+use crate::screenshot;
+use crate::transfer::Uploader;
+use crate::validation;
+#[cfg(feature = "renderdoc")]
+use renderdoc::RenderDoc;
+#[cfg(feature = "renderdoc")]
+use renderdoc::V110;
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use tracing::debug;
+use tracing::error;
+use tracing::info;
+use tracing::info_span;
+use tracing::warn;
+use vulkano::command_buffer::AutoCommandBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::device::Device;
+use vulkano::device::DeviceExtensions;
+use vulkano::device::Features;
+use vulkano::device::Queue;
+use vulkano::device::RawDeviceExtensions;
+use vulkano::format::Format;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::image::AttachmentImage;
+use vulkano::image::SwapchainImage;
+use vulkano::instance::debug::DebugCallback;
+use vulkano::instance::debug::MessageSeverity;
+use vulkano::instance::Instance;
+use vulkano::instance::InstanceExtensions;
+use vulkano::instance::PhysicalDevice;
+use vulkano::instance::PhysicalDeviceType;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::swapchain;
+use vulkano::swapchain::AcquireError;
+use vulkano::swapchain::PresentMode;
+use vulkano::swapchain::SupportedPresentModes;
+use vulkano::swapchain::Surface;
+use vulkano::swapchain::SurfaceTransform;
+use vulkano::swapchain::Swapchain;
+use vulkano::swapchain::SwapchainAcquireFuture;
+use vulkano::swapchain::SwapchainCreationError;
+use vulkano::sync;
+use vulkano::sync::FlushError;
+use vulkano::sync::GpuFuture;
+use vulkano_win::VkSurfaceBuild;
+use winit::dpi::LogicalPosition;
+use winit::dpi::LogicalSize;
+use winit::event::ElementState;
+use winit::event::Event;
+use winit::event::KeyboardInput;
+use winit::event::VirtualKeyCode;
+use winit::event::WindowEvent;
+use winit::event_loop::ControlFlow;
+use winit::event_loop::EventLoop;
+use winit::monitor::MonitorHandle;
+use winit::monitor::VideoMode;
+use winit::window::Fullscreen;
+use winit::window::Icon;
+use winit::window::Window;
+use winit::window::WindowBuilder;
+
+// How often a paused loop wakes to check on the window.
+pub const PAUSED_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    Windowed,
+    Borderless,
+    Exclusive,
+}
+
+impl WindowMode {
+    pub fn next(self) -> WindowMode {
+        match self {
+            WindowMode::Windowed => WindowMode::Borderless,
+            WindowMode::Borderless => WindowMode::Exclusive,
+            WindowMode::Exclusive => WindowMode::Windowed,
+        }
+    }
+}
+
+impl Default for WindowMode {
+    fn default() -> Self {
+        WindowMode::Windowed
+    }
+}
+
+// Exclusive fullscreen resolution and refresh rate; whatever is left unset
+// takes the largest, fastest mode the monitor offers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VideoModeRequest {
+    pub size: Option<[u32; 2]>,
+    pub refresh_rate: Option<u16>,
+}
+
+// Forces a particular GPU, by its index in the enumeration or a
+// case-insensitive substring of its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceRequest {
+    Index(usize),
+    Name(String),
+}
+
+pub struct Options {
+    pub title: String,
+    // Logical size in points; None lets the platform pick.
+    pub size: Option<[u32; 2]>,
+    pub min_size: Option<[u32; 2]>,
+    pub resizable: bool,
+    // PNG bytes, e.g. from `include_bytes!`.
+    pub icon: Option<&'static [u8]>,
+    pub force_sdr: bool,
+    // None picks the best supported mode; see `select_present_mode`.
+    pub present_mode: Option<PresentMode>,
+    // Swapchain images to request, e.g. 2 for double or 3 for triple
+    // buffering; clamped to what the surface allows. None takes the
+    // surface minimum.
+    pub image_count: Option<u32>,
+    pub video_mode: VideoModeRequest,
+    // Index into the available monitors; None keeps the platform's choice
+    // unless `position` is set, which then places on the primary monitor.
+    pub monitor: Option<usize>,
+    // Logical offset from the monitor's top left corner; None centers the
+    // window on the chosen monitor.
+    pub position: Option<[i32; 2]>,
+    // Enables the Khronos validation layer, logging messages at this
+    // severity and above.
+    pub validation: Option<MessageSeverity>,
+    // None picks the best usable device; see `select_physical_device`.
+    pub device: Option<DeviceRequest>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            title: "vulkano-triangle".to_owned(),
+            size: None,
+            min_size: None,
+            resizable: true,
+            icon: None,
+            force_sdr: false,
+            present_mode: None,
+            image_count: None,
+            video_mode: VideoModeRequest::default(),
+            monitor: None,
+            position: None,
+            validation: None,
+            device: None,
+        }
+    }
+}
+
+// Instance, window, device and swapchain setup shared by the main binary
+// and the examples.
+pub struct Renderer {
+    pub instance: Arc<Instance>,
+    pub surface: Arc<Surface<Window>>,
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+    // A queue from a compute-only family when the device has one, so
+    // compute work can overlap graphics; otherwise the graphics queue.
+    pub compute_queue: Arc<Queue>,
+    // Likewise a transfer-only family for asset uploads.
+    pub transfer_queue: Arc<Queue>,
+    pub swapchain: Arc<Swapchain<Window>>,
+    pub images: Vec<Arc<SwapchainImage<Window>>>,
+    pub output: hdr::Output,
+    // Whether indirect draws take their count from the GPU.
+    pub count_mode: indirect::CountMode,
+    pub present_mode: PresentMode,
+    pub window_mode: WindowMode,
+    pub video_mode: VideoModeRequest,
+    // The window title from the options, for restoring after status text.
+    pub title: String,
+    pub needs_recreate: bool,
+    capture: Option<PathBuf>,
+    physical_index: usize,
+    _messenger: Option<DebugCallback>,
+    // Loaded before the instance so RenderDoc can hook it.
+    #[cfg(feature = "renderdoc")]
+    renderdoc: Option<RenderDoc<V110>>,
+}
+
+impl Renderer {
+    pub fn new(
+        events_loop: &EventLoop<()>,
+        options: &Options,
+    ) -> Result<Renderer> {
+        #[cfg(feature = "renderdoc")]
+        let renderdoc = RenderDoc::new().ok();
+        let instance = info_span!("instance").in_scope(|| {
+            validation::instance(
+                &vulkano_win::required_extensions(),
+                options.validation.is_some(),
+            )
+        })?;
+        let messenger = options
+            .validation
+            .and_then(|severity| validation::messenger(&instance, severity));
+
+        let surface = window_builder(options)
+            .build_vk_surface(events_loop, instance.clone())?;
+        let physical = select_physical_device(
+            &instance,
+            options.device.as_ref(),
+            |physical| {
+                DeviceExtensions::supported_by_device(physical).khr_swapchain
+                    && physical.queue_families().any(|q| {
+                        q.supports_graphics()
+                            && surface.is_supported(q).unwrap_or(false)
+                    })
+            },
+        )
+        .ok_or(Error::NoDevice("can present to the window"))?;
+        info!(name = %physical.name(), ty = ?physical.ty(), "selected device");
+        if options.monitor.is_some() || options.position.is_some() {
+            let window = surface.window();
+            let monitor = options
+                .monitor
+                .and_then(|index| window.available_monitors().nth(index))
+                .unwrap_or_else(|| window.primary_monitor());
+            place_window(window, &monitor, options.position);
+        }
+
+        let queue_family = physical
+            .queue_families()
+            .find(|&q| {
+                q.supports_graphics()
+                    && surface.is_supported(q).unwrap_or(false)
+            })
+            .unwrap();
+        let compute_family = physical
+            .queue_families()
+            .find(|&q| q.supports_compute() && !q.supports_graphics());
+        let transfer_family = physical.queue_families().find(|&q| {
+            q.explicitly_supports_transfers()
+                && !q.supports_graphics()
+                && !q.supports_compute()
+        });
+
+        let count_mode = indirect::CountMode::select(physical);
+        info!(?count_mode, "indirect draw count mode");
+        let mut device_ext = RawDeviceExtensions::from(&DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::none()
+        });
+        if count_mode == indirect::CountMode::GpuCount {
+            device_ext
+                .insert(CString::new(indirect::DRAW_INDIRECT_COUNT).unwrap());
+        }
+        let device_span = info_span!("device").entered();
+        let (device, mut queues) = Device::new(
+            physical,
+            &device_features(physical),
+            device_ext,
+            [(queue_family, 0.5)]
+                .iter()
+                .cloned()
+                .chain(compute_family.map(|family| (family, 0.5)))
+                .chain(transfer_family.map(|family| (family, 0.5))),
+        )?;
+
+        let queue = queues.next().unwrap();
+        let compute_queue = match compute_family {
+            Some(_) => queues.next().unwrap(),
+            None => queue.clone(),
+        };
+        let transfer_queue = match transfer_family {
+            Some(_) => queues.next().unwrap(),
+            None => queue.clone(),
+        };
+        info!(
+            family = compute_queue.family().id(),
+            dedicated = compute_family.is_some(),
+            "compute queue"
+        );
+        info!(
+            family = transfer_queue.family().id(),
+            dedicated = transfer_family.is_some(),
+            "transfer queue"
+        );
+        drop(device_span);
+
+        let caps = surface.capabilities(physical)?;
+        let present_mode =
+            select_present_mode(caps.present_modes, options.present_mode);
+        info!(?present_mode, "swapchain present mode");
+        let image_count = select_image_count(
+            caps.min_image_count,
+            caps.max_image_count,
+            options.image_count,
+        );
+
+        let (swapchain, images, output) = {
+            let usage = caps.supported_usage_flags;
+            let alpha = caps.supported_composite_alpha.iter().next().unwrap();
+            let (format, color_space, output) =
+                hdr::select(&caps.supported_formats, options.force_sdr);
+            info!(?format, ?color_space, "swapchain format");
+            let initial_dimensions = window_dimensions(surface.window());
+
+            let (swapchain, images) = Swapchain::new(
+                device.clone(),
+                surface.clone(),
+                image_count,
+                format,
+                initial_dimensions,
+                1,
+                usage,
+                &queue,
+                SurfaceTransform::Identity,
+                alpha,
+                present_mode,
+                true,
+                None,
+            )?;
+            (swapchain, images, output)
+        };
+        info!(count = images.len(), "swapchain images");
+
+        Ok(Renderer {
+            physical_index: physical.index(),
+            instance,
+            surface,
+            device,
+            queue,
+            compute_queue,
+            transfer_queue,
+            swapchain,
+            images,
+            output,
+            count_mode,
+            present_mode,
+            window_mode: WindowMode::Windowed,
+            video_mode: options.video_mode,
+            title: options.title.clone(),
+            needs_recreate: false,
+            capture: None,
+            _messenger: messenger,
+            #[cfg(feature = "renderdoc")]
+            renderdoc,
+        })
+    }
+
+    pub fn physical(&self) -> PhysicalDevice {
+        PhysicalDevice::from_index(&self.instance, self.physical_index).unwrap()
+    }
+
+    pub fn uploader(&self) -> Uploader {
+        Uploader::new(self.transfer_queue.clone(), self.queue.clone())
+    }
+
+    pub fn window(&self) -> &Window {
+        self.surface.window()
+    }
+
+    // A minimized window has no area to present to; skip frames until it
+    // is restored rather than failing to recreate the swapchain.
+    pub fn is_minimized(&self) -> bool {
+        let [width, height] = window_dimensions(self.window());
+        width == 0 || height == 0
+    }
+
+    // Physical pixels per logical point, for UI that should stay the same
+    // apparent size but render at native resolution.
+    pub fn scale_factor(&self) -> f64 {
+        self.window().hidpi_factor()
+    }
+
+    // Returns false when the surface currently can't be presented to, e.g.
+    // while minimized; try again on the next frame.
+    pub fn recreate(&mut self) -> Result<bool> {
+        let dimensions = window_dimensions(self.window());
+        let _span = info_span!("recreate_swapchain", ?dimensions).entered();
+        let recreated = if self.swapchain.present_mode() == self.present_mode {
+            self.swapchain.recreate_with_dimension(dimensions)
+        } else {
+            let caps = self.surface.capabilities(self.physical())?;
+            Swapchain::new(
+                self.device.clone(),
+                self.surface.clone(),
+                self.swapchain.num_images(),
+                self.swapchain.format(),
+                dimensions,
+                1,
+                caps.supported_usage_flags,
+                &self.queue,
+                SurfaceTransform::Identity,
+                self.swapchain.composite_alpha(),
+                self.present_mode,
+                true,
+                Some(&self.swapchain),
+            )
+        };
+        match recreated {
+            Ok((swapchain, images)) => {
+                self.swapchain = swapchain;
+                self.images = images;
+                self.needs_recreate = false;
+                debug!("swapchain recreated");
+                Ok(true)
+            }
+            Err(SwapchainCreationError::UnsupportedDimensions) => {
+                debug!("unsupported dimensions, retrying later");
+                Ok(false)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // Exclusive fullscreen takes the current monitor's video mode closest to
+    // `video_mode`, or falls back to borderless when it reports none. The
+    // swapchain must be recreated at the new size afterwards.
+    pub fn set_window_mode(&mut self, mode: WindowMode) -> WindowMode {
+        let monitor = self.window().current_monitor();
+        self.apply_window_mode(mode, monitor)
+    }
+
+    fn apply_window_mode(
+        &mut self,
+        mode: WindowMode,
+        monitor: MonitorHandle,
+    ) -> WindowMode {
+        let window = self.window();
+        let mode = match mode {
+            WindowMode::Windowed => {
+                window.set_fullscreen(None);
+                mode
+            }
+            WindowMode::Borderless => {
+                window.set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+                mode
+            }
+            WindowMode::Exclusive => {
+                match select_video_mode(
+                    monitor.video_modes().collect(),
+                    self.video_mode,
+                ) {
+                    Some(video_mode) => {
+                        window.set_fullscreen(Some(Fullscreen::Exclusive(
+                            video_mode,
+                        )));
+                        mode
+                    }
+                    None => {
+                        window.set_fullscreen(Some(Fullscreen::Borderless(
+                            monitor,
+                        )));
+                        WindowMode::Borderless
+                    }
+                }
+            }
+        };
+        self.window_mode = mode;
+        self.needs_recreate = true;
+        mode
+    }
+
+    // Applies straight away when already in exclusive fullscreen, otherwise
+    // the next time it's entered.
+    pub fn set_video_mode(&mut self, request: VideoModeRequest) {
+        self.video_mode = request;
+        if self.window_mode == WindowMode::Exclusive {
+            self.set_window_mode(WindowMode::Exclusive);
+        }
+    }
+
+    pub fn monitors(&self) -> Vec<MonitorHandle> {
+        self.window().available_monitors().collect()
+    }
+
+    // Moves a windowed window to `monitor`, see `Options::position`, or
+    // takes fullscreen there. The monitor's scale factor may differ, so the
+    // swapchain is recreated either way.
+    pub fn move_to_monitor(
+        &mut self,
+        monitor: &MonitorHandle,
+        position: Option<[i32; 2]>,
+    ) {
+        match self.window_mode {
+            WindowMode::Windowed => {
+                place_window(self.window(), monitor, position);
+                self.needs_recreate = true;
+            }
+            mode => {
+                self.apply_window_mode(mode, monitor.clone());
+            }
+        }
+    }
+
+    // Every mode the window's current monitor offers, largest and fastest
+    // first.
+    pub fn video_modes(&self) -> Vec<VideoMode> {
+        let mut modes: Vec<_> =
+            self.window().current_monitor().video_modes().collect();
+        modes.sort_by_key(|mode| std::cmp::Reverse(video_mode_rank(mode)));
+        modes
+    }
+
+    // Takes effect on the next `recreate`.
+    pub fn set_present_mode(&mut self, mode: PresentMode) -> PresentMode {
+        let caps = self.surface.capabilities(self.physical()).unwrap();
+        self.present_mode = select_present_mode(caps.present_modes, Some(mode));
+        self.needs_recreate = true;
+        self.present_mode
+    }
+
+    pub fn vsync(&self) -> bool {
+        match self.present_mode {
+            PresentMode::Fifo | PresentMode::Relaxed => true,
+            _ => false,
+        }
+    }
+
+    // Without vsync, Mailbox where available and Immediate otherwise.
+    pub fn set_vsync(&mut self, vsync: bool) -> PresentMode {
+        let caps = self.surface.capabilities(self.physical()).unwrap();
+        let mode = if vsync {
+            PresentMode::Fifo
+        } else if caps.present_modes.mailbox {
+            PresentMode::Mailbox
+        } else {
+            PresentMode::Immediate
+        };
+        self.set_present_mode(mode)
+    }
+
+    // The images the swapchain actually has, which may exceed the count
+    // asked for; size per-frame resources by this.
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn dynamic_state(&self) -> DynamicState {
+        let dimensions = self.images[0].dimensions();
+        DynamicState {
+            line_width: None,
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                depth_range: 0.0..1.0,
+            }]),
+            scissors: None,
+        }
+    }
+
+    // One framebuffer per swapchain image, with an optional shared depth
+    // attachment as the second attachment.
+    pub fn framebuffers(
+        &self,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+        depth_format: Option<Format>,
+    ) -> Vec<Arc<dyn FramebufferAbstract + Send + Sync>> {
+        let depth = depth_format.map(|format| {
+            AttachmentImage::transient(
+                self.device.clone(),
+                self.images[0].dimensions(),
+                format,
+            )
+            .unwrap()
+        });
+        self.images
+            .iter()
+            .map(|image| {
+                let framebuffer = Framebuffer::start(render_pass.clone())
+                    .add(image.clone())
+                    .unwrap();
+                match &depth {
+                    Some(depth) => Arc::new(
+                        framebuffer
+                            .add(depth.clone())
+                            .unwrap()
+                            .build()
+                            .unwrap(),
+                    )
+                        as Arc<dyn FramebufferAbstract + Send + Sync>,
+                    None => Arc::new(framebuffer.build().unwrap()),
+                }
+            })
+            .collect()
+    }
+
+    // None when the swapchain is out of date and must be recreated first.
+    pub fn acquire(
+        &mut self,
+    ) -> Result<Option<(usize, SwapchainAcquireFuture<Window>)>> {
+        match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+            Ok(r) => Ok(Some(r)),
+            Err(AcquireError::OutOfDate) => {
+                self.needs_recreate = true;
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // Has RenderDoc capture the next frame presented. False when the build
+    // lacks the `renderdoc` feature or the RenderDoc library isn't loaded.
+    #[cfg(feature = "renderdoc")]
+    pub fn capture_frame(&mut self) -> bool {
+        match self.renderdoc.as_mut() {
+            Some(renderdoc) => {
+                renderdoc.trigger_capture();
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[cfg(not(feature = "renderdoc"))]
+    pub fn capture_frame(&mut self) -> bool {
+        false
+    }
+
+    // Saves the next presented swapchain image as a PNG.
+    pub fn capture_next_frame<P: Into<PathBuf>>(&mut self, path: P) {
+        self.capture = Some(path.into());
+    }
+
+    // The copy of swapchain image `image_num` for a requested capture.
+    // `present` does this itself; loops that present on their own submit
+    // the command buffer before presenting and save once the fence
+    // signals.
+    pub fn take_capture(
+        &mut self,
+        image_num: usize,
+    ) -> Option<(screenshot::Pending, AutoCommandBuffer)> {
+        let path = self.capture.take()?;
+        Some(screenshot::Pending::record(
+            self.device.clone(),
+            &self.queue,
+            self.images[image_num].clone(),
+            self.swapchain.format(),
+            self.swapchain.dimensions(),
+            path,
+        ))
+    }
+
+    pub fn present<F>(
+        &mut self,
+        future: F,
+        image_num: usize,
+    ) -> Box<dyn GpuFuture>
+    where
+        F: GpuFuture + 'static,
+    {
+        let (pending, future) = match self.take_capture(image_num) {
+            Some((pending, copy)) => (
+                Some(pending),
+                Box::new(future.then_execute(self.queue.clone(), copy).unwrap())
+                    as Box<dyn GpuFuture>,
+            ),
+            None => (None, Box::new(future) as Box<_>),
+        };
+        let future = future
+            .then_swapchain_present(
+                self.queue.clone(),
+                self.swapchain.clone(),
+                image_num,
+            )
+            .then_signal_fence_and_flush();
+
+        match future {
+            Ok(future) => {
+                if let Some(pending) = pending {
+                    future.wait(None).unwrap();
+                    pending.save();
+                }
+                Box::new(future)
+            }
+            Err(FlushError::OutOfDate) => {
+                self.needs_recreate = true;
+                Box::new(sync::now(self.device.clone()))
+            }
+            Err(e) => {
+                error!(error = ?e, "frame submission failed");
+                Box::new(sync::now(self.device.clone()))
+            }
+        }
+    }
+}
+
+// The requested mode when the surface supports it, otherwise Mailbox for
+// low latency without tearing, falling back to Fifo, which is always
+// available.
+pub fn select_present_mode(
+    supported: SupportedPresentModes,
+    requested: Option<PresentMode>,
+) -> PresentMode {
+    match requested {
+        Some(mode) if supported.supports(mode) => mode,
+        Some(mode) => {
+            warn!(?mode, "present mode unsupported, using default");
+            select_present_mode(supported, None)
+        }
+        None if supported.mailbox => PresentMode::Mailbox,
+        None => PresentMode::Fifo,
+    }
+}
+
+// `max` is None when the surface sets no upper limit.
+pub fn select_image_count(
+    min: u32,
+    max: Option<u32>,
+    requested: Option<u32>,
+) -> u32 {
+    let count = requested.unwrap_or(min).max(min);
+    match max {
+        Some(max) => count.min(max),
+        None => count,
+    }
+}
+
+// Higher is better: discrete over integrated over anything else, then the
+// larger device-local heap.
+pub fn device_rank(physical: PhysicalDevice) -> (u32, usize) {
+    let kind = match physical.ty() {
+        PhysicalDeviceType::DiscreteGpu => 3,
+        PhysicalDeviceType::IntegratedGpu => 2,
+        PhysicalDeviceType::VirtualGpu => 1,
+        PhysicalDeviceType::Cpu | PhysicalDeviceType::Other => 0,
+    };
+    let memory = physical
+        .memory_heaps()
+        .filter(|heap| heap.is_device_local())
+        .map(|heap| heap.size())
+        .max()
+        .unwrap_or(0);
+    (kind, memory)
+}
+
+// The requested device when it is usable, otherwise the usable device with
+// the best `device_rank`. `usable` checks for the queue families and
+// extensions the caller needs.
+pub fn select_physical_device<'a, F>(
+    instance: &'a Arc<Instance>,
+    request: Option<&DeviceRequest>,
+    usable: F,
+) -> Option<PhysicalDevice<'a>>
+where
+    F: Fn(PhysicalDevice) -> bool,
+{
+    let candidates: Vec<_> = PhysicalDevice::enumerate(instance)
+        .filter(|&physical| {
+            let usable = usable(physical);
+            debug!(
+                index = physical.index(),
+                name = %physical.name(),
+                rank = ?device_rank(physical),
+                usable,
+                "found device"
+            );
+            usable
+        })
+        .collect();
+    let requested = request.and_then(|request| {
+        let found = candidates.iter().cloned().find(|physical| match request {
+            DeviceRequest::Index(index) => physical.index() == *index,
+            DeviceRequest::Name(name) => physical
+                .name()
+                .to_lowercase()
+                .contains(&name.to_lowercase()),
+        });
+        if found.is_none() {
+            warn!(?request, "requested device unusable, using the best");
+        }
+        found
+    });
+    requested.or_else(|| candidates.into_iter().max_by_key(|&p| device_rank(p)))
+}
+
+// `1` selects by index, anything else by name, e.g. `nvidia`.
+pub fn parse_device_request(text: &str) -> DeviceRequest {
+    match text.parse() {
+        Ok(index) => DeviceRequest::Index(index),
+        Err(_) => DeviceRequest::Name(text.to_owned()),
+    }
+}
+
+fn video_mode_rank(mode: &VideoMode) -> (u32, u16) {
+    let (width, height): (u32, u32) = mode.size().into();
+    (width * height, mode.refresh_rate())
+}
+
+// The best mode matching every part of `request` that is set. When nothing
+// matches, the best mode overall, since a wrong resolution beats no
+// fullscreen.
+pub fn select_video_mode(
+    modes: Vec<VideoMode>,
+    request: VideoModeRequest,
+) -> Option<VideoMode> {
+    let matches = |mode: &VideoMode| {
+        let (width, height): (u32, u32) = mode.size().into();
+        request.size.map_or(true, |size| size == [width, height])
+            && request
+                .refresh_rate
+                .map_or(true, |rate| rate == mode.refresh_rate())
+    };
+    let best = modes
+        .iter()
+        .filter(|mode| matches(mode))
+        .max_by_key(|mode| video_mode_rank(mode))
+        .cloned();
+    if best.is_none() {
+        warn!(?request, "no video mode matches, using the best");
+    }
+    best.or_else(|| modes.into_iter().max_by_key(video_mode_rank))
+}
+
+// `1920x1080`, `1920x1080@144` or `@144`.
+pub fn parse_video_mode(text: &str) -> Option<VideoModeRequest> {
+    let mut parts = text.splitn(2, '@');
+    let size = match parts.next()? {
+        "" => None,
+        size => {
+            let mut dimensions = size.splitn(2, 'x');
+            let width = dimensions.next()?.parse().ok()?;
+            let height = dimensions.next()?.parse().ok()?;
+            Some([width, height])
+        }
+    };
+    let refresh_rate = match parts.next() {
+        Some(rate) => Some(rate.parse().ok()?),
+        None => None,
+    };
+    Some(VideoModeRequest { size, refresh_rate })
+}
+
+pub fn parse_present_mode(name: &str) -> Option<PresentMode> {
+    match name {
+        "fifo" => Some(PresentMode::Fifo),
+        "relaxed" => Some(PresentMode::Relaxed),
+        "mailbox" => Some(PresentMode::Mailbox),
+        "immediate" => Some(PresentMode::Immediate),
+        _ => None,
+    }
+}
+
+pub fn place_window(
+    window: &Window,
+    monitor: &MonitorHandle,
+    position: Option<[i32; 2]>,
+) {
+    let scale = monitor.hidpi_factor();
+    let origin = monitor.position().to_logical(scale);
+    let [x, y] = position.unwrap_or_else(|| {
+        let area = monitor.size().to_logical(scale);
+        let size = window.outer_size();
+        [
+            ((area.width - size.width) / 2.0).max(0.0) as i32,
+            ((area.height - size.height) / 2.0).max(0.0) as i32,
+        ]
+    });
+    window.set_outer_position(LogicalPosition::new(
+        origin.x + x as f64,
+        origin.y + y as f64,
+    ));
+}
+
+pub fn window_builder(options: &Options) -> WindowBuilder {
+    let mut builder = WindowBuilder::new()
+        .with_title(options.title.clone())
+        .with_resizable(options.resizable)
+        .with_window_icon(options.icon.map(icon));
+    if let Some([width, height]) = options.size {
+        builder = builder
+            .with_inner_size(LogicalSize::new(width as f64, height as f64));
+    }
+    if let Some([width, height]) = options.min_size {
+        builder = builder
+            .with_min_inner_size(LogicalSize::new(width as f64, height as f64));
+    }
+    builder
+}
+
+pub fn icon(png: &[u8]) -> Icon {
+    let image = image::load_from_memory(png).unwrap().to_rgba();
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height).unwrap()
+}
+
+// Physical pixels, which is what the swapchain is sized in. winit reports
+// logical sizes, so this has to be recomputed when the scale factor
+// changes as well as on resize.
+pub fn window_dimensions(window: &Window) -> [u32; 2] {
+    let dimensions: (u32, u32) = window
+        .inner_size()
+        .to_physical(window.hidpi_factor())
+        .into();
+    [dimensions.0, dimensions.1]
+}
+
+// The optional features the passes use, each enabled only if the device
+// has it. Passes check `enabled_features` and skip or fall back without;
+// `passes::check_features` rejects the ones that can't.
+pub fn device_features(physical: PhysicalDevice) -> Features {
+    let supported = physical.supported_features();
+    Features {
+        // Occlusion proxies flag visible objects from the fragment shader.
+        fragment_stores_and_atomics: supported.fragment_stores_and_atomics,
+        // GPU culling draws its whole bucket in one indirect call.
+        multi_draw_indirect: supported.multi_draw_indirect,
+        fill_mode_non_solid: supported.fill_mode_non_solid,
+        tessellation_shader: supported.tessellation_shader,
+        geometry_shader: supported.geometry_shader,
+        // OIT accumulates into two attachments with different blends.
+        independent_blend: supported.independent_blend,
+        // Particles are points larger than a pixel.
+        large_points: supported.large_points,
+        // Sprites pick their texture from an array by push constant.
+        shader_sampled_image_array_dynamic_indexing: supported
+            .shader_sampled_image_array_dynamic_indexing,
+        ..Features::none()
+    }
+}
+
+// Device and queue without a window, for compute-only tools and offscreen
+// rendering. Prefers a family that can also draw.
+pub fn headless_device() -> (Arc<Device>, Arc<Queue>) {
+    find_headless_device().expect("no Vulkan device supports compute")
+}
+
+// As `headless_device`, but None when there's no Vulkan loader or device,
+// so tests can skip instead of failing on machines without a GPU.
+pub fn find_headless_device() -> Option<(Arc<Device>, Arc<Queue>)> {
+    let instance =
+        Instance::new(None, &InstanceExtensions::none(), None).ok()?;
+    let physical = select_physical_device(&instance, None, |physical| {
+        physical.queue_families().any(|q| q.supports_compute())
+    })?;
+    let queue_family = physical
+        .queue_families()
+        .find(|&q| q.supports_graphics() && q.supports_compute())
+        .or_else(|| physical.queue_families().find(|&q| q.supports_compute()))
+        .unwrap();
+    let (device, mut queues) = Device::new(
+        physical,
+        &device_features(physical),
+        &DeviceExtensions::none(),
+        [(queue_family, 0.5)].iter().cloned(),
+    )
+    .ok()?;
+    Some((device, queues.next().unwrap()))
+}
+
+pub trait App {
+    // Called at startup and whenever the swapchain images change.
+    fn resized(&mut self, renderer: &Renderer);
+
+    fn draw(
+        &mut self,
+        renderer: &Renderer,
+        image_num: usize,
+        builder: AutoCommandBufferBuilder,
+    ) -> AutoCommandBufferBuilder;
+
+    fn key_pressed(&mut self, _key: VirtualKeyCode) {}
+}
+
+// Minimal frame loop for the examples: recreate, acquire, record, present.
+pub fn run<A: App + 'static>(
+    mut renderer: Renderer,
+    events_loop: EventLoop<()>,
+    mut app: A,
+) -> ! {
+    app.resized(&renderer);
+    let mut previous_frame_end = Some(Box::new(sync::now(
+        renderer.device.clone(),
+    )) as Box<dyn GpuFuture>);
+
+    events_loop.run(move |ev, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        previous_frame_end.as_mut().unwrap().cleanup_finished();
+        match ev {
+            Event::EventsCleared if renderer.is_minimized() => {
+                *control_flow =
+                    ControlFlow::WaitUntil(Instant::now() + PAUSED_POLL)
+            }
+            Event::EventsCleared => renderer.window().request_redraw(),
+            Event::WindowEvent {
+                event: WindowEvent::RedrawRequested,
+                ..
+            } => {
+                if renderer.needs_recreate {
+                    match renderer.recreate() {
+                        Ok(true) => app.resized(&renderer),
+                        Ok(false) => return,
+                        Err(err) => {
+                            error!("{}", err);
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+                    }
+                }
+
+                let (image_num, acquire_future) = match renderer.acquire() {
+                    Ok(Some(r)) => r,
+                    Ok(None) => return,
+                    Err(err) => {
+                        error!("{}", err);
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                };
+
+                let builder =
+                    AutoCommandBufferBuilder::primary_one_time_submit(
+                        renderer.device.clone(),
+                        renderer.queue.family(),
+                    )
+                    .unwrap();
+                let command_buffer =
+                    app.draw(&renderer, image_num, builder).build().unwrap();
+
+                let future = previous_frame_end
+                    .take()
+                    .unwrap()
+                    .join(acquire_future)
+                    .then_execute(renderer.queue.clone(), command_buffer)
+                    .unwrap();
+                previous_frame_end = Some(renderer.present(future, image_num));
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            Event::WindowEvent {
+                event: WindowEvent::Resized(_),
+                ..
+            }
+            | Event::WindowEvent {
+                event: WindowEvent::HiDpiFactorChanged(_),
+                ..
+            } => renderer.needs_recreate = true,
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => app.key_pressed(key),
+            _ => (),
+        }
+    })
+}
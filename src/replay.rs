@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use winit::dpi::LogicalPosition;
+use winit::event::DeviceId;
+use winit::event::ElementState;
+use winit::event::KeyboardInput;
+use winit::event::ModifiersState;
+use winit::event::MouseButton;
+use winit::event::MouseScrollDelta;
+use winit::event::TouchPhase;
+use winit::event::VirtualKeyCode;
+use winit::event::WindowEvent;
+
+// The window events that count as user input, in a form that can be
+// written out and turned back into events.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Input {
+    Key {
+        scancode: u32,
+        state: ElementState,
+        key: Option<VirtualKeyCode>,
+        modifiers: ModifiersState,
+    },
+    Character(char),
+    CursorMoved {
+        x: f64,
+        y: f64,
+        modifiers: ModifiersState,
+    },
+    Button {
+        state: ElementState,
+        button: MouseButton,
+        modifiers: ModifiersState,
+    },
+    Wheel {
+        delta: MouseScrollDelta,
+        modifiers: ModifiersState,
+    },
+}
+
+impl Input {
+    pub fn from_event(event: &WindowEvent) -> Option<Input> {
+        Some(match *event {
+            WindowEvent::KeyboardInput { input, .. } => Input::Key {
+                scancode: input.scancode,
+                state: input.state,
+                key: input.virtual_keycode,
+                modifiers: input.modifiers,
+            },
+            WindowEvent::ReceivedCharacter(c) => Input::Character(c),
+            WindowEvent::CursorMoved {
+                position,
+                modifiers,
+                ..
+            } => Input::CursorMoved {
+                x: position.x,
+                y: position.y,
+                modifiers,
+            },
+            WindowEvent::MouseInput {
+                state,
+                button,
+                modifiers,
+                ..
+            } => Input::Button {
+                state,
+                button,
+                modifiers,
+            },
+            WindowEvent::MouseWheel {
+                delta, modifiers, ..
+            } => Input::Wheel { delta, modifiers },
+            _ => return None,
+        })
+    }
+
+    pub fn to_event(&self) -> WindowEvent {
+        // Replayed events didn't come from a device; apps that tell
+        // devices apart see them all as the same one.
+        let device_id = unsafe { DeviceId::dummy() };
+        match *self {
+            Input::Key {
+                scancode,
+                state,
+                key,
+                modifiers,
+            } => WindowEvent::KeyboardInput {
+                device_id,
+                input: KeyboardInput {
+                    scancode,
+                    state,
+                    virtual_keycode: key,
+                    modifiers,
+                },
+            },
+            Input::Character(c) => WindowEvent::ReceivedCharacter(c),
+            Input::CursorMoved { x, y, modifiers } => {
+                WindowEvent::CursorMoved {
+                    device_id,
+                    position: LogicalPosition::new(x, y),
+                    modifiers,
+                }
+            }
+            Input::Button {
+                state,
+                button,
+                modifiers,
+            } => WindowEvent::MouseInput {
+                device_id,
+                state,
+                button,
+                modifiers,
+            },
+            Input::Wheel { delta, modifiers } => WindowEvent::MouseWheel {
+                device_id,
+                delta,
+                phase: TouchPhase::Moved,
+                modifiers,
+            },
+        }
+    }
+}
+
+// One line of a recording: input that arrived once `frame` frames had
+// been rendered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    frame: u64,
+    input: Input,
+}
+
+// Appends input to a JSON lines file as it arrives.
+pub struct Recorder {
+    writer: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Recorder> {
+        Ok(Recorder {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn record(
+        &mut self,
+        frame: u64,
+        event: &WindowEvent,
+    ) -> io::Result<()> {
+        let input = match Input::from_event(event) {
+            Some(input) => input,
+            None => return Ok(()),
+        };
+        serde_json::to_writer(&mut self.writer, &Entry { frame, input })?;
+        writeln!(self.writer)?;
+        // Keep what was captured if the app crashes, which is when a
+        // recording is most wanted.
+        self.writer.flush()
+    }
+}
+
+// Hands a recording's input back out at the frames it arrived on. Frame
+// counts only line up with the recording if the app steps the same way,
+// e.g. with a fixed frame time.
+pub struct Replay {
+    entries: VecDeque<Entry>,
+    due: VecDeque<Input>,
+}
+
+impl Replay {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        let mut entries = VecDeque::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            entries.push_back(serde_json::from_str(&line)?);
+        }
+        Ok(Replay {
+            entries,
+            due: VecDeque::new(),
+        })
+    }
+
+    // Moves input recorded at or before `frame` to the due queue and
+    // returns how many became due.
+    pub fn advance(&mut self, frame: u64) -> usize {
+        let mut count = 0;
+        while self.entries.front().map_or(false, |e| e.frame <= frame) {
+            let entry = self.entries.pop_front().unwrap();
+            self.due.push_back(entry.input);
+            count += 1;
+        }
+        count
+    }
+
+    pub fn next(&mut self) -> Option<WindowEvent> {
+        self.due.pop_front().map(|input| input.to_event())
+    }
+
+    pub fn finished(&self) -> bool {
+        self.entries.is_empty() && self.due.is_empty()
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use vulkano::buffer::BufferSlice;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::device::Device;
+
+pub type Slice<T> = BufferSlice<[T], Arc<CpuAccessibleBuffer<[T]>>>;
+
+// Linear allocator for data that only lives for one frame. Each frame in
+// flight gets its own host-visible buffer, which vulkano keeps mapped for
+// the buffer's lifetime; `begin_frame` rewinds to the oldest one.
+pub struct Ring<T> {
+    device: Arc<Device>,
+    usage: BufferUsage,
+    frames: Vec<Arc<CpuAccessibleBuffer<[T]>>>,
+    capacity: usize,
+    frame: usize,
+    cursor: usize,
+}
+
+impl<T> Ring<T>
+where
+    T: Clone + Default + Send + Sync + 'static,
+{
+    pub fn new(
+        device: Arc<Device>,
+        capacity: usize,
+        frames: usize,
+        usage: BufferUsage,
+    ) -> Ring<T> {
+        let frames = (0..frames.max(1))
+            .map(|_| create(device.clone(), capacity, usage))
+            .collect();
+        Ring {
+            device,
+            usage,
+            frames,
+            capacity,
+            frame: 0,
+            cursor: 0,
+        }
+    }
+
+    // The GPU still holds a buffer whose frame hasn't been cleaned up yet;
+    // rather than stall, that slot gets a fresh buffer and the old one is
+    // dropped once its frame retires.
+    pub fn begin_frame(&mut self) {
+        self.frame = (self.frame + 1) % self.frames.len();
+        self.cursor = 0;
+        if self.frames[self.frame].write().is_err() {
+            self.frames[self.frame] =
+                create(self.device.clone(), self.capacity, self.usage);
+        }
+    }
+
+    // None when this frame's buffer is full.
+    pub fn push(&mut self, data: &[T]) -> Option<Slice<T>> {
+        let start = self.cursor;
+        let end = start + data.len();
+        if end > self.capacity {
+            return None;
+        }
+        let buffer = &self.frames[self.frame];
+        buffer.write().unwrap()[start..end].clone_from_slice(data);
+        self.cursor = end;
+        BufferSlice::from_typed_buffer_access(buffer.clone()).slice(start..end)
+    }
+
+    pub fn used(&self) -> usize {
+        self.cursor
+    }
+}
+
+fn create<T>(
+    device: Arc<Device>,
+    capacity: usize,
+    usage: BufferUsage,
+) -> Arc<CpuAccessibleBuffer<[T]>>
+where
+    T: Clone + Default + Send + Sync + 'static,
+{
+    CpuAccessibleBuffer::from_iter(
+        device,
+        usage,
+        vec![T::default(); capacity].into_iter(),
+    )
+    .unwrap()
+}
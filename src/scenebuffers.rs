@@ -0,0 +1,141 @@
+use crate::arena::Allocation;
+use crate::arena::Arena;
+use crate::culling;
+use crate::culling::CpuCuller;
+use crate::culling::Culler;
+use crate::dbgpipe;
+use crate::dbgpipe::Vertex;
+use crate::error::Error;
+use crate::error::Result;
+use crate::indirect::Bucket;
+use crate::indirect::CountMode;
+use crate::lightmap;
+use crate::lightmappipe;
+use crate::lod::LodMesh;
+use crate::occlusion::Occlusion;
+use crate::transfer::Uploader;
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::DeviceLocalBuffer;
+use vulkano::device::Device;
+use vulkano::sync::GpuFuture;
+
+// Which of the optional per-scene structures to build. Decided once at
+// startup from the settings and the device's features.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    pub gpu_cull: bool,
+    // How GPU-culled draws get their count.
+    pub count_mode: CountMode,
+    pub cpu_cull: bool,
+    pub occlusion: bool,
+    // Levels per LOD mesh, None without LODs.
+    pub lod_levels: Option<usize>,
+}
+
+// The opaque scene's triangles on the GPU and everything built from them:
+// culling bounds, LOD meshes and occlusion proxies. Rebuilt whole when the
+// triangles change.
+pub struct SceneBuffers {
+    pub allocation: Allocation,
+    pub vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+    pub lightmap_vertex_buffer: Arc<DeviceLocalBuffer<[lightmappipe::Vertex]>>,
+    pub lods: Option<Vec<LodMesh>>,
+    pub gpu_culling: Option<(Culler, Bucket)>,
+    pub cpu_culling: Option<CpuCuller>,
+    pub occlusion: Option<Occlusion>,
+    pub triangles: u64,
+}
+
+impl SceneBuffers {
+    pub fn new(
+        device: Arc<Device>,
+        uploader: &Uploader,
+        arena: &mut Arena<Vertex>,
+        debug: &dbgpipe::Pipeline,
+        scene: &[[f32; 4]],
+        options: Options,
+    ) -> Result<(SceneBuffers, Box<dyn GpuFuture>)> {
+        let (allocation, vertex_upload) = arena
+            .upload(
+                uploader,
+                scene.iter().map(|&position| Vertex { position }).collect(),
+            )
+            .ok_or(Error::ArenaFull("scene"))?;
+        let vertex_buffer = Arc::new(arena.slice(allocation))
+            as Arc<dyn BufferAccess + Send + Sync>;
+        let mut future = Box::new(vertex_upload) as Box<dyn GpuFuture>;
+
+        let lods = match options.lod_levels {
+            Some(levels) => {
+                let (lods, upload) =
+                    LodMesh::upload_scene(arena, uploader, scene, levels)
+                        .ok_or(Error::ArenaFull("scene LODs"))?;
+                future = Box::new(future.join(upload));
+                Some(lods)
+            }
+            None => None,
+        };
+
+        let (lightmap_vertex_buffer, lightmap_upload) = uploader.buffer(
+            scene
+                .iter()
+                .zip(lightmap::uvs(scene.len() / 3, lightmap::SIZE))
+                .map(|(&position, lightmap_uv)| lightmappipe::Vertex {
+                    position,
+                    lightmap_uv,
+                })
+                .collect(),
+            BufferUsage::vertex_buffer(),
+        );
+        future = Box::new(future.join(lightmap_upload));
+
+        let gpu_culling = if options.gpu_cull {
+            let objects = culling::objects(scene);
+            let bucket = Bucket::new(
+                device.clone(),
+                options.count_mode,
+                objects.len() as u32,
+            );
+            let culler = Culler::new(device.clone(), objects, &bucket);
+            Some((culler, bucket))
+        } else {
+            None
+        };
+        let cpu_culling = if options.cpu_cull {
+            Some(CpuCuller::new(culling::objects(scene)))
+        } else {
+            None
+        };
+        let occlusion = if options.occlusion {
+            Some(Occlusion::new(device, debug, culling::objects(scene)))
+        } else {
+            None
+        };
+
+        Ok((
+            SceneBuffers {
+                allocation,
+                vertex_buffer,
+                lightmap_vertex_buffer,
+                lods,
+                gpu_culling,
+                cpu_culling,
+                occlusion,
+                triangles: scene.len() as u64 / 3,
+            },
+            future,
+        ))
+    }
+
+    // Returns the arena ranges. The GPU must be done with them.
+    pub fn free(self, arena: &mut Arena<Vertex>) {
+        arena.free(self.allocation);
+        for lod in self.lods.into_iter().flatten() {
+            for level in lod.levels {
+                arena.free(level);
+            }
+        }
+    }
+}
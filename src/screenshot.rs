@@ -0,0 +1,157 @@
+use image::RgbaImage;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::error;
+use tracing::info;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::command_buffer::AutoCommandBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::device::Device;
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::ImageAccess;
+
+// A copy of one rendered image on its way back to the host. Submit the
+// command buffer from `record` after the frame's commands and before
+// present, then call `save` once its fence has signalled.
+pub struct Pending {
+    path: PathBuf,
+    format: Format,
+    dimensions: [u32; 2],
+    buffer: Arc<CpuAccessibleBuffer<[u8]>>,
+}
+
+impl Pending {
+    pub fn record<I>(
+        device: Arc<Device>,
+        queue: &Queue,
+        image: I,
+        format: Format,
+        dimensions: [u32; 2],
+        path: PathBuf,
+    ) -> (Pending, AutoCommandBuffer)
+    where
+        I: ImageAccess + Send + Sync + 'static,
+    {
+        let size =
+            dimensions[0] * dimensions[1] * format.size().unwrap() as u32;
+        let buffer = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_destination(),
+            (0..size).map(|_| 0u8),
+        )
+        .unwrap();
+        let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
+            device,
+            queue.family(),
+        )
+        .unwrap()
+        .copy_image_to_buffer(image, buffer.clone())
+        .unwrap()
+        .build()
+        .unwrap();
+        let pending = Pending {
+            path,
+            format,
+            dimensions,
+            buffer,
+        };
+        (pending, command_buffer)
+    }
+
+    pub fn save(self) {
+        let data = self.buffer.read().unwrap();
+        let image = match to_rgba(self.format, self.dimensions, &data) {
+            Some(image) => image,
+            None => {
+                error!(format = ?self.format, "can't capture this format");
+                return;
+            }
+        };
+        match image.save(&self.path) {
+            Ok(()) => info!(path = %self.path.display(), "saved screenshot"),
+            Err(e) => error!(error = ?e, "failed to save screenshot"),
+        }
+    }
+}
+
+// sRGB-encoded 8-bit RGBA, as PNG expects. Srgb formats are stored encoded
+// already; Unorm and scRGB float formats hold linear values and are
+// encoded here. HDR10's PQ curve would need tone mapping, so it's None.
+pub fn to_rgba(
+    format: Format,
+    dimensions: [u32; 2],
+    data: &[u8],
+) -> Option<RgbaImage> {
+    let pixels: Vec<u8> = match format {
+        Format::R8G8B8A8Srgb
+        | Format::B8G8R8A8Srgb
+        | Format::R8G8B8A8Unorm
+        | Format::B8G8R8A8Unorm => {
+            let bgra =
+                matches!(format, Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb);
+            let srgb =
+                matches!(format, Format::B8G8R8A8Srgb | Format::R8G8B8A8Srgb);
+            data.chunks(4)
+                .flat_map(|p| {
+                    let mut texel = [p[0], p[1], p[2], p[3]];
+                    if bgra {
+                        texel.swap(0, 2);
+                    }
+                    if !srgb {
+                        for c in &mut texel[..3] {
+                            *c = encode(*c);
+                        }
+                    }
+                    texel.to_vec()
+                })
+                .collect()
+        }
+        Format::R16G16B16A16Sfloat => data
+            .chunks(8)
+            .flat_map(|p| {
+                let channel = |i: usize| {
+                    half(u16::from_le_bytes([p[i * 2], p[i * 2 + 1]]))
+                };
+                vec![
+                    quantize(to_srgb(channel(0))),
+                    quantize(to_srgb(channel(1))),
+                    quantize(to_srgb(channel(2))),
+                    quantize(channel(3)),
+                ]
+            })
+            .collect(),
+        _ => return None,
+    };
+    RgbaImage::from_raw(dimensions[0], dimensions[1], pixels)
+}
+
+fn encode(c: u8) -> u8 {
+    quantize(to_srgb(c as f32 / 255.0))
+}
+
+fn quantize(c: f32) -> u8 {
+    (c.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+fn to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// IEEE 754 binary16 to f32.
+fn half(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * std::f32::INFINITY,
+        0x1f => std::f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
@@ -0,0 +1,70 @@
+use crate::error::Error;
+use crate::error::Result;
+use rayon::prelude::*;
+use std::sync::Arc;
+use vulkano::command_buffer::AutoCommandBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::device::Device;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::instance::QueueFamily;
+
+// Records the draws of one subpass, to be executed from a primary buffer
+// whose render pass was begun with `secondary: true`.
+pub fn builder(
+    device: Arc<Device>,
+    family: QueueFamily,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    subpass: u32,
+) -> Result<AutoCommandBufferBuilder> {
+    let builder = AutoCommandBufferBuilder::secondary_graphics_one_time_submit(
+        device,
+        family,
+        Subpass::from(render_pass, subpass).unwrap(),
+    )?;
+    Ok(builder)
+}
+
+pub type Job<'a> = Box<
+    dyn FnOnce(AutoCommandBufferBuilder) -> Result<AutoCommandBufferBuilder>
+        + Send
+        + 'a,
+>;
+
+// Records each job into its own secondary buffer on rayon's thread pool
+// and returns them in job order. Builders are created on the recording
+// thread because vulkano's standard command pools are per-thread; the
+// pool's threads live on, so their command pools are reused each frame.
+pub fn record_parallel(
+    device: Arc<Device>,
+    family: QueueFamily,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    subpass: u32,
+    jobs: Vec<Job>,
+) -> Result<Vec<AutoCommandBuffer>> {
+    let family_id = family.id();
+    jobs.into_par_iter()
+        .map(|job| -> Result<AutoCommandBuffer> {
+            let physical = device.physical_device();
+            let family = physical
+                .queue_family_by_id(family_id)
+                .ok_or(Error::QueueFamily(family_id))?;
+            let builder =
+                builder(device.clone(), family, render_pass.clone(), subpass)?;
+            Ok(job(builder)?.build()?)
+        })
+        .collect()
+}
+
+pub fn execute(
+    mut builder: AutoCommandBufferBuilder,
+    secondaries: Vec<AutoCommandBuffer>,
+) -> Result<AutoCommandBufferBuilder> {
+    for secondary in secondaries {
+        // vulkano doesn't yet check resource synchronization inside
+        // secondaries; ours are recorded for the current frame against the
+        // same resources the primary submission keeps alive.
+        builder = unsafe { builder.execute_commands(secondary)? };
+    }
+    Ok(builder)
+}
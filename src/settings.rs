@@ -0,0 +1,83 @@
+use crate::renderer::Renderer;
+use crate::renderer::WindowMode;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use tracing::info;
+
+// Preferences kept between runs. Command line flags take precedence over
+// these; `remember` picks up the window and vsync changes made while
+// running so they can be written back on exit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // None keeps the default present mode.
+    pub vsync: Option<bool>,
+    // An index or name substring, as for `--device`.
+    pub device: Option<String>,
+    pub window: WindowSettings,
+    pub graphics: Graphics,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    // Logical size of the windowed window.
+    pub size: Option<[u32; 2]>,
+    pub monitor: Option<usize>,
+    pub mode: WindowMode,
+}
+
+// Each is also enabled by its command line flag.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Graphics {
+    pub deferred: bool,
+    pub oit: bool,
+    pub taa: bool,
+    pub lod: bool,
+    pub occlusion: bool,
+    pub tessellation: bool,
+    pub motion_blur: bool,
+    pub motion_blur_samples: Option<u32>,
+    pub sdr: bool,
+}
+
+impl Settings {
+    // A missing file gives the defaults.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Settings> {
+        let path = path.as_ref();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Settings::default())
+            }
+            Err(e) => return Err(e),
+        };
+        info!(path = %path.display(), "loading settings");
+        toml::from_str(&text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let text = toml::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    pub fn remember(&mut self, renderer: &Renderer) {
+        let window = renderer.window();
+        self.vsync = Some(renderer.vsync());
+        self.window.mode = renderer.window_mode;
+        if renderer.window_mode == WindowMode::Windowed {
+            let size = window.inner_size();
+            self.window.size = Some([size.width as u32, size.height as u32]);
+        }
+        let current = window.current_monitor().name();
+        self.window.monitor = renderer
+            .monitors()
+            .iter()
+            .position(|monitor| monitor.name() == current);
+    }
+}
@@ -0,0 +1,183 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use tracing::warn;
+use vulkano::instance::PhysicalDevice;
+
+// Compiled SPIR-V, kept per platform and driver. Pipelines aren't cached
+// here: vulkano 0.14's graphics and compute pipeline builders always pass
+// a null VkPipelineCache, so a saved cache would never be filled or read.
+// Until they take one, the driver's own cache is all there is.
+#[derive(Clone)]
+pub struct ShaderCache {
+    dir: PathBuf,
+}
+
+impl ShaderCache {
+    pub fn new<P: AsRef<Path>>(
+        root: P,
+        physical: PhysicalDevice,
+    ) -> io::Result<ShaderCache> {
+        let dir = root
+            .as_ref()
+            .join(format!(
+                "{}-{}",
+                std::env::consts::OS,
+                std::env::consts::ARCH
+            ))
+            .join(format!(
+                "{:04x}-{:04x}-{:08x}",
+                physical.pci_vendor_id(),
+                physical.pci_device_id(),
+                physical.driver_version()
+            ));
+        fs::create_dir_all(&dir)?;
+        Ok(ShaderCache { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Compiled SPIR-V for `source`, from disk when neither it nor any
+    // header it includes from `include_dirs` has changed since it was
+    // cached, otherwise from `compile`.
+    pub fn spirv<F, E>(
+        &self,
+        name: &str,
+        source: &str,
+        include_dirs: &[PathBuf],
+        compile: F,
+    ) -> Result<Vec<u32>, E>
+    where
+        F: FnOnce(&str) -> Result<Vec<u32>, E>,
+    {
+        let mut key = source.as_bytes().to_vec();
+        for header in includes(source, include_dirs) {
+            if let Ok(content) = fs::read(&header) {
+                key.extend_from_slice(&content);
+            }
+        }
+        let path = self.dir.join(format!("{}-{:016x}.spv", name, hash(&key)));
+
+        if let Ok(bytes) = fs::read(&path) {
+            if bytes.len() % 4 == 0 {
+                debug!(path = %path.display(), "loaded cached spir-v");
+                return Ok(words(&bytes));
+            }
+        }
+
+        let spirv = compile(source)?;
+        self.evict(name);
+        if let Err(e) = fs::write(&path, bytes(&spirv)) {
+            warn!(path = %path.display(), error = ?e, "failed to cache");
+        }
+        Ok(spirv)
+    }
+
+    fn evict(&self, name: &str) {
+        let prefix = format!("{}-", name);
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            if file_name.starts_with(&prefix) && file_name.ends_with(".spv") {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+// Every header `source` pulls in through `#include`, directly or through
+// other headers, resolved against `dirs` in order. Headers that can't be
+// found are left for the compiler to report.
+pub fn includes(source: &str, dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![source.to_owned()];
+    while let Some(source) = pending.pop() {
+        for name in source.lines().filter_map(include_name) {
+            let path = match dirs
+                .iter()
+                .map(|dir| dir.join(name))
+                .find(|path| path.is_file())
+            {
+                Some(path) => path,
+                None => continue,
+            };
+            if found.contains(&path) {
+                continue;
+            }
+            if let Ok(header) = fs::read_to_string(&path) {
+                pending.push(header);
+            }
+            found.push(path);
+        }
+    }
+    found
+}
+
+// `foo.glsl` from `#include <foo.glsl>` or `#include "foo.glsl"`.
+fn include_name(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix("include")?.trim();
+    let close = match rest.chars().next()? {
+        '<' => '>',
+        '"' => '"',
+        _ => return None,
+    };
+    let rest = &rest[1..];
+    rest.find(close).map(|end| &rest[..end])
+}
+
+// FNV-1a, stable across runs and toolchains unlike `DefaultHasher`.
+pub fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn words(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect()
+}
+
+fn bytes(words: &[u32]) -> Vec<u8> {
+    words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn include_names() {
+        assert_eq!(include_name("#include <a.glsl>"), Some("a.glsl"));
+        assert_eq!(include_name("  # include \"b.glsl\""), Some("b.glsl"));
+        assert_eq!(include_name("// #include <c.glsl>"), None);
+        assert_eq!(include_name("#version 450"), None);
+    }
+
+    #[test]
+    fn includes_follow_nested_headers() {
+        let dir = std::env::temp_dir()
+            .join(format!("shadercache-includes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.glsl"), "#include <b.glsl>\n").unwrap();
+        fs::write(dir.join("b.glsl"), "#include <a.glsl>\n").unwrap();
+        let found = includes(
+            "#include <a.glsl>\n#include <missing.glsl>\n",
+            std::slice::from_ref(&dir),
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(found, vec![dir.join("a.glsl"), dir.join("b.glsl")]);
+    }
+}
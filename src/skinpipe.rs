@@ -0,0 +1,253 @@
+use crate::dbgpipe;
+use cgmath::Deg;
+use cgmath::Matrix4;
+use cgmath::SquareMatrix;
+use cgmath::Vector3;
+use std::sync::Arc;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::buffer::CpuBufferPool;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::framebuffer::Subpass;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+#[derive(Debug, Clone, Default)]
+pub struct Vertex {
+    pub position: [f32; 4],
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+vulkano::impl_vertex!(Vertex, position, joints, weights);
+
+pub mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+layout (location = 0) in vec4 position;
+layout (location = 1) in uvec4 joints;
+layout (location = 2) in vec4 weights;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+// one skinning matrix (joint world transform * inverse bind) per joint
+layout (set = 1, binding = 0) readonly buffer Bones {
+    mat4 bones[];
+} palette;
+
+// position offsets of every vertex for target 0, then target 1, ...
+layout (set = 2, binding = 0) readonly buffer Deltas {
+    vec4 deltas[];
+} morph;
+
+layout (set = 2, binding = 1) readonly buffer Weights {
+    float weights[];
+} morph_weights;
+
+layout (push_constant) uniform Push {
+    mat4 model;
+    uint vertices;
+    uint targets;
+} push;
+
+void main() {
+    // blend shapes apply before skinning, as in glTF
+    vec4 morphed = position;
+    for (uint i = 0; i < push.targets; i++) {
+        uint delta = i * push.vertices + uint(gl_VertexIndex);
+        morphed.xyz += morph_weights.weights[i] * morph.deltas[delta].xyz;
+    }
+    mat4 skin = weights.x * palette.bones[joints.x]
+        + weights.y * palette.bones[joints.y]
+        + weights.z * palette.bones[joints.z]
+        + weights.w * palette.bones[joints.w];
+    gl_Position = vp_inst.vp * push.model * skin * morphed;
+}"
+    }
+}
+
+pub struct Pipeline {
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    bones: CpuBufferPool<[[f32; 4]; 4]>,
+    weights: CpuBufferPool<f32>,
+}
+
+// Blend shapes for one mesh: a position offset per vertex per target,
+// weighted each frame by `Pipeline::morph_set`.
+pub struct MorphTargets {
+    pub deltas: Arc<CpuAccessibleBuffer<[[f32; 4]]>>,
+    pub vertices: u32,
+    pub targets: u32,
+}
+
+// A frame's weights bound with the deltas they scale.
+pub struct MorphSet {
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    vertices: u32,
+    targets: u32,
+}
+
+impl MorphTargets {
+    // `targets` holds one offset per vertex each; none is fine.
+    pub fn new(
+        device: Arc<Device>,
+        vertices: usize,
+        targets: &[Vec<[f32; 4]>],
+    ) -> Result<MorphTargets, DeviceMemoryAllocError> {
+        let mut deltas = targets.iter().flatten().cloned().collect::<Vec<_>>();
+        // Storage buffers can't be empty.
+        if deltas.is_empty() {
+            deltas.push([0.0; 4]);
+        }
+        Ok(MorphTargets {
+            deltas: CpuAccessibleBuffer::from_iter(
+                device,
+                BufferUsage::storage_buffer(),
+                deltas.into_iter(),
+            )?,
+            vertices: vertices as u32,
+            targets: targets.len() as u32,
+        })
+    }
+}
+
+impl Pipeline {
+    pub fn new(device: Arc<Device>, scene: &dbgpipe::Pipeline) -> Pipeline {
+        let vs = vs::Shader::load(device.clone()).unwrap();
+        let fs = dbgpipe::fs::Shader::load(device.clone()).unwrap();
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(vs.main_entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil_simple_depth()
+                .fragment_shader(fs.main_entry_point(), ())
+                .render_pass(
+                    Subpass::from(scene.render_pass.clone(), 0).unwrap(),
+                )
+                .build(device.clone())
+                .unwrap(),
+        );
+
+        Pipeline {
+            pipeline,
+            bones: CpuBufferPool::new(
+                device.clone(),
+                BufferUsage::storage_buffer(),
+            ),
+            weights: CpuBufferPool::new(device, BufferUsage::storage_buffer()),
+        }
+    }
+
+    // Uploads this frame's skinning matrices.
+    pub fn bone_set(
+        &self,
+        bones: &[Matrix4<f32>],
+    ) -> Arc<dyn DescriptorSet + Send + Sync> {
+        let chunk = self
+            .bones
+            .chunk(bones.iter().map(|&bone| bone.into()))
+            .unwrap();
+        Arc::new(
+            PersistentDescriptorSet::start(self.pipeline.clone(), 1)
+                .add_buffer(chunk)
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+    }
+
+    // Uploads this frame's blend shape weights, one per target; missing
+    // weights count as zero.
+    pub fn morph_set(&self, morph: &MorphTargets, weights: &[f32]) -> MorphSet {
+        let weights = (0..morph.targets.max(1) as usize)
+            .map(|i| weights.get(i).cloned().unwrap_or(0.0));
+        let chunk = self.weights.chunk(weights).unwrap();
+        MorphSet {
+            set: Arc::new(
+                PersistentDescriptorSet::start(self.pipeline.clone(), 2)
+                    .add_buffer(morph.deltas.clone())
+                    .unwrap()
+                    .add_buffer(chunk)
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            ),
+            vertices: morph.vertices,
+            targets: morph.targets,
+        }
+    }
+
+    pub fn draw(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        dynamic_state: &DynamicState,
+        vertex_buffer: Arc<CpuAccessibleBuffer<[Vertex]>>,
+        view_set: Arc<dyn DescriptorSet + Send + Sync>,
+        bone_set: Arc<dyn DescriptorSet + Send + Sync>,
+        morph_set: MorphSet,
+    ) -> AutoCommandBufferBuilder {
+        builder
+            .draw(
+                self.pipeline.clone(),
+                dynamic_state,
+                vec![vertex_buffer],
+                vec![view_set, bone_set, morph_set.set],
+                vs::ty::Push {
+                    model: Matrix4::identity().into(),
+                    vertices: morph_set.vertices,
+                    targets: morph_set.targets,
+                },
+            )
+            .unwrap()
+    }
+}
+
+// A horizontal strip of `segments` quads bound to two joints, with
+// weights blending from the first joint to the second along its length.
+pub fn strip(segments: usize, length: f32, width: f32) -> Vec<Vertex> {
+    let vertex = |i: usize, y: f32| {
+        let t = i as f32 / segments as f32;
+        Vertex {
+            position: [t * length, y, 0.0, 1.0],
+            joints: [0, 1, 0, 0],
+            weights: [1.0 - t, t, 0.0, 0.0],
+        }
+    };
+    let half = width * 0.5;
+    (0..segments)
+        .flat_map(|i| {
+            vec![
+                vertex(i, -half),
+                vertex(i + 1, -half),
+                vertex(i + 1, half),
+                vertex(i, -half),
+                vertex(i + 1, half),
+                vertex(i, half),
+            ]
+        })
+        .collect()
+}
+
+// Skinning matrices for `strip`: the second joint sits at `pivot` and
+// rotates by `angle` about z.
+pub fn bend(pivot: f32, angle: Deg<f32>) -> Vec<Matrix4<f32>> {
+    let to_pivot = Matrix4::from_translation(Vector3::new(pivot, 0.0, 0.0));
+    let from_pivot = Matrix4::from_translation(Vector3::new(-pivot, 0.0, 0.0));
+    vec![
+        Matrix4::identity(),
+        to_pivot * Matrix4::from_angle_z(angle) * from_pivot,
+    ]
+}
@@ -0,0 +1,156 @@
+use crate::billboardpipe::Billboard;
+use crate::camera::Camera;
+use crate::fog::Fog;
+use crate::foliage::Foliage;
+use crate::layers;
+use crate::lod::Lod;
+use crate::motionblurpipe::MotionBlur;
+use crate::particles::Emitter;
+use crate::spritepipe::Sprite;
+use crate::transparent::Instance;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Light {
+    pub direction: [f32; 4],
+    pub color: [f32; 4],
+}
+
+// A point light, or a spot light when `cone` is set. Only the deferred
+// lighting pass shades these; see gbufpipe::MAX_LOCAL_LIGHTS.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LocalLight {
+    pub position: [f32; 3],
+    // Where a spot light points.
+    #[serde(default)]
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    // Past this distance the light is off; unlimited when None.
+    #[serde(default)]
+    pub range: Option<f32>,
+    // Cosines of the inner and outer cone angles.
+    #[serde(default)]
+    pub cone: Option<[f32; 2]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub deferred: bool,
+    #[serde(default)]
+    pub oit: bool,
+    pub lut: Option<String>,
+    pub camera: Camera,
+    pub scene: Vec<[f32; 4]>,
+    #[serde(default)]
+    pub transparent: Vec<Instance>,
+    pub light: Light,
+    #[serde(default)]
+    pub local_lights: Vec<LocalLight>,
+    #[serde(default)]
+    pub fog: Fog,
+    #[serde(default)]
+    pub force_sdr: bool,
+    #[serde(default)]
+    pub lightmap: Option<String>,
+    #[serde(default)]
+    pub taa: bool,
+    #[serde(default)]
+    pub gpu_cull: bool,
+    #[serde(default)]
+    pub cpu_cull: bool,
+    #[serde(default)]
+    pub occlusion: bool,
+    #[serde(default)]
+    pub lod: Lod,
+    #[serde(default)]
+    pub skinning: bool,
+    #[serde(default)]
+    pub tessellation: bool,
+    #[serde(default)]
+    pub sprites: Vec<Sprite>,
+    // the texture table, one texture per path at its own size
+    #[serde(default)]
+    pub sprite_textures: Vec<String>,
+    // drawn from the sprite textures
+    #[serde(default)]
+    pub billboards: Vec<Billboard>,
+    #[serde(default)]
+    pub motion_blur: MotionBlur,
+    #[serde(default)]
+    pub emitter: Emitter,
+    #[serde(default)]
+    pub foliage: Foliage,
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Snapshot {
+            deferred: false,
+            oit: false,
+            lut: None,
+            camera: Camera::default(),
+            scene: vec![
+                [-0.5, -0.25, 0.0, 1.0],
+                [0.0, 0.5, 0.0, 1.0],
+                [0.25, -0.1, 0.0, 1.0],
+            ],
+            transparent: vec![
+                Instance {
+                    offset: [0.2, 0.1, -0.2],
+                    color: [0.0, 1.0, 0.0, 0.5],
+                    layers: layers::DEFAULT,
+                },
+                Instance {
+                    offset: [0.4, -0.1, -0.6],
+                    color: [1.0, 1.0, 0.0, 0.5],
+                    layers: layers::DEFAULT,
+                },
+                Instance {
+                    offset: [0.1, -0.2, -0.4],
+                    color: [0.0, 1.0, 1.0, 0.5],
+                    layers: layers::DEBUG,
+                },
+            ],
+            light: Light {
+                direction: [0.3, -0.5, 1.0, 0.0],
+                color: [1.0, 1.0, 1.0, 1.0],
+            },
+            local_lights: Vec::new(),
+            fog: Fog::default(),
+            force_sdr: false,
+            lightmap: None,
+            taa: false,
+            gpu_cull: false,
+            cpu_cull: false,
+            occlusion: false,
+            lod: Lod::default(),
+            skinning: false,
+            tessellation: false,
+            sprites: Vec::new(),
+            sprite_textures: Vec::new(),
+            billboards: Vec::new(),
+            motion_blur: MotionBlur::default(),
+            emitter: Emitter::default(),
+            foliage: Foliage::default(),
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Snapshot> {
+        let path = path.as_ref();
+        info!(path = %path.display(), "loading snapshot");
+        let reader = BufReader::new(File::open(path)?);
+        serde_json::from_reader(reader).map_err(io::Error::from)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self).map_err(io::Error::from)
+    }
+}
@@ -0,0 +1,82 @@
+use crate::error::Error;
+use crate::error::Result;
+use crate::reflect;
+use crate::reflect::Reflection;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use vulkano::device::Device;
+use vulkano::pipeline::shader::ShaderModule;
+
+// SPIR-V that build.rs compiled from `shaders/`, one word array per file,
+// e.g. `DEBUG_VERT` for `shaders/debug.vert`.
+include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+
+pub fn module(
+    device: Arc<Device>,
+    name: &'static str,
+    words: &[u32],
+) -> Result<Arc<ShaderModule>> {
+    unsafe { ShaderModule::from_words(device, words) }
+        .map_err(Error::shader(name))
+}
+
+// Reads a module compiled outside the crate, e.g. by glslc, so shaders can
+// be added or swapped without rebuilding. Pair it with
+// `entrypoint::StageInterface` and `entrypoint::LayoutDesc` describing it,
+// or use `load_reflected`.
+pub fn load<P: AsRef<Path>>(
+    device: Arc<Device>,
+    path: P,
+) -> Result<Arc<ShaderModule>> {
+    let (name, words) = read(path.as_ref())?;
+    unsafe { ShaderModule::from_words(device, &words) }
+        .map_err(|source| Error::Module { name, source })
+}
+
+// Like `load`, with the description derived from the module itself.
+pub fn load_reflected<P: AsRef<Path>>(
+    device: Arc<Device>,
+    path: P,
+) -> Result<(Arc<ShaderModule>, Reflection)> {
+    let (name, words) = read(path.as_ref())?;
+    let reflection =
+        reflect::reflect(&words).map_err(|message| Error::Reflect {
+            name: name.clone(),
+            message,
+        })?;
+    let module = unsafe { ShaderModule::from_words(device, &words) }
+        .map_err(|source| Error::Module { name, source })?;
+    Ok((module, reflection))
+}
+
+fn read(path: &Path) -> Result<(String, Vec<u32>)> {
+    let name = path.display().to_string();
+    let bytes =
+        fs::read(path).map_err(Error::io(format!("reading {}", name)))?;
+    let words =
+        words(&bytes).ok_or_else(|| Error::InvalidSpirv(name.clone()))?;
+    Ok((name, words))
+}
+
+// Accepts either byte order, as the magic number shows which was used.
+fn words(bytes: &[u8]) -> Option<Vec<u32>> {
+    const MAGIC: u32 = 0x0723_0203;
+    if bytes.len() % 4 != 0 || bytes.len() < 4 {
+        return None;
+    }
+    let word = |chunk: &[u8]| {
+        u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])
+    };
+    let swap = match word(&bytes[..4]) {
+        MAGIC => false,
+        magic if magic.swap_bytes() == MAGIC => true,
+        _ => return None,
+    };
+    let words = bytes.chunks(4).map(word);
+    if swap {
+        Some(words.map(u32::swap_bytes).collect())
+    } else {
+        Some(words.collect())
+    }
+}
@@ -0,0 +1,197 @@
+use crate::compat;
+use crate::dbgpipe;
+use crate::error::Result;
+use crate::texarray;
+use crate::texarray::Batch;
+use crate::texarray::Table;
+use serde::{Deserialize, Serialize};
+use std::mem;
+use std::sync::Arc;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::device::Device;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+#[derive(Debug, Clone, Default)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+vulkano::impl_vertex!(Vertex, position, uv);
+
+// An xy-plane quad in world space showing one of the sprite textures,
+// named by its path or given by index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sprite {
+    pub position: [f32; 3],
+    pub size: [f32; 2],
+    // Index into the sprite textures when there's no `texture` name.
+    #[serde(default)]
+    pub layer: u32,
+    #[serde(default)]
+    pub texture: Option<String>,
+}
+
+pub mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec2 uv;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+layout (location = 0) out vec2 out_uv;
+
+void main() {
+    gl_Position = vp_inst.vp * vec4(position, 1.0);
+    out_uv = uv;
+}"
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+layout (location = 0) in vec2 uv;
+
+// texarray::CAPACITY
+layout (set = 1, binding = 0) uniform sampler2D textures[8];
+
+layout (push_constant) uniform Push {
+    uint index;
+} push;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    f_color = texture(textures[push.index], uv);
+}
+"
+    }
+}
+
+pub struct Pipeline {
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+pub fn interface() -> compat::Interface {
+    compat::Interface {
+        bindings: vec![
+            compat::Binding {
+                set: compat::VIEW_SET,
+                binding: 0,
+                kind: compat::Kind::UniformBuffer,
+            },
+            compat::Binding {
+                set: compat::MATERIAL_SET,
+                binding: 0,
+                kind: compat::Kind::CombinedImageSampler,
+            },
+        ],
+        push_constants: mem::size_of::<fs::ty::Push>(),
+        blocks: vec![compat::Block::of::<dbgpipe::ViewBlock>(
+            compat::VIEW_SET,
+            0,
+        )],
+    }
+}
+
+// What the shaders declare. The SPIR-V isn't kept, so the view block's size
+// comes from the struct vulkano generated for it.
+pub fn shader_interface(pipeline: &Pipeline) -> compat::Interface {
+    compat::Interface {
+        blocks: vec![compat::Block::of::<vs::ty::VP_BLOCK>(
+            compat::VIEW_SET,
+            0,
+        )],
+        ..compat::reflect(&*pipeline.pipeline)
+    }
+}
+
+// Draws in the forward scene pass, blended over opaque geometry.
+pub fn build(device: Arc<Device>, scene: &dbgpipe::Pipeline) -> Pipeline {
+    let vs = vs::Shader::load(device.clone()).unwrap();
+    let fs = fs::Shader::load(device.clone()).unwrap();
+
+    let pipeline = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Vertex>()
+            .vertex_shader(vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .blend_alpha_blending()
+            .fragment_shader(fs.main_entry_point(), ())
+            .render_pass(Subpass::from(scene.render_pass.clone(), 0).unwrap())
+            .build(device)
+            .unwrap(),
+    );
+
+    Pipeline { pipeline }
+}
+
+// The sprites' vertices, grouped by texture.
+pub fn quads(
+    sprites: &[Sprite],
+    table: &Table,
+) -> Result<(Vec<Vertex>, Vec<texarray::Run>)> {
+    texarray::runs(
+        sprites,
+        |sprite| {
+            table.resolve(
+                sprite.texture.as_ref().map(String::as_str),
+                sprite.layer,
+            )
+        },
+        |sprite| {
+            let [x, y, z] = sprite.position;
+            let [w, h] = [sprite.size[0] * 0.5, sprite.size[1] * 0.5];
+            let corner = |dx: f32, dy: f32, u: f32, v: f32| Vertex {
+                position: [x + dx, y + dy, z],
+                uv: [u, v],
+            };
+            vec![
+                corner(-w, -h, 0.0, 0.0),
+                corner(w, -h, 1.0, 0.0),
+                corner(w, h, 1.0, 1.0),
+                corner(-w, -h, 0.0, 0.0),
+                corner(w, h, 1.0, 1.0),
+                corner(-w, h, 0.0, 1.0),
+            ]
+        },
+    )
+}
+
+// One draw per texture, with the table's set bound throughout.
+pub fn draw(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &Pipeline,
+    dynamic_state: &DynamicState,
+    batches: &[Batch],
+    view_set: Arc<dyn DescriptorSet + Send + Sync>,
+    table_set: Arc<dyn DescriptorSet + Send + Sync>,
+) -> AutoCommandBufferBuilder {
+    batches.iter().fold(builder, |builder, batch| {
+        builder
+            .draw(
+                pipeline.pipeline.clone(),
+                dynamic_state,
+                vec![batch.vertices.clone()],
+                vec![view_set.clone(), table_set.clone()],
+                fs::ty::Push {
+                    index: batch.texture,
+                },
+            )
+            .unwrap()
+    })
+}
@@ -0,0 +1,386 @@
+use crate::dbgpipe;
+use crate::dbgpipe::Vertex;
+use crate::error::Error;
+use crate::error::Result;
+use crate::fullscreen;
+use cgmath::Matrix4;
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::ClearValue;
+use vulkano::format::Format;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::framebuffer::Subpass;
+use vulkano::image::AttachmentImage;
+use vulkano::image::ImageUsage;
+use vulkano::pipeline::vertex::BufferlessDefinition;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::sampler::Sampler;
+
+pub const VELOCITY_FORMAT: Format = Format::R16G16Sfloat;
+pub const FEEDBACK: f32 = 0.1;
+
+pub mod velocity_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+layout (location = 0) in vec4 position;
+
+layout (set = 0, binding = 0) uniform VELOCITY_BLOCK {
+    mat4 jittered_vp;
+    mat4 vp;
+    mat4 previous_vp;
+} view;
+
+layout (push_constant) uniform Push {
+    mat4 model;
+    mat4 previous_model;
+} push;
+
+layout (location = 0) out vec4 out_current;
+layout (location = 1) out vec4 out_previous;
+
+void main() {
+    out_current = view.vp * push.model * position;
+    out_previous = view.previous_vp * push.previous_model * position;
+    gl_Position = view.jittered_vp * push.model * position;
+}"
+    }
+}
+
+pub mod velocity_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+layout (location = 0) in vec4 current;
+layout (location = 1) in vec4 previous;
+
+layout (location = 0) out vec2 f_velocity;
+
+void main() {
+    f_velocity = current.xy / current.w - previous.xy / previous.w;
+}
+"
+    }
+}
+
+pub mod resolve_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+layout (location = 0) in vec2 uv;
+
+layout (set = 0, binding = 0) uniform sampler2D current;
+layout (set = 0, binding = 1) uniform sampler2D history;
+layout (set = 0, binding = 2) uniform sampler2D velocity;
+
+layout (push_constant) uniform Resolve {
+    uint reset;
+    float feedback;
+} resolve;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    vec4 color = texture(current, uv);
+    if (resolve.reset != 0) {
+        f_color = color;
+        return;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(current, 0));
+    vec4 low = color;
+    vec4 high = color;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            vec4 neighbor = texture(current, uv + vec2(x, y) * texel);
+            low = min(low, neighbor);
+            high = max(high, neighbor);
+        }
+    }
+
+    vec2 history_uv = uv - texture(velocity, uv).xy * 0.5;
+    if (any(lessThan(history_uv, vec2(0.0)))
+            || any(greaterThan(history_uv, vec2(1.0)))) {
+        f_color = color;
+        return;
+    }
+
+    vec4 previous = clamp(texture(history, history_uv), low, high);
+    f_color = mix(previous, color, resolve.feedback);
+}
+"
+    }
+}
+
+pub struct Pipeline {
+    pub velocity_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub velocity: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pub resolve_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub resolve: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+}
+
+pub struct Targets {
+    pub velocity_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    pub resolve_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    pub resolve_set: Arc<dyn DescriptorSet + Send + Sync>,
+    pub resolved: Arc<AttachmentImage>,
+    pub history: Arc<AttachmentImage>,
+    pub velocity: Arc<AttachmentImage>,
+}
+
+pub fn build(device: Arc<Device>, format: Format) -> Result<Pipeline> {
+    let velocity_vs = velocity_vs::Shader::load(device.clone())
+        .map_err(Error::shader("velocity vertex"))?;
+    let velocity_fs = velocity_fs::Shader::load(device.clone())
+        .map_err(Error::shader("velocity fragment"))?;
+    let resolve_vs = fullscreen::vs::Shader::load(device.clone())
+        .map_err(Error::shader("fullscreen vertex"))?;
+    let resolve_fs = resolve_fs::Shader::load(device.clone())
+        .map_err(Error::shader("TAA resolve fragment"))?;
+
+    let velocity_pass = Arc::new(
+        vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                velocity: {
+                    load: Clear,
+                    store: Store,
+                    format: VELOCITY_FORMAT,
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: dbgpipe::DEPTH_FORMAT,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [velocity],
+                depth_stencil: {depth}
+            }
+        )
+        .map_err(Error::render_pass("velocity"))?,
+    );
+
+    let resolve_pass = Arc::new(
+        vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                resolved: {
+                    load: DontCare,
+                    store: Store,
+                    format: format,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [resolved],
+                depth_stencil: {}
+            }
+        )
+        .map_err(Error::render_pass("TAA resolve"))?,
+    );
+
+    let velocity = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Vertex>()
+            .vertex_shader(velocity_vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .depth_stencil_simple_depth()
+            .fragment_shader(velocity_fs.main_entry_point(), ())
+            .render_pass(Subpass::from(velocity_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("velocity"))?,
+    );
+
+    let resolve = Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition)
+            .vertex_shader(resolve_vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(resolve_fs.main_entry_point(), ())
+            .render_pass(Subpass::from(resolve_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .map_err(Error::pipeline("TAA resolve"))?,
+    );
+
+    Ok(Pipeline {
+        velocity_pass,
+        velocity,
+        resolve_pass,
+        resolve,
+    })
+}
+
+pub fn velocity_set<B>(
+    pipeline: &Pipeline,
+    buffer: B,
+) -> Result<Arc<dyn DescriptorSet + Send + Sync>>
+where
+    B: BufferAccess + Send + Sync + 'static,
+{
+    Ok(Arc::new(
+        PersistentDescriptorSet::start(pipeline.velocity.clone(), 0)
+            .add_buffer(buffer)?
+            .build()?,
+    ))
+}
+
+pub fn targets(
+    pipeline: &Pipeline,
+    device: Arc<Device>,
+    current: Arc<AttachmentImage>,
+    linear: Arc<Sampler>,
+    nearest: Arc<Sampler>,
+) -> Result<Targets> {
+    let dimensions = current.dimensions();
+    let format = current.format();
+    let velocity =
+        AttachmentImage::sampled(device.clone(), dimensions, VELOCITY_FORMAT)
+            .map_err(Error::image("velocity"))?;
+    let depth = AttachmentImage::transient(
+        device.clone(),
+        dimensions,
+        dbgpipe::DEPTH_FORMAT,
+    )
+    .map_err(Error::image("velocity depth"))?;
+    let resolved = AttachmentImage::with_usage(
+        device.clone(),
+        dimensions,
+        format,
+        ImageUsage {
+            sampled: true,
+            transfer_source: true,
+            ..ImageUsage::none()
+        },
+    )
+    .map_err(Error::image("TAA resolved"))?;
+    let history = AttachmentImage::with_usage(
+        device.clone(),
+        dimensions,
+        format,
+        ImageUsage {
+            sampled: true,
+            transfer_destination: true,
+            ..ImageUsage::none()
+        },
+    )
+    .map_err(Error::image("TAA history"))?;
+
+    let velocity_framebuffer = Arc::new(
+        Framebuffer::start(pipeline.velocity_pass.clone())
+            .add(velocity.clone())?
+            .add(depth)?
+            .build()?,
+    );
+
+    let resolve_framebuffer = Arc::new(
+        Framebuffer::start(pipeline.resolve_pass.clone())
+            .add(resolved.clone())?
+            .build()?,
+    );
+
+    let resolve_set = Arc::new(
+        PersistentDescriptorSet::start(pipeline.resolve.clone(), 0)
+            .add_sampled_image(current, linear.clone())?
+            .add_sampled_image(history.clone(), linear)?
+            .add_sampled_image(velocity.clone(), nearest)?
+            .build()?,
+    );
+
+    Ok(Targets {
+        velocity_framebuffer,
+        resolve_framebuffer,
+        resolve_set,
+        resolved,
+        history,
+        velocity,
+    })
+}
+
+pub fn draw_velocity(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &Pipeline,
+    targets: &Targets,
+    dynamic_state: &DynamicState,
+    vertex_buffers: Vec<Arc<dyn BufferAccess + Send + Sync>>,
+    velocity_set: Arc<dyn DescriptorSet + Send + Sync>,
+    models: &[(Matrix4<f32>, Matrix4<f32>)],
+) -> Result<AutoCommandBufferBuilder> {
+    let mut builder = builder.begin_render_pass(
+        targets.velocity_framebuffer.clone(),
+        false,
+        vec![[0.0, 0.0].into(), 1.0f32.into()],
+    )?;
+    for (model, previous_model) in models {
+        builder = builder.draw(
+            pipeline.velocity.clone(),
+            dynamic_state,
+            vertex_buffers.clone(),
+            vec![velocity_set.clone()],
+            velocity_vs::ty::Push {
+                model: (*model).into(),
+                previous_model: (*previous_model).into(),
+            },
+        )?;
+    }
+    let builder = builder.end_render_pass()?;
+    Ok(builder)
+}
+
+// Blends the current frame into the history and copies the result back so
+// the next frame can reproject it.
+pub fn resolve(
+    builder: AutoCommandBufferBuilder,
+    pipeline: &Pipeline,
+    targets: &Targets,
+    dynamic_state: &DynamicState,
+    reset: bool,
+) -> Result<AutoCommandBufferBuilder> {
+    let dimensions = targets.resolved.dimensions();
+    let builder = builder
+        .begin_render_pass(
+            targets.resolve_framebuffer.clone(),
+            false,
+            vec![ClearValue::None],
+        )?
+        .draw(
+            pipeline.resolve.clone(),
+            dynamic_state,
+            fullscreen::vertices(),
+            vec![targets.resolve_set.clone()],
+            resolve_fs::ty::Resolve {
+                reset: reset as u32,
+                feedback: FEEDBACK,
+            },
+        )?
+        .end_render_pass()?
+        .copy_image(
+            targets.resolved.clone(),
+            [0, 0, 0],
+            0,
+            0,
+            targets.history.clone(),
+            [0, 0, 0],
+            0,
+            0,
+            [dimensions[0], dimensions[1], 1],
+            1,
+        )?;
+    Ok(builder)
+}
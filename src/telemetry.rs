@@ -0,0 +1,153 @@
+use serde::Serialize;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameSample {
+    pub frame_ms: f64,
+    pub record_ms: f64,
+    pub gpu_wait_ms: f64,
+    pub draws: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Aggregate {
+    pub elapsed_s: f64,
+    pub frames: usize,
+    pub fps: f64,
+    pub frame_p50_ms: f64,
+    pub frame_p95_ms: f64,
+    pub frame_p99_ms: f64,
+    pub record_mean_ms: f64,
+    pub gpu_wait_mean_ms: f64,
+    pub resident_mb: Option<f64>,
+    pub draws_mean: f64,
+}
+
+enum Sink {
+    Csv,
+    Json,
+}
+
+pub struct Telemetry {
+    writer: BufWriter<File>,
+    sink: Sink,
+    start: Instant,
+    window_start: Instant,
+    samples: Vec<FrameSample>,
+}
+
+impl Telemetry {
+    // Writes JSON lines for a .json/.jsonl path and CSV otherwise.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Telemetry> {
+        let sink = match path.as_ref().extension().and_then(|ext| ext.to_str())
+        {
+            Some("json") | Some("jsonl") => Sink::Json,
+            _ => Sink::Csv,
+        };
+        let mut writer = BufWriter::new(File::create(path)?);
+        if let Sink::Csv = sink {
+            writeln!(
+                writer,
+                "elapsed_s,frames,fps,frame_p50_ms,frame_p95_ms,frame_p99_ms,\
+                 record_mean_ms,gpu_wait_mean_ms,resident_mb,draws_mean"
+            )?;
+        }
+        let now = Instant::now();
+        Ok(Telemetry {
+            writer,
+            sink,
+            start: now,
+            window_start: now,
+            samples: Vec::new(),
+        })
+    }
+
+    pub fn frame(&mut self, sample: FrameSample) -> io::Result<()> {
+        self.samples.push(sample);
+        if self.window_start.elapsed() < INTERVAL {
+            return Ok(());
+        }
+        let aggregate = self.aggregate();
+        self.samples.clear();
+        self.window_start = Instant::now();
+        self.write(&aggregate)
+    }
+
+    fn aggregate(&self) -> Aggregate {
+        let frames = self.samples.len();
+        let window = self.window_start.elapsed().as_secs_f64();
+        let mut frame_ms = self
+            .samples
+            .iter()
+            .map(|sample| sample.frame_ms)
+            .collect::<Vec<_>>();
+        frame_ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mean = |value: fn(&FrameSample) -> f64| {
+            self.samples.iter().map(value).sum::<f64>() / frames.max(1) as f64
+        };
+
+        Aggregate {
+            elapsed_s: self.start.elapsed().as_secs_f64(),
+            frames,
+            fps: frames as f64 / window,
+            frame_p50_ms: percentile(&frame_ms, 0.50),
+            frame_p95_ms: percentile(&frame_ms, 0.95),
+            frame_p99_ms: percentile(&frame_ms, 0.99),
+            record_mean_ms: mean(|sample| sample.record_ms),
+            gpu_wait_mean_ms: mean(|sample| sample.gpu_wait_ms),
+            resident_mb: resident_bytes()
+                .map(|bytes| bytes as f64 / (1024.0 * 1024.0)),
+            draws_mean: mean(|sample| sample.draws as f64),
+        }
+    }
+
+    fn write(&mut self, aggregate: &Aggregate) -> io::Result<()> {
+        match self.sink {
+            Sink::Json => {
+                serde_json::to_writer(&mut self.writer, aggregate)?;
+                writeln!(self.writer)?;
+            }
+            Sink::Csv => writeln!(
+                self.writer,
+                "{:.3},{},{:.2},{:.3},{:.3},{:.3},{:.3},{:.3},{},{:.1}",
+                aggregate.elapsed_s,
+                aggregate.frames,
+                aggregate.fps,
+                aggregate.frame_p50_ms,
+                aggregate.frame_p95_ms,
+                aggregate.frame_p99_ms,
+                aggregate.record_mean_ms,
+                aggregate.gpu_wait_mean_ms,
+                aggregate
+                    .resident_mb
+                    .map(|mb| format!("{:.1}", mb))
+                    .unwrap_or_default(),
+                aggregate.draws_mean,
+            )?,
+        }
+        // Flush every interval so a crashed or killed soak run keeps its data.
+        self.writer.flush()
+    }
+}
+
+// Nearest-rank percentile of ascending `sorted`.
+pub fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = ((sorted.len() - 1) as f64 * fraction).round() as usize;
+    sorted[index]
+}
+
+pub fn resident_bytes() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages = statm.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(pages * 4096)
+}
@@ -0,0 +1,236 @@
+use crate::dbgpipe;
+use crate::dbgpipe::Vertex;
+use cgmath::Matrix4;
+use cgmath::SquareMatrix;
+use std::sync::Arc;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::DynamicState;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::device::Device;
+use vulkano::framebuffer::Subpass;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+
+// Target length of a tessellated edge on screen, in pixels.
+pub const EDGE_PIXELS: f32 = 12.0;
+pub const MAX_LEVEL: f32 = 32.0;
+
+pub mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+#version 450
+
+layout (location = 0) in vec4 position;
+
+layout (location = 0) out vec4 v_position;
+
+void main() {
+    v_position = position;
+}"
+    }
+}
+
+pub mod tcs {
+    vulkano_shaders::shader! {
+        ty: "tess_ctrl",
+        src: "
+#version 450
+
+layout (vertices = 4) out;
+
+layout (location = 0) in vec4 v_position[];
+layout (location = 0) out vec4 c_position[];
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+// viewport: framebuffer size in pixels, edge: target pixels per segment
+layout (push_constant) uniform Push {
+    mat4 model;
+    vec2 viewport;
+    float edge;
+    float max_level;
+} push;
+
+vec2 screen(vec4 position) {
+    vec4 clip = vp_inst.vp * push.model * position;
+    return clip.xy / max(abs(clip.w), 1e-5) * 0.5 * push.viewport;
+}
+
+float level(vec4 a, vec4 b) {
+    float pixels = distance(screen(a), screen(b));
+    return clamp(pixels / push.edge, 1.0, push.max_level);
+}
+
+// corners: 0 = (0, 0), 1 = (1, 0), 2 = (1, 1), 3 = (0, 1)
+void main() {
+    c_position[gl_InvocationID] = v_position[gl_InvocationID];
+    if (gl_InvocationID == 0) {
+        gl_TessLevelOuter[0] = level(v_position[3], v_position[0]);
+        gl_TessLevelOuter[1] = level(v_position[0], v_position[1]);
+        gl_TessLevelOuter[2] = level(v_position[1], v_position[2]);
+        gl_TessLevelOuter[3] = level(v_position[2], v_position[3]);
+        gl_TessLevelInner[0] =
+            max(gl_TessLevelOuter[1], gl_TessLevelOuter[3]);
+        gl_TessLevelInner[1] =
+            max(gl_TessLevelOuter[0], gl_TessLevelOuter[2]);
+    }
+}"
+    }
+}
+
+pub mod tes {
+    vulkano_shaders::shader! {
+        ty: "tess_eval",
+        src: "
+#version 450
+
+layout (quads, fractional_even_spacing, ccw) in;
+
+layout (location = 0) in vec4 c_position[];
+layout (location = 0) out float t_height;
+
+layout (set = 0, binding = 0) uniform VP_BLOCK {
+    mat4 vp;
+} vp_inst;
+
+layout (push_constant) uniform Push {
+    mat4 model;
+} push;
+
+float height(vec2 p) {
+    return 0.15 * sin(p.x * 4.0) * cos(p.y * 3.0)
+        + 0.05 * sin(p.x * 13.0 + p.y * 7.0);
+}
+
+void main() {
+    vec4 bottom = mix(c_position[0], c_position[1], gl_TessCoord.x);
+    vec4 top = mix(c_position[3], c_position[2], gl_TessCoord.x);
+    vec4 position = mix(bottom, top, gl_TessCoord.y);
+    t_height = height(position.xy);
+    position.y += t_height;
+    gl_Position = vp_inst.vp * push.model * position;
+}"
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+#version 450
+
+layout (location = 0) in float t_height;
+
+layout (location = 0) out vec4 f_color;
+
+void main() {
+    float t = clamp(t_height * 2.5 + 0.5, 0.0, 1.0);
+    f_color = vec4(mix(vec3(0.1, 0.3, 0.1), vec3(0.8, 0.8, 0.6), t), 1.0);
+}
+"
+    }
+}
+
+pub struct Pipeline {
+    pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pub patches: Arc<CpuAccessibleBuffer<[Vertex]>>,
+}
+
+impl Pipeline {
+    // None when the device lacks tessellationShader.
+    pub fn new(
+        device: Arc<Device>,
+        scene: &dbgpipe::Pipeline,
+    ) -> Option<Pipeline> {
+        if !device.enabled_features().tessellation_shader {
+            return None;
+        }
+        let vs = vs::Shader::load(device.clone()).unwrap();
+        let tcs = tcs::Shader::load(device.clone()).unwrap();
+        let tes = tes::Shader::load(device.clone()).unwrap();
+        let fs = fs::Shader::load(device.clone()).unwrap();
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(vs.main_entry_point(), ())
+                .tessellation_shaders(
+                    tcs.main_entry_point(),
+                    (),
+                    tes.main_entry_point(),
+                    (),
+                )
+                .patch_list(4)
+                .viewports_dynamic_scissors_irrelevant(1)
+                .depth_stencil_simple_depth()
+                .fragment_shader(fs.main_entry_point(), ())
+                .render_pass(
+                    Subpass::from(scene.render_pass.clone(), 0).unwrap(),
+                )
+                .build(device.clone())
+                .unwrap(),
+        );
+
+        let patches = CpuAccessibleBuffer::from_iter(
+            device,
+            BufferUsage::vertex_buffer(),
+            grid([-4.0, -1.0], [8.0, 2.0], [8, 2]).into_iter(),
+        )
+        .unwrap();
+
+        Some(Pipeline { pipeline, patches })
+    }
+
+    pub fn draw(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        dynamic_state: &DynamicState,
+        view_set: Arc<dyn DescriptorSet + Send + Sync>,
+    ) -> AutoCommandBufferBuilder {
+        let viewport = dynamic_state.viewports.as_ref().unwrap()[0].dimensions;
+        builder
+            .draw(
+                self.pipeline.clone(),
+                dynamic_state,
+                vec![self.patches.clone()],
+                vec![view_set],
+                tcs::ty::Push {
+                    model: Matrix4::identity().into(),
+                    viewport,
+                    edge: EDGE_PIXELS,
+                    max_level: MAX_LEVEL,
+                },
+            )
+            .unwrap()
+    }
+}
+
+// Quad patches covering `size` from `origin` in the xy plane, four
+// control points each in the order the control shader expects.
+pub fn grid(origin: [f32; 2], size: [f32; 2], cells: [u32; 2]) -> Vec<Vertex> {
+    let step = [size[0] / cells[0] as f32, size[1] / cells[1] as f32];
+    let corner = |x: u32, y: u32| Vertex {
+        position: [
+            origin[0] + x as f32 * step[0],
+            origin[1] + y as f32 * step[1],
+            0.0,
+            1.0,
+        ],
+    };
+    (0..cells[1])
+        .flat_map(|y| (0..cells[0]).map(move |x| (x, y)))
+        .flat_map(|(x, y)| {
+            vec![
+                corner(x, y),
+                corner(x + 1, y),
+                corner(x + 1, y + 1),
+                corner(x, y + 1),
+            ]
+        })
+        .collect()
+}
@@ -0,0 +1,220 @@
+use crate::compat;
+use crate::error::Error;
+use crate::error::Result;
+use crate::transfer::Uploader;
+use image::RgbaImage;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+use vulkano::buffer::BufferAccess;
+use vulkano::buffer::BufferSlice;
+use vulkano::buffer::DeviceLocalBuffer;
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::device::DeviceOwned;
+use vulkano::format::Format;
+use vulkano::image::Dimensions;
+use vulkano::image::ImmutableImage;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::sampler::Sampler;
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+
+// Concatenates same-size images into array layers, in order.
+pub fn pack(layers: &[RgbaImage]) -> (Dimensions, Vec<u8>) {
+    let (width, height) = layers[0].dimensions();
+    let mut data =
+        Vec::with_capacity((width * height * 4) as usize * layers.len());
+    for (index, layer) in layers.iter().enumerate() {
+        assert_eq!(
+            layer.dimensions(),
+            (width, height),
+            "texture array layer {} is not {}x{}",
+            index,
+            width,
+            height
+        );
+        data.extend_from_slice(layer);
+    }
+    let dimensions = Dimensions::Dim2dArray {
+        width,
+        height,
+        array_layers: layers.len() as u32,
+    };
+    (dimensions, data)
+}
+
+pub fn load<P: AsRef<Path>>(paths: &[P]) -> Vec<RgbaImage> {
+    paths
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            info!(path = %path.display(), "loading texture");
+            image::open(path).unwrap().to_rgba()
+        })
+        .collect()
+}
+
+pub fn upload(
+    layers: &[RgbaImage],
+    uploader: &Uploader,
+) -> (Arc<ImmutableImage<Format>>, Box<dyn GpuFuture>) {
+    let (dimensions, data) = pack(layers);
+    uploader.image(data, dimensions, Format::R8G8B8A8Srgb)
+}
+
+// How many textures a table holds. The sprite and billboard fragment
+// shaders declare their texture arrays with this size.
+pub const CAPACITY: usize = 8;
+
+// Textures at their own sizes, bound once as an array of sampled images.
+// Draws pick one by index in a push constant instead of binding a set per
+// texture, which needs shaderSampledImageArrayDynamicIndexing.
+pub struct Table {
+    pub textures: Vec<Arc<ImmutableImage<Format>>>,
+    pub names: Vec<String>,
+}
+
+impl Table {
+    pub fn load(
+        paths: &[String],
+        uploader: &Uploader,
+    ) -> Result<(Table, Box<dyn GpuFuture>)> {
+        if paths.is_empty() || paths.len() > CAPACITY {
+            return Err(Error::TextureTable {
+                count: paths.len(),
+                capacity: CAPACITY,
+            });
+        }
+        let mut textures = Vec::with_capacity(paths.len());
+        let mut future = Box::new(sync::now(uploader.transfer.device().clone()))
+            as Box<dyn GpuFuture>;
+        for path in paths {
+            info!(%path, "loading texture");
+            let texture = image::open(path)
+                .map_err(|source| Error::Texture {
+                    path: path.clone(),
+                    source,
+                })?
+                .to_rgba();
+            let (width, height) = texture.dimensions();
+            let (image, upload) = uploader.image(
+                texture.into_raw(),
+                Dimensions::Dim2d { width, height },
+                Format::R8G8B8A8Srgb,
+            );
+            textures.push(image);
+            future = Box::new(future.join(upload));
+        }
+        let table = Table {
+            textures,
+            names: paths.to_vec(),
+        };
+        Ok((table, future))
+    }
+
+    // A texture's index, by name when one is given.
+    pub fn resolve(&self, name: Option<&str>, index: u32) -> Result<u32> {
+        match name {
+            Some(name) => self
+                .names
+                .iter()
+                .position(|candidate| candidate == name)
+                .map(|index| index as u32)
+                .ok_or_else(|| Error::UnknownTexture(name.to_owned())),
+            None if (index as usize) < self.textures.len() => Ok(index),
+            None => Err(Error::UnknownTexture(format!("#{}", index))),
+        }
+    }
+
+    // The table as `pipeline`'s material set. Every array element has to be
+    // written, so the slots past the last texture repeat it.
+    pub fn set(
+        &self,
+        pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        sampler: Arc<Sampler>,
+    ) -> Result<Arc<dyn DescriptorSet + Send + Sync>> {
+        let last = self.textures.len() - 1;
+        let slot = |index: usize| self.textures[index.min(last)].clone();
+        // Each element changes the builder's type, so there's no loop.
+        let set =
+            PersistentDescriptorSet::start(pipeline, compat::MATERIAL_SET)
+                .enter_array()?
+                .add_sampled_image(slot(0), sampler.clone())?
+                .add_sampled_image(slot(1), sampler.clone())?
+                .add_sampled_image(slot(2), sampler.clone())?
+                .add_sampled_image(slot(3), sampler.clone())?
+                .add_sampled_image(slot(4), sampler.clone())?
+                .add_sampled_image(slot(5), sampler.clone())?
+                .add_sampled_image(slot(6), sampler.clone())?
+                .add_sampled_image(slot(7), sampler)?
+                .leave_array()?
+                .build()?;
+        Ok(Arc::new(set))
+    }
+}
+
+// A run of vertices drawn with one texture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    pub texture: u32,
+    pub vertices: Range<usize>,
+}
+
+// Groups `items` by texture, keeping their order within each texture, and
+// returns the vertices `corners` makes for them with one run per texture.
+pub fn runs<T, V>(
+    items: &[T],
+    texture: impl Fn(&T) -> Result<u32>,
+    corners: impl Fn(&T) -> Vec<V>,
+) -> Result<(Vec<V>, Vec<Run>)> {
+    let mut keyed = items
+        .iter()
+        .map(|item| texture(item).map(|texture| (texture, item)))
+        .collect::<Result<Vec<_>>>()?;
+    keyed.sort_by_key(|&(texture, _)| texture);
+
+    let mut vertices = Vec::new();
+    let mut runs: Vec<Run> = Vec::new();
+    for (texture, item) in keyed {
+        let start = vertices.len();
+        vertices.extend(corners(item));
+        match runs.last_mut() {
+            Some(run) if run.texture == texture => {
+                run.vertices.end = vertices.len()
+            }
+            _ => runs.push(Run {
+                texture,
+                vertices: start..vertices.len(),
+            }),
+        }
+    }
+    Ok((vertices, runs))
+}
+
+// Vertices drawn with one texture of a table.
+pub struct Batch {
+    pub texture: u32,
+    pub vertices: Arc<dyn BufferAccess + Send + Sync>,
+}
+
+// `buffer` split along `runs`.
+pub fn batches<T>(
+    buffer: Arc<DeviceLocalBuffer<[T]>>,
+    runs: &[Run],
+) -> Vec<Batch>
+where
+    T: Send + Sync + 'static,
+{
+    runs.iter()
+        .map(|run| Batch {
+            texture: run.texture,
+            vertices: Arc::new(
+                BufferSlice::from_typed_buffer_access(buffer.clone())
+                    .slice(run.vertices.clone())
+                    .unwrap(),
+            ),
+        })
+        .collect()
+}
@@ -0,0 +1,49 @@
+// Turns variable frame times into a whole number of fixed-length updates,
+// carrying the remainder over to the next frame, so simulation behaves the
+// same at any frame rate. Rendering blends between the last two updates by
+// `alpha`.
+pub struct FixedStep {
+    pub step: f32,
+    max_steps: u32,
+    accumulator: f32,
+    time: f64,
+}
+
+impl FixedStep {
+    pub fn new(rate: f32, max_steps: u32) -> FixedStep {
+        FixedStep {
+            step: 1.0 / rate,
+            max_steps,
+            accumulator: 0.0,
+            time: 0.0,
+        }
+    }
+
+    // Adds a frame's elapsed time. Anything beyond `max_steps` updates is
+    // dropped, so a long stall doesn't leave the loop ever further behind.
+    pub fn advance(&mut self, dt: f32) {
+        self.accumulator =
+            (self.accumulator + dt).min(self.step * self.max_steps as f32);
+    }
+
+    // Takes one step's worth of accumulated time; run an update for each
+    // true.
+    pub fn tick(&mut self) -> bool {
+        if self.accumulator < self.step {
+            return false;
+        }
+        self.accumulator -= self.step;
+        self.time += self.step as f64;
+        true
+    }
+
+    // Fraction of a step since the last update, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).min(1.0)
+    }
+
+    // Simulated seconds after the steps taken so far.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+}
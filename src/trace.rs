@@ -0,0 +1,123 @@
+use cgmath::{InnerSpace, Vector3};
+
+pub const EPSILON: f32 = 1e-4;
+
+pub struct Triangle {
+    pub a: Vector3<f32>,
+    pub b: Vector3<f32>,
+    pub c: Vector3<f32>,
+}
+
+impl Triangle {
+    pub fn normal(&self) -> Vector3<f32> {
+        (self.b - self.a).cross(self.c - self.a).normalize()
+    }
+
+    pub fn point(&self, u: f32, v: f32) -> Vector3<f32> {
+        self.a + (self.b - self.a) * u + (self.c - self.a) * v
+    }
+
+    // Moller-Trumbore, returning the hit distance along a unit direction.
+    pub fn intersect(
+        &self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+    ) -> Option<f32> {
+        let edge1 = self.b - self.a;
+        let edge2 = self.c - self.a;
+        let p = direction.cross(edge2);
+        let det = edge1.dot(p);
+        if det.abs() < EPSILON {
+            return None;
+        }
+        let t_vec = origin - self.a;
+        let u = t_vec.dot(p) / det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = t_vec.cross(edge1);
+        let v = direction.dot(q) / det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) / det;
+        if t > EPSILON {
+            Some(t)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn triangles(scene: &[[f32; 4]]) -> Vec<Triangle> {
+    let point = |p: &[f32; 4]| Vector3::new(p[0], p[1], p[2]);
+    scene
+        .chunks_exact(3)
+        .map(|corners| Triangle {
+            a: point(&corners[0]),
+            b: point(&corners[1]),
+            c: point(&corners[2]),
+        })
+        .collect()
+}
+
+pub fn closest(
+    triangles: &[Triangle],
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+) -> Option<(&Triangle, f32)> {
+    triangles
+        .iter()
+        .filter_map(|triangle| {
+            triangle
+                .intersect(origin, direction)
+                .map(|distance| (triangle, distance))
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+}
+
+pub fn occluded(
+    triangles: &[Triangle],
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+) -> bool {
+    triangles
+        .iter()
+        .any(|triangle| triangle.intersect(origin, direction).is_some())
+}
+
+pub struct Random(pub u32);
+
+impl Random {
+    pub fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32
+    }
+}
+
+// Cosine-weighted direction around a unit normal.
+pub fn hemisphere(normal: Vector3<f32>, random: &mut Random) -> Vector3<f32> {
+    let r1 = random.next() * 2.0 * std::f32::consts::PI;
+    let r2 = random.next();
+    let tangent = if normal.x.abs() > 0.9 {
+        Vector3::unit_y()
+    } else {
+        Vector3::unit_x()
+    }
+    .cross(normal)
+    .normalize();
+    let bitangent = normal.cross(tangent);
+    (tangent * r1.cos() * r2.sqrt()
+        + bitangent * r1.sin() * r2.sqrt()
+        + normal * (1.0 - r2).sqrt())
+    .normalize()
+}
+
+pub fn sphere(random: &mut Random) -> Vector3<f32> {
+    let z = random.next() * 2.0 - 1.0;
+    let phi = random.next() * 2.0 * std::f32::consts::PI;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
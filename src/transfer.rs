@@ -0,0 +1,149 @@
+use std::sync::Arc;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::buffer::DeviceLocalBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::Dimensions;
+use vulkano::image::ImageLayout;
+use vulkano::image::ImageUsage;
+use vulkano::image::ImmutableImage;
+use vulkano::image::MipmapsCount;
+use vulkano::instance::QueueFamily;
+use vulkano::sync::GpuFuture;
+
+// Records asset uploads on the transfer queue. Resources are shared
+// concurrently with the graphics family instead of being handed over with
+// ownership barriers, which vulkano doesn't expose.
+#[derive(Clone)]
+pub struct Uploader {
+    pub transfer: Arc<Queue>,
+    pub graphics: Arc<Queue>,
+}
+
+impl Uploader {
+    pub fn new(transfer: Arc<Queue>, graphics: Arc<Queue>) -> Uploader {
+        Uploader { transfer, graphics }
+    }
+
+    pub fn dedicated(&self) -> bool {
+        !self.transfer.is_same(&self.graphics)
+    }
+
+    pub fn families(&self) -> Vec<QueueFamily> {
+        let mut families = vec![self.graphics.family()];
+        if self.transfer.family().id() != self.graphics.family().id() {
+            families.push(self.transfer.family());
+        }
+        families
+    }
+
+    // The returned future signals a semaphore when the copy ran on another
+    // queue, so it can be joined into the graphics frame future.
+    pub fn finish<C>(&self, command_buffer: C) -> Box<dyn GpuFuture>
+    where
+        C: CommandBuffer + 'static,
+    {
+        let future = command_buffer.execute(self.transfer.clone()).unwrap();
+        if self.dedicated() {
+            Box::new(future.then_signal_semaphore_and_flush().unwrap())
+        } else {
+            Box::new(future)
+        }
+    }
+
+    // Static data staged into device-local memory; `usage` only needs the
+    // flags the buffer is read with, transfer_destination is added here.
+    pub fn buffer<T>(
+        &self,
+        data: Vec<T>,
+        usage: BufferUsage,
+    ) -> (Arc<DeviceLocalBuffer<[T]>>, Box<dyn GpuFuture>)
+    where
+        T: Send + Sync + 'static,
+    {
+        let device = self.transfer.device().clone();
+        let len = data.len();
+        let source = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_source(),
+            data.into_iter(),
+        )
+        .unwrap();
+        let destination = DeviceLocalBuffer::array(
+            device.clone(),
+            len,
+            BufferUsage {
+                transfer_destination: true,
+                ..usage
+            },
+            self.families(),
+        )
+        .unwrap();
+
+        let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
+            device,
+            self.transfer.family(),
+        )
+        .unwrap()
+        .copy_buffer(source, destination.clone())
+        .unwrap()
+        .build()
+        .unwrap();
+
+        (destination, self.finish(command_buffer))
+    }
+
+    pub fn image(
+        &self,
+        data: Vec<u8>,
+        dimensions: Dimensions,
+        format: Format,
+    ) -> (Arc<ImmutableImage<Format>>, Box<dyn GpuFuture>) {
+        let device = self.transfer.device().clone();
+        let source = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_source(),
+            data.into_iter(),
+        )
+        .unwrap();
+
+        let usage = ImageUsage {
+            transfer_destination: true,
+            sampled: true,
+            ..ImageUsage::none()
+        };
+        let (image, init) = ImmutableImage::uninitialized(
+            device.clone(),
+            dimensions,
+            format,
+            MipmapsCount::One,
+            usage,
+            ImageLayout::ShaderReadOnlyOptimal,
+            self.families(),
+        )
+        .unwrap();
+
+        let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
+            device,
+            self.transfer.family(),
+        )
+        .unwrap()
+        .copy_buffer_to_image_dimensions(
+            source,
+            init,
+            [0, 0, 0],
+            dimensions.width_height_depth(),
+            0,
+            dimensions.array_layers_with_cube(),
+            0,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+
+        (image, self.finish(command_buffer))
+    }
+}
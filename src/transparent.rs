@@ -0,0 +1,47 @@
+use crate::layers;
+use cgmath::{Matrix4, Vector3, Vector4};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Instance {
+    pub offset: [f32; 3],
+    pub color: [f32; 4],
+    #[serde(default = "layers::default_layers")]
+    pub layers: u32,
+}
+
+impl Instance {
+    pub fn model(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(Vector3::from(self.offset))
+    }
+
+    pub fn view_depth(&self, view: &Matrix4<f32>) -> f32 {
+        (view * self.model() * Vector4::new(0.0, 0.0, 0.0, 1.0)).z
+    }
+}
+
+// Sorted on the CPU: each instance is its own draw with its transform in
+// push constants, so the draw order has to be known while recording.
+// Particles, which can number in the thousands, are sorted on the GPU.
+pub fn sort_back_to_front(instances: &mut [Instance], view: &Matrix4<f32>) {
+    instances.sort_by(|a, b| {
+        a.view_depth(view)
+            .partial_cmp(&b.view_depth(view))
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+pub fn draw_list(
+    instances: &[Instance],
+    view: &Matrix4<f32>,
+    mask: u32,
+) -> Vec<Instance> {
+    let mut visible = instances
+        .iter()
+        .filter(|instance| layers::visible(instance.layers, mask))
+        .cloned()
+        .collect::<Vec<_>>();
+    sort_back_to_front(&mut visible, view);
+    visible
+}
@@ -0,0 +1,98 @@
+use log::Level;
+use std::sync::Arc;
+use tracing::warn;
+use vulkano::instance;
+use vulkano::instance::debug::DebugCallback;
+use vulkano::instance::debug::Message;
+use vulkano::instance::debug::MessageSeverity;
+use vulkano::instance::debug::MessageType;
+use vulkano::instance::Instance;
+use vulkano::instance::InstanceCreationError;
+use vulkano::instance::InstanceExtensions;
+
+pub const LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+pub fn available() -> bool {
+    instance::layers_list()
+        .map(|mut layers| layers.any(|layer| layer.name() == LAYER))
+        .unwrap_or(false)
+}
+
+// With `validation`, enables the Khronos layer when it's installed and the
+// debug utils extension its messages arrive through; otherwise a plain
+// instance.
+pub fn instance(
+    extensions: &InstanceExtensions,
+    validation: bool,
+) -> Result<Arc<Instance>, InstanceCreationError> {
+    if validation && !available() {
+        warn!(layer = LAYER, "not installed, validation disabled");
+    }
+    if !validation || !available() {
+        return Instance::new(None, extensions, None);
+    }
+    let extensions = InstanceExtensions {
+        ext_debug_utils: true,
+        ..*extensions
+    };
+    Instance::new(None, &extensions, [LAYER].iter())
+}
+
+// Routes validation messages at `severity` and above into `log`. Dropping
+// the callback stops them.
+pub fn messenger(
+    instance: &Arc<Instance>,
+    severity: MessageSeverity,
+) -> Option<DebugCallback> {
+    if !instance.loaded_extensions().ext_debug_utils {
+        return None;
+    }
+    DebugCallback::new(instance, severity, MessageType::all(), |message| {
+        log::log!(
+            level(message),
+            "[{}] {}",
+            kind(message),
+            message.description
+        )
+    })
+    .ok()
+}
+
+// Everything at `name` or more severe: error, warning, info or verbose.
+pub fn parse_severity(name: &str) -> Option<MessageSeverity> {
+    let verbose = name == "verbose";
+    let information = verbose || name == "info";
+    let warning = information || name == "warning";
+    let error = warning || name == "error";
+    if !error {
+        return None;
+    }
+    Some(MessageSeverity {
+        error,
+        warning,
+        information,
+        verbose,
+    })
+}
+
+fn level(message: &Message) -> Level {
+    if message.severity.error {
+        Level::Error
+    } else if message.severity.warning {
+        Level::Warn
+    } else if message.severity.information {
+        Level::Info
+    } else {
+        Level::Debug
+    }
+}
+
+fn kind(message: &Message) -> &'static str {
+    if message.ty.validation {
+        "validation"
+    } else if message.ty.performance {
+        "performance"
+    } else {
+        "general"
+    }
+}
@@ -0,0 +1,436 @@
+use crate::bvh;
+use crate::bvh::Aabb;
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::layers;
+use crate::snapshot::Light;
+use crate::snapshot::LocalLight;
+use crate::snapshot::Snapshot;
+use crate::transparent::Instance;
+use cgmath::Matrix4;
+use cgmath::One;
+use cgmath::Quaternion;
+use cgmath::SquareMatrix;
+use cgmath::Vector3;
+use cgmath::Vector4;
+use hecs::Entity;
+use hecs::EntityBuilder;
+use hecs::World;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use tracing::info;
+
+// Where an entity sits, relative to its `Parent` if it has one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Transform {
+    pub fn at(x: f32, y: f32, z: f32) -> Transform {
+        Transform {
+            translation: Vector3::new(x, y, z),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(
+                self.scale.x,
+                self.scale.y,
+                self.scale.z,
+            )
+    }
+}
+
+// Makes an entity's transform relative to another's, forming the scene
+// graph. Parents must not form a cycle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Parent(pub Entity);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Name(pub String);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Mesh {
+    // A triangle list in the entity's space, drawn by the opaque passes.
+    // The scene buffers are built from these once at startup, so later
+    // changes only reach the state and not the screen.
+    Static(Vec<[f32; 4]>),
+    // The triangle instanced by the transparent passes.
+    Triangle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Material {
+    pub color: [f32; 4],
+    #[serde(default = "layers::default_layers")]
+    pub layers: u32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            color: [1.0; 4],
+            layers: layers::DEFAULT,
+        }
+    }
+}
+
+// Game code owns an ECS world of `Mesh`, `Material`, `Transform`, `Light`,
+// `LocalLight` and `Camera` components; `extract` turns it into the snapshot the frame
+// loop draws from. Lights and cameras are in world space, whatever their
+// entity's transform.
+#[derive(Default)]
+pub struct RenderWorld {
+    pub world: World,
+}
+
+impl RenderWorld {
+    pub fn new() -> RenderWorld {
+        RenderWorld::default()
+    }
+
+    // One entity per piece of `state` the components can describe.
+    pub fn from_snapshot(state: &Snapshot) -> RenderWorld {
+        let mut world = World::new();
+        world.spawn((
+            Transform::at(0.0, 0.0, 0.0),
+            Mesh::Static(state.scene.clone()),
+            Material::default(),
+        ));
+        for instance in &state.transparent {
+            let [x, y, z] = instance.offset;
+            world.spawn((
+                Transform::at(x, y, z),
+                Mesh::Triangle,
+                Material {
+                    color: instance.color,
+                    layers: instance.layers,
+                },
+            ));
+        }
+        world.spawn((state.light,));
+        for &light in &state.local_lights {
+            world.spawn((light,));
+        }
+        world.spawn((state.camera,));
+        RenderWorld { world }
+    }
+
+    // Points every camera entity at `camera`, for input that still drives
+    // the snapshot's camera directly.
+    pub fn set_camera(&mut self, camera: Camera) {
+        for (_, current) in self.world.query::<&mut Camera>().iter() {
+            *current = camera;
+        }
+    }
+
+    // `entity`'s transform composed with those of its ancestors.
+    pub fn world_matrix(&self, entity: Entity) -> Matrix4<f32> {
+        let mut matrix = Matrix4::identity();
+        let mut current = Some(entity);
+        while let Some(entity) = current {
+            if let Ok(transform) = self.world.get::<Transform>(entity) {
+                matrix = transform.matrix() * matrix;
+            }
+            current = self.world.get::<Parent>(entity).ok().map(|p| p.0);
+        }
+        matrix
+    }
+
+    // What `entity`'s transform is relative to.
+    pub fn parent_matrix(&self, entity: Entity) -> Matrix4<f32> {
+        match self.world.get::<Parent>(entity) {
+            Ok(parent) => self.world_matrix(parent.0),
+            Err(_) => Matrix4::identity(),
+        }
+    }
+
+    // Overwrites the scene, transparent instances, lights and camera in
+    // `state`. The first directional light and camera found win; the state
+    // keeps its own when the world has none.
+    // Returns whether the opaque triangles or the transparent instance
+    // offsets changed, meaning the scene buffers and picker are stale.
+    pub fn extract(&self, state: &mut Snapshot) -> bool {
+        let mut scene = Vec::with_capacity(state.scene.len());
+        let mut transparent = Vec::with_capacity(state.transparent.len());
+        state.local_lights.clear();
+        let mut query = self.world.query::<(&Mesh, &Material)>();
+        for (entity, (mesh, material)) in query.iter() {
+            let matrix = self.world_matrix(entity);
+            match mesh {
+                Mesh::Static(vertices) => scene.extend(
+                    vertices
+                        .iter()
+                        .map(|&v| (matrix * Vector4::from(v)).into()),
+                ),
+                Mesh::Triangle => transparent.push(Instance {
+                    offset: matrix.w.truncate().into(),
+                    color: material.color,
+                    layers: material.layers,
+                }),
+            }
+        }
+        let changed = scene != state.scene
+            || transparent.len() != state.transparent.len()
+            || transparent
+                .iter()
+                .zip(&state.transparent)
+                .any(|(new, old)| new.offset != old.offset);
+        state.scene = scene;
+        state.transparent = transparent;
+        if let Some((_, light)) = self.world.query::<&Light>().iter().next() {
+            state.light = *light;
+        }
+        state.local_lights.extend(
+            self.world
+                .query::<&LocalLight>()
+                .iter()
+                .map(|(_, light)| *light),
+        );
+        if let Some((_, camera)) = self.world.query::<&Camera>().iter().next() {
+            state.camera = *camera;
+        }
+        changed
+    }
+
+    // The whole world as RON for a .ron path, otherwise JSON. Parents are
+    // written as indices into the entity list.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let entities = self.world.iter().map(|(e, _)| e).collect::<Vec<_>>();
+        let index = entities
+            .iter()
+            .enumerate()
+            .map(|(i, &entity)| (entity, i))
+            .collect::<HashMap<_, _>>();
+        let get = |entity| EntityDesc {
+            name: self.world.get::<Name>(entity).ok().map(|n| n.0.clone()),
+            parent: self
+                .world
+                .get::<Parent>(entity)
+                .ok()
+                .and_then(|parent| index.get(&parent.0).cloned()),
+            transform: self
+                .world
+                .get::<Transform>(entity)
+                .ok()
+                .map(|t| TransformDesc::from(*t)),
+            mesh: self.world.get::<Mesh>(entity).ok().map(|m| m.clone()),
+            material: self.world.get::<Material>(entity).ok().map(|m| *m),
+            light: self.world.get::<Light>(entity).ok().map(|l| *l),
+            local_light: self.world.get::<LocalLight>(entity).ok().map(|l| *l),
+            camera: self.world.get::<Camera>(entity).ok().map(|c| *c),
+        };
+        let scene = SceneDesc {
+            entities: entities.into_iter().map(get).collect(),
+        };
+        let text = if is_ron(path.as_ref()) {
+            ron::ser::to_string_pretty(&scene, Default::default()).map_err(
+                |e| io::Error::new(io::ErrorKind::Other, e.to_string()),
+            )?
+        } else {
+            serde_json::to_string_pretty(&scene)?
+        };
+        fs::write(path, text)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<RenderWorld> {
+        let path = path.as_ref();
+        info!(path = %path.display(), "loading scene");
+        let text = fs::read_to_string(path)?;
+        let scene: SceneDesc = if is_ron(path) {
+            ron::de::from_str(&text).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+            })?
+        } else {
+            serde_json::from_str(&text)?
+        };
+
+        let mut world = World::new();
+        let entities = scene
+            .entities
+            .iter()
+            .map(|desc| {
+                let mut builder = EntityBuilder::new();
+                if let Some(name) = &desc.name {
+                    builder.add(Name(name.clone()));
+                }
+                if let Some(transform) = desc.transform {
+                    builder.add(Transform::from(transform));
+                }
+                if let Some(mesh) = &desc.mesh {
+                    builder.add(mesh.clone());
+                }
+                if let Some(material) = desc.material {
+                    builder.add(material);
+                }
+                if let Some(light) = desc.light {
+                    builder.add(light);
+                }
+                if let Some(light) = desc.local_light {
+                    builder.add(light);
+                }
+                if let Some(camera) = desc.camera {
+                    builder.add(camera);
+                }
+                world.spawn(builder.build())
+            })
+            .collect::<Vec<_>>();
+        for (desc, &entity) in scene.entities.iter().zip(&entities) {
+            if let Some(&parent) = desc.parent.and_then(|i| entities.get(i)) {
+                world.insert_one(entity, Parent(parent)).unwrap();
+            }
+        }
+        Ok(RenderWorld { world })
+    }
+}
+
+// World space triangles of the meshes `extract` draws, each tagged with
+// its entity, for finding what's under the cursor. Build a new one after
+// the world changes.
+pub struct Picker {
+    triangles: Vec<[Vector3<f32>; 3]>,
+    owners: Vec<Entity>,
+    bvh: Bvh,
+}
+
+impl Picker {
+    pub fn new(render_world: &RenderWorld) -> Picker {
+        let world = &render_world.world;
+        let mut triangles = Vec::new();
+        let mut owners = Vec::new();
+        let mut instances = Vec::new();
+        for (entity, (mesh, _)) in world.query::<(&Mesh, &Material)>().iter() {
+            let matrix = render_world.world_matrix(entity);
+            match mesh {
+                Mesh::Static(vertices) => {
+                    for triangle in vertices.chunks_exact(3) {
+                        let corner = |i: usize| {
+                            (matrix * Vector4::from(triangle[i])).truncate()
+                        };
+                        triangles.push([corner(0), corner(1), corner(2)]);
+                        owners.push(entity);
+                    }
+                }
+                Mesh::Triangle => {
+                    instances.push((entity, matrix.w.truncate()));
+                }
+            }
+        }
+        // Transparent instances draw the whole scene buffer, offset.
+        let scene = triangles.clone();
+        for (entity, offset) in instances {
+            for &[a, b, c] in &scene {
+                triangles.push([a + offset, b + offset, c + offset]);
+                owners.push(entity);
+            }
+        }
+
+        let bounds = triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                let corner = Aabb { min: a, max: a };
+                corner
+                    .union(&Aabb { min: b, max: b })
+                    .union(&Aabb { min: c, max: c })
+            })
+            .collect::<Vec<_>>();
+        Picker {
+            bvh: Bvh::build(&bounds),
+            triangles,
+            owners,
+        }
+    }
+
+    // The nearest entity the ray hits, and how far along the ray it is.
+    pub fn pick(
+        &self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+    ) -> Option<(Entity, f32)> {
+        self.bvh
+            .raycast(origin, direction, |i| {
+                bvh::ray_triangle(origin, direction, &self.triangles[i])
+            })
+            .map(|(i, distance)| (self.owners[i], distance))
+    }
+}
+
+fn is_ron(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("ron")
+}
+
+#[derive(Serialize, Deserialize)]
+struct SceneDesc {
+    entities: Vec<EntityDesc>,
+}
+
+// Every component is optional, and left out of the file when absent.
+#[derive(Serialize, Deserialize)]
+struct EntityDesc {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transform: Option<TransformDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mesh: Option<Mesh>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    material: Option<Material>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    light: Option<Light>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    local_light: Option<LocalLight>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    camera: Option<Camera>,
+}
+
+// Rotation as x, y, z, w, as in glTF.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct TransformDesc {
+    translation: [f32; 3],
+    #[serde(default = "identity_rotation")]
+    rotation: [f32; 4],
+    #[serde(default = "unit_scale")]
+    scale: [f32; 3],
+}
+
+fn identity_rotation() -> [f32; 4] {
+    [0.0, 0.0, 0.0, 1.0]
+}
+
+fn unit_scale() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+impl From<Transform> for TransformDesc {
+    fn from(transform: Transform) -> Self {
+        let Quaternion { s, v } = transform.rotation;
+        TransformDesc {
+            translation: transform.translation.into(),
+            rotation: [v.x, v.y, v.z, s],
+            scale: transform.scale.into(),
+        }
+    }
+}
+
+impl From<TransformDesc> for Transform {
+    fn from(desc: TransformDesc) -> Self {
+        let [x, y, z, w] = desc.rotation;
+        Transform {
+            translation: desc.translation.into(),
+            rotation: Quaternion::new(w, x, y, z),
+            scale: desc.scale.into(),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::env;
+use vulkano_triangle::golden;
+use vulkano_triangle::layers;
+use vulkano_triangle::renderer;
+use vulkano_triangle::snapshot::Snapshot;
+
+// Reference scenes rendered offscreen and compared with the PNGs in
+// tests/goldens. They need a Vulkan device, so they only run with
+// `cargo test -- --ignored`, and then fail without one unless SKIP_GOLDENS
+// is set. The goldens are the renderer's own output; create them, or
+// accept new output after an intended change, with
+//
+//     UPDATE_GOLDENS=1 cargo test --test golden -- --ignored
+//
+// then look over the images and check them in.
+const GOLDEN_DIR: &str = "tests/goldens";
+const DIMENSIONS: [u32; 2] = [256, 256];
+// Absorbs rounding differences between drivers.
+const TOLERANCE: u8 = 2;
+const MAX_MISMATCHED: usize = 16;
+const SKIP_VAR: &str = "SKIP_GOLDENS";
+
+fn check(name: &str, state: &Snapshot) {
+    if env::var_os(SKIP_VAR).is_some() {
+        eprintln!("skipping {}: {} is set", name, SKIP_VAR);
+        return;
+    }
+    let (device, queue) = match renderer::find_headless_device() {
+        Some(found) => found,
+        None => panic!("{}: no Vulkan device; set {} to skip", name, SKIP_VAR),
+    };
+    let image =
+        golden::render_snapshot(device, queue, state, DIMENSIONS).unwrap();
+    if let Err(message) =
+        golden::check(GOLDEN_DIR, name, &image, TOLERANCE, MAX_MISMATCHED)
+    {
+        panic!("{}", message);
+    }
+}
+
+#[test]
+#[ignore]
+fn default_scene() {
+    check("default_scene", &Snapshot::default());
+}
+
+#[test]
+#[ignore]
+fn opaque_only() {
+    let state = Snapshot {
+        transparent: Vec::new(),
+        ..Snapshot::default()
+    };
+    check("opaque_only", &state);
+}
+
+#[test]
+#[ignore]
+fn debug_layer_hidden() {
+    let mut state = Snapshot::default();
+    state.camera.cull_mask &= !layers::DEBUG;
+    check("debug_layer_hidden", &state);
+}